@@ -2,18 +2,25 @@
 
 import sys
 import pathlib
+import shutil
 import subprocess
 
-# Usage: java-compiler.py
+# Usage: java-compile.py -o <JAR file> --main-class <class name> -d <output dir> [javac options...]
+#        <source file>
+#
+# The source file is copied to <output dir>/<class name>.java before compilation since javac
+# requires the name of a source file to match the public class declared in it. The output directory
+# is created if it does not exist.
 
 class Args():
     def __init__(self):
         self.output_dir = None
         self.output_file = None
+        self.main_class = None
         self.javac_argv = ['javac']
 
 def get_args():
-    argv = sys.argv
+    argv = sys.argv[1:]
     args = Args()
 
     i = 0
@@ -21,6 +28,9 @@ def get_args():
         if argv[i] == '-o':
             args.output_file = pathlib.Path(argv[i + 1])
             i += 1
+        elif argv[i] == '--main-class':
+            args.main_class = argv[i + 1]
+            i += 1
         else:
             args.javac_argv.append(argv[i])
             if argv[i] == '-d':
@@ -36,16 +46,28 @@ if args.output_dir == None:
 if args.output_file == None:
     print('No output JAR file specified.', file=sys.stderr)
     sys.exit(-1)
+if args.main_class == None:
+    print('No main class specified.', file=sys.stderr)
+    sys.exit(-1)
+
+# Copy the source file, which is the last argument, to a file named after the main class.
+args.output_dir.mkdir(parents=True, exist_ok=True)
+source_file = pathlib.Path(args.javac_argv[-1])
+main_source_file = args.output_dir.joinpath(args.main_class + '.java')
+if source_file.resolve() != main_source_file.resolve():
+    shutil.copyfile(str(source_file), str(main_source_file))
+args.javac_argv[-1] = str(main_source_file)
 
 # Invoke javac.
 proc = subprocess.run(args.javac_argv)
 if proc.returncode != 0:
     sys.exit(proc.returncode)
 
-# List all generated *.class file under output directory.
-class_files = list(map(str, args.output_dir.glob('*.class')))
-jar_file = str(output_dir.joinpath(args.output_file))
-jar_argv = ['jar', '--create', '--file', jar_file] + class_files
+# Add all generated *.class files under output directory to the JAR file. The class files are
+# added relative to the output directory so that they are placed at the root of the JAR file.
+jar_argv = ['jar', '--create', '--file', str(args.output_file), '--main-class', args.main_class]
+for class_file in args.output_dir.glob('*.class'):
+    jar_argv += ['-C', str(args.output_dir), class_file.name]
 
 # Invoke jar.
 proc = subprocess.run(jar_argv)
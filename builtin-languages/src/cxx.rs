@@ -76,6 +76,32 @@ impl Config for CXXLanguageConfig { }
 /// Name of the WaveTestLib library.
 const WAVETESTLIB_LIB_NAME: &'static str = "wavetest";
 
+/// Optimization levels that submissions can request through compiler flags.
+const ALLOWED_OPTIMIZATION_FLAGS: &[&str] = &["-O0", "-O1", "-O2", "-O3", "-Os", "-Og"];
+
+/// Determine whether the given compiler flag requested by a submission written in the given
+/// language is honored. Only optimization levels in `ALLOWED_OPTIMIZATION_FLAGS` and `-std=`
+/// flags naming a version of the same language in the metadata are honored. Other flags, such as
+/// include paths, plugins or linker options, could be used to read files on the judge host or run
+/// code inside the compiler, so they are ignored.
+fn is_flag_allowed(language: &str, flag: &str) -> bool {
+    if ALLOWED_OPTIMIZATION_FLAGS.contains(&flag) {
+        return true;
+    }
+
+    if !flag.starts_with("-std=") {
+        return false;
+    }
+    let version = &flag["-std=".len()..];
+    let metadata = match language {
+        "c" => unsafe { C_METADATA.as_ref() },
+        "cpp" => unsafe { CPP_METADATA.as_ref() },
+        _ => None
+    };
+    metadata.map(|metadata| metadata.branches.iter().any(|branch| branch.version() == version))
+        .unwrap_or(false)
+}
+
 struct CXXLanguageProvider {
     config: CXXLanguageConfig,
 }
@@ -85,7 +111,11 @@ impl CXXLanguageProvider {
         CXXLanguageProvider { config }
     }
 
-    fn compile(&self, program: &Program, kind: ProgramKind, output_dir: Option<PathBuf>)
+    /// Create a `CompilationInfo` instance for the given program. The honored flags in `flags` are
+    /// appended after the default flags so that they override the default optimization level and
+    /// language standard; see `is_flag_allowed` for the flags honored. Other flags are ignored.
+    fn compile(&self,
+        program: &Program, kind: ProgramKind, output_dir: Option<PathBuf>, flags: &[String])
         -> Result<CompilationInfo, Box<dyn std::error::Error>> {
         let compiler = match (program.language.language(), program.language.dialect()) {
             ("c", "gnu") => PathBuf::from("gcc"),
@@ -99,22 +129,30 @@ impl CXXLanguageProvider {
         let output_file = crate::utils::make_output_file_path(&program.file, output_dir);
 
         let mut ci = CompilationInfo::new(compiler, output_file.clone());
-        ci.compiler.args.push(String::from("-O2"));
-        ci.compiler.args.push(format!("-std={}", program.language.version()));
-        ci.compiler.args.push(String::from("-DONLINE_JUDGE"));
+        ci.compiler.add_arg("-O2")?;
+        ci.compiler.add_arg(format!("-std={}", program.language.version()))?;
+        ci.compiler.add_arg("-DONLINE_JUDGE")?;
+
+        for flag in flags {
+            if is_flag_allowed(program.language.language(), flag) {
+                ci.compiler.add_arg(flag.as_str())?;
+            } else {
+                log::warn!("Ignored disallowed compiler flag: {}", flag);
+            }
+        }
 
         if kind.is_jury() {
-            ci.compiler.args.push(
-                format!("-I{}", self.config.testlib_include_dir.display()));
-            ci.compiler.args.push(format!("-L{}", self.config.testlib_lib_dir.display()));
+            ci.compiler.add_arg(
+                format!("-I{}", self.config.testlib_include_dir.display()))?;
+            ci.compiler.add_arg(format!("-L{}", self.config.testlib_lib_dir.display()))?;
         }
 
-        ci.compiler.args.push(String::from("-o"));
-        ci.compiler.args.push(format!("{}", output_file.display()));
-        ci.compiler.args.push(format!("{}", program.file.display()));
+        ci.compiler.add_arg("-o")?;
+        ci.compiler.add_arg(format!("{}", output_file.display()))?;
+        ci.compiler.add_arg(format!("{}", program.file.display()))?;
 
         if kind.is_jury() {
-            ci.compiler.args.push(format!("-l{}", WAVETESTLIB_LIB_NAME));
+            ci.compiler.add_arg(format!("-l{}", WAVETESTLIB_LIB_NAME))?;
         }
 
         Ok(ci)
@@ -146,9 +184,19 @@ impl LanguageProvider for CLanguageProvider {
         unsafe { C_METADATA.as_ref().unwrap() }
     }
 
+    fn source_extension(&self) -> &'static str {
+        "c"
+    }
+
     fn compile(&self, program: &Program, kind: ProgramKind, output_dir: Option<PathBuf>)
         -> Result<CompilationInfo, Box<dyn std::error::Error>> {
-        self.cxx_prov.compile(program, kind, output_dir)
+        self.cxx_prov.compile(program, kind, output_dir, &[])
+    }
+
+    fn compile_with_flags(&self,
+        program: &Program, kind: ProgramKind, output_dir: Option<PathBuf>, flags: &[String])
+        -> Result<CompilationInfo, Box<dyn std::error::Error>> {
+        self.cxx_prov.compile(program, kind, output_dir, flags)
     }
 
     fn execute(&self, program: &Program, kind: ProgramKind)
@@ -177,9 +225,19 @@ impl LanguageProvider for CPPLanguageProvider {
         unsafe { CPP_METADATA.as_ref().unwrap() }
     }
 
+    fn source_extension(&self) -> &'static str {
+        "cpp"
+    }
+
     fn compile(&self, program: &Program, kind: ProgramKind, output_dir: Option<PathBuf>)
         -> Result<CompilationInfo, Box<dyn std::error::Error>> {
-        self.cxx_prov.compile(program, kind, output_dir)
+        self.cxx_prov.compile(program, kind, output_dir, &[])
+    }
+
+    fn compile_with_flags(&self,
+        program: &Program, kind: ProgramKind, output_dir: Option<PathBuf>, flags: &[String])
+        -> Result<CompilationInfo, Box<dyn std::error::Error>> {
+        self.cxx_prov.compile(program, kind, output_dir, flags)
     }
 
     fn execute(&self, program: &Program, kind: ProgramKind)
@@ -201,3 +259,71 @@ pub fn init_cxx_providers(lang: &mut LanguageProviderRegister) -> Result<(), Ini
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use judge::languages::LanguageIdentifier;
+
+    fn make_config() -> CXXLanguageConfig {
+        CXXLanguageConfig {
+            testlib_include_dir: PathBuf::from("include"),
+            testlib_lib_dir: PathBuf::from("lib"),
+        }
+    }
+
+    #[test]
+    fn c_source_extension() {
+        let prov = CLanguageProvider::new(make_config());
+        assert_eq!("c", prov.source_extension());
+    }
+
+    #[test]
+    fn cpp_source_extension() {
+        let prov = CPPLanguageProvider::new(make_config());
+        assert_eq!("cpp", prov.source_extension());
+    }
+
+    #[test]
+    fn compile_flags_honored() {
+        init_metadata();
+        let prov = CPPLanguageProvider::new(make_config());
+        let program = Program::new("main.cpp",
+            LanguageIdentifier::new("cpp", LanguageBranch::new("gnu", "c++11")));
+        let flags = vec![String::from("-O0"), String::from("-std=c++17")];
+        let ci = prov.compile_with_flags(&program, ProgramKind::Judgee, None, &flags).unwrap();
+
+        let args = &ci.compiler.args;
+        let position = |arg: &str| args.iter().position(|a| a == arg).unwrap();
+        assert!(position("-O0") > position("-O2"));
+        assert!(position("-std=c++17") > position("-std=c++11"));
+    }
+
+    #[test]
+    fn compile_flags_disallowed() {
+        init_metadata();
+        let prov = CLanguageProvider::new(make_config());
+        let program = Program::new("main.c",
+            LanguageIdentifier::new("c", LanguageBranch::new("gnu", "c11")));
+        let flags = vec![
+            String::from("-fplugin=evil.so"),
+            String::from("-I/etc"),
+            String::from("-std=c++17"),
+            String::from("-O3"),
+        ];
+        let ci = prov.compile_with_flags(&program, ProgramKind::Judgee, None, &flags).unwrap();
+
+        let args = &ci.compiler.args;
+        assert!(args.contains(&String::from("-O3")));
+        for disallowed in &["-fplugin=evil.so", "-I/etc", "-std=c++17"] {
+            assert!(!args.iter().any(|a| a == disallowed));
+        }
+    }
+
+    #[test]
+    fn no_memory_overhead() {
+        let prov = CPPLanguageProvider::new(make_config());
+        assert_eq!(0, prov.memory_overhead().bytes());
+    }
+}
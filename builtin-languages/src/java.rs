@@ -10,6 +10,7 @@ use crate::InitLanguageError;
 use crate::utils::Config;
 
 use judge::{
+    MemorySize,
     Program,
     ProgramKind,
 };
@@ -57,11 +58,109 @@ struct JavaLanguageConfig {
     #[serde(rename = "compile_script")]
     #[serde(default = "get_default_compile_script")]
     compile_script: PathBuf,
+
+    /// Extra flags passed to the JVM when executing Java programs, e.g. `-XX:+UseSerialGC`.
+    #[serde(rename = "runtime_flags")]
+    #[serde(default)]
+    runtime_flags: Vec<String>,
 }
 
 impl Config for JavaLanguageConfig { }
 
+/// Determine whether the given identifier tokens, which precede a `{` at the top level of a Java
+/// source file, form the header of a public type declaration. If so, returns the name of the
+/// declared type.
+fn get_public_type_name(tokens: &[String]) -> Option<String> {
+    let kw_pos = tokens.iter()
+        .position(|t| t == "class" || t == "interface" || t == "enum")?;
+    if !tokens[..kw_pos].iter().any(|t| t == "public") {
+        return None;
+    }
+
+    tokens.get(kw_pos + 1).cloned()
+}
+
+/// Find the name of the public top-level type declared in the given Java source code. Comments,
+/// string literals and character literals are skipped, as well as types nested in other types.
+///
+/// This function returns `None` if no public top-level type is declared in the source code.
+fn find_public_class_name(source: &str) -> Option<String> {
+    // Identifier tokens found at the top level since the last `;`, `{` or `}`.
+    let mut tokens = Vec::new();
+    let mut depth = 0usize;
+
+    let mut chars = source.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '/' if chars.peek() == Some(&'/') => {
+                while let Some(ch) = chars.next() {
+                    if ch == '\n' {
+                        break;
+                    }
+                }
+            },
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut prev = '\0';
+                while let Some(ch) = chars.next() {
+                    if prev == '*' && ch == '/' {
+                        break;
+                    }
+                    prev = ch;
+                }
+            },
+            '"' | '\'' => {
+                while let Some(c) = chars.next() {
+                    if c == '\\' {
+                        chars.next();
+                    } else if c == ch {
+                        break;
+                    }
+                }
+            },
+            '{' => {
+                if depth == 0 {
+                    if let Some(name) = get_public_type_name(&tokens) {
+                        return Some(name);
+                    }
+                }
+                depth += 1;
+                tokens.clear();
+            },
+            '}' => {
+                depth = depth.saturating_sub(1);
+                tokens.clear();
+            },
+            ';' => tokens.clear(),
+            ch if ch.is_alphabetic() || ch == '_' || ch == '$' => {
+                let mut ident = ch.to_string();
+                while let Some(&ch) = chars.peek() {
+                    if !ch.is_alphanumeric() && ch != '_' && ch != '$' {
+                        break;
+                    }
+                    ident.push(ch);
+                    chars.next();
+                }
+
+                if depth == 0 {
+                    tokens.push(ident);
+                }
+            },
+            _ => ()
+        };
+    }
+
+    None
+}
+
 /// Java language provider.
+///
+/// Java requires the name of a source file to match the public class declared in it, while source
+/// files are saved under arbitrary names (e.g. temporary files holding submitted source code). The
+/// provider therefore parses the name of the public class out of the source file, and the compile
+/// script copies the source file to `<ClassName>.java` under a working directory dedicated to the
+/// program before invoking `javac`. The generated JAR file is named after the public class, too, so
+/// that the class to be executed can be recovered from the path of the JAR file.
 struct JavaLanguageProvider {
     /// The Java language configuration.
     config: JavaLanguageConfig,
@@ -79,51 +178,79 @@ impl LanguageProvider for JavaLanguageProvider {
         unsafe { JAVA_METADATA.as_ref().unwrap() }
     }
 
+    fn source_extension(&self) -> &'static str {
+        "java"
+    }
+
+    fn memory_overhead(&self) -> MemorySize {
+        // The JVM reserves virtual memory for its code cache, metaspace and thread stacks in
+        // addition to the heap.
+        MemorySize::MegaBytes(256)
+    }
+
     fn compile(&self, program: &Program, kind: ProgramKind, output_dir: Option<PathBuf>)
         -> Result<CompilationInfo, Box<dyn std::error::Error>> {
-        let mut output_file = crate::utils::make_output_file_path(&program.file, output_dir);
-        output_file.set_extension("jar");
-
-        let output_dir = match output_file.parent() {
-            Some(d) => d.to_owned(),
-            None => PathBuf::from(".")
+        let source = std::fs::read_to_string(&program.file)?;
+        let class_name = match find_public_class_name(&source) {
+            Some(name) => name,
+            None => return Err(Box::new(judge::Error::from(judge::ErrorKind::LanguageError(
+                format!("no public class found in Java source file \"{}\"",
+                    program.file.display())))))
         };
 
+        // Each program is compiled in its own working directory so that the class files generated
+        // for different programs do not mix up.
+        let work_dir = crate::utils::make_output_file_path(&program.file, output_dir);
+        let output_file = work_dir.join(format!("{}.jar", class_name));
+
         let mut ci = CompilationInfo::new(self.config.compile_script.clone(), output_file.clone());
-        // The following two arguments are passed to the compiler script to specify the path to the
-        // output JAR file. This two arguments should not be passed to the java compiler.
-        ci.compiler.args.push(String::from("-o"));
-        ci.compiler.args.push(format!("{}", output_file.display()));
+        // The following four arguments are passed to the compiler script to specify the path to the
+        // output JAR file and the name of the main class. They should not be passed to the java
+        // compiler.
+        ci.compiler.add_arg("-o")?;
+        ci.compiler.add_arg(format!("{}", output_file.display()))?;
+        ci.compiler.add_arg("--main-class")?;
+        ci.compiler.add_arg(class_name)?;
 
-        ci.compiler.args.push(String::from("-d"));
-        ci.compiler.args.push(format!("{}", output_dir.display()));
+        ci.compiler.add_arg("-d")?;
+        ci.compiler.add_arg(format!("{}", work_dir.display()))?;
 
         if kind.is_jury() {
-            ci.compiler.args.push(String::from("-cp"));
-            ci.compiler.args.push(format!("{}", self.config.testlib_jar.display()));
+            ci.compiler.add_arg("-cp")?;
+            ci.compiler.add_arg(format!("{}", self.config.testlib_jar.display()))?;
         }
 
-        ci.compiler.args.push(String::from("--release"));
-        ci.compiler.args.push(format!("{}", program.language.version()));
-        ci.compiler.args.push(String::from("--source"));
-        ci.compiler.args.push(format!("{}", program.language.version()));
+        ci.compiler.add_arg("--release")?;
+        ci.compiler.add_arg(format!("{}", program.language.version()))?;
+        ci.compiler.add_arg("--source")?;
+        ci.compiler.add_arg(format!("{}", program.language.version()))?;
 
-        ci.compiler.args.push(format!("{}", program.file.display()));
+        ci.compiler.add_arg(format!("{}", program.file.display()))?;
 
         Ok(ci)
     }
 
     fn execute(&self, program: &Program, kind: ProgramKind)
         -> Result<ExecutionInfo, Box<dyn std::error::Error>> {
-        let mut ei = ExecutionInfo::new("java");
+        let class_name = program.file.file_stem()
+            .expect("no file_stem found on the JAR file.")
+            .to_string_lossy()
+            .into_owned();
 
+        let mut class_path = format!("{}", program.file.display());
         if kind.is_jury() {
-            ei.args.push(String::from("-cp"));
-            ei.args.push(format!("{}", self.config.testlib_jar.display()));
+            class_path.push(':');
+            class_path.push_str(&format!("{}", self.config.testlib_jar.display()));
+        }
+
+        let mut ei = ExecutionInfo::new("java");
+        for flag in &self.config.runtime_flags {
+            ei.add_arg(flag.clone())?;
         }
 
-        ei.args.push(String::from("-jar"));
-        ei.args.push(format!("{}", program.file.display()));
+        ei.add_arg("-cp")?;
+        ei.add_arg(class_path)?;
+        ei.add_arg(class_name)?;
 
         Ok(ei)
     }
@@ -142,3 +269,154 @@ pub fn init_java_providers(lang: &mut LanguageProviderRegister) -> Result<(), In
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use judge::languages::LanguageIdentifier;
+    use tempfile::TempDir;
+
+    fn make_provider() -> JavaLanguageProvider {
+        JavaLanguageProvider::new(JavaLanguageConfig {
+            testlib_jar: PathBuf::from("testlib.jar"),
+            compile_script: get_default_compile_script(),
+            runtime_flags: Vec::new(),
+        })
+    }
+
+    fn make_program<P>(file: P) -> Program
+        where P: Into<PathBuf> {
+        Program::new(file, LanguageIdentifier::new("java", LanguageBranch::new("java", "11")))
+    }
+
+    /// Save the given source code into a file with the given name under a fresh temporary
+    /// directory, and returns the directory.
+    fn save_source(file_name: &str, source: &str) -> TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join(file_name), source).unwrap();
+        dir
+    }
+
+    #[test]
+    fn source_extension() {
+        assert_eq!("java", make_provider().source_extension());
+    }
+
+    #[test]
+    fn memory_overhead() {
+        assert_eq!(MemorySize::MegaBytes(256), make_provider().memory_overhead());
+    }
+
+    mod find_public_class_name {
+        use super::*;
+
+        #[test]
+        fn main_class() {
+            let source = "import java.util.*;\n\npublic class Main {\n}\n";
+            assert_eq!(Some("Main"), find_public_class_name(source).as_deref());
+        }
+
+        #[test]
+        fn generic_class_with_modifiers() {
+            let source = "public final class Solution<T> extends Base implements Runnable { }";
+            assert_eq!(Some("Solution"), find_public_class_name(source).as_deref());
+        }
+
+        #[test]
+        fn skip_comments_and_literals() {
+            let source = "// public class A {\n/* public class B { */\nclass C {\n\
+                String s = \"public class D {\";\n\
+                char c = '{';\n\
+                public static class E { }\n\
+            }\n\
+            public class F { }\n";
+            assert_eq!(Some("F"), find_public_class_name(source).as_deref());
+        }
+
+        #[test]
+        fn no_public_class() {
+            let source = "class Main {\n    public static class Nested { }\n}\n";
+            assert_eq!(None, find_public_class_name(source));
+        }
+    }
+
+    mod compile {
+        use super::*;
+
+        #[test]
+        fn main_class() {
+            let temp_dir = save_source("Main.java", "public class Main { }");
+            let dir = temp_dir.path().to_owned();
+            let program = make_program(dir.join("Main.java"));
+            let ci = make_provider().compile(&program, ProgramKind::Judgee, Some(dir.clone()))
+                .unwrap();
+
+            assert_eq!(dir.join("Main").join("Main.jar"), ci.output_file);
+            assert_eq!(vec![
+                "-o", ci.output_file.to_str().unwrap(),
+                "--main-class", "Main",
+                "-d", dir.join("Main").to_str().unwrap(),
+                "--release", "11",
+                "--source", "11",
+                program.file.to_str().unwrap()
+            ], ci.compiler.args);
+        }
+
+        #[test]
+        fn mismatched_file_name() {
+            let temp_dir = save_source("source123.java", "public class Solution { }");
+            let dir = temp_dir.path().to_owned();
+            let program = make_program(dir.join("source123.java"));
+            let ci = make_provider().compile(&program, ProgramKind::Judgee, Some(dir.clone()))
+                .unwrap();
+
+            assert_eq!(dir.join("source123").join("Solution.jar"), ci.output_file);
+            assert!(ci.compiler.args.windows(2).any(|a| a == ["--main-class", "Solution"]));
+        }
+
+        #[test]
+        fn no_public_class() {
+            let temp_dir = save_source("Main.java", "class Main { }");
+            let dir = temp_dir.path().to_owned();
+            let program = make_program(dir.join("Main.java"));
+            let err = make_provider().compile(&program, ProgramKind::Judgee, Some(dir.clone()))
+                .unwrap_err();
+
+            assert!(format!("{}", err).contains("no public class found"));
+        }
+    }
+
+    mod execute {
+        use super::*;
+
+        #[test]
+        fn judgee() {
+            let program = make_program("out/source123/Solution.jar");
+            let ei = make_provider().execute(&program, ProgramKind::Judgee).unwrap();
+            assert_eq!(PathBuf::from("java"), ei.executable);
+            assert_eq!(vec!["-cp", "out/source123/Solution.jar", "Solution"], ei.args);
+        }
+
+        #[test]
+        fn jury() {
+            let program = make_program("out/jury/Main.jar");
+            let ei = make_provider().execute(&program, ProgramKind::Checker).unwrap();
+            assert_eq!(vec!["-cp", "out/jury/Main.jar:testlib.jar", "Main"], ei.args);
+        }
+
+        #[test]
+        fn runtime_flags() {
+            let mut prov = make_provider();
+            prov.config.runtime_flags = vec![
+                String::from("-XX:+UseSerialGC"),
+                String::from("-Xss64m"),
+            ];
+
+            let program = make_program("out/source123/Main.jar");
+            let ei = prov.execute(&program, ProgramKind::Judgee).unwrap();
+            assert_eq!(vec!["-XX:+UseSerialGC", "-Xss64m", "-cp", "out/source123/Main.jar", "Main"],
+                ei.args);
+        }
+    }
+}
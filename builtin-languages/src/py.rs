@@ -10,6 +10,7 @@ use crate::InitLanguageError;
 use crate::utils::Config;
 
 use judge::{
+    MemorySize,
     Program,
     ProgramKind,
 };
@@ -42,7 +43,12 @@ fn init_metadata() {
 /// Provide configuration for python language providers.
 #[derive(Debug, Clone, Deserialize)]
 struct PythonLanguageConfig {
+    /// Path to the directory containing module files of WaveTestLib.
     testlib_module_dir: PathBuf,
+
+    /// Extra flags passed to the Python interpreter when executing Python programs, e.g. `-S`.
+    #[serde(default)]
+    runtime_flags: Vec<String>,
 }
 
 impl Config for PythonLanguageConfig { }
@@ -65,24 +71,50 @@ impl LanguageProvider for PythonLanguageProvider {
         unsafe { METADATA.as_ref().unwrap() }
     }
 
+    fn source_extension(&self) -> &'static str {
+        "py"
+    }
+
+    fn memory_overhead(&self) -> MemorySize {
+        // The interpreter and the modules loaded at startup take about 30 megabytes of virtual
+        // memory.
+        MemorySize::MegaBytes(32)
+    }
+
     fn compile(&self, _program: &Program, _kind: ProgramKind, _output_dir: Option<PathBuf>)
         -> Result<CompilationInfo, Box<dyn std::error::Error>> {
         // Because python is an interpreted language, this function is not reachable.
         unreachable!()
     }
 
+    fn syntax_check(&self, program: &Program, _kind: ProgramKind)
+        -> Result<Option<CompilationInfo>, Box<dyn std::error::Error>> {
+        // `py_compile` writes the compiled bytecode next to the source file, so the source code is
+        // compiled in memory by the `compile` builtin instead.
+        let mut ci = CompilationInfo::new(
+            format!("python{}", program.language.version()), &program.file);
+        ci.compiler.add_arg("-c")?;
+        ci.compiler.add_arg(
+            "import sys; compile(open(sys.argv[1], 'rb').read(), sys.argv[1], 'exec')")?;
+        ci.compiler.add_arg(format!("{}", program.file.display()))?;
+        Ok(Some(ci))
+    }
+
     fn execute(&self, program: &Program, kind: ProgramKind)
         -> Result<ExecutionInfo, Box<dyn std::error::Error>> {
         let mut ei = ExecutionInfo::new(format!("python{}", program.language.version()));
-        ei.args.push(String::from("-OO"));
-        ei.args.push(String::from("-B"));
+        ei.add_arg("-OO")?;
+        ei.add_arg("-B")?;
+        for flag in &self.config.runtime_flags {
+            ei.add_arg(flag.clone())?;
+        }
 
         if kind.is_jury() {
-            ei.envs.push((String::from("PYTHONPATH"),
-                format!("{}", self.config.testlib_module_dir.display())));
+            ei.add_env("PYTHONPATH",
+                format!("{}", self.config.testlib_module_dir.display()))?;
         }
 
-        ei.args.push(format!("{}", program.file.display()));
+        ei.add_arg(format!("{}", program.file.display()))?;
         Ok(ei)
     }
 }
@@ -99,3 +131,61 @@ pub fn init_py_providers(lang: &mut LanguageProviderRegister) -> Result<(), Init
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use judge::languages::LanguageIdentifier;
+
+    fn make_config() -> PythonLanguageConfig {
+        PythonLanguageConfig {
+            testlib_module_dir: PathBuf::from("testlib"),
+            runtime_flags: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn source_extension() {
+        let prov = PythonLanguageProvider::new(make_config());
+        assert_eq!("py", prov.source_extension());
+    }
+
+    #[test]
+    fn memory_overhead() {
+        let prov = PythonLanguageProvider::new(make_config());
+        assert_eq!(MemorySize::MegaBytes(32), prov.memory_overhead());
+    }
+
+    #[test]
+    fn deserialize_without_runtime_flags() {
+        let config: PythonLanguageConfig = serde_yaml::from_str("testlib_module_dir: testlib")
+            .unwrap();
+        assert!(config.runtime_flags.is_empty());
+    }
+
+    #[test]
+    fn runtime_flags() {
+        let mut config = make_config();
+        config.runtime_flags = vec![String::from("-S"), String::from("-E")];
+        let prov = PythonLanguageProvider::new(config);
+
+        let program = Program::new("main.py",
+            LanguageIdentifier::new("python", LanguageBranch::new("cpy", "3.8")));
+        let ei = prov.execute(&program, ProgramKind::Judgee).unwrap();
+        assert_eq!(PathBuf::from("python3.8"), ei.executable);
+        assert_eq!(vec!["-OO", "-B", "-S", "-E", "main.py"], ei.args);
+    }
+
+    #[test]
+    fn syntax_check() {
+        let prov = PythonLanguageProvider::new(make_config());
+        let program = Program::new("main.py",
+            LanguageIdentifier::new("python", LanguageBranch::new("cpy", "3.8")));
+        let ci = prov.syntax_check(&program, ProgramKind::Judgee).unwrap().unwrap();
+        assert_eq!(PathBuf::from("python3.8"), ci.compiler.executable);
+        assert_eq!("-c", ci.compiler.args[0]);
+        assert_eq!("main.py", ci.compiler.args[2]);
+        assert_eq!(PathBuf::from("main.py"), ci.output_file);
+    }
+}
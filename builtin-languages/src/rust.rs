@@ -66,28 +66,32 @@ impl LanguageProvider for RustLanguageProvider {
         unsafe { METADATA.as_ref().unwrap() }
     }
 
+    fn source_extension(&self) -> &'static str {
+        "rs"
+    }
+
     fn compile(&self, program: &Program, kind: ProgramKind, output_dir: Option<PathBuf>)
         -> Result<CompilationInfo, Box<dyn std::error::Error>> {
         let output_file = crate::utils::make_output_file_path(&program.file, output_dir);
 
         let mut ci = CompilationInfo::new("rustup", output_file);
-        ci.compiler.args.push(String::from("run"));
-        ci.compiler.args.push(program.language.version().to_owned());
-        ci.compiler.args.push(String::from("rustc"));
-        ci.compiler.args.push(String::from("-C"));
-        ci.compiler.args.push(String::from("opt-level=2"));
-        ci.compiler.args.push(String::from("--cfg"));
-        ci.compiler.args.push(String::from("online_judge"));
+        ci.compiler.add_arg("run")?;
+        ci.compiler.add_arg(program.language.version().to_owned())?;
+        ci.compiler.add_arg("rustc")?;
+        ci.compiler.add_arg("-C")?;
+        ci.compiler.add_arg("opt-level=2")?;
+        ci.compiler.add_arg("--cfg")?;
+        ci.compiler.add_arg("online_judge")?;
 
         if kind.is_jury() {
-            ci.compiler.args.push(String::from("-L"));
-            ci.compiler.args.push(format!("{}", self.config.testlib_dir.display()));
+            ci.compiler.add_arg("-L")?;
+            ci.compiler.add_arg(format!("{}", self.config.testlib_dir.display()))?;
         }
 
-        ci.compiler.args.push(String::from("-o"));
-        ci.compiler.args.push(format!("{}", ci.output_file.display()));
+        ci.compiler.add_arg("-o")?;
+        ci.compiler.add_arg(format!("{}", ci.output_file.display()))?;
 
-        ci.compiler.args.push(format!("{}", program.file.display()));
+        ci.compiler.add_arg(format!("{}", program.file.display()))?;
 
         Ok(ci)
     }
@@ -111,3 +115,16 @@ pub fn init_rust_providers(lang: &mut LanguageProviderRegister) -> Result<(), In
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn source_extension() {
+        let prov = RustLanguageProvider::new(RustLanguageConfig {
+            testlib_dir: PathBuf::from("testlib"),
+        });
+        assert_eq!("rs", prov.source_extension());
+    }
+}
@@ -0,0 +1,80 @@
+//! This module implements the `cache` subcommand, through which operators can inspect and reclaim
+//! the problems and test archives cached on the local disk.
+//!
+
+use std::str::FromStr;
+
+use clap::ArgMatches;
+
+use crate::AppContext;
+use crate::restful::entities::ObjectId;
+
+error_chain::error_chain! {
+    types {
+        Error, ErrorKind, ResultExt, Result;
+    }
+
+    links {
+        ArchivesError(crate::storage::archives::Error, crate::storage::archives::ErrorKind);
+        ProblemsError(crate::storage::problems::Error, crate::storage::problems::ErrorKind);
+    }
+
+    errors {
+        InvalidObjectId(id: String) {
+            description("invalid object ID"),
+            display("invalid object ID: {}", id)
+        }
+    }
+}
+
+/// Build the definition of the `cache` subcommand.
+pub(crate) fn subcommand<'a, 'b>() -> clap::App<'a, 'b> {
+    clap::SubCommand::with_name("cache")
+        .about("Inspect or reclaim the problems and test archives cached on this node")
+        .setting(clap::AppSettings::SubcommandRequiredElseHelp)
+        .subcommand(clap::SubCommand::with_name("list")
+            .about("List the cached problems and test archives"))
+        .subcommand(clap::SubCommand::with_name("evict")
+            .about("Remove a cached test archive from the local disk")
+            .arg(clap::Arg::with_name("archive_id")
+                .value_name("ARCHIVE_ID")
+                .help("ID of the test archive to be removed")
+                .required(true)))
+}
+
+/// Print the cached problems and test archives to stdout.
+fn list(context: &AppContext) -> Result<()> {
+    println!("Problems:");
+    for problem in context.storage.problems.list_cached()? {
+        println!("  {}\t{:?}\tarchive {}\ttimestamp {}",
+            problem.id, problem.judge_mode, problem.archive_id, problem.timestamp);
+    }
+
+    println!("Archives:");
+    for (id, size) in context.storage.archives.list_cached()? {
+        println!("  {}\t{} bytes", id, size);
+    }
+
+    Ok(())
+}
+
+/// Remove the test archive specified on the command line from the local disk.
+fn evict(args: &ArgMatches<'_>, context: &AppContext) -> Result<()> {
+    let id = args.value_of("archive_id").expect("failed to get archive ID");
+    let id = ObjectId::from_str(id)
+        .map_err(|_| Error::from(ErrorKind::InvalidObjectId(String::from(id))))?;
+
+    context.storage.archives.evict(id)?;
+    println!("Evicted archive {}", id);
+
+    Ok(())
+}
+
+/// Execute the `cache` subcommand with the given arguments.
+pub(crate) fn run(args: &ArgMatches<'_>, context: &AppContext) -> Result<()> {
+    match args.subcommand() {
+        ("list", Some(..)) => list(context),
+        ("evict", Some(evict_args)) => evict(evict_args, context),
+        _ => unreachable!()
+    }
+}
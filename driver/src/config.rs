@@ -1,9 +1,17 @@
 //! This module maintains application wide configurations.
 //!
 
+use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
+use std::str::FromStr;
 
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+
+use sandbox::SystemCall;
+
+use judge::languages::LanguageIdentifier;
+
+use crate::restful::entities::{JudgeMode, LanguageTriple};
 
 error_chain::error_chain! {
     types {
@@ -19,12 +27,24 @@ error_chain::error_chain! {
         InvalidConfigFile {
             description("invalid config file")
         }
+
+        UnknownSystemCall(name: String) {
+            description("unknown system call in system call whitelist"),
+            display("unknown system call in system call whitelist: \"{}\"", name)
+        }
+
+        InvalidConfig(problems: Vec<String>) {
+            description("invalid configuration"),
+            display("invalid configuration: {}", problems.join("; "))
+        }
     }
 }
 
+/// Maximum number of workers.
+pub const MAX_WORKERS: u32 = 10;
 
 /// Provide application wide configurations.
-#[derive(Debug, Deserialize)]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct AppConfig {
     /// Number of workers.
     pub workers: u32,
@@ -37,10 +57,17 @@ pub struct AppConfig {
 
     /// Judge engine related configurations.
     pub engine: JudgeEngineConfig,
+
+    /// Path to the file into which metrics of this judge node are written in the Prometheus text
+    /// format after each submission is judged. Omit to disable exporting metrics.
+    #[serde(default)]
+    pub metrics_file: Option<PathBuf>,
 }
 
 impl AppConfig {
-    /// Load configuration information from the specified file.
+    /// Load configuration information from the specified file. Relative paths to the files
+    /// referenced by the configuration and loaded along with it, i.e. the system call whitelist
+    /// file, are resolved against the directory containing the configuration file.
     pub fn from_file<P>(path: P) -> Result<Self>
         where P: AsRef<Path> {
         let path = path.as_ref();
@@ -49,16 +76,151 @@ impl AppConfig {
         let config_content = std::fs::read_to_string(path)
             .chain_err(|| Error::from(ErrorKind::InvalidConfigFile))
             ?;
-        let config: AppConfig = serde_yaml::from_str(&config_content)
+        let mut config: AppConfig = serde_yaml::from_str(&config_content)
             .chain_err(|| Error::from(ErrorKind::InvalidConfigFile))
             ?;
+        let config_dir = path.parent().unwrap_or_else(|| Path::new(""));
+        config.engine.load_syscall_whitelist_file(config_dir)?;
 
         Ok(config)
     }
+
+    /// Check that the configuration is usable, so that mistakes in the configuration are reported
+    /// at startup instead of surfacing as errors at runtime. All problems found are reported in a
+    /// single `ErrorKind::InvalidConfig` error.
+    pub fn validate(&self) -> Result<()> {
+        let mut problems = Vec::new();
+
+        if self.workers == 0 || self.workers > MAX_WORKERS {
+            problems.push(format!("workers: must be between 1 and {}, got {}",
+                MAX_WORKERS, self.workers));
+        }
+
+        if let Err(e) = reqwest::Url::parse(&self.cluster.judge_board_url) {
+            problems.push(format!("cluster.judge_board_url: invalid URL \"{}\": {}",
+                self.cluster.judge_board_url, e));
+        }
+        if self.cluster.heartbeat_interval == 0 {
+            problems.push(String::from("cluster.heartbeat_interval: must be positive"));
+        }
+        if self.cluster.long_poll_wait == Some(0) {
+            problems.push(String::from("cluster.long_poll_wait: must be positive"));
+        }
+        let key_file = &self.cluster.authenticate_key_file;
+        match std::fs::read(key_file) {
+            Ok(data) => {
+                if let Err(e) = crate::restful::load_auth_key(&data) {
+                    problems.push(format!("cluster.authenticate_key_file: \"{}\": {}",
+                        key_file.display(), e));
+                }
+            },
+            Err(e) => problems.push(format!("cluster.authenticate_key_file: cannot read \"{}\": {}",
+                key_file.display(), e)),
+        };
+
+        let db_dir = self.storage.db_file.parent().unwrap_or_else(|| Path::new(""));
+        let dirs = [
+            ("storage.db_file", db_dir),
+            ("storage.archive_dir", self.storage.archive_dir.as_path()),
+            ("storage.jury_dir", self.storage.jury_dir.as_path()),
+        ];
+        for (name, dir) in dirs.iter() {
+            if let Err(e) = check_dir_creatable(dir) {
+                problems.push(format!("{}: {}", name, e));
+            }
+        }
+
+        let limits = [
+            ("engine.jury_cpu_time_limit", self.engine.jury_cpu_time_limit),
+            ("engine.jury_real_time_limit", self.engine.jury_real_time_limit),
+            ("engine.jury_memory_limit", self.engine.jury_memory_limit as u64),
+            ("engine.max_source_size", self.engine.max_source_size as u64),
+        ];
+        for (name, limit) in limits.iter() {
+            if *limit == 0 {
+                problems.push(format!("{}: must be positive", name));
+            }
+        }
+
+        let multipliers = &self.engine.real_time_multipliers;
+        let multipliers = [
+            ("standard", multipliers.standard),
+            ("special_judge", multipliers.special_judge),
+            ("interactive", multipliers.interactive),
+        ];
+        for (name, multiplier) in multipliers.iter() {
+            if !multiplier.is_finite() || *multiplier <= 0.0 {
+                problems.push(format!(
+                    "engine.real_time_multipliers.{}: must be a positive number, got {}",
+                    name, multiplier));
+            }
+        }
+
+        let bounds = &self.engine.problem_limit_bounds;
+        if bounds.min_time_limit == 0 || bounds.min_time_limit > bounds.max_time_limit {
+            problems.push(String::from(
+                "engine.problem_limit_bounds: time limit bounds must be positive and ordered"));
+        }
+        if bounds.min_memory_limit == 0 || bounds.min_memory_limit > bounds.max_memory_limit {
+            problems.push(String::from(
+                "engine.problem_limit_bounds: memory limit bounds must be positive and ordered"));
+        }
+
+        if self.engine.judgee_nice.map(|nice| nice < -20 || nice > 19).unwrap_or(false) {
+            problems.push(String::from("engine.judgee_nice: must range from -20 to 19"));
+        }
+        if self.engine.judgee_io_priority.map(|level| level > 7).unwrap_or(false) {
+            problems.push(String::from("engine.judgee_io_priority: must range from 0 to 7"));
+        }
+
+        for lang in self.engine.enabled_languages.iter().flatten() {
+            let valid = if lang.contains(':') {
+                LanguageTriple::from_str(lang).is_ok()
+            } else {
+                !lang.is_empty()
+            };
+            if !valid {
+                problems.push(format!("engine.enabled_languages: invalid language \"{}\"", lang));
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::from(ErrorKind::InvalidConfig(problems)))
+        }
+    }
+}
+
+/// Check that the given directory exists or can be created by the current process. Returns a
+/// description of the problem if not.
+fn check_dir_creatable(dir: &Path) -> std::result::Result<(), String> {
+    // An empty path refers to the current working directory.
+    let dir = if dir.as_os_str().is_empty() { Path::new(".") } else { dir };
+
+    let mut existing = dir;
+    while !existing.exists() {
+        existing = match existing.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+    }
+
+    if !existing.is_dir() {
+        return Err(format!("\"{}\" is not a directory", existing.display()));
+    }
+    if existing != dir {
+        if let Err(e) = nix::unistd::access(existing, nix::unistd::AccessFlags::W_OK) {
+            return Err(format!("cannot create \"{}\" under \"{}\": {}",
+                dir.display(), existing.display(), e));
+        }
+    }
+
+    Ok(())
 }
 
 /// Provide cluster related configurations.
-#[derive(Debug, Deserialize)]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct ClusterConfig {
     /// The endpoint of judge board.
     pub judge_board_url: String,
@@ -66,12 +228,35 @@ pub struct ClusterConfig {
     /// The time interval between two adjacent heartbeat packets.
     pub heartbeat_interval: u32,
 
-    /// Path to a PEM file containing the private key used for judge node authentication.
+    /// The maximal time interval between two adjacent heartbeat packets when heartbeat packets fail
+    /// consecutively, in seconds. Omit to use the default value.
+    #[serde(default)]
+    pub heartbeat_max_backoff: Option<u32>,
+
+    /// Path to a file containing the RSA private key used for judge node authentication, in either
+    /// the PEM or the DER format; see `restful::load_auth_key` for the supported formats.
     pub authenticate_key_file: PathBuf,
+
+    /// Maximal duration the judge board is asked to hold a request for submissions until a
+    /// submission becomes available, measured in seconds. Omit to poll for submissions at fixed
+    /// intervals instead, which also happens if the judge board does not support long polling.
+    #[serde(default)]
+    pub long_poll_wait: Option<u64>,
+
+    /// Maximal number of workers requesting for submissions from the judge board at the same time.
+    /// Omit to let workers request for submissions one at a time.
+    #[serde(default)]
+    pub max_concurrent_fetches: Option<u32>,
+
+    /// Minimal time interval between two adjacent progress updates of a submission reported to the
+    /// judge board while the submission is being judged, measured in milliseconds. Progress updates
+    /// within the interval are dropped. Defaults to 1000.
+    #[serde(default = "default_progress_report_interval")]
+    pub progress_report_interval: u64,
 }
 
 /// Provide storage related configurations.
-#[derive(Debug, Deserialize)]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct StorageConfig {
     /// Path to the database file that contains a sqlite database.
     pub db_file: PathBuf,
@@ -81,10 +266,22 @@ pub struct StorageConfig {
 
     /// The directory under which all the compiled jury programs will be maintained.
     pub jury_dir: PathBuf,
+
+    /// Number of leading test cases (ordered by name) in each test archive that are public sample
+    /// test cases. Data views are only reported for public test cases. This setting also applies
+    /// to test archives cached before it is changed.
+    #[serde(default)]
+    pub public_test_cases: usize,
+
+    /// Maximum total size of the cached test archives, measured in megabytes. The least recently
+    /// used archives are evicted when a new archive would exceed this limit. Omit to disable the
+    /// limit.
+    #[serde(default)]
+    pub archive_cache_max: Option<u64>,
 }
 
 /// Provide judge engine related configurations.
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct JudgeEngineConfig {
     /// The directory under which judge tasks will be performed.
     pub judge_dir: PathBuf,
@@ -92,12 +289,71 @@ pub struct JudgeEngineConfig {
     /// Paths to dynamic linking libraries containing language providers.
     pub language_dylibs: Vec<PathBuf>,
 
+    /// Languages accepted by this judge node. Each entry is either a language identifier such as
+    /// `cpp`, which enables all branches of the language, or a language triple such as
+    /// `cpp:gnu:c++17`. Omit to accept all languages whose providers are loaded, which is not
+    /// desired if the toolchains of some loaded providers are not installed on this judge node.
+    #[serde(default)]
+    pub enabled_languages: Option<Vec<String>>,
+
     /// The identity of the user to be used as the effective user of judgees.
     pub judge_username: String,
 
     /// System call whitelist for the judgee process.
+    #[serde(default)]
     pub judgee_syscall_whitelist: Vec<String>,
 
+    /// Path to a YAML file containing system call whitelists for the judgee and the jury. The
+    /// system calls listed in the file are appended to `judgee_syscall_whitelist` and
+    /// `jury_syscall_whitelist`, respectively. A relative path is resolved against the directory
+    /// containing the configuration file.
+    #[serde(default)]
+    pub syscall_whitelist_file: Option<PathBuf>,
+
+    /// Limit on the total size of the files the judgee can write into the judge directory,
+    /// measured in megabytes. Omit to disable the limit.
+    #[serde(default)]
+    pub judgee_disk_quota: Option<usize>,
+
+    /// Limit on the number of system calls the judgee can invoke. Omit to disable the limit.
+    #[serde(default)]
+    pub judgee_syscall_budget: Option<u64>,
+
+    /// Whether the address space of the judgee is additionally bounded by the kernel through
+    /// `RLIMIT_AS`, which stops the judgee from allocating memory unboundedly between two samplings
+    /// of its memory usage. Defaults to `false`.
+    #[serde(default)]
+    pub judgee_native_memory_limit: bool,
+
+    /// The nice value of the judgee, ranging from -20 to 19. A nice value higher than the one of
+    /// the judge node keeps the measurements of the judgee fair when the judge node is busy. Omit
+    /// to run the judgee with the nice value of the judge node.
+    #[serde(default)]
+    pub judgee_nice: Option<i32>,
+
+    /// The level of the best-effort I/O priority of the judgee, ranging from 0 (the highest) to 7
+    /// (the lowest). Omit to run the judgee with the I/O priority of the judge node.
+    #[serde(default)]
+    pub judgee_io_priority: Option<u8>,
+
+    /// Environment variables set for both the judgee and the jury. Defaults to `LC_ALL=C` so that
+    /// number formatting does not depend on the system locale of the judge node.
+    #[serde(default = "default_judge_env")]
+    pub judge_env: BTreeMap<String, String>,
+
+    /// Whether the contents written by the judgee to its stderr are captured and reported in the
+    /// judge result. Defaults to `true`.
+    #[serde(default = "default_capture_judgee_stderr")]
+    pub capture_judgee_stderr: bool,
+
+    /// Directory into which the outputs of judgees failing on test cases (e.g. on exceeding the
+    /// time limit) are preserved for debugging. Each judge task preserves its outputs into a
+    /// subdirectory of its own, and only the most recent subdirectories are kept. The size of the
+    /// preserved outputs is capped per test case and per judge task. Omit to disable preserving
+    /// outputs.
+    #[serde(default)]
+    pub preserved_output_dir: Option<PathBuf>,
+
     /// CPU time limit to be applied on the jury (the answer checkers and the interactors), measured
     /// in milliseconds.
     pub jury_cpu_time_limit: u64,
@@ -111,15 +367,296 @@ pub struct JudgeEngineConfig {
     pub jury_memory_limit: usize,
 
     /// System call whitelist for the jury (the answer checkers and the interactors) process.
+    #[serde(default)]
     pub jury_syscall_whitelist: Vec<String>,
+
+    /// Whether the jury is trusted and runs without the system call filter. Resource limits still
+    /// apply. Enable this only if every problem comes from trusted problem setters. Defaults to
+    /// `false`.
+    #[serde(default)]
+    pub trust_jury: bool,
+
+    /// Whether the judgee and the jury refuse to run without system call filtering if seccomp is
+    /// not available on the judge node, e.g. on some container hosts. If `false`, they run without
+    /// system call filtering in that case, and a warning is logged. Defaults to `false`.
+    #[serde(default)]
+    pub require_seccomp: bool,
+
+    /// Whether the answer checker runs when the judgee prints nothing, so that checkers accepting
+    /// empty output can decide the verdict. Defaults to `true`.
+    #[serde(default = "default_run_checker_on_empty_output")]
+    pub run_checker_on_empty_output: bool,
+
+    /// Whether a judgee exiting normally with a non-zero exit code gets the `RuntimeError` verdict.
+    /// If `false`, the exit code of the judgee is ignored and its output is checked as usual.
+    /// Problems may set their own policy, which overrides this one. Defaults to `true`.
+    #[serde(default = "default_treat_nonzero_exit_as_runtime_error")]
+    pub treat_nonzero_exit_as_runtime_error: bool,
+
+    /// Number of times a compilation is retried after a transient infrastructure error (e.g. the
+    /// judge node temporarily ran out of processes or memory). Compilation errors in the source
+    /// code are never retried. Defaults to 0.
+    #[serde(default)]
+    pub compile_retries: u32,
+
+    /// Maximum size of the source code of submissions accepted for compilation, measured in
+    /// kilobytes. Larger source code is rejected before it is written to the disk. Jury programs
+    /// compiled for problems are not limited. Defaults to 256.
+    #[serde(default = "default_max_source_size")]
+    pub max_source_size: usize,
+
+    /// Maximum number of test cases allowed in a single judge task. Omit to allow any number of
+    /// test cases.
+    #[serde(default)]
+    pub max_test_cases: Option<usize>,
+
+    /// Whether judge tasks exceeding `max_test_cases` are judged on the first `max_test_cases` test
+    /// cases only, instead of being rejected.
+    #[serde(default)]
+    pub truncate_test_suite: bool,
+
+    /// Wall-clock time budget of a single judge task, measured in milliseconds. Test cases left
+    /// when the budget runs out are not judged. Omit to disable the budget.
+    #[serde(default)]
+    pub total_judge_time_limit: Option<u64>,
+
+    /// Wall-clock deadline of processing a single submission, including downloading the test
+    /// archive, compiling and judging, measured in milliseconds. The time remaining is the time
+    /// budget of compiling and judging; submissions whose compilation cannot finish before the
+    /// deadline are reported as `JudgeFailed`, and test cases not judged before the deadline get
+    /// the `JudgeTimeLimitExceeded` verdict, so that a single submission cannot occupy a worker
+    /// for too long. Omit to disable the deadline.
+    #[serde(default)]
+    pub submission_deadline: Option<u64>,
+
+    /// Ratios between the real time limit and the CPU time limit of judgees, per judge mode.
+    #[serde(default)]
+    pub real_time_multipliers: RealTimeMultipliers,
+
+    /// Bounds of the time limits and the memory limits of problems.
+    #[serde(default)]
+    pub problem_limit_bounds: ProblemLimitBounds,
+
+    /// Time interval between two adjacent pings sent to the fork server to check its liveness,
+    /// measured in seconds. The fork server is restarted if it does not respond. Set to 0 to
+    /// disable the periodic pings. Defaults to 30.
+    #[serde(default = "default_fork_server_ping_interval")]
+    pub fork_server_ping_interval: u64,
+}
+
+/// Provide the ratios between the real time limit and the CPU time limit of judgees, per judge
+/// mode. All ratios default to 3.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RealTimeMultipliers {
+    /// The ratio for problems judged in standard mode.
+    pub standard: f64,
+
+    /// The ratio for problems judged in special judge mode.
+    pub special_judge: f64,
+
+    /// The ratio for problems judged in interactive mode, which usually needs to be larger since
+    /// the judgee waits for the interactor.
+    pub interactive: f64,
+}
+
+impl RealTimeMultipliers {
+    /// Get the ratio for problems judged in the given mode.
+    pub fn for_judge_mode(&self, mode: JudgeMode) -> f64 {
+        match mode {
+            JudgeMode::Standard => self.standard,
+            JudgeMode::SpecialJudge => self.special_judge,
+            JudgeMode::Interactive => self.interactive,
+        }
+    }
+}
+
+impl Default for RealTimeMultipliers {
+    fn default() -> Self {
+        RealTimeMultipliers {
+            standard: 3.0,
+            special_judge: 3.0,
+            interactive: 3.0,
+        }
+    }
+}
+
+/// Provide the bounds into which the time limits and the memory limits of problems are clamped, so
+/// that a zero or absurd limit given by the judge board does not fail every submission to the
+/// problem.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProblemLimitBounds {
+    /// Minimal time limit, in milliseconds. Defaults to 100.
+    pub min_time_limit: u64,
+
+    /// Maximal time limit, in milliseconds. Defaults to 60000.
+    pub max_time_limit: u64,
+
+    /// Minimal memory limit, in megabytes. Defaults to 16.
+    pub min_memory_limit: u64,
+
+    /// Maximal memory limit, in megabytes. Defaults to 4096.
+    pub max_memory_limit: u64,
+}
+
+impl ProblemLimitBounds {
+    /// Clamp the given time limit, in milliseconds, into the bounds.
+    pub fn clamp_time_limit(&self, limit: u64) -> u64 {
+        limit.max(self.min_time_limit).min(self.max_time_limit)
+    }
+
+    /// Clamp the given memory limit, in megabytes, into the bounds.
+    pub fn clamp_memory_limit(&self, limit: u64) -> u64 {
+        limit.max(self.min_memory_limit).min(self.max_memory_limit)
+    }
+}
+
+impl Default for ProblemLimitBounds {
+    fn default() -> Self {
+        ProblemLimitBounds {
+            min_time_limit: 100,
+            max_time_limit: 60000,
+            min_memory_limit: 16,
+            max_memory_limit: 4096,
+        }
+    }
+}
+
+impl JudgeEngineConfig {
+    /// Determine whether the given language is accepted by this judge node according to
+    /// `enabled_languages`.
+    pub fn is_language_enabled(&self, lang: &LanguageIdentifier) -> bool {
+        let enabled_languages = match self.enabled_languages {
+            Some(ref enabled_languages) => enabled_languages,
+            None => return true
+        };
+
+        enabled_languages.iter().any(|entry| {
+            if !entry.contains(':') {
+                return entry == lang.language();
+            }
+            match LanguageTriple::from_str(entry) {
+                Ok(triple) => triple.to_judge_language() == *lang,
+                Err(..) => false
+            }
+        })
+    }
+
+    /// Load the system call whitelists from `syscall_whitelist_file`, if any, and append them to
+    /// the whitelists given in this configuration. Then check that all the system calls in the
+    /// whitelists are known.
+    ///
+    /// A relative `syscall_whitelist_file` is resolved against the given directory containing the
+    /// configuration file, and replaced by the resolved path.
+    fn load_syscall_whitelist_file(&mut self, config_dir: &Path) -> Result<()> {
+        if let Some(ref mut path) = self.syscall_whitelist_file {
+            if path.is_relative() {
+                *path = config_dir.join(&path);
+            }
+            let whitelist = SyscallWhitelistConfig::from_file(path)?;
+            self.judgee_syscall_whitelist.extend(whitelist.judgee);
+            self.jury_syscall_whitelist.extend(whitelist.jury);
+        }
+
+        check_syscall_names(&self.judgee_syscall_whitelist)?;
+        check_syscall_names(&self.jury_syscall_whitelist)?;
+
+        Ok(())
+    }
+}
+
+/// Provide system call whitelists loaded from the file referenced by
+/// `JudgeEngineConfig::syscall_whitelist_file`.
+#[derive(Debug, Deserialize)]
+pub struct SyscallWhitelistConfig {
+    /// System call whitelist for the judgee process.
+    #[serde(default)]
+    pub judgee: Vec<String>,
+
+    /// System call whitelist for the jury (the answer checkers and the interactors) process.
+    #[serde(default)]
+    pub jury: Vec<String>,
+}
+
+impl SyscallWhitelistConfig {
+    /// Load system call whitelists from the specified YAML file.
+    pub fn from_file<P>(path: P) -> Result<Self>
+        where P: AsRef<Path> {
+        let path = path.as_ref();
+        log::info!("Loading system call whitelists from file: {}", path.display());
+
+        let content = std::fs::read_to_string(path)
+            .chain_err(|| Error::from(ErrorKind::InvalidConfigFile))
+            ?;
+        Self::from_yaml(&content)
+    }
+
+    /// Parse system call whitelists from the given YAML string. This function returns an
+    /// `UnknownSystemCall` error if any of the system calls is unknown.
+    fn from_yaml(yaml: &str) -> Result<Self> {
+        let whitelist: SyscallWhitelistConfig = serde_yaml::from_str(yaml)
+            .chain_err(|| Error::from(ErrorKind::InvalidConfigFile))
+            ?;
+        check_syscall_names(&whitelist.judgee)?;
+        check_syscall_names(&whitelist.jury)?;
+
+        Ok(whitelist)
+    }
+}
+
+/// Check that all the given system call names can be resolved by `SystemCall::from_name`.
+fn check_syscall_names(names: &[String]) -> Result<()> {
+    for name in names {
+        if SystemCall::from_name(name).is_err() {
+            return Err(Error::from(ErrorKind::UnknownSystemCall(name.clone())));
+        }
+    }
+
+    Ok(())
+}
+
+/// Get the default value of `ClusterConfig::progress_report_interval`.
+fn default_progress_report_interval() -> u64 {
+    1000
+}
+
+/// Get the default value of `JudgeEngineConfig::fork_server_ping_interval`.
+fn default_fork_server_ping_interval() -> u64 {
+    30
+}
+
+/// Get the default value of `JudgeEngineConfig::judge_env`.
+fn default_judge_env() -> BTreeMap<String, String> {
+    let mut env = BTreeMap::new();
+    env.insert(String::from("LC_ALL"), String::from("C"));
+    env
+}
+
+/// Get the default value of `JudgeEngineConfig::capture_judgee_stderr`.
+fn default_capture_judgee_stderr() -> bool {
+    true
+}
+
+/// Get the default value of `JudgeEngineConfig::run_checker_on_empty_output`.
+fn default_run_checker_on_empty_output() -> bool {
+    true
+}
+
+/// Get the default value of `JudgeEngineConfig::treat_nonzero_exit_as_runtime_error`.
+fn default_treat_nonzero_exit_as_runtime_error() -> bool {
+    true
+}
+
+/// Get the default value of `JudgeEngineConfig::max_source_size`.
+fn default_max_source_size() -> usize {
+    256
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    use std::str::FromStr;
-
     #[test]
     fn deserialize_app_config_yaml() {
         let yaml = r#"
@@ -159,4 +696,307 @@ mod tests {
         assert_eq!(vec!["open", "read", "write", "close", "exit"],
             config.engine.jury_syscall_whitelist);
     }
+
+    mod validate {
+        use super::*;
+
+        use tempfile::TempDir;
+
+        /// Make a valid configuration whose files are all placed under the given directory.
+        fn make_config(dir: &TempDir) -> AppConfig {
+            let key_file = dir.path().join("key.pem");
+            let key = openssl::rsa::Rsa::generate(1024).unwrap();
+            std::fs::write(&key_file, key.private_key_to_pem().unwrap()).unwrap();
+
+            let yaml = format!(r#"
+                workers: 3
+                cluster:
+                    judge_board_url: "http://judge_board"
+                    heartbeat_interval: 5
+                    authenticate_key_file: "{0}/key.pem"
+                storage:
+                    db_file: "{0}/db/judge.db"
+                    archive_dir: "{0}/archives"
+                    jury_dir: "{0}/jury"
+                engine:
+                    judge_dir: "{0}/judge"
+                    language_dylibs: []
+                    judge_username: "nobody"
+                    jury_cpu_time_limit: 1000
+                    jury_real_time_limit: 10000
+                    jury_memory_limit: 1024
+            "#, dir.path().display());
+            serde_yaml::from_str(&yaml).unwrap()
+        }
+
+        fn get_problems(config: &AppConfig) -> Vec<String> {
+            match config.validate() {
+                Ok(..) => Vec::new(),
+                Err(Error(ErrorKind::InvalidConfig(problems), _)) => problems,
+                Err(e) => panic!("unexpected error: {}", e),
+            }
+        }
+
+        #[test]
+        fn valid() {
+            let dir = tempfile::tempdir().unwrap();
+            assert!(get_problems(&make_config(&dir)).is_empty());
+        }
+
+        #[test]
+        fn invalid_workers() {
+            let dir = tempfile::tempdir().unwrap();
+            let mut config = make_config(&dir);
+            config.workers = 0;
+            assert_eq!(1, get_problems(&config).len());
+
+            config.workers = MAX_WORKERS + 1;
+            assert_eq!(1, get_problems(&config).len());
+        }
+
+        #[test]
+        fn invalid_judge_board_url() {
+            let dir = tempfile::tempdir().unwrap();
+            let mut config = make_config(&dir);
+            config.cluster.judge_board_url = String::from("judge board");
+
+            let problems = get_problems(&config);
+            assert_eq!(1, problems.len());
+            assert!(problems[0].starts_with("cluster.judge_board_url"));
+        }
+
+        #[test]
+        fn invalid_authenticate_key() {
+            let dir = tempfile::tempdir().unwrap();
+            let mut config = make_config(&dir);
+            std::fs::write(&config.cluster.authenticate_key_file, "not a key").unwrap();
+            assert_eq!(1, get_problems(&config).len());
+
+            config.cluster.authenticate_key_file = dir.path().join("missing.pem");
+            assert_eq!(1, get_problems(&config).len());
+        }
+
+        #[test]
+        fn uncreatable_directories() {
+            let dir = tempfile::tempdir().unwrap();
+            let mut config = make_config(&dir);
+            let file = dir.path().join("file");
+            std::fs::write(&file, "").unwrap();
+            config.storage.archive_dir = file.join("archives");
+            config.storage.jury_dir = file.clone();
+
+            let problems = get_problems(&config);
+            assert_eq!(2, problems.len());
+            assert!(problems[0].starts_with("storage.archive_dir"));
+            assert!(problems[1].starts_with("storage.jury_dir"));
+        }
+
+        #[test]
+        fn aggregated() {
+            let dir = tempfile::tempdir().unwrap();
+            let mut config = make_config(&dir);
+            config.workers = 0;
+            config.cluster.heartbeat_interval = 0;
+            config.engine.jury_memory_limit = 0;
+            assert_eq!(3, get_problems(&config).len());
+        }
+
+        #[test]
+        fn invalid_enabled_languages() {
+            let dir = tempfile::tempdir().unwrap();
+            let mut config = make_config(&dir);
+            config.engine.enabled_languages = Some(vec![
+                String::from("cpp"),
+                String::from("java:java:8"),
+                String::from("cpp:gnu"),
+                String::new(),
+            ]);
+
+            let problems = get_problems(&config);
+            assert_eq!(2, problems.len());
+            assert!(problems[0].starts_with("engine.enabled_languages"));
+        }
+
+        #[test]
+        fn invalid_problem_limit_bounds() {
+            let dir = tempfile::tempdir().unwrap();
+            let mut config = make_config(&dir);
+            config.engine.problem_limit_bounds.min_time_limit = 0;
+            config.engine.problem_limit_bounds.min_memory_limit = 8192;
+
+            let problems = get_problems(&config);
+            assert_eq!(2, problems.len());
+            assert!(problems[0].starts_with("engine.problem_limit_bounds"));
+        }
+
+        #[test]
+        fn invalid_long_poll_wait() {
+            let dir = tempfile::tempdir().unwrap();
+            let mut config = make_config(&dir);
+            config.cluster.long_poll_wait = Some(0);
+
+            let problems = get_problems(&config);
+            assert_eq!(1, problems.len());
+            assert!(problems[0].starts_with("cluster.long_poll_wait"));
+        }
+
+        #[test]
+        fn invalid_real_time_multipliers() {
+            let dir = tempfile::tempdir().unwrap();
+            let mut config = make_config(&dir);
+            config.engine.real_time_multipliers.standard = -1.0;
+            config.engine.real_time_multipliers.special_judge = std::f64::NAN;
+            config.engine.real_time_multipliers.interactive = std::f64::INFINITY;
+
+            let problems = get_problems(&config);
+            assert_eq!(3, problems.len());
+            assert!(problems[0].starts_with("engine.real_time_multipliers.standard"));
+        }
+
+        #[test]
+        fn invalid_judgee_priorities() {
+            let dir = tempfile::tempdir().unwrap();
+            let mut config = make_config(&dir);
+            config.engine.judgee_nice = Some(20);
+            config.engine.judgee_io_priority = Some(8);
+
+            let problems = get_problems(&config);
+            assert_eq!(2, problems.len());
+            assert!(problems[0].starts_with("engine.judgee_nice"));
+            assert!(problems[1].starts_with("engine.judgee_io_priority"));
+        }
+    }
+
+    mod enabled_languages {
+        use super::*;
+
+        use judge::languages::LanguageBranch;
+
+        fn lang(language: &str, dialect: &str, version: &str) -> LanguageIdentifier {
+            LanguageIdentifier::new(language, LanguageBranch::new(dialect, version))
+        }
+
+        fn config_with(enabled_languages: Option<Vec<&str>>) -> JudgeEngineConfig {
+            let mut config: JudgeEngineConfig = serde_yaml::from_str(r#"
+                judge_dir: "/judge"
+                language_dylibs: []
+                judge_username: "nobody"
+                jury_cpu_time_limit: 1000
+                jury_real_time_limit: 10000
+                jury_memory_limit: 1024
+            "#).unwrap();
+            config.enabled_languages = enabled_languages
+                .map(|langs| langs.into_iter().map(String::from).collect());
+            config
+        }
+
+        #[test]
+        fn all_enabled_by_default() {
+            let config = config_with(None);
+            assert!(config.is_language_enabled(&lang("cpp", "gnu", "c++17")));
+            assert!(config.is_language_enabled(&lang("python", "cpy", "3.8")));
+        }
+
+        #[test]
+        fn filtered() {
+            let config = config_with(Some(vec!["cpp", "python:cpy:3.8"]));
+            assert!(config.is_language_enabled(&lang("cpp", "gnu", "c++17")));
+            assert!(config.is_language_enabled(&lang("cpp", "clang", "c++11")));
+            assert!(config.is_language_enabled(&lang("python", "cpy", "3.8")));
+            assert!(!config.is_language_enabled(&lang("python", "cpy", "3.7")));
+            assert!(!config.is_language_enabled(&lang("java", "java", "8")));
+
+            let config = config_with(Some(Vec::new()));
+            assert!(!config.is_language_enabled(&lang("cpp", "gnu", "c++17")));
+        }
+    }
+
+    mod real_time_multipliers {
+        use super::*;
+
+        #[test]
+        fn defaults() {
+            let multipliers = RealTimeMultipliers::default();
+            assert_eq!(3.0, multipliers.for_judge_mode(JudgeMode::Standard));
+            assert_eq!(3.0, multipliers.for_judge_mode(JudgeMode::SpecialJudge));
+            assert_eq!(3.0, multipliers.for_judge_mode(JudgeMode::Interactive));
+        }
+
+        #[test]
+        fn select_by_judge_mode() {
+            let multipliers: RealTimeMultipliers = serde_yaml::from_str(r#"
+                special_judge: 4
+                interactive: 10.5
+            "#).unwrap();
+            assert_eq!(3.0, multipliers.for_judge_mode(JudgeMode::Standard));
+            assert_eq!(4.0, multipliers.for_judge_mode(JudgeMode::SpecialJudge));
+            assert_eq!(10.5, multipliers.for_judge_mode(JudgeMode::Interactive));
+        }
+    }
+
+    mod syscall_whitelist_config {
+        use super::*;
+
+        #[test]
+        fn parse_whitelists() {
+            let yaml = r#"
+                judgee: ["read", "write", "exit"]
+                jury: ["open", "close"]
+            "#;
+            let whitelist = SyscallWhitelistConfig::from_yaml(yaml).unwrap();
+            assert_eq!(vec!["read", "write", "exit"], whitelist.judgee);
+            assert_eq!(vec!["open", "close"], whitelist.jury);
+        }
+
+        #[test]
+        fn missing_whitelist() {
+            let whitelist = SyscallWhitelistConfig::from_yaml("judgee: [\"read\"]").unwrap();
+            assert!(whitelist.jury.is_empty());
+        }
+
+        #[test]
+        fn unknown_syscall() {
+            let yaml = r#"
+                judgee: ["read", "write"]
+                jury: ["open", "no_such_syscall"]
+            "#;
+            match SyscallWhitelistConfig::from_yaml(yaml) {
+                Err(Error(ErrorKind::UnknownSystemCall(name), _)) =>
+                    assert_eq!("no_such_syscall", name),
+                res => panic!("unexpected result: {:?}", res)
+            };
+        }
+
+        #[test]
+        fn relative_to_config_file() {
+            let dir = tempfile::tempdir().unwrap();
+            std::fs::write(dir.path().join("whitelist.yaml"), "judgee: [\"exit\"]").unwrap();
+            let config_file = dir.path().join("config.yaml");
+            std::fs::write(&config_file, r#"
+                workers: 1
+                cluster:
+                    judge_board_url: "http://judge_board"
+                    heartbeat_interval: 5
+                    authenticate_key_file: "key.pem"
+                storage:
+                    db_file: "judge.db"
+                    archive_dir: "archives"
+                    jury_dir: "jury"
+                engine:
+                    judge_dir: "judge"
+                    language_dylibs: []
+                    judge_username: "nobody"
+                    judgee_syscall_whitelist: ["read"]
+                    syscall_whitelist_file: "whitelist.yaml"
+                    jury_cpu_time_limit: 1000
+                    jury_real_time_limit: 10000
+                    jury_memory_limit: 1024
+            "#).unwrap();
+
+            let config = AppConfig::from_file(&config_file).unwrap();
+            assert_eq!(Some(dir.path().join("whitelist.yaml")),
+                config.engine.syscall_whitelist_file);
+            assert_eq!(vec!["read", "exit"], config.engine.judgee_syscall_whitelist);
+        }
+    }
 }
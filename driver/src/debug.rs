@@ -0,0 +1,138 @@
+//! This module implements the `debug-submission` subcommand, through which operators can judge a
+//! local source file against a problem on the judge board in the same way as the workers do. The
+//! judge result is printed to stdout and is not reported to the judge board.
+//!
+
+use std::str::FromStr;
+use std::time::Instant;
+
+use clap::ArgMatches;
+
+use crate::AppContext;
+use crate::restful::entities::{LanguageTriple, ObjectId, SubmissionInfo};
+
+error_chain::error_chain! {
+    types {
+        Error, ErrorKind, ResultExt, Result;
+    }
+
+    links {
+        WorkerError(crate::workers::Error, crate::workers::ErrorKind);
+    }
+
+    foreign_links {
+        IoError(::std::io::Error);
+        SerdeJsonError(::serde_json::Error);
+    }
+
+    errors {
+        InvalidObjectId(id: String) {
+            description("invalid object ID"),
+            display("invalid object ID: {}", id)
+        }
+
+        InvalidLanguageTriple(lang: String) {
+            description("invalid language triple"),
+            display("invalid language triple: {}", lang)
+        }
+    }
+}
+
+/// Build the definition of the `debug-submission` subcommand.
+pub(crate) fn subcommand<'a, 'b>() -> clap::App<'a, 'b> {
+    clap::SubCommand::with_name("debug-submission")
+        .about("Judge a local source file against a problem and print the judge result")
+        .arg(clap::Arg::with_name("problem")
+            .long("problem")
+            .value_name("PROBLEM_ID")
+            .help("ID of the problem to judge against")
+            .takes_value(true)
+            .required(true))
+        .arg(clap::Arg::with_name("lang")
+            .long("lang")
+            .value_name("LANG")
+            .help("language triple of the source file, in the form of identifier:dialect:version")
+            .takes_value(true)
+            .required(true))
+        .arg(clap::Arg::with_name("source")
+            .long("source")
+            .value_name("SOURCE_FILE")
+            .help("path to the source file to be judged")
+            .takes_value(true)
+            .required(true))
+}
+
+/// Build the submission described by the given arguments.
+fn get_submission(args: &ArgMatches<'_>) -> Result<SubmissionInfo> {
+    let problem_id = args.value_of("problem").expect("failed to get problem ID");
+    let problem_id = ObjectId::from_str(problem_id)
+        .map_err(|_| Error::from(ErrorKind::InvalidObjectId(String::from(problem_id))))?;
+
+    let lang = args.value_of("lang").expect("failed to get language");
+    let language = LanguageTriple::from_str(lang)
+        .map_err(|_| Error::from(ErrorKind::InvalidLanguageTriple(String::from(lang))))?;
+
+    let source_file = args.value_of("source").expect("failed to get source file");
+    let source = std::fs::read_to_string(source_file)?;
+
+    Ok(SubmissionInfo {
+        id: ObjectId::default(),
+        problem_id,
+        source,
+        language,
+    })
+}
+
+/// Execute the `debug-submission` subcommand with the given arguments.
+pub(crate) fn run(args: &ArgMatches<'_>, context: &AppContext) -> Result<()> {
+    let submission = get_submission(args)?;
+    let result = crate::workers::handle_submission(
+        &submission, Instant::now(), None, None, context)?;
+    println!("{}", serde_json::to_string_pretty(&result)?);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    mod get_submission {
+        use super::*;
+
+        fn get_matches<'a>(args: &[&str]) -> ArgMatches<'a> {
+            let mut argv = vec!["debug-submission"];
+            argv.extend_from_slice(args);
+            subcommand().get_matches_from_safe(argv).unwrap()
+        }
+
+        #[test]
+        fn ok() {
+            let source = tempfile::NamedTempFile::new().unwrap();
+            std::fs::write(source.path(), "int main() { }").unwrap();
+
+            let matches = get_matches(&["--problem", "0123456789abcdef01234567",
+                "--lang", "cpp:gnu:17", "--source", source.path().to_str().unwrap()]);
+            let submission = get_submission(&matches).unwrap();
+            assert_eq!("0123456789abcdef01234567", submission.problem_id.to_string());
+            assert_eq!("cpp", submission.language.identifier);
+            assert_eq!("int main() { }", submission.source);
+        }
+
+        #[test]
+        fn invalid_arguments() {
+            let matches = get_matches(&["--problem", "bad", "--lang", "cpp:gnu:17",
+                "--source", "/dev/null"]);
+            assert!(get_submission(&matches).is_err());
+
+            let matches = get_matches(&["--problem", "0123456789abcdef01234567", "--lang", "cpp",
+                "--source", "/dev/null"]);
+            assert!(get_submission(&matches).is_err());
+        }
+
+        #[test]
+        fn missing_arguments() {
+            assert!(subcommand().get_matches_from_safe(vec!["debug-submission"]).is_err());
+        }
+    }
+}
@@ -0,0 +1,148 @@
+//! This module implements the `dump-config` subcommand, through which operators can check the
+//! effective application configuration, i.e. the configuration file with the default values filled
+//! in and the system call whitelist file merged.
+//!
+
+use clap::ArgMatches;
+
+use crate::config::AppConfig;
+
+error_chain::error_chain! {
+    types {
+        Error, ErrorKind, ResultExt, Result;
+    }
+
+    links {
+        ConfigError(crate::config::Error, crate::config::ErrorKind);
+    }
+
+    foreign_links {
+        SerdeJsonError(::serde_json::Error);
+        SerdeYamlError(::serde_yaml::Error);
+    }
+}
+
+/// Build the definition of the `dump-config` subcommand.
+pub(crate) fn subcommand<'a, 'b>() -> clap::App<'a, 'b> {
+    clap::SubCommand::with_name("dump-config")
+        .about("Print the effective configuration and exit")
+        .arg(clap::Arg::with_name("format")
+            .long("format")
+            .value_name("FORMAT")
+            .help("Set the output format")
+            .takes_value(true)
+            .possible_values(&["yaml", "json"])
+            .default_value("yaml"))
+}
+
+/// Load the effective configuration from the given file. The whitelists in the system call
+/// whitelist file are already merged into the system call whitelists, so the path to the file is
+/// omitted from the effective configuration; otherwise loading the dumped configuration would
+/// append the whitelists in the file once more.
+fn load_effective_config<P>(config_file: P) -> Result<AppConfig>
+    where P: AsRef<std::path::Path> {
+    let mut config = AppConfig::from_file(config_file)?;
+    config.engine.syscall_whitelist_file = None;
+    Ok(config)
+}
+
+/// Serialize the given configuration in the given format, which is either `yaml` or `json`.
+fn dump(config: &AppConfig, format: &str) -> Result<String> {
+    match format {
+        "json" => Ok(serde_json::to_string_pretty(config)?),
+        _ => Ok(serde_yaml::to_string(config)?)
+    }
+}
+
+/// Execute the `dump-config` subcommand with the given arguments. `config_file` is the path to the
+/// application configuration file.
+///
+/// The subcommand runs before the application is initialized, so that the configuration can be
+/// checked even if the judge board cannot be reached.
+pub(crate) fn run<P>(args: &ArgMatches<'_>, config_file: P) -> Result<()>
+    where P: AsRef<std::path::Path> {
+    let config = load_effective_config(config_file)?;
+    config.validate()?;
+
+    let format = args.value_of("format").expect("failed to get output format");
+    println!("{}", dump(&config, format)?);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    mod dump {
+        use super::*;
+
+        fn make_config() -> AppConfig {
+            let yaml = r#"
+                workers: 3
+                cluster:
+                    judge_board_url: "http://judge_board"
+                    heartbeat_interval: 5
+                    authenticate_key_file: "/judge/key.pem"
+                    long_poll_wait: 30
+                storage:
+                    db_file: "/judge/db/judge.db"
+                    archive_dir: "/judge/archives"
+                    jury_dir: "/judge/jury"
+                engine:
+                    judge_dir: "/judge/judge"
+                    language_dylibs: ["/judge/lib/libcpp.so"]
+                    judge_username: "nobody"
+                    judgee_syscall_whitelist: ["read", "write"]
+                    jury_cpu_time_limit: 1000
+                    jury_real_time_limit: 10000
+                    jury_memory_limit: 1024
+                    real_time_multipliers:
+                        interactive: 5
+            "#;
+            serde_yaml::from_str(yaml).unwrap()
+        }
+
+        #[test]
+        fn yaml_round_trip() {
+            let config = make_config();
+            let dumped = dump(&config, "yaml").unwrap();
+            let loaded: AppConfig = serde_yaml::from_str(&dumped).unwrap();
+            assert_eq!(config, loaded);
+        }
+
+        #[test]
+        fn json_round_trip() {
+            let config = make_config();
+            let dumped = dump(&config, "json").unwrap();
+            let loaded: AppConfig = serde_json::from_str(&dumped).unwrap();
+            assert_eq!(config, loaded);
+        }
+
+        #[test]
+        fn whitelist_file_merged_once() {
+            let dir = tempfile::tempdir().unwrap();
+            let whitelist_file = dir.path().join("whitelist.yaml");
+            std::fs::write(&whitelist_file, "judgee: [\"exit\"]\njury: [\"open\"]\n").unwrap();
+            let mut config = make_config();
+            config.engine.syscall_whitelist_file = Some(whitelist_file);
+            let config_file = dir.path().join("config.yaml");
+            std::fs::write(&config_file, dump(&config, "yaml").unwrap()).unwrap();
+
+            let effective = load_effective_config(&config_file).unwrap();
+            assert_eq!(None, effective.engine.syscall_whitelist_file);
+            assert_eq!(vec!["read", "write", "exit"], effective.engine.judgee_syscall_whitelist);
+
+            // Loading the dumped configuration yields the same whitelists.
+            std::fs::write(&config_file, dump(&effective, "yaml").unwrap()).unwrap();
+            assert_eq!(effective, load_effective_config(&config_file).unwrap());
+        }
+
+        #[test]
+        fn defaults_included() {
+            let dumped = dump(&make_config(), "yaml").unwrap();
+            assert!(dumped.contains("capture_judgee_stderr: true"));
+            assert!(dumped.contains("LC_ALL: C"));
+        }
+    }
+}
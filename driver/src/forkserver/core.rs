@@ -3,7 +3,7 @@
 
 use std::time::Duration;
 
-use sandbox::{MemorySize, SystemCall};
+use sandbox::{IoPriority, MemoryLimitPolicy, MemorySize, SystemCall};
 
 use judge::{
     CompilationTaskDescriptor,
@@ -12,8 +12,10 @@ use judge::{
     JudgeResult,
 };
 use judge::engine::{
+    CheckerRegistry,
     JudgeEngine,
     JudgeEngineConfig,
+    JudgeEngineConfigBuilder,
 };
 
 use super::{Error, Result};
@@ -24,70 +26,102 @@ use super::ForkServerSocket;
 use crate::config::JudgeEngineConfig as AppJudgeEngineConfig;
 
 /// The entry point of the fork server. This function should never returns on normal execution.
-pub(super) fn fork_server_main(config: &AppJudgeEngineConfig, mut socket: ForkServerSocket)
-    -> Result<()> {
+/// `checkers` is the registry of in-process answer checkers used by the judge engine.
+pub(super) fn fork_server_main(config: &AppJudgeEngineConfig, checkers: CheckerRegistry,
+    mut socket: ForkServerSocket) -> Result<()> {
     // TODO: Change the return type of this function from `Result<()>` to `Result<!>` after the
     // TODO: never type `!` stablize.
 
     log::info!("Starting fork server");
-    let handler = CommandHandler::new(config);
+    let handler = CommandHandler::new(config, checkers)?;
     log::info!("Fork server started");
 
     loop {
         let cmd: Command = socket.receive()?;
         log::debug!("Fork server receives command: {:?}", cmd);
-        let res = handler.handle_cmd(cmd)?;
+        let res = handler.handle_cmd(cmd, &mut socket)?;
         socket.send(&res)?;
     }
 }
 
+/// Convert the given system call names into `SystemCall` values. Unknown names are logged and
+/// skipped.
+fn convert_syscall_whitelist(names: &[String]) -> Vec<SystemCall> {
+    names.iter()
+        .filter_map(|name| match SystemCall::from_name(name) {
+            Ok(syscall) => Some(syscall),
+            Err(e) => {
+                log::error!("Cannot identify system call: {}: {}", name, e);
+                None
+            }
+        })
+        .collect()
+}
+
 /// Get the judge engine configuration from the given application wide judge engine configuration.
-fn get_judge_engine_config(app_config: &AppJudgeEngineConfig) -> JudgeEngineConfig {
-    let mut engine_config = JudgeEngineConfig::new();
-
-    engine_config.judge_uid = match super::io::lookup_uid(&app_config.judge_username) {
-        Ok(Some(uid)) => Some(uid),
-        Ok(None) => {
-            log::warn!("Cannot lookup user: {}", app_config.judge_username);
-            None
+/// The configuration is built by `JudgeEngineConfigBuilder`, so that invalid configurations are
+/// reported as errors.
+fn get_judge_engine_config(app_config: &AppJudgeEngineConfig) -> Result<JudgeEngineConfig> {
+    let mut builder = JudgeEngineConfigBuilder::new();
+
+    match super::io::lookup_uid(&app_config.judge_username) {
+        Ok(Some(uid)) => {
+            builder.judge_uid(uid);
         },
-        Err(e) => {
-            log::error!("Failed to lookup user: {}: {}", app_config.judge_username, e);
-            None
-        }
+        Ok(None) => log::warn!("Cannot lookup user: {}", app_config.judge_username),
+        Err(e) => log::error!("Failed to lookup user: {}: {}", app_config.judge_username, e)
     };
 
-    engine_config.judge_dir = Some(app_config.judge_dir.clone());
+    // The builder requires the judge directory to exist.
+    std::fs::create_dir_all(&app_config.judge_dir)?;
+    builder.judge_dir(app_config.judge_dir.clone());
 
-    fn syscall_convert_and_push<T>(name: T, output: &mut Vec<SystemCall>)
-        where T: AsRef<str> {
-        let syscall = match SystemCall::from_name(name.as_ref()) {
-            Ok(sc) => sc,
-            Err(e) => {
-                log::error!("Cannot identify system call: {}: {}", name.as_ref(), e);
-                return;
-            }
-        };
-
-        output.push(syscall);
+    builder.judgee_syscall_whitelist(
+        convert_syscall_whitelist(&app_config.judgee_syscall_whitelist));
+    if let Some(quota) = app_config.judgee_disk_quota {
+        builder.judgee_disk_quota(MemorySize::MegaBytes(quota))?;
     }
-
-    for syscall_name in &app_config.judgee_syscall_whitelist {
-        syscall_convert_and_push(syscall_name, &mut engine_config.judgee_syscall_whitelist);
+    if let Some(budget) = app_config.judgee_syscall_budget {
+        builder.judgee_syscall_budget(budget);
+    }
+    builder.judgee_memory_limit_policy(if app_config.judgee_native_memory_limit {
+        MemoryLimitPolicy::Native
+    } else {
+        MemoryLimitPolicy::Daemon
+    });
+    if let Some(nice) = app_config.judgee_nice {
+        builder.judgee_nice(nice)?;
+    }
+    if let Some(level) = app_config.judgee_io_priority {
+        builder.judgee_io_priority(IoPriority::BestEffort(level))?;
+    }
+    builder.judge_env(app_config.judge_env.iter()
+        .map(|(name, value)| (name.clone(), value.clone()))
+        .collect())?;
+    builder.capture_judgee_stderr(app_config.capture_judgee_stderr);
+    if let Some(ref dir) = app_config.preserved_output_dir {
+        builder.preserved_output_dir(dir.clone());
     }
 
-    engine_config.jury_cpu_time_limit = Some(
-        Duration::from_millis(app_config.jury_cpu_time_limit));
-    engine_config.jury_real_time_limit = Some(
-        Duration::from_millis(app_config.jury_real_time_limit));
-    engine_config.jury_memory_limit = Some(
-        MemorySize::MegaBytes(app_config.jury_memory_limit));
+    builder.jury_cpu_time_limit(Duration::from_millis(app_config.jury_cpu_time_limit))?;
+    builder.jury_real_time_limit(Duration::from_millis(app_config.jury_real_time_limit))?;
+    builder.jury_memory_limit(MemorySize::MegaBytes(app_config.jury_memory_limit))?;
+    builder.jury_syscall_whitelist(convert_syscall_whitelist(&app_config.jury_syscall_whitelist));
+    builder.trust_jury(app_config.trust_jury);
+    builder.require_seccomp(app_config.require_seccomp);
+    builder.run_checker_on_empty_output(app_config.run_checker_on_empty_output);
+    builder.compile_retries(app_config.compile_retries);
+    builder.max_source_size(Some(MemorySize::KiloBytes(app_config.max_source_size)))?;
 
-    for syscall_name in &app_config.jury_syscall_whitelist {
-        syscall_convert_and_push(syscall_name, &mut engine_config.jury_syscall_whitelist);
+    if let Some(max) = app_config.max_test_cases {
+        builder.max_test_cases(max)?;
+    }
+    builder.truncate_test_suite(app_config.truncate_test_suite);
+    if let Some(limit) = app_config.total_judge_time_limit {
+        builder.total_judge_time_limit(Duration::from_millis(limit))?;
     }
 
-    engine_config
+    Ok(builder.build()?)
 }
 
 /// Implement the command handler used in the fork server. The command handler is just a thin
@@ -99,10 +133,11 @@ struct CommandHandler {
 }
 
 impl CommandHandler {
-    /// Create and initializes a new `CommandHandler`.
-    fn new(app_config: &AppJudgeEngineConfig) -> Self {
-        let engine_config = get_judge_engine_config(app_config);
-        let engine = JudgeEngine::with_config(engine_config);
+    /// Create and initializes a new `CommandHandler` whose judge engine looks up in-process answer
+    /// checkers in the given checker registry. Fails if the judge engine configuration is invalid.
+    fn new(app_config: &AppJudgeEngineConfig, checkers: CheckerRegistry) -> Result<Self> {
+        let engine_config = get_judge_engine_config(app_config)?;
+        let engine = JudgeEngine::with_checkers(engine_config, checkers);
 
         log::info!("Loading language provider dynamic libraries");
         for lang_so in &app_config.language_dylibs {
@@ -114,22 +149,36 @@ impl CommandHandler {
             };
         }
 
-        CommandHandler {
-            judge_engine: engine
+        for lang in engine.languages().languages() {
+            if app_config.is_language_enabled(&lang) {
+                log::info!("Language {} is enabled", lang);
+            } else {
+                log::info!("Language {} is disabled by configuration", lang);
+            }
         }
+
+        let mut checker_names = engine.checkers().names();
+        checker_names.sort();
+        log::info!("Registered checkers: {}", checker_names.join(", "));
+
+        Ok(CommandHandler {
+            judge_engine: engine
+        })
     }
 
-    /// Execute the given command.
-    fn handle_cmd(&self, cmd: Command) -> Result<CommandResult> {
+    /// Execute the given command. The progress of judge commands is sent through the given socket
+    /// before the result is returned.
+    fn handle_cmd(&self, cmd: Command, socket: &mut ForkServerSocket) -> Result<CommandResult> {
         match cmd {
             Command::Compile(task) => {
                 let task_result = self.handle_compile_task(task)?;
                 Ok(CommandResult::from(task_result))
             },
             Command::Judge(task) => {
-                let task_result = self.handle_judge_task(task)?;
+                let task_result = self.handle_judge_task(task, socket)?;
                 Ok(CommandResult::from(task_result))
             },
+            Command::Ping => Ok(CommandResult::Pong),
         }
     }
 
@@ -138,8 +187,77 @@ impl CommandHandler {
         self.judge_engine.compile(task).map_err(Error::from)
     }
 
-    /// Execute the given judge command, using the judge engine contained in this handler.
-    fn handle_judge_task(&self, task: JudgeTaskDescriptor) -> Result<JudgeResult> {
-        self.judge_engine.judge(task).map_err(Error::from)
+    /// Execute the given judge command, using the judge engine contained in this handler. The
+    /// progress of the judge task is sent through the given socket.
+    fn handle_judge_task(&self, task: JudgeTaskDescriptor, socket: &mut ForkServerSocket)
+        -> Result<JudgeResult> {
+        self.judge_engine.judge_streaming(task, |progress| {
+            if let Err(e) = socket.send(&CommandResult::Progress(*progress)) {
+                log::error!("Failed to send judge progress: {}", e);
+            }
+        }).map_err(Error::from)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Make an application judge engine configuration with the given judge directory and extra
+    /// YAML fields.
+    fn app_config(judge_dir: &std::path::Path, extra: &str) -> AppJudgeEngineConfig {
+        serde_yaml::from_str(&format!(r#"
+            judge_dir: "{}"
+            language_dylibs: []
+            judge_username: "nobody"
+            jury_cpu_time_limit: 1000
+            jury_real_time_limit: 10000
+            jury_memory_limit: 1024
+            {}
+        "#, judge_dir.display(), extra)).unwrap()
+    }
+
+    mod get_judge_engine_config {
+        use super::*;
+
+        use crate::forkserver::ErrorKind;
+
+        #[test]
+        fn judge_dir_created() {
+            let dir = tempfile::tempdir().unwrap();
+            let judge_dir = dir.path().join("judge");
+            get_judge_engine_config(&app_config(&judge_dir, "")).unwrap();
+            assert!(judge_dir.is_dir());
+        }
+
+        #[test]
+        fn invalid_config_rejected() {
+            let dir = tempfile::tempdir().unwrap();
+            let config = app_config(dir.path(), "truncate_test_suite: true");
+            match get_judge_engine_config(&config) {
+                Err(Error(ErrorKind::JudgeError(judge::ErrorKind::InvalidEngineConfig(..)), _)) =>
+                    (),
+                res => panic!("unexpected result: {:?}", res),
+            };
+        }
+    }
+
+    mod command_handler {
+        use super::*;
+
+        #[test]
+        fn registered_checkers_used() {
+            fn custom_checker(_context: &mut judge::engine::CheckerContext)
+                -> std::io::Result<judge::engine::CheckerResult> {
+                Ok(judge::engine::CheckerResult::accepted(None))
+            }
+
+            let dir = tempfile::tempdir().unwrap();
+            let mut checkers = CheckerRegistry::new();
+            checkers.register("custom", custom_checker);
+            let handler = CommandHandler::new(&app_config(dir.path(), ""), checkers).unwrap();
+            assert!(handler.judge_engine.checkers().find("custom").is_some());
+            assert!(handler.judge_engine.checkers().find("default").is_some());
+        }
     }
 }
@@ -3,7 +3,11 @@
 
 use std::ffi::CString;
 use std::fs::File;
-use std::os::unix::io::{FromRawFd};
+use std::os::unix::io::{AsRawFd, FromRawFd};
+use std::time::Duration;
+
+use nix::fcntl::{FcntlArg, OFlag};
+use nix::poll::{PollFd, PollFlags};
 
 /// Represents a pipe.
 pub struct Pipe {
@@ -14,15 +18,42 @@ pub struct Pipe {
     pub writer: File,
 }
 
-/// Create a new anonymous pipe.
+/// Create a new anonymous pipe. Both ends of the pipe are closed on `exec`.
 pub fn create_pipe() -> nix::Result<Pipe> {
-    let (reader_fd, writer_fd) = nix::unistd::pipe()?;
+    let (reader_fd, writer_fd) = nix::unistd::pipe2(OFlag::O_CLOEXEC)?;
     Ok(Pipe {
         reader: unsafe { File::from_raw_fd(reader_fd) },
         writer: unsafe { File::from_raw_fd(writer_fd) }
     })
 }
 
+/// Move the standard input and the standard output of the current process into a new pipe-like
+/// pair of files that are closed on `exec`. The standard input is then redirected to `/dev/null`
+/// and the standard output is redirected to the standard error, so that nothing else written to
+/// the standard output can interfere with the returned files.
+pub fn take_stdio() -> nix::Result<Pipe> {
+    let reader_fd = nix::fcntl::fcntl(libc::STDIN_FILENO, FcntlArg::F_DUPFD_CLOEXEC(3))?;
+    let reader = unsafe { File::from_raw_fd(reader_fd) };
+    let writer_fd = nix::fcntl::fcntl(libc::STDOUT_FILENO, FcntlArg::F_DUPFD_CLOEXEC(3))?;
+    let writer = unsafe { File::from_raw_fd(writer_fd) };
+
+    let null_fd = nix::fcntl::open(
+        "/dev/null", OFlag::O_RDONLY | OFlag::O_CLOEXEC, nix::sys::stat::Mode::empty())?;
+    nix::unistd::dup2(null_fd, libc::STDIN_FILENO)?;
+    nix::unistd::close(null_fd)?;
+    nix::unistd::dup2(libc::STDERR_FILENO, libc::STDOUT_FILENO)?;
+
+    Ok(Pipe { reader, writer })
+}
+
+/// Wait until the given file becomes readable or the given timeout elapses. Returns `false` if the
+/// timeout elapses. A file whose other end is closed is considered readable.
+pub fn wait_readable(file: &File, timeout: Duration) -> nix::Result<bool> {
+    let mut fds = [PollFd::new(file.as_raw_fd(), PollFlags::POLLIN)];
+    let timeout_ms = std::cmp::min(timeout.as_millis(), libc::c_int::max_value() as u128);
+    Ok(nix::poll::poll(&mut fds, timeout_ms as libc::c_int)? > 0)
+}
+
 /// Get a mutable reference to `errno`.
 fn get_errno_mut() -> &'static mut i32 {
     unsafe { libc::__errno_location().as_mut().unwrap() }
@@ -5,9 +5,12 @@ mod core;
 mod io;
 
 use std::fs::File;
-use std::sync::Mutex;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
 
-use nix::unistd::{Pid, ForkResult};
+use nix::unistd::Pid;
 use nix::sys::signal::Signal;
 
 use serde::{Serialize, Deserialize};
@@ -18,7 +21,9 @@ use judge::{
     CompilationResult,
     JudgeTaskDescriptor,
     JudgeResult,
+    JudgeProgress,
 };
+use judge::engine::CheckerRegistry;
 use judge::languages::LanguageIdentifier;
 
 use crate::config::JudgeEngineConfig;
@@ -38,8 +43,25 @@ error_chain::error_chain! {
     links {
         JudgeError(::judge::Error, ::judge::ErrorKind);
     }
+
+    errors {
+        PingTimeout(timeout: Duration) {
+            description("fork server did not respond to ping in time")
+            display("fork server did not respond to ping in {} ms", timeout.as_millis())
+        }
+
+        UnexpectedResult {
+            description("fork server responded with an unexpected command result")
+        }
+    }
 }
 
+/// Maximal time to wait for the fork server to respond to a ping.
+pub const PING_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Name of the hidden subcommand under which the application runs as a fork server.
+pub const FORK_SERVER_SUBCOMMAND: &str = "fork-server";
+
 /// Represent a command to be sent to the fork server.
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum Command {
@@ -48,6 +70,9 @@ pub enum Command {
 
     /// The judge command. The fork server will tries to execute the specified judge task.
     Judge(JudgeTaskDescriptor),
+
+    /// The ping command. The fork server responds with `CommandResult::Pong` immediately.
+    Ping,
 }
 
 impl From<CompilationTaskDescriptor> for Command {
@@ -89,7 +114,14 @@ pub enum CommandResult {
     Compile(CompilationResult),
 
     /// The result of a judge task.
-    Judge(JudgeResult)
+    Judge(JudgeResult),
+
+    /// The progress of the judge task under execution. Zero or more progress messages are sent
+    /// before the result of a judge task.
+    Progress(JudgeProgress),
+
+    /// The response to a ping command.
+    Pong,
 }
 
 impl CommandResult {
@@ -162,6 +194,20 @@ impl ForkServerSocket {
         let value: T = rmp_serde::decode::from_read(&mut self.reader)?;
         Ok(value)
     }
+
+    /// Send a ping command through the socket and wait for the pong response for at most the given
+    /// duration.
+    fn ping(&mut self, timeout: Duration) -> Result<()> {
+        self.send(&Command::Ping)?;
+        if !io::wait_readable(&self.reader, timeout)? {
+            return Err(Error::from(ErrorKind::PingTimeout(timeout)));
+        }
+
+        match self.receive()? {
+            CommandResult::Pong => Ok(()),
+            _ => Err(Error::from(ErrorKind::UnexpectedResult))
+        }
+    }
 }
 
 /// Represent a fork server socket pair. The socke pair contains two sockets that are internally
@@ -180,51 +226,130 @@ impl ForkServerSocketPair {
     }
 }
 
-/// Provide a client through which one can communicate with the fork server.
-pub struct ForkServerClient {
+/// Represent a connection to a running fork server.
+struct ForkServerConnection {
     /// The socket to the fork server.
-    socket: Mutex<ForkServerSocket>,
+    socket: ForkServerSocket,
 
     /// Pid of the fork server.
-    pub fork_server_id: Pid,
+    fork_server_id: Pid,
+}
+
+impl ForkServerConnection {
+    /// Kill the fork server and reap it.
+    fn kill(&self) {
+        nix::sys::signal::kill(self.fork_server_id, Signal::SIGKILL).ok();
+        nix::sys::wait::waitpid(self.fork_server_id, None).ok();
+    }
+}
+
+/// Provide a client through which one can communicate with the fork server.
+pub struct ForkServerClient {
+    /// The connection to the fork server, which is replaced when the fork server restarts.
+    conn: Mutex<ForkServerConnection>,
+
+    /// The configuration the fork server is started with, which is used to restart it.
+    config: JudgeEngineConfig,
+
+    /// Path to the log configuration file the fork server is started with.
+    log_config_file: PathBuf,
+
+    /// Maximum size of the source code accepted by `compile_source`, in bytes.
+    max_source_size: usize,
 }
 
 impl ForkServerClient {
     /// Create a new `ForkServerClient` value.
-    fn new(socket: ForkServerSocket, fork_server_id: Pid) -> Self {
+    fn new(conn: ForkServerConnection, config: &JudgeEngineConfig, log_config_file: &Path)
+        -> Self {
         ForkServerClient {
-            socket: Mutex::new(socket),
-            fork_server_id
+            conn: Mutex::new(conn),
+            config: config.clone(),
+            log_config_file: log_config_file.to_owned(),
+            max_source_size: config.max_source_size * 1024,
+        }
+    }
+
+    /// Lock the connection to the fork server.
+    fn lock(&self) -> MutexGuard<'_, ForkServerConnection> {
+        self.conn.lock().expect("failed to lock mutex: poisoned")
+    }
+
+    /// Kill the fork server behind the given connection and replace it with a new one started
+    /// with the same configuration.
+    fn restart(&self, conn: &mut ForkServerConnection) -> Result<()> {
+        log::warn!("Restarting fork server {}", conn.fork_server_id);
+        conn.kill();
+        *conn = spawn_fork_server(&self.config, &self.log_config_file)?;
+        log::info!("Fork server restarted as {}", conn.fork_server_id);
+
+        Ok(())
+    }
+
+    /// Ping the fork server with `PING_TIMEOUT`, and restart it if it does not respond. The fork
+    /// server handles commands one at a time, so this function blocks until the commands under
+    /// execution finish before sending the ping.
+    ///
+    /// The fork server is restarted whenever the ping fails, so that a late pong response never
+    /// stays in the socket to be taken as the result of the next command.
+    pub fn ensure_alive(&self) -> Result<()> {
+        let mut conn = self.lock();
+        if let Err(e) = conn.socket.ping(PING_TIMEOUT) {
+            log::error!("Fork server is not responding: {}", e);
+            self.restart(&mut conn)?;
         }
+
+        Ok(())
     }
 
     /// Execute the given command on the fork server.
     pub fn execute_cmd(&self, cmd: &Command) -> Result<CommandResult> {
-        let mut lock = self.socket.lock().expect("failed to lock mutex: poisoned");
-        lock.send(cmd)?;
-        Ok(lock.receive()?)
+        self.execute_cmd_streaming(cmd, |_| ())
+    }
+
+    /// Execute the given command on the fork server, calling the given function with the progress
+    /// of the judge task after each test case is judged, if the command is a judge command.
+    pub fn execute_cmd_streaming<F>(&self, cmd: &Command, mut on_progress: F)
+        -> Result<CommandResult>
+        where F: FnMut(&JudgeProgress) {
+        let mut conn = self.lock();
+        conn.socket.send(cmd)?;
+        loop {
+            match conn.socket.receive()? {
+                CommandResult::Progress(progress) => on_progress(&progress),
+                result => return Ok(result)
+            };
+        }
     }
 }
 
 impl Drop for ForkServerClient {
     fn drop(&mut self) {
         // Kill the fork server process.
-        nix::sys::signal::kill(self.fork_server_id, Signal::SIGKILL).ok();
+        let conn = self.conn.get_mut().unwrap_or_else(|e| e.into_inner());
+        nix::sys::signal::kill(conn.fork_server_id, Signal::SIGKILL).ok();
     }
 }
 
 /// Provide extension functions for `ForkServerClient`.
 pub trait ForkServerClientExt {
-    /// Compile the literal source code into executable file.
-    fn compile_source<T>(&self, source: &T, lang: LanguageIdentifier, kind: ProgramKind)
-        -> Result<CompilationResult>
+    /// Compile the literal source code into executable file, killing the compiler if it runs
+    /// longer than the given time limit. Source code of judgees larger than the maximum source
+    /// size configured in `JudgeEngineConfig::max_source_size` is rejected with a failed
+    /// compilation result without being written to the disk; jury programs are not limited.
+    fn compile_source<T>(&self, source: &T, lang: LanguageIdentifier, kind: ProgramKind,
+        time_limit: Option<Duration>) -> Result<CompilationResult>
         where T: ?Sized + AsRef<str>;
 }
 
 impl ForkServerClientExt for ForkServerClient {
-    fn compile_source<T>(&self, source: &T, lang: LanguageIdentifier, kind: ProgramKind)
-        -> Result<CompilationResult>
+    fn compile_source<T>(&self, source: &T, lang: LanguageIdentifier, kind: ProgramKind,
+        time_limit: Option<Duration>) -> Result<CompilationResult>
         where T: ?Sized + AsRef<str> {
+        if let Some(result) = check_source_size(source.as_ref(), kind, self.max_source_size) {
+            return Ok(result);
+        }
+
         // Create a temp file to store the source code of jury.
         let src_file = tempfile::NamedTempFile::new()?;
         std::fs::write(src_file.path(), source.as_ref())?;
@@ -236,6 +361,7 @@ impl ForkServerClientExt for ForkServerClient {
         let output_dir = tempfile::tempdir()?;
         task.output_dir = Some(output_dir.path().to_owned());
         task.kind = kind;
+        task.time_limit = time_limit;
 
         // Execute the compilation job.
         let cmd = Command::Compile(task);
@@ -245,23 +371,217 @@ impl ForkServerClientExt for ForkServerClient {
     }
 }
 
-/// Start the fork server.
-pub fn start_fork_server(judge_engine_config: &JudgeEngineConfig) -> Result<ForkServerClient> {
-    let sock_pair = ForkServerSocketPair::new()?;
-
-    // The first component of sock_pair (`sock_pair.0`) will be passed to the client and the second
-    // component of sock_pair (`sock_pair.1`) will be passed to the fork server.
-    match nix::unistd::fork()? {
-        ForkResult::Parent { child: fork_server_pid } => {
-            // Close the second component of sock_pair
-            drop(sock_pair.1);
-            Ok(ForkServerClient::new(sock_pair.0, fork_server_pid))
-        },
-        ForkResult::Child => {
-            // Close the first component of sock_pair and enter the fork server main.
-            drop(sock_pair.0);
-            core::fork_server_main(judge_engine_config, sock_pair.1)?;
-            unreachable!()
+/// Start a daemon thread pinging the given fork server at the given interval, which restarts the
+/// fork server if it does not respond.
+pub fn start_health_check(client: Arc<ForkServerClient>, interval: Duration) {
+    std::thread::spawn(move || {
+        loop {
+            std::thread::sleep(interval);
+            if let Err(e) = client.ensure_alive() {
+                log::error!("Failed to restart fork server: {}", e);
+            }
+        }
+    });
+}
+
+/// Check the size of the given source code of a program of the given kind against the given
+/// maximum size in bytes. Returns the compilation result to be reported if the source code is too
+/// large. The source code of jury programs is not checked.
+fn check_source_size(source: &str, kind: ProgramKind, max_size: usize)
+    -> Option<CompilationResult> {
+    if !kind.is_jury() && source.len() > max_size {
+        Some(CompilationResult::source_too_large(source.len(), max_size))
+    } else {
+        None
+    }
+}
+
+/// Start the fork server. `log_config_file` is the path to the log configuration file used by the
+/// fork server.
+pub fn start_fork_server<P>(judge_engine_config: &JudgeEngineConfig, log_config_file: P)
+    -> Result<ForkServerClient>
+    where P: AsRef<Path> {
+    let log_config_file = log_config_file.as_ref();
+    let conn = spawn_fork_server(judge_engine_config, log_config_file)?;
+    Ok(ForkServerClient::new(conn, judge_engine_config, log_config_file))
+}
+
+/// Start a mock fork server on a thread of the current process, which responds to each command
+/// with the result returned by the given function, and get the client connected to it.
+#[cfg(test)]
+pub(crate) fn start_mock_fork_server<F>(mut handler: F) -> ForkServerClient
+    where F: FnMut(Command) -> CommandResult + Send + 'static {
+    let config: JudgeEngineConfig = serde_yaml::from_str(r#"
+        judge_dir: "/judge"
+        language_dylibs: []
+        judge_username: "nobody"
+        jury_cpu_time_limit: 1000
+        jury_real_time_limit: 10000
+        jury_memory_limit: 1024
+    "#).unwrap();
+
+    let ForkServerSocketPair(socket, mut server_socket) = ForkServerSocketPair::new().unwrap();
+    std::thread::spawn(move || {
+        while let Ok(cmd) = server_socket.receive::<Command>() {
+            if server_socket.send(&handler(cmd)).is_err() {
+                break;
+            }
+        }
+    });
+
+    // The client kills the fork server process when it is dropped. An exited child process that is
+    // never reaped stands in for the fork server process so that no other process is killed.
+    let placeholder = std::process::Command::new("true").spawn().unwrap();
+    let conn = ForkServerConnection {
+        socket,
+        fork_server_id: Pid::from_raw(placeholder.id() as libc::pid_t),
+    };
+    ForkServerClient::new(conn, &config, Path::new("/dev/null"))
+}
+
+/// Start a new fork server process and get the connection to it.
+///
+/// The fork server is started by executing the current executable under the hidden fork server
+/// subcommand instead of forking the current process, which may be running other threads. Only
+/// the fork server end of the socket pair is inherited by the new process; it is passed as the
+/// standard input and the standard output. The configuration of the fork server is then sent as
+/// the first message through the socket.
+fn spawn_fork_server(judge_engine_config: &JudgeEngineConfig, log_config_file: &Path)
+    -> Result<ForkServerConnection> {
+    let ForkServerSocketPair(socket, server_socket) = ForkServerSocketPair::new()?;
+    let ForkServerSocket { reader, writer } = server_socket;
+
+    let child = std::process::Command::new("/proc/self/exe")
+        .arg("--logconfig")
+        .arg(log_config_file)
+        .arg(FORK_SERVER_SUBCOMMAND)
+        .stdin(Stdio::from(reader))
+        .stdout(Stdio::from(writer))
+        .spawn()?;
+    let mut conn = ForkServerConnection {
+        socket,
+        fork_server_id: Pid::from_raw(child.id() as libc::pid_t),
+    };
+    if let Err(e) = conn.socket.send(judge_engine_config) {
+        conn.kill();
+        return Err(e);
+    }
+
+    Ok(conn)
+}
+
+/// Run the current process as a fork server. The current process should be started by
+/// `spawn_fork_server`. `init_log` is called to initialize log facilities after the standard output
+/// has been moved away from the socket to the client. The judge engine in the fork server looks up
+/// in-process answer checkers in the given checker registry.
+pub fn run_fork_server<F, E>(init_log: F, checkers: CheckerRegistry) -> Result<()>
+    where F: FnOnce() -> std::result::Result<(), E>,
+          E: std::error::Error + Send + 'static {
+    let pipe = io::take_stdio()?;
+    init_log().chain_err(|| "failed to initialize log facilities in the fork server")?;
+
+    let mut socket = ForkServerSocket::from_pipes(pipe.reader, pipe.writer);
+    let judge_engine_config: JudgeEngineConfig = socket.receive()?;
+    core::fork_server_main(&judge_engine_config, checkers, socket)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    mod ping {
+        use super::*;
+
+        #[test]
+        fn protocol_round_trip() {
+            let mut sock_pair = ForkServerSocketPair::new().unwrap();
+            sock_pair.0.send(&Command::Ping).unwrap();
+            match sock_pair.1.receive::<Command>().unwrap() {
+                Command::Ping => (),
+                cmd => panic!("unexpected command: {:?}", cmd)
+            };
+
+            sock_pair.1.send(&CommandResult::Pong).unwrap();
+            match sock_pair.0.receive::<CommandResult>().unwrap() {
+                CommandResult::Pong => (),
+                res => panic!("unexpected command result: {:?}", res)
+            };
+        }
+
+        #[test]
+        fn responded() {
+            let ForkServerSocketPair(mut client, mut server) = ForkServerSocketPair::new().unwrap();
+            let server = std::thread::spawn(move || {
+                let _cmd: Command = server.receive().unwrap();
+                server.send(&CommandResult::Pong).unwrap();
+            });
+
+            client.ping(Duration::from_secs(5)).unwrap();
+            server.join().unwrap();
+        }
+
+        #[test]
+        fn timed_out() {
+            let ForkServerSocketPair(mut client, _server) = ForkServerSocketPair::new().unwrap();
+            match client.ping(Duration::from_millis(50)) {
+                Err(Error(ErrorKind::PingTimeout(..), _)) => (),
+                r => panic!("unexpected result: {:?}", r)
+            };
+        }
+
+        #[test]
+        fn dead_server() {
+            let ForkServerSocketPair(mut client, server) = ForkServerSocketPair::new().unwrap();
+            drop(server);
+            assert!(client.ping(Duration::from_secs(5)).is_err());
+        }
+    }
+
+    mod spawn {
+        use super::*;
+
+        #[test]
+        fn config_round_trip() {
+            let mut config: JudgeEngineConfig = serde_yaml::from_str(r#"
+                judge_dir: "/judge"
+                language_dylibs: []
+                judge_username: "nobody"
+                jury_cpu_time_limit: 1000
+                jury_real_time_limit: 10000
+                jury_memory_limit: 1024
+            "#).unwrap();
+            config.enabled_languages = Some(vec![String::from("cpp")]);
+            config.judgee_nice = Some(5);
+            config.judge_env.insert(String::from("LANG"), String::from("C"));
+
+            let ForkServerSocketPair(mut client, mut server) = ForkServerSocketPair::new().unwrap();
+            client.send(&config).unwrap();
+            assert_eq!(config, server.receive::<JudgeEngineConfig>().unwrap());
+        }
+    }
+
+    mod check_source_size {
+        use super::*;
+
+        #[test]
+        fn oversized_source_rejected() {
+            let source = "a".repeat(257 * 1024);
+            let result = check_source_size(&source, ProgramKind::Judgee, 256 * 1024).unwrap();
+            assert!(!result.succeeded);
+            assert!(result.compiler_out.unwrap().starts_with("source too large"));
+        }
+
+        #[test]
+        fn source_accepted() {
+            assert!(check_source_size("int main() { }", ProgramKind::Judgee, 256 * 1024).is_none());
+            assert!(check_source_size(&"a".repeat(1024), ProgramKind::Judgee, 1024).is_none());
+        }
+
+        #[test]
+        fn jury_source_accepted() {
+            let source = "a".repeat(257 * 1024);
+            assert!(check_source_size(&source, ProgramKind::Checker, 256 * 1024).is_none());
+            assert!(check_source_size(&source, ProgramKind::Interactor, 256 * 1024).is_none());
         }
     }
 }
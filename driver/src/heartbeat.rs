@@ -3,7 +3,7 @@
 //!
 
 use std::time::Duration;
-use std::sync::Arc;
+use std::sync::{Arc, Once};
 
 use procfs::{CpuInfo, Meminfo};
 
@@ -59,45 +59,128 @@ impl MemoryFootprint {
 }
 
 /// Create a new heartbeat packet.
-fn create_heartbeat() -> Result<Heartbeat> {
+///
+/// Statistics that cannot be read from `/proc` are left zero, and the error is logged only the
+/// first time it happens to avoid flooding the log on every heartbeat.
+fn create_heartbeat() -> Heartbeat {
+    static CORES_ERROR: Once = Once::new();
+    static MEMORY_ERROR: Once = Once::new();
+
     let mut hb = Heartbeat::new();
-    let memory = MemoryFootprint::new()?;
 
-    hb.cores = get_cores()?;
-    hb.total_physical_memory = memory.total_physical_memory;
-    hb.free_physical_memory = memory.free_physical_memory;
-    hb.total_swap_space = memory.total_swap_space;
-    hb.free_swap_space = memory.free_swap_space;
-    hb.cached_swap_space = memory.cached_swap_space;
+    match get_cores() {
+        Ok(cores) => hb.cores = cores,
+        Err(e) => CORES_ERROR.call_once(|| {
+            log::error!("failed to get number of CPU cores from /proc/cpuinfo: {}", e);
+        })
+    };
+
+    match MemoryFootprint::new() {
+        Ok(memory) => {
+            hb.total_physical_memory = memory.total_physical_memory;
+            hb.free_physical_memory = memory.free_physical_memory;
+            hb.total_swap_space = memory.total_swap_space;
+            hb.free_swap_space = memory.free_swap_space;
+            hb.cached_swap_space = memory.cached_swap_space;
+        },
+        Err(e) => MEMORY_ERROR.call_once(|| {
+            log::error!("failed to get memory statistics from /proc/meminfo: {}", e);
+        })
+    };
 
-    Ok(hb)
+    hb
 }
 
 /// The minimal number of seconds between two adjacent heartbeat packets.
 const MIN_HEARTBEAT_INTERVAL: Duration = Duration::from_secs(3);
 
+/// The default maximal delay between two adjacent heartbeat packets after consecutive failures.
+const DEFAULT_MAX_HEARTBEAT_BACKOFF: Duration = Duration::from_secs(60);
+
+/// Compute the delays between adjacent heartbeat packets. The delay doubles after each consecutive
+/// failure to send a heartbeat packet, up to a maximal value, and is reset after a success.
+#[derive(Debug, Clone, Copy)]
+struct HeartbeatBackoff {
+    /// The delay when no failure happens.
+    interval: Duration,
+
+    /// The maximal delay.
+    max_delay: Duration,
+
+    /// Number of consecutive failures.
+    failures: u32,
+}
+
+impl HeartbeatBackoff {
+    /// Create a new `HeartbeatBackoff` value.
+    fn new(interval: Duration, max_delay: Duration) -> Self {
+        HeartbeatBackoff {
+            interval,
+            max_delay: *crate::utils::max(&max_delay, &interval),
+            failures: 0,
+        }
+    }
+
+    /// Get the delay before sending the next heartbeat packet.
+    fn delay(&self) -> Duration {
+        let mut delay = self.interval;
+        for _ in 0..self.failures {
+            delay *= 2;
+            if delay >= self.max_delay {
+                return self.max_delay;
+            }
+        }
+
+        delay
+    }
+
+    /// Record a failure to send a heartbeat packet.
+    fn on_failure(&mut self) {
+        self.failures = self.failures.saturating_add(1);
+    }
+
+    /// Record a success to send a heartbeat packet. Returns `true` if the previous heartbeat packet
+    /// failed, i.e. the judge board server becomes reachable again.
+    fn on_success(&mut self) -> bool {
+        let recovered = self.failures > 0;
+        self.failures = 0;
+        recovered
+    }
+}
+
+/// Send a heartbeat packet to the judge board server and record the outcome in the given backoff.
+/// The `on_recovered` callback of the given options is called if the judge board server becomes
+/// reachable again after previous heartbeat packets failed.
+fn send_heartbeat(options: &HeartbeatDaemonOptions, backoff: &mut HeartbeatBackoff) {
+    let heartbeat = create_heartbeat();
+
+    if let Err(e) = options.rest.patch_heartbeat(&heartbeat) {
+        backoff.on_failure();
+        log::error!("failed to send heartbeat packet: {}. Retry in {} seconds.",
+            e, backoff.delay().as_secs());
+        return;
+    }
+
+    log::trace!("heartbeat packet sent successfully.");
+    if backoff.on_success() {
+        // The heartbeat packet carries the capabilities of this judge node, so the judge board
+        // server has the node registered again with up-to-date information at this point.
+        log::info!("judge board server is reachable again. Judge node re-registered.");
+        if let Some(ref on_recovered) = options.on_recovered {
+            on_recovered(&options.rest);
+        }
+    }
+}
+
 /// This function is the entry point of the heartbeat daemon thread.
 fn heartbeat_daemon_entry(options: HeartbeatDaemonOptions) {
     let heartbeat_interval = *crate::utils::max(
         &options.heartbeat_interval, &MIN_HEARTBEAT_INTERVAL);
+    let mut backoff = HeartbeatBackoff::new(heartbeat_interval, options.max_backoff);
 
     loop {
-        std::thread::sleep(heartbeat_interval);
-
-        let heartbeat = match create_heartbeat() {
-            Ok(hb) => hb,
-            Err(e) => {
-                log::error!("failed to create heartbeat packet: {}", e);
-                continue;
-            }
-        };
-
-        match options.rest.patch_heartbeat(&heartbeat) {
-            Ok(..) => (),
-            Err(e) => log::error!("failed to send heartbeat packet: {}", e)
-        };
-
-        log::trace!("heartbeat packet sent successfully.");
+        std::thread::sleep(backoff.delay());
+        send_heartbeat(&options, &mut backoff);
     }
 }
 
@@ -108,12 +191,25 @@ pub struct HeartbeatDaemonOptions {
 
     /// The interval between two consecutive heartbeat packets, in seconds.
     pub heartbeat_interval: Duration,
+
+    /// The maximal interval between two consecutive heartbeat packets after consecutive failures to
+    /// send heartbeat packets.
+    pub max_backoff: Duration,
+
+    /// Callback invoked when the judge board server becomes reachable again after heartbeat packets
+    /// failed, which can be used to re-send information the judge board server may have lost.
+    pub on_recovered: Option<Box<dyn Fn(&RestfulClient) + Send>>,
 }
 
 impl HeartbeatDaemonOptions {
     /// Create a new `HeartbeatDaemonOptions` value.
     pub fn new(rest: Arc<RestfulClient>, heartbeat_interval: Duration) -> Self {
-        HeartbeatDaemonOptions { rest, heartbeat_interval }
+        HeartbeatDaemonOptions {
+            rest,
+            heartbeat_interval,
+            max_backoff: DEFAULT_MAX_HEARTBEAT_BACKOFF,
+            on_recovered: None,
+        }
     }
 }
 
@@ -121,3 +217,104 @@ impl HeartbeatDaemonOptions {
 pub fn start_daemon(options: HeartbeatDaemonOptions) {
     std::thread::spawn(move || heartbeat_daemon_entry(options));
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    mod create_heartbeat {
+        use super::*;
+
+        #[test]
+        fn statistics_are_plausible() {
+            let hb = create_heartbeat();
+            assert!(hb.cores > 0);
+            assert!(hb.total_physical_memory >= 16 * 1024 * 1024);
+            assert!(hb.free_physical_memory > 0);
+            assert!(hb.free_physical_memory <= hb.total_physical_memory);
+            assert!(hb.free_swap_space <= hb.total_swap_space);
+        }
+    }
+
+    mod heartbeat_backoff {
+        use super::*;
+
+        #[test]
+        fn failures_increase_delay_up_to_cap() {
+            let mut backoff = HeartbeatBackoff::new(Duration::from_secs(3), Duration::from_secs(20));
+            assert_eq!(Duration::from_secs(3), backoff.delay());
+
+            backoff.on_failure();
+            assert_eq!(Duration::from_secs(6), backoff.delay());
+            backoff.on_failure();
+            assert_eq!(Duration::from_secs(12), backoff.delay());
+            backoff.on_failure();
+            assert_eq!(Duration::from_secs(20), backoff.delay());
+
+            for _ in 0..100 {
+                backoff.on_failure();
+            }
+            assert_eq!(Duration::from_secs(20), backoff.delay());
+        }
+
+        #[test]
+        fn success_resets_delay() {
+            let mut backoff = HeartbeatBackoff::new(Duration::from_secs(3), Duration::from_secs(20));
+            assert!(!backoff.on_success());
+
+            backoff.on_failure();
+            backoff.on_failure();
+            assert!(backoff.on_success());
+            assert_eq!(Duration::from_secs(3), backoff.delay());
+            assert!(!backoff.on_success());
+        }
+
+        #[test]
+        fn max_delay_not_less_than_interval() {
+            let mut backoff = HeartbeatBackoff::new(Duration::from_secs(10), Duration::from_secs(5));
+            backoff.on_failure();
+            assert_eq!(Duration::from_secs(10), backoff.delay());
+        }
+    }
+
+    mod send_heartbeat {
+        use super::*;
+
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        use openssl::rsa::Rsa;
+
+        use crate::restful::mock::start_mock_judge_board;
+
+        #[test]
+        fn recovery_callback_called() {
+            let rsa_key = Rsa::generate(1024).unwrap();
+            let (server, url) = start_mock_judge_board("Standard", rsa_key.clone());
+            let mut options = HeartbeatDaemonOptions::new(
+                Arc::new(RestfulClient::new(url, rsa_key)), Duration::from_secs(3));
+            let recovered = Arc::new(AtomicUsize::new(0));
+            let recovered_clone = recovered.clone();
+            options.on_recovered = Some(Box::new(move |_| {
+                recovered_clone.fetch_add(1, Ordering::SeqCst);
+            }));
+            let mut backoff = HeartbeatBackoff::new(
+                options.heartbeat_interval, options.max_backoff);
+
+            send_heartbeat(&options, &mut backoff);
+            assert_eq!(0, recovered.load(Ordering::SeqCst));
+
+            server.offline.store(true, Ordering::SeqCst);
+            send_heartbeat(&options, &mut backoff);
+            assert_eq!(Duration::from_secs(6), backoff.delay());
+            assert_eq!(0, recovered.load(Ordering::SeqCst));
+
+            server.offline.store(false, Ordering::SeqCst);
+            send_heartbeat(&options, &mut backoff);
+            assert_eq!(Duration::from_secs(3), backoff.delay());
+            assert_eq!(1, recovered.load(Ordering::SeqCst));
+
+            send_heartbeat(&options, &mut backoff);
+            assert_eq!(1, recovered.load(Ordering::SeqCst));
+        }
+    }
+}
@@ -3,8 +3,7 @@
 
 use std::path::Path;
 use std::sync::Arc;
-
-use openssl::rsa::Rsa;
+use std::time::Duration;
 
 use clap::ArgMatches;
 
@@ -12,6 +11,7 @@ use crate::AppContext;
 
 use crate::config::AppConfig;
 use crate::forkserver::ForkServerClient;
+use crate::metrics::Metrics;
 use crate::storage::AppStorageFacade;
 use crate::restful::RestfulClient;
 
@@ -31,6 +31,7 @@ error_chain::error_chain! {
         ConfigError(crate::config::Error, crate::config::ErrorKind);
         ForkServerError(crate::forkserver::Error, crate::forkserver::ErrorKind);
         StorageError(crate::storage::Error, crate::storage::ErrorKind);
+        RestfulError(crate::restful::Error, crate::restful::ErrorKind);
     }
 }
 
@@ -47,6 +48,9 @@ struct AppContextBuilder {
 
     /// The application storage facade.
     storage: Option<AppStorageFacade>,
+
+    /// The metrics registry.
+    metrics: Arc<Metrics>,
 }
 
 impl AppContextBuilder {
@@ -57,6 +61,7 @@ impl AppContextBuilder {
             fork_server: None,
             rest: None,
             storage: None,
+            metrics: Arc::new(Metrics::new()),
         }
     }
 
@@ -67,6 +72,10 @@ impl AppContextBuilder {
         log::info!("Initializing application configuration from file {}", config_file.display());
 
         let config = AppConfig::from_file(config_file)?;
+        if let Err(e) = config.validate() {
+            log::error!("{}", e);
+            return Err(Error::from(e));
+        }
         self.config = Some(Arc::new(config));
         Ok(())
     }
@@ -77,10 +86,11 @@ impl AppContextBuilder {
         &*self.config.as_ref().expect("Application configuration has not been initialized yet.")
     }
 
-    /// Initialize fork server.
-    fn init_fork_server(&mut self) -> Result<()> {
+    /// Initialize fork server. `log_config_file` is the path to the log configuration file used by
+    /// the fork server.
+    fn init_fork_server(&mut self, log_config_file: &Path) -> Result<()> {
         let judge_config = &self.get_app_config().engine;
-        let client = crate::forkserver::start_fork_server(judge_config)?;
+        let client = crate::forkserver::start_fork_server(judge_config, log_config_file)?;
         self.fork_server = Some(Arc::new(client));
         Ok(())
     }
@@ -107,9 +117,9 @@ impl AppContextBuilder {
             }
         };
 
-        log::debug!("Loading authenticate key from PEM file: \"{}\"",
+        log::debug!("Loading authenticate key from file: \"{}\"",
             config.authenticate_key_file.display());
-        let pem_data = match std::fs::read(&config.authenticate_key_file) {
+        let key_data = match std::fs::read(&config.authenticate_key_file) {
             Ok(d) => d,
             Err(e) => {
                 log::error!("Failed to load authenticate key from file \"{}\": {}",
@@ -117,9 +127,13 @@ impl AppContextBuilder {
                 return Err(Error::from(e));
             }
         };
-        let auth_key = Rsa::private_key_from_pem(&pem_data)?;
+        let auth_key = crate::restful::load_auth_key(&key_data)?;
 
-        let rest = RestfulClient::new(judge_board_url, auth_key);
+        let mut rest = RestfulClient::new(judge_board_url, auth_key);
+        if let Some(wait) = config.long_poll_wait {
+            log::info!("Long polling for submissions with wait duration of {} seconds", wait);
+            rest.enable_long_poll(Duration::from_secs(wait))?;
+        }
         self.rest = Some(Arc::new(rest));
 
         Ok(())
@@ -140,20 +154,19 @@ impl AppContextBuilder {
         let config = self.get_app_config();
         let rest = self.get_rest();
         let fork_server = self.get_fork_server();
-        let storage = AppStorageFacade::new(&config, rest, fork_server)?;
+        let storage = AppStorageFacade::new(&config, rest, fork_server, self.metrics.clone())?;
 
         self.storage = Some(storage);
         Ok(())
     }
 
     /// Initialize all components. `config_path` is the path to the application wide configuration
-    /// file.
-    fn init_all<P>(&mut self, config_path: P) -> Result<()>
-        where P: AsRef<Path> {
+    /// file and `log_config_path` is the path to the log configuration file.
+    fn init_all<P1, P2>(&mut self, config_path: P1, log_config_path: P2) -> Result<()>
+        where P1: AsRef<Path>,
+              P2: AsRef<Path> {
         self.init_app_config(config_path)?;
-        // The initialization of fork server should be as early as possible to avoid unnecessary
-        // memory footprint in the fork server process.
-        self.init_fork_server()?;
+        self.init_fork_server(log_config_path.as_ref())?;
         self.init_rest()?;
         self.init_storage_facade()?;
 
@@ -168,19 +181,20 @@ impl AppContextBuilder {
             fork_server: self.fork_server.expect("Fork server has not been initialized yet."),
             rest: self.rest.expect("RESTful client has not been initialized yet."),
             storage: self.storage.expect("Application storage has not been initialized yet."),
+            metrics: self.metrics,
         }
     }
 }
 
 /// Initialize log facilities. `log_config_file` is the path to the log configuration file.
-fn init_log<P>(log_config_file: P) -> Result<()>
+pub(crate) fn init_log<P>(log_config_file: P) -> Result<()>
     where P: AsRef<Path> {
     log4rs::init_file(log_config_file, log4rs::file::Deserializers::default())?;
     Ok(())
 }
 
 /// Initialize the application and returns a `AppContext` object.
-pub(crate) fn init(args: ArgMatches<'_>) -> Result<AppContext> {
+pub(crate) fn init(args: &ArgMatches<'_>) -> Result<AppContext> {
     let log_config_file_path = args.value_of("log_config_file")
         .expect("failed to get path to log file");
     init_log(log_config_file_path)?;
@@ -189,7 +203,7 @@ pub(crate) fn init(args: ArgMatches<'_>) -> Result<AppContext> {
 
     let config_file = args.value_of("config_file")
         .expect("failed to get path to the configuration file");
-    builder.init_all(config_file)?;
+    builder.init_all(config_file, log_config_file_path)?;
 
     Ok(builder.build_app_context())
 }
@@ -14,16 +14,23 @@ extern crate serde_json;
 extern crate serde_yaml;
 extern crate rmp_serde;
 extern crate zip;
+extern crate tar;
+extern crate flate2;
 extern crate tempfile;
 extern crate clap;
 
 extern crate judge;
 extern crate sandbox;
 
+mod cache;
 mod config;
+mod debug;
+mod dump;
 mod forkserver;
 mod heartbeat;
 mod init;
+mod metrics;
+mod prewarm;
 mod restful;
 mod storage;
 mod sync;
@@ -36,6 +43,7 @@ use std::time::Duration;
 use config::AppConfig;
 use forkserver::ForkServerClient;
 use heartbeat::HeartbeatDaemonOptions;
+use metrics::Metrics;
 use restful::RestfulClient;
 use storage::AppStorageFacade;
 
@@ -45,7 +53,12 @@ error_chain::error_chain! {
     }
 
     links {
+        CacheError(cache::Error, cache::ErrorKind);
+        DebugError(debug::Error, debug::ErrorKind);
+        DumpError(dump::Error, dump::ErrorKind);
+        ForkServerError(forkserver::Error, forkserver::ErrorKind);
         InitializationError(init::Error, init::ErrorKind);
+        PrewarmError(prewarm::Error, prewarm::ErrorKind);
         WorkerError(workers::Error, workers::ErrorKind);
     }
 }
@@ -63,6 +76,9 @@ struct AppContext {
 
     /// The storage facade of this application.
     storage: AppStorageFacade,
+
+    /// The metrics registry of this application.
+    metrics: Arc<Metrics>,
 }
 
 fn do_main() -> Result<()> {
@@ -85,15 +101,73 @@ fn do_main() -> Result<()> {
             .takes_value(true)
             .required(false)
             .default_value("config/app.yaml"))
+        .subcommand(cache::subcommand())
+        .subcommand(debug::subcommand())
+        .subcommand(dump::subcommand())
+        .subcommand(prewarm::subcommand())
+        .subcommand(clap::SubCommand::with_name(forkserver::FORK_SERVER_SUBCOMMAND)
+            .about("Run as the fork server of a judge node; used internally")
+            .setting(clap::AppSettings::Hidden))
         .get_matches();
-    let context = init::init(arg_matches)?;
+
+    if arg_matches.subcommand_name() == Some(forkserver::FORK_SERVER_SUBCOMMAND) {
+        let log_config_file = arg_matches.value_of("log_config_file")
+            .expect("failed to get path to log file");
+        // Custom in-process answer checkers are registered here, along with the built-in ones.
+        let checkers = judge::engine::CheckerRegistry::new();
+        forkserver::run_fork_server(|| init::init_log(log_config_file), checkers)?;
+        return Ok(());
+    }
+
+    if let ("dump-config", Some(dump_args)) = arg_matches.subcommand() {
+        let config_file = arg_matches.value_of("config_file")
+            .expect("failed to get path to the configuration file");
+        dump::run(dump_args, config_file)?;
+        return Ok(());
+    }
+
+    let context = init::init(&arg_matches)?;
+
+    match arg_matches.subcommand() {
+        ("cache", Some(cache_args)) => {
+            cache::run(cache_args, &context)?;
+            return Ok(());
+        },
+        ("debug-submission", Some(debug_args)) => {
+            debug::run(debug_args, &context)?;
+            return Ok(());
+        },
+        ("prewarm", Some(prewarm_args)) => {
+            prewarm::run(prewarm_args, &context)?;
+            return Ok(());
+        },
+        _ => ()
+    };
 
     // Start heartbeat daemon threads.
-    let hb_options = HeartbeatDaemonOptions::new(
+    let mut hb_options = HeartbeatDaemonOptions::new(
         context.rest.clone(),
         Duration::from_secs(context.config.cluster.heartbeat_interval as u64));
+    if let Some(max_backoff) = context.config.cluster.heartbeat_max_backoff {
+        hb_options.max_backoff = Duration::from_secs(max_backoff as u64);
+    }
+    // Re-run the self test of the fork server once the judge board server is reachable again, so
+    // that submissions dispatched to this judge node afterwards can be judged.
+    let fork_server = context.fork_server.clone();
+    hb_options.on_recovered = Some(Box::new(move |_| {
+        if let Err(e) = fork_server.ensure_alive() {
+            log::error!("Fork server self test failed after reconnection: {}", e);
+        }
+    }));
     heartbeat::start_daemon(hb_options);
 
+    // Start the health check thread of the fork server.
+    let ping_interval = context.config.engine.fork_server_ping_interval;
+    if ping_interval > 0 {
+        forkserver::start_health_check(
+            context.fork_server.clone(), Duration::from_secs(ping_interval));
+    }
+
     workers::run(Arc::new(context))?;
     Ok(())
 }
@@ -0,0 +1,270 @@
+//! This module maintains metrics of the judge node, such as the judge throughput and the time spent
+//! on compiling and judging submissions. The metrics are exported in the Prometheus text format
+//! into the file given by `AppConfig::metrics_file`, which can be collected by the textfile
+//! collector of the Prometheus node exporter.
+//!
+
+use std::collections::BTreeMap;
+use std::fmt::Write as FmtWrite;
+use std::fs::Permissions;
+use std::io::Write as IoWrite;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::restful::entities::Verdict;
+
+/// Upper bounds of the buckets of the histogram of queue latencies, in seconds.
+const QUEUE_SECONDS_BUCKETS: &[f64] = &[0.01, 0.05, 0.1, 0.5, 1.0, 5.0, 10.0, 30.0, 60.0];
+
+/// Upper bounds of the buckets of the histogram of compilation durations, in seconds.
+const COMPILE_SECONDS_BUCKETS: &[f64] = &[0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0];
+
+/// Upper bounds of the buckets of the histogram of judge durations, in seconds.
+const JUDGE_SECONDS_BUCKETS: &[f64] = &[0.1, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 120.0];
+
+/// A histogram of observed values, in the way Prometheus histograms are defined.
+#[derive(Clone, Debug)]
+struct Histogram {
+    /// Upper bounds of the buckets, in ascending order.
+    bounds: &'static [f64],
+
+    /// Number of observed values in each bucket. Buckets are not cumulative here; they are
+    /// accumulated when rendered.
+    counts: Vec<u64>,
+
+    /// Sum of all observed values.
+    sum: f64,
+
+    /// Number of observed values.
+    count: u64,
+}
+
+impl Histogram {
+    /// Create a new `Histogram` value with the given bucket bounds.
+    fn new(bounds: &'static [f64]) -> Self {
+        Histogram {
+            bounds,
+            counts: vec![0; bounds.len()],
+            sum: 0.0,
+            count: 0,
+        }
+    }
+
+    /// Observe the given value.
+    fn observe(&mut self, value: f64) {
+        if let Some(bucket) = self.bounds.iter().position(|bound| value <= *bound) {
+            self.counts[bucket] += 1;
+        }
+        self.sum += value;
+        self.count += 1;
+    }
+
+    /// Render this histogram with the given metric name in the Prometheus text format.
+    fn render(&self, name: &str, help: &str, output: &mut String) {
+        writeln!(output, "# HELP {} {}", name, help).unwrap();
+        writeln!(output, "# TYPE {} histogram", name).unwrap();
+
+        let mut cumulative = 0;
+        for (bound, count) in self.bounds.iter().zip(&self.counts) {
+            cumulative += count;
+            writeln!(output, "{}_bucket{{le=\"{}\"}} {}", name, bound, cumulative).unwrap();
+        }
+        writeln!(output, "{}_bucket{{le=\"+Inf\"}} {}", name, self.count).unwrap();
+        writeln!(output, "{}_sum {}", name, self.sum).unwrap();
+        writeln!(output, "{}_count {}", name, self.count).unwrap();
+    }
+}
+
+/// Values of the metrics.
+#[derive(Clone, Debug)]
+struct MetricsData {
+    /// Number of judged submissions, by the names of their verdicts.
+    submissions: BTreeMap<String, u64>,
+
+    /// Latencies from receiving submissions until they start compiling.
+    queue_seconds: Histogram,
+
+    /// Durations of compiling submissions.
+    compile_seconds: Histogram,
+
+    /// Durations of judging compiled submissions.
+    judge_seconds: Histogram,
+
+    /// Total size of the downloaded test archives, in bytes.
+    archive_download_bytes: u64,
+}
+
+/// Provide a registry of the metrics of the judge node. This structure is thread safe.
+pub struct Metrics {
+    /// The values of the metrics.
+    data: Mutex<MetricsData>,
+}
+
+impl Metrics {
+    /// Create a new `Metrics` instance with all metrics set to zero.
+    pub fn new() -> Self {
+        Metrics {
+            data: Mutex::new(MetricsData {
+                submissions: BTreeMap::new(),
+                queue_seconds: Histogram::new(QUEUE_SECONDS_BUCKETS),
+                compile_seconds: Histogram::new(COMPILE_SECONDS_BUCKETS),
+                judge_seconds: Histogram::new(JUDGE_SECONDS_BUCKETS),
+                archive_download_bytes: 0,
+            }),
+        }
+    }
+
+    /// Record a judged submission with the given verdict.
+    pub fn record_submission(&self, verdict: Verdict) {
+        let mut data = self.data.lock().unwrap();
+        *data.submissions.entry(format!("{}", verdict)).or_insert(0) += 1;
+    }
+
+    /// Record the latency from fetching a submission until it starts compiling, which includes
+    /// fetching its problem and test archive and waiting for the fork server.
+    pub fn observe_queue(&self, latency: Duration) {
+        self.data.lock().unwrap().queue_seconds.observe(latency.as_secs_f64());
+    }
+
+    /// Record the duration of compiling a submission.
+    pub fn observe_compile(&self, duration: Duration) {
+        self.data.lock().unwrap().compile_seconds.observe(duration.as_secs_f64());
+    }
+
+    /// Record the duration of judging a compiled submission.
+    pub fn observe_judge(&self, duration: Duration) {
+        self.data.lock().unwrap().judge_seconds.observe(duration.as_secs_f64());
+    }
+
+    /// Record the size of a downloaded test archive, in bytes.
+    pub fn add_archive_download_bytes(&self, bytes: u64) {
+        self.data.lock().unwrap().archive_download_bytes += bytes;
+    }
+
+    /// Render all metrics in the Prometheus text format.
+    pub fn render(&self) -> String {
+        let data = self.data.lock().unwrap().clone();
+        let mut output = String::new();
+
+        output.push_str("# HELP wave_judge_submissions_total Number of judged submissions.\n");
+        output.push_str("# TYPE wave_judge_submissions_total counter\n");
+        for (verdict, count) in &data.submissions {
+            writeln!(output, "wave_judge_submissions_total{{verdict=\"{}\"}} {}", verdict, count)
+                .unwrap();
+        }
+
+        data.queue_seconds.render("wave_judge_queue_seconds",
+            "Time from receiving submissions until they start compiling.", &mut output);
+        data.compile_seconds.render("wave_judge_compile_seconds",
+            "Time spent on compiling submissions.", &mut output);
+        data.judge_seconds.render("wave_judge_judge_seconds",
+            "Time spent on judging compiled submissions.", &mut output);
+
+        output.push_str(
+            "# HELP wave_judge_archive_download_bytes Total size of downloaded test archives.\n");
+        output.push_str("# TYPE wave_judge_archive_download_bytes counter\n");
+        writeln!(output, "wave_judge_archive_download_bytes {}", data.archive_download_bytes)
+            .unwrap();
+
+        output
+    }
+
+    /// Write all metrics in the Prometheus text format into the given file. The file is replaced
+    /// atomically so that readers never see a partially written file. The file is readable by all
+    /// users so that collectors running as other users can read it.
+    pub fn write_to_file<P>(&self, path: P) -> std::io::Result<()>
+        where P: AsRef<Path> {
+        let path = path.as_ref();
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new(".")
+        };
+
+        let mut file = tempfile::NamedTempFile::new_in(dir)?;
+        file.write_all(self.render().as_bytes())?;
+        file.as_file().set_permissions(Permissions::from_mode(0o644))?;
+        file.persist(path).map_err(|e| e.error)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    mod render {
+        use super::*;
+
+        fn lines(metrics: &Metrics) -> Vec<String> {
+            metrics.render().lines().map(String::from).collect()
+        }
+
+        #[test]
+        fn initial() {
+            let lines = lines(&Metrics::new());
+            assert!(!lines.iter().any(|line| line.starts_with("wave_judge_submissions_total")));
+            assert!(lines.contains(&String::from("wave_judge_queue_seconds_count 0")));
+            assert!(lines.contains(&String::from("wave_judge_compile_seconds_count 0")));
+            assert!(lines.contains(&String::from("wave_judge_archive_download_bytes 0")));
+        }
+
+        #[test]
+        fn simulated_judge() {
+            let metrics = Metrics::new();
+            metrics.add_archive_download_bytes(1024);
+            for (verdict, compile_millis, judge_millis) in &[
+                (Verdict::Accepted, 300, 1250),
+                (Verdict::WrongAnswer, 700, 500),
+                (Verdict::Accepted, 200, 40000)] {
+                metrics.observe_queue(Duration::from_millis(20));
+                metrics.observe_compile(Duration::from_millis(*compile_millis));
+                metrics.observe_judge(Duration::from_millis(*judge_millis));
+                metrics.record_submission(*verdict);
+            }
+            metrics.record_submission(Verdict::CompilationFailed);
+
+            let lines = lines(&metrics);
+            for expected in &[
+                "wave_judge_submissions_total{verdict=\"Accepted\"} 2",
+                "wave_judge_submissions_total{verdict=\"WrongAnswer\"} 1",
+                "wave_judge_submissions_total{verdict=\"CompilationFailed\"} 1",
+                "wave_judge_queue_seconds_bucket{le=\"0.01\"} 0",
+                "wave_judge_queue_seconds_bucket{le=\"0.05\"} 3",
+                "wave_judge_queue_seconds_count 3",
+                "wave_judge_compile_seconds_bucket{le=\"0.25\"} 1",
+                "wave_judge_compile_seconds_bucket{le=\"0.5\"} 2",
+                "wave_judge_compile_seconds_bucket{le=\"+Inf\"} 3",
+                "wave_judge_compile_seconds_count 3",
+                "wave_judge_judge_seconds_bucket{le=\"0.5\"} 1",
+                "wave_judge_judge_seconds_bucket{le=\"30\"} 2",
+                "wave_judge_judge_seconds_bucket{le=\"60\"} 3",
+                "wave_judge_judge_seconds_sum 41.75",
+                "wave_judge_archive_download_bytes 1024",
+            ] {
+                assert!(lines.contains(&String::from(*expected)), "missing line: {}", expected);
+            }
+        }
+    }
+
+    mod write_to_file {
+        use super::*;
+
+        #[test]
+        fn written() {
+            let dir = tempfile::tempdir().unwrap();
+            let path = dir.path().join("wave_judge.prom");
+            let metrics = Metrics::new();
+            metrics.record_submission(Verdict::Accepted);
+            metrics.write_to_file(&path).unwrap();
+
+            assert_eq!(metrics.render(), std::fs::read_to_string(&path).unwrap());
+            // The temporary file has been renamed to the metrics file.
+            assert_eq!(1, std::fs::read_dir(dir.path()).unwrap().count());
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(0o644, mode & 0o777);
+        }
+    }
+}
@@ -0,0 +1,67 @@
+//! This module implements the `prewarm` subcommand, through which operators can fetch problems,
+//! compile their jury programs and download their test archives ahead of time, e.g. before a
+//! contest starts.
+//!
+
+use std::str::FromStr;
+
+use clap::ArgMatches;
+
+use crate::AppContext;
+use crate::restful::entities::ObjectId;
+
+error_chain::error_chain! {
+    types {
+        Error, ErrorKind, ResultExt, Result;
+    }
+
+    errors {
+        InvalidObjectId(id: String) {
+            description("invalid object ID"),
+            display("invalid object ID: {}", id)
+        }
+
+        PrewarmFailed(count: usize) {
+            description("failed to prewarm some problems"),
+            display("failed to prewarm {} problem(s)", count)
+        }
+    }
+}
+
+/// Build the definition of the `prewarm` subcommand.
+pub(crate) fn subcommand<'a, 'b>() -> clap::App<'a, 'b> {
+    clap::SubCommand::with_name("prewarm")
+        .about("Fetch problems, compile their juries and download their test archives in advance")
+        .arg(clap::Arg::with_name("problem_id")
+            .value_name("PROBLEM_ID")
+            .help("IDs of the problems to be prewarmed")
+            .multiple(true)
+            .required(true))
+}
+
+/// Execute the `prewarm` subcommand with the given arguments.
+pub(crate) fn run(args: &ArgMatches<'_>, context: &AppContext) -> Result<()> {
+    let mut ids = Vec::new();
+    for id in args.values_of("problem_id").expect("failed to get problem IDs") {
+        let id = ObjectId::from_str(id)
+            .map_err(|_| Error::from(ErrorKind::InvalidObjectId(String::from(id))))?;
+        ids.push(id);
+    }
+
+    let mut failures = 0;
+    for (id, result) in context.storage.problems.prewarm(&ids, &context.storage.archives) {
+        match result {
+            Ok(..) => println!("  {}\tok", id),
+            Err(e) => {
+                println!("  {}\tfailed: {}", id, e);
+                failures += 1;
+            }
+        };
+    }
+
+    if failures > 0 {
+        return Err(Error::from(ErrorKind::PrewarmFailed(failures)));
+    }
+
+    Ok(())
+}
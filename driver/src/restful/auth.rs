@@ -1,19 +1,72 @@
 //! This module handles client authentication to the judge board server.
 //!
+//! Besides the JWT, every request sent through the `Authenticator` carries a timestamp, a random
+//! nonce and an RSA signature in the `X-Auth-Timestamp`, `X-Auth-Nonce` and `X-Auth-Signature`
+//! headers respectively, which protects requests from being replayed. The signature is computed
+//! with SHA-256 over the payload produced by `signed_payload`, which consists of the timestamp, the
+//! nonce, the request method, the request path and query, and the base64 encoded SHA-256 digest of
+//! the request body, separated by line feeds. The digest of an empty body is used for requests
+//! without a body.
+//!
+//! The judge board server is expected to verify the signature with the public key of the judge
+//! node, reject requests whose timestamp differs from its own clock by more than 300 seconds, and
+//! reject requests whose nonce has been seen within that window.
+//!
 
 use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
 
-use openssl::pkey::Private as PrivateKey;
+use openssl::hash::MessageDigest;
+use openssl::pkey::{PKey, Private as PrivateKey};
 use openssl::rsa::{Rsa, Padding as RsaPadding};
+use openssl::sha::sha256;
+use openssl::sign::Signer;
+
+use rand::Rng;
 
 use reqwest::Client as HttpClient;
-use reqwest::{Url, Response};
+use reqwest::{Method, Url, Response};
 
 use serde::Deserialize;
 
 use super::pipeline::{Error, ErrorKind, Result};
 use super::pipeline::{Middleware, PipelineContext};
 
+/// Name of the header containing the timestamp of a request.
+const TIMESTAMP_HEADER: &str = "X-Auth-Timestamp";
+
+/// Name of the header containing the nonce of a request.
+const NONCE_HEADER: &str = "X-Auth-Nonce";
+
+/// Name of the header containing the signature of a request.
+const SIGNATURE_HEADER: &str = "X-Auth-Signature";
+
+/// Build the payload to be signed for a request with the given method, URL, body, timestamp and
+/// nonce.
+fn signed_payload(method: &Method, url: &Url, body: &[u8], timestamp: u64, nonce: &str)
+    -> String {
+    let mut target = String::from(url.path());
+    if let Some(query) = url.query() {
+        target.push('?');
+        target.push_str(query);
+    }
+
+    format!("{}\n{}\n{}\n{}\n{}", timestamp, nonce, method.as_str(), target,
+        base64::encode(&sha256(body)))
+}
+
+/// Replay protection information of a request.
+struct RequestSignature {
+    /// Seconds since the UNIX epoch when the request is signed.
+    timestamp: u64,
+
+    /// Base64 encoded random nonce.
+    nonce: String,
+
+    /// Base64 encoded signature of the payload.
+    signature: String,
+}
+
 // TODO: enhancement: multiple authentication requests might be sent in a short period of time
 // TODO: under concurrent environment.
 
@@ -87,8 +140,43 @@ impl Authenticator {
         Ok((*jwt_lock).clone().unwrap())
     }
 
-    fn handle_with_jwt(mut context: PipelineContext<'_>, jwt: &str) -> Result<Response> {
-        context.map_request(|req| req.bearer_auth(jwt));
+    /// Sign a request with the given method, URL and body with a fresh timestamp and nonce.
+    fn sign_request(&self, method: &Method, url: &Url, body: &[u8])
+        -> Result<RequestSignature> {
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        let nonce = base64::encode(&rand::thread_rng().gen::<[u8; 16]>());
+        let payload = signed_payload(method, url, body, timestamp, &nonce);
+
+        let signature = PKey::from_rsa(self.rsa_key.clone())
+            .and_then(|key| {
+                let mut signer = Signer::new(MessageDigest::sha256(), &key)?;
+                signer.update(payload.as_bytes())?;
+                signer.sign_to_vec()
+            })
+            .map_err(|e| Error::from(ErrorKind::MiddlewareError(
+                format!("failed to sign request using RSA private key: {}", e))))?;
+
+        Ok(RequestSignature {
+            timestamp,
+            nonce,
+            signature: base64::encode(&signature),
+        })
+    }
+
+    fn handle_with_jwt(&self, mut context: PipelineContext<'_>, jwt: &str) -> Result<Response> {
+        let (method, url) = match context.request_target() {
+            Some(target) => target,
+            None => return Err(Error::from(ErrorKind::MiddlewareError(
+                String::from("failed to get request target for signing."))))
+        };
+        let sig = self.sign_request(&method, &url, context.request_body())?;
+
+        context.map_request(|req| req.bearer_auth(jwt)
+            .header(TIMESTAMP_HEADER, sig.timestamp.to_string())
+            .header(NONCE_HEADER, sig.nonce)
+            .header(SIGNATURE_HEADER, sig.signature));
         context.invoke_next()
     }
 }
@@ -103,7 +191,7 @@ impl Middleware for Authenticator {
 
         let saved_context = context.try_clone();
 
-        let response = Self::handle_with_jwt(context, &jwt)?;
+        let response = self.handle_with_jwt(context, &jwt)?;
         let status_code = response.status().as_u16();
         if status_code == 401 || status_code == 403 {
             if saved_context.is_none() {
@@ -115,7 +203,7 @@ impl Middleware for Authenticator {
 
             // Authorization failed. Re-authenticate this session.
             let fresh_jwt = self.authenticate(true)?;
-            Self::handle_with_jwt(saved_context.unwrap(), &fresh_jwt)
+            self.handle_with_jwt(saved_context.unwrap(), &fresh_jwt)
         } else {
             Ok(response)
         }
@@ -144,3 +232,67 @@ struct AuthenticationInfo {
     #[serde(rename = "jwt")]
     jwt: String,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    mod sign_request {
+        use super::*;
+
+        use openssl::sign::Verifier;
+
+        fn verify(key: &Rsa<PrivateKey>, payload: &str, signature: &str) -> bool {
+            let key = PKey::from_rsa(key.clone()).unwrap();
+            let mut verifier = Verifier::new(MessageDigest::sha256(), &key).unwrap();
+            verifier.update(payload.as_bytes()).unwrap();
+            verifier.verify(&base64::decode(signature).unwrap()).unwrap()
+        }
+
+        #[test]
+        fn signatures_differ() {
+            let key = Rsa::generate(2048).unwrap();
+            let auth = Authenticator::new(Url::parse("http://localhost/").unwrap(), key.clone());
+            let url = Url::parse("http://localhost/submissions?wait=30s").unwrap();
+
+            let first = auth.sign_request(&Method::GET, &url, b"").unwrap();
+            let second = auth.sign_request(&Method::GET, &url, b"").unwrap();
+            assert_ne!(first.nonce, second.nonce);
+            assert_ne!(first.signature, second.signature);
+
+            for sig in &[first, second] {
+                let payload = signed_payload(&Method::GET, &url, b"", sig.timestamp, &sig.nonce);
+                assert!(verify(&key, &payload, &sig.signature));
+            }
+        }
+
+        #[test]
+        fn payload_includes_timestamp_and_nonce() {
+            let url = Url::parse("http://localhost/submissions?wait=30s").unwrap();
+            assert_eq!(concat!("1577836800\nbm9uY2U=\nGET\n/submissions?wait=30s\n",
+                "47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU="),
+                signed_payload(&Method::GET, &url, b"", 1577836800, "bm9uY2U="));
+
+            let url = Url::parse("http://localhost/problems/1").unwrap();
+            assert_eq!(concat!("1577836800\nbm9uY2U=\nPATCH\n/problems/1\n",
+                "RBNvo1WzZ4oRRq0W9+hknpT7T8If536DEMBg9hyq/4o="),
+                signed_payload(&Method::PATCH, &url, b"{}", 1577836800, "bm9uY2U="));
+        }
+
+        #[test]
+        fn body_signed() {
+            let key = Rsa::generate(2048).unwrap();
+            let auth = Authenticator::new(Url::parse("http://localhost/").unwrap(), key.clone());
+            let url = Url::parse("http://localhost/judges").unwrap();
+
+            let sig = auth.sign_request(&Method::PATCH, &url, br#"{"cpu":1}"#).unwrap();
+            let payload = signed_payload(
+                &Method::PATCH, &url, br#"{"cpu":1}"#, sig.timestamp, &sig.nonce);
+            assert!(verify(&key, &payload, &sig.signature));
+
+            let payload = signed_payload(
+                &Method::PATCH, &url, br#"{"cpu":2}"#, sig.timestamp, &sig.nonce);
+            assert!(!verify(&key, &payload, &sig.signature));
+        }
+    }
+}
@@ -165,6 +165,20 @@ impl LanguageTriple {
     }
 }
 
+impl FromStr for LanguageTriple {
+    type Err = ();
+
+    /// Parse a language triple in the form of `identifier:dialect:version`, e.g. `cpp:gnu:17`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.split(':').collect();
+        if parts.len() != 3 || parts.iter().any(|part| part.is_empty()) {
+            return Err(());
+        }
+
+        Ok(LanguageTriple::new(parts[0], parts[1], parts[2]))
+    }
+}
+
 /// Judge mode.
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Hash)]
 pub enum JudgeMode {
@@ -229,6 +243,44 @@ pub struct ProblemInfo {
     /// Timestamp of the problem metadata.
     #[serde(rename = "timestamp")]
     pub timestamp: u64,
+
+    /// Subtasks grouping the test cases of the problem, if the problem is scored by subtasks.
+    #[serde(rename = "subtasks", default)]
+    pub subtasks: Vec<ProblemSubtask>,
+
+    /// Whether a judgee exiting with a non-zero exit code gets the `RuntimeError` verdict on this
+    /// problem. The policy given by the application configuration is used if this is `None`.
+    #[serde(rename = "treatNonzeroExitAsRuntimeError", default)]
+    pub treat_nonzero_exit_as_runtime_error: Option<bool>,
+}
+
+/// Provide information about a subtask of a problem.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ProblemSubtask {
+    /// Identifier of the subtask.
+    #[serde(rename = "id")]
+    pub id: u32,
+
+    /// Indices of the test cases of the subtask in the test archive.
+    #[serde(rename = "testCases")]
+    pub test_cases: Vec<usize>,
+
+    /// Score of the subtask.
+    #[serde(rename = "score")]
+    pub score: f64,
+
+    /// Identifiers of the subtasks this subtask depends on.
+    #[serde(rename = "dependencies", default)]
+    pub dependencies: Vec<u32>,
+}
+
+impl ProblemSubtask {
+    /// Convert this subtask into the subtask of a judge task.
+    pub fn to_judge_subtask(&self) -> judge::Subtask {
+        let mut subtask = judge::Subtask::new(self.id, self.test_cases.clone(), self.score);
+        subtask.dependencies = self.dependencies.clone();
+        subtask
+    }
 }
 
 /// Provide information about a submission.
@@ -278,6 +330,9 @@ pub enum Verdict {
     /// Bad system call.
     BadSystemCall,
 
+    /// Disk limit exceeded.
+    DiskLimitExceeded,
+
     /// Checker failed to compile.
     CheckerCompilationFailed,
 
@@ -290,6 +345,12 @@ pub enum Verdict {
     /// Interactor program failed.
     InteractorFailed,
 
+    /// The judge task ran out of its total time budget.
+    JudgeTimeLimitExceeded,
+
+    /// The test case was skipped since its subtasks failed.
+    Skipped,
+
     /// Judge failed.
     JudgeFailed,
 }
@@ -299,14 +360,18 @@ impl From<judge::Verdict> for Verdict {
         use judge::Verdict::*;
         match verdict {
             Accepted => Verdict::Accepted,
+            CompilationError => Verdict::CompilationFailed,
             WrongAnswer => Verdict::WrongAnswer,
             RuntimeError => Verdict::RuntimeError,
             TimeLimitExceeded => Verdict::TimeLimitExceeded,
             MemoryLimitExceeded => Verdict::MemoryLimitExceeded,
             IdlenessLimitExceeded => Verdict::IdlenessLimitExceeded,
             BannedSystemCall => Verdict::BadSystemCall,
+            DiskLimitExceeded => Verdict::DiskLimitExceeded,
             CheckerFailed => Verdict::CheckerFailed,
             InteractorFailed => Verdict::InteractorFailed,
+            JudgeTimeLimitExceeded => Verdict::JudgeTimeLimitExceeded,
+            Skipped => Verdict::Skipped,
         }
     }
 }
@@ -323,10 +388,13 @@ impl Display for Verdict {
             MemoryLimitExceeded => f.write_str("MemoryLimitExceeded"),
             IdlenessLimitExceeded => f.write_str("IdlenessLimitExceeded"),
             BadSystemCall => f.write_str("BadSystemCall"),
+            DiskLimitExceeded => f.write_str("DiskLimitExceeded"),
             CheckerCompilationFailed => f.write_str("CheckerCompilationFailed"),
             CheckerFailed => f.write_str("CheckerFailed"),
             InteractorCompilationFailed => f.write_str("InteractorCompilationFailed"),
             InteractorFailed => f.write_str("InteractorFailed"),
+            JudgeTimeLimitExceeded => f.write_str("JudgeTimeLimitExceeded"),
+            Skipped => f.write_str("Skipped"),
             JudgeFailed => f.write_str("JudgeFailed"),
         }
     }
@@ -354,19 +422,92 @@ pub struct SubmissionJudgeResult {
     /// Judge result on each test case.
     #[serde(rename = "testCases")]
     pub test_cases: Vec<TestCaseJudgeResult>,
+
+    /// Judge result on each subtask, if the problem is scored by subtasks. This field is omitted
+    /// otherwise.
+    #[serde(rename = "subtasks", skip_serializing_if = "Vec::is_empty")]
+    pub subtasks: Vec<SubtaskJudgeResult>,
+
+    /// Total score granted on all subtasks, if the problem is scored by subtasks. This field is
+    /// omitted otherwise.
+    #[serde(rename = "subtaskScore", skip_serializing_if = "Option::is_none")]
+    pub subtask_score: Option<f64>,
 }
 
 impl From<judge::JudgeResult> for SubmissionJudgeResult {
     fn from(res: judge::JudgeResult) -> Self {
+        let subtask_score = if res.subtasks.is_empty() {
+            None
+        } else {
+            Some(res.subtask_score())
+        };
         let test_cases: Vec<TestCaseJudgeResult> = res.test_suite.into_iter()
             .map(TestCaseJudgeResult::from)
             .collect();
+        let subtasks: Vec<SubtaskJudgeResult> = res.subtasks.into_iter()
+            .map(SubtaskJudgeResult::from)
+            .collect();
         SubmissionJudgeResult {
             verdict: Verdict::from(res.verdict),
-            compiler_message: String::new(),
+            compiler_message: res.compiler_out.unwrap_or_default(),
             time: res.rusage.user_cpu_time.as_secs(),
             memory: res.rusage.virtual_mem_size.bytes() as u64,
             test_cases,
+            subtasks,
+            subtask_score,
+        }
+    }
+}
+
+/// Judge result of a submission on a specific subtask.
+#[derive(Clone, Debug, Serialize)]
+pub struct SubtaskJudgeResult {
+    /// Identifier of the subtask.
+    #[serde(rename = "id")]
+    pub id: u32,
+
+    /// Verdict of the subtask.
+    #[serde(rename = "verdict")]
+    pub verdict: Verdict,
+
+    /// Score granted on the subtask.
+    #[serde(rename = "score")]
+    pub score: f64,
+}
+
+impl From<judge::SubtaskResult> for SubtaskJudgeResult {
+    fn from(res: judge::SubtaskResult) -> Self {
+        SubtaskJudgeResult {
+            id: res.id,
+            verdict: Verdict::from(res.verdict),
+            score: res.score,
+        }
+    }
+}
+
+/// Progress of judging a submission, reported to the judge board while the submission is being
+/// judged.
+#[derive(Clone, Debug, Serialize)]
+pub struct SubmissionJudgeProgress {
+    /// Number of test cases judged so far.
+    #[serde(rename = "casesDone")]
+    pub cases_done: usize,
+
+    /// Total number of test cases to be judged.
+    #[serde(rename = "casesTotal")]
+    pub cases_total: usize,
+
+    /// Verdict of the test cases judged so far.
+    #[serde(rename = "verdict")]
+    pub verdict: Verdict,
+}
+
+impl From<judge::JudgeProgress> for SubmissionJudgeProgress {
+    fn from(progress: judge::JudgeProgress) -> Self {
+        SubmissionJudgeProgress {
+            cases_done: progress.test_cases_done,
+            cases_total: progress.test_cases_total,
+            verdict: Verdict::from(progress.verdict),
         }
     }
 }
@@ -390,21 +531,63 @@ pub struct TestCaseJudgeResult {
     #[serde(rename = "exitCode")]
     pub exit_code: i32,
 
-    /// View of the input data.
-    #[serde(rename = "inputView")]
-    pub input_view: String,
+    /// View of the input data. This field is omitted for hidden test cases.
+    #[serde(rename = "inputView", skip_serializing_if = "Option::is_none")]
+    pub input_view: Option<String>,
 
-    /// View of the answer data.
-    #[serde(rename = "answerView")]
-    pub answer_view: String,
+    /// View of the answer data. This field is omitted for hidden test cases.
+    #[serde(rename = "answerView", skip_serializing_if = "Option::is_none")]
+    pub answer_view: Option<String>,
 
-    /// View of the output data generated by the user's program.
-    #[serde(rename = "outputView")]
-    pub output_view: String,
+    /// View of the output data generated by the user's program. This field is omitted for hidden test cases.
+    #[serde(rename = "outputView", skip_serializing_if = "Option::is_none")]
+    pub output_view: Option<String>,
 
     /// Judge's comment.
     #[serde(rename = "comment")]
     pub comment: String,
+
+    /// Reason of the `CheckerFailed` verdict, e.g. `CheckerTimeLimitExceeded`. This field is
+    /// omitted for other verdicts.
+    #[serde(rename = "failureReason", skip_serializing_if = "Option::is_none")]
+    pub failure_reason: Option<String>,
+
+    /// Score of the user's program as a fraction of the full score, if the interactor declared
+    /// one. This field is omitted otherwise.
+    #[serde(rename = "score", skip_serializing_if = "Option::is_none")]
+    pub score: Option<f64>,
+
+    /// Structured view of the first difference between the answer and the output of the user's
+    /// program. This field is omitted for hidden test cases and accepted outputs.
+    #[serde(rename = "diff", skip_serializing_if = "Option::is_none")]
+    pub diff: Option<DiffView>,
+}
+
+/// Structured view of the first difference between the answer and the output of the user's
+/// program.
+#[derive(Clone, Debug, Serialize)]
+pub struct DiffView {
+    /// Snippet of the answer around the first difference.
+    #[serde(rename = "expectedSnippet")]
+    pub expected_snippet: String,
+
+    /// Snippet of the output of the user's program around the first difference.
+    #[serde(rename = "actualSnippet")]
+    pub actual_snippet: String,
+
+    /// Offset of the first difference in both snippets, in bytes.
+    #[serde(rename = "firstDiffOffset")]
+    pub first_diff_offset: usize,
+}
+
+impl From<judge::DiffView> for DiffView {
+    fn from(diff: judge::DiffView) -> Self {
+        DiffView {
+            expected_snippet: diff.expected_snippet,
+            actual_snippet: diff.actual_snippet,
+            first_diff_offset: diff.first_diff_offset,
+        }
+    }
 }
 
 impl From<judge::TestCaseResult> for TestCaseJudgeResult {
@@ -414,10 +597,13 @@ impl From<judge::TestCaseResult> for TestCaseJudgeResult {
             time: res.rusage.user_cpu_time.as_secs(),
             memory: res.rusage.virtual_mem_size.bytes() as u64,
             exit_code: res.judgee_exit_status.exit_code().unwrap_or_default(),
-            input_view: res.input_view.unwrap_or_default(),
-            answer_view: res.answer_view.unwrap_or_default(),
-            output_view: res.output_view.unwrap_or_default(),
+            input_view: res.input_view,
+            answer_view: res.answer_view,
+            output_view: res.output_view,
             comment: res.comment.unwrap_or_default(),
+            failure_reason: res.failure_reason.map(|reason| String::from(reason.name())),
+            score: res.score,
+            diff: res.diff.map(DiffView::from),
         }
     }
 }
@@ -426,6 +612,25 @@ impl From<judge::TestCaseResult> for TestCaseJudgeResult {
 mod tests {
     use super::*;
 
+    mod language_triple {
+        use super::*;
+
+        #[test]
+        fn from_str_ok() {
+            let triple = LanguageTriple::from_str("cpp:gnu:17").unwrap();
+            assert_eq!("cpp", triple.identifier);
+            assert_eq!("gnu", triple.dialect);
+            assert_eq!("17", triple.version);
+        }
+
+        #[test]
+        fn from_str_invalid() {
+            assert!(LanguageTriple::from_str("cpp:gnu").is_err());
+            assert!(LanguageTriple::from_str("cpp::17").is_err());
+            assert!(LanguageTriple::from_str("cpp:gnu:17:x").is_err());
+        }
+    }
+
     mod object_id {
         use super::*;
 
@@ -468,5 +673,113 @@ mod tests {
                 serde_json::from_str::<ObjectId>("\"0123456789abcdef01234567\"").unwrap());
         }
     }
-}
 
+    mod submission_judge_result {
+        use super::*;
+
+        #[test]
+        fn from_compilation_failed() {
+            let res = SubmissionJudgeResult::from(
+                judge::JudgeResult::compilation_failed("error: expected `;`"));
+            assert_eq!(Verdict::CompilationFailed, res.verdict);
+            assert_eq!("error: expected `;`", res.compiler_message);
+            assert!(res.test_cases.is_empty());
+        }
+
+        #[test]
+        fn comment_not_compiler_message() {
+            let mut judge_result = judge::JudgeResult::new();
+            judge_result.verdict = judge::Verdict::JudgeTimeLimitExceeded;
+            judge_result.comment = Some(String::from("judge time budget exhausted"));
+
+            let res = SubmissionJudgeResult::from(judge_result);
+            assert_eq!(Verdict::JudgeTimeLimitExceeded, res.verdict);
+            assert!(res.compiler_message.is_empty());
+        }
+
+        #[test]
+        fn subtasks_reported() {
+            let mut judge_result = judge::JudgeResult::new();
+            judge_result.subtasks.push(judge::SubtaskResult {
+                id: 1,
+                verdict: judge::Verdict::Accepted,
+                score: 30.0,
+            });
+            judge_result.subtasks.push(judge::SubtaskResult {
+                id: 2,
+                verdict: judge::Verdict::Skipped,
+                score: 0.0,
+            });
+
+            let res = SubmissionJudgeResult::from(judge_result);
+            assert_eq!(Some(30.0), res.subtask_score);
+            assert_eq!(2, res.subtasks.len());
+            assert_eq!(2, res.subtasks[1].id);
+            assert_eq!(Verdict::Skipped, res.subtasks[1].verdict);
+
+            let json = serde_json::to_value(&res).unwrap();
+            assert_eq!(30.0, json["subtaskScore"]);
+            assert_eq!(1, json["subtasks"][0]["id"]);
+        }
+
+        #[test]
+        fn subtasks_omitted() {
+            let res = SubmissionJudgeResult::from(judge::JudgeResult::new());
+            assert!(res.subtasks.is_empty());
+            assert_eq!(None, res.subtask_score);
+
+            let json = serde_json::to_value(&res).unwrap();
+            assert!(json.get("subtasks").is_none());
+            assert!(json.get("subtaskScore").is_none());
+        }
+    }
+
+    mod test_case_judge_result {
+        use super::*;
+
+        #[test]
+        fn hidden_views_omitted() {
+            let res = TestCaseJudgeResult::from(judge::TestCaseResult::new());
+            let json = serde_json::to_value(&res).unwrap();
+            assert!(json.get("inputView").is_none());
+            assert!(json.get("answerView").is_none());
+            assert!(json.get("outputView").is_none());
+            assert!(json.get("score").is_none());
+            assert!(json.get("diff").is_none());
+        }
+
+        #[test]
+        fn public_views_reported() {
+            let mut tc = judge::TestCaseResult::new();
+            tc.input_view = Some(String::from("1 2"));
+            tc.answer_view = Some(String::from("3"));
+            tc.output_view = Some(String::from("3"));
+
+            let json = serde_json::to_value(&TestCaseJudgeResult::from(tc)).unwrap();
+            assert_eq!("1 2", json["inputView"]);
+            assert_eq!("3", json["answerView"]);
+            assert_eq!("3", json["outputView"]);
+        }
+
+        #[test]
+        fn score_reported() {
+            let mut tc = judge::TestCaseResult::new();
+            tc.score = Some(0.5);
+
+            let json = serde_json::to_value(&TestCaseJudgeResult::from(tc)).unwrap();
+            assert_eq!(0.5, json["score"]);
+        }
+
+        #[test]
+        fn diff_reported() {
+            let mut tc = judge::TestCaseResult::new();
+            tc.verdict = judge::Verdict::WrongAnswer;
+            tc.diff = Some(judge::DiffView::new("42", "43"));
+
+            let json = serde_json::to_value(&TestCaseJudgeResult::from(tc)).unwrap();
+            assert_eq!("42", json["diff"]["expectedSnippet"]);
+            assert_eq!("43", json["diff"]["actualSnippet"]);
+            assert_eq!(1, json["diff"]["firstDiffOffset"]);
+        }
+    }
+}
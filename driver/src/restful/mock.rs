@@ -0,0 +1,238 @@
+//! This module implements a mock judge board server used in tests.
+//!
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use openssl::pkey::Private;
+use openssl::rsa::{Padding as RsaPadding, Rsa};
+
+/// ID of the problem served by the mock judge board server.
+pub(crate) const PROBLEM_ID: &str = "000000000000000000000001";
+
+/// ID of the test archive served by the mock judge board server.
+pub(crate) const ARCHIVE_ID: &str = "000000000000000000000002";
+
+/// A mock judge board server serving a single problem and its test archive, and accepting heartbeat
+/// packets. Connections are closed without a response once the server goes offline.
+///
+/// The test archive is served with an entity tag, and range requests for it are honored unless
+/// their `If-Range` header does not match the entity tag.
+pub(crate) struct MockServer {
+    /// The problem information in JSON format.
+    problem: String,
+
+    /// Content of the test archive.
+    archive: Mutex<Vec<u8>>,
+
+    /// Version of the test archive, from which its entity tag is derived.
+    archive_version: AtomicUsize,
+
+    /// The RSA key of the judge node, used for authentication.
+    rsa_key: Rsa<Private>,
+
+    /// Whether the server is offline.
+    pub(crate) offline: AtomicBool,
+
+    /// Whether range requests for the test archive are honored.
+    pub(crate) support_range: AtomicBool,
+
+    /// Number of the following responses serving the test archive that are interrupted halfway.
+    pub(crate) archive_interruptions: Mutex<usize>,
+
+    /// The `Range` and `If-Range` headers of the requests for the test archive received.
+    pub(crate) archive_requests: Mutex<Vec<(Option<String>, Option<String>)>>,
+}
+
+impl MockServer {
+    /// Replace the content of the test archive, which changes its entity tag.
+    pub(crate) fn set_archive(&self, archive: Vec<u8>) {
+        *self.archive.lock().unwrap() = archive;
+        self.archive_version.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// Get the content of the test archive.
+    pub(crate) fn archive(&self) -> Vec<u8> {
+        self.archive.lock().unwrap().clone()
+    }
+
+    /// Get the current entity tag of the test archive.
+    pub(crate) fn archive_etag(&self) -> String {
+        format!("\"{}\"", self.archive_version.load(Ordering::SeqCst))
+    }
+
+    fn handle(&self, stream: TcpStream) {
+        if self.offline.load(Ordering::SeqCst) {
+            return;
+        }
+
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line).unwrap();
+
+        let mut content_length = 0;
+        let mut range = None;
+        let mut if_range = None;
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            let line = line.trim_end();
+            if line.is_empty() {
+                break;
+            }
+
+            let mut parts = line.splitn(2, ':');
+            let name = parts.next().unwrap().to_lowercase();
+            let value = parts.next().unwrap_or_default().trim().to_owned();
+            match name.as_str() {
+                "content-length" => content_length = value.parse().unwrap(),
+                "range" => range = Some(value),
+                "if-range" => if_range = Some(value),
+                _ => ()
+            };
+        }
+        let mut body = vec![0u8; content_length];
+        reader.read_exact(&mut body).unwrap();
+
+        let mut stream = stream;
+        let mut words = request_line.split_whitespace();
+        let (method, path) = (words.next().unwrap(), words.next().unwrap());
+        if method == "GET" && path == format!("/archives/{}", ARCHIVE_ID) {
+            self.serve_archive(&mut stream, range, if_range);
+            return;
+        }
+
+        let body = match (method, path) {
+            ("POST", "/auth") => {
+                let mut challenge = vec![0u8; self.rsa_key.size() as usize];
+                let len = self.rsa_key.public_encrypt(
+                    b"challenge", &mut challenge, RsaPadding::PKCS1).unwrap();
+                format!(r#"{{"id":"1","challenge":"{}"}}"#,
+                    base64::encode(&challenge[..len])).into_bytes()
+            },
+            ("PATCH", "/auth/1") => br#"{"jwt":"token"}"#.to_vec(),
+            ("PATCH", "/judges") => Vec::new(),
+            ("GET", p) if p == format!("/problems/{}", PROBLEM_ID) =>
+                self.problem.clone().into_bytes(),
+            ("GET", p) if p == format!("/problems/{}/timestamp", PROBLEM_ID) =>
+                b"42".to_vec(),
+            _ => panic!("unexpected request: {}", request_line),
+        };
+        respond(&mut stream, "200 OK", &[], &body, None);
+    }
+
+    /// Serve the test archive to a request with the given `Range` and `If-Range` headers.
+    fn serve_archive(&self, stream: &mut TcpStream, range: Option<String>,
+        if_range: Option<String>) {
+        self.archive_requests.lock().unwrap().push((range.clone(), if_range.clone()));
+        let archive = self.archive();
+        let etag = self.archive_etag();
+        let etag_header = format!("ETag: {}", etag);
+
+        let honor_range = self.support_range.load(Ordering::SeqCst) &&
+            if_range.map(|tag| tag == etag).unwrap_or(true);
+        let start = match range {
+            Some(ref range) if honor_range => range
+                .trim_start_matches("bytes=")
+                .trim_end_matches('-')
+                .parse()
+                .unwrap(),
+            _ => 0
+        };
+        if start > 0 && start >= archive.len() {
+            let content_range = format!("Content-Range: bytes */{}", archive.len());
+            respond(stream, "416 Range Not Satisfiable", &[&etag_header, &content_range], &[],
+                None);
+            return;
+        }
+
+        let content = &archive[start..];
+        let interrupted = {
+            let mut interruptions = self.archive_interruptions.lock().unwrap();
+            let interrupted = *interruptions > 0;
+            if interrupted {
+                *interruptions -= 1;
+            }
+            interrupted
+        };
+        let truncate = if interrupted { Some(content.len() / 2) } else { None };
+        if start > 0 {
+            let content_range = format!("Content-Range: bytes {}-{}/{}",
+                start, archive.len() - 1, archive.len());
+            respond(stream, "206 Partial Content", &[&etag_header, &content_range], content,
+                truncate);
+        } else {
+            respond(stream, "200 OK", &[&etag_header], content, truncate);
+        }
+    }
+}
+
+/// Write a response with the given status, headers and body to the given stream. If `truncate` is
+/// not `None`, only the given number of bytes of the body are written.
+fn respond(stream: &mut TcpStream, status: &str, headers: &[&str], body: &[u8],
+    truncate: Option<usize>) {
+    let mut response = format!(
+        "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n", status, body.len());
+    for header in headers {
+        response.push_str(header);
+        response.push_str("\r\n");
+    }
+    response.push_str("\r\n");
+
+    stream.write_all(response.as_bytes()).unwrap();
+    stream.write_all(&body[..truncate.unwrap_or(body.len())]).unwrap();
+    stream.flush().unwrap();
+}
+
+/// Start a mock judge board server serving a problem in the given judge mode.
+pub(crate) fn start_mock_judge_board(judge_mode: &str, rsa_key: Rsa<Private>)
+    -> (Arc<MockServer>, reqwest::Url) {
+    start_mock_judge_board_with_fields(judge_mode, "", rsa_key)
+}
+
+/// Start a mock judge board server serving a problem in the given judge mode. `extra_fields` are
+/// appended to the fields of the problem information in JSON format, e.g. `"subtasks": []`.
+pub(crate) fn start_mock_judge_board_with_fields(judge_mode: &str, extra_fields: &str,
+    rsa_key: Rsa<Private>) -> (Arc<MockServer>, reqwest::Url) {
+    let mut archive = std::io::Cursor::new(Vec::new());
+    {
+        let mut writer = zip::ZipWriter::new(&mut archive);
+        for name in &["tc1.in", "tc1.ans"] {
+            writer.start_file(*name, zip::write::FileOptions::default()).unwrap();
+            writer.write_all(b"1").unwrap();
+        }
+        writer.finish().unwrap();
+    }
+
+    let problem = format!(r#"{{
+        "id": "{}", "judgeMode": "{}", "timeLimit": 1000, "memoryLimit": 256,
+        "jurySource": "int main() {{}}",
+        "juryLanguage": {{"identifier": "cpp", "dialect": "gnu", "version": "c++17"}},
+        "archiveId": "{}", "timestamp": 42{}{}
+    }}"#, PROBLEM_ID, judge_mode, ARCHIVE_ID,
+        if extra_fields.is_empty() { "" } else { ", " }, extra_fields);
+    let server = Arc::new(MockServer {
+        problem,
+        archive: Mutex::new(archive.into_inner()),
+        archive_version: AtomicUsize::new(1),
+        rsa_key,
+        offline: AtomicBool::new(false),
+        support_range: AtomicBool::new(true),
+        archive_interruptions: Mutex::new(0),
+        archive_requests: Mutex::new(Vec::new()),
+    });
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = reqwest::Url::parse(&format!("http://{}", listener.local_addr().unwrap()))
+        .unwrap();
+    let server_clone = server.clone();
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            server_clone.handle(stream.unwrap());
+        }
+    });
+
+    (server, url)
+}
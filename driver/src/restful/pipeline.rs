@@ -2,8 +2,10 @@
 //!
 
 use reqwest::{
+    Method,
     RequestBuilder,
     Response,
+    Url,
 };
 
 error_chain::error_chain! {
@@ -50,7 +52,15 @@ impl Pipeline {
 
     /// Execute the request, using the given HTTP client.
     pub fn execute(&self, req: RequestBuilder) -> Result<Response> {
-        let context = PipelineContext::new(self, req);
+        self.execute_with_body(req, None)
+    }
+
+    /// Execute the request with the given body. The body should not be set on the request itself;
+    /// it is buffered in the pipeline context, so that middlewares can inspect it and the request
+    /// can be re-executed, and attached to the request when the request is sent.
+    pub fn execute_with_body(&self, req: RequestBuilder, body: Option<Vec<u8>>)
+        -> Result<Response> {
+        let context = PipelineContext::new(self, req, body);
         context.invoke_next()
     }
 }
@@ -63,16 +73,20 @@ pub struct PipelineContext<'a> {
     /// The request.
     request: Option<RequestBuilder>,
 
+    /// The buffered body of the request, if any.
+    body: Option<Vec<u8>>,
+
     /// Index of the next middleware to be invoked.
     next_index: usize,
 }
 
 impl<'a> PipelineContext<'a> {
     /// Create a new `PipelineContext` object.
-    fn new(pipeline: &'a Pipeline, request: RequestBuilder) -> Self {
+    fn new(pipeline: &'a Pipeline, request: RequestBuilder, body: Option<Vec<u8>>) -> Self {
         PipelineContext {
             pipeline,
             request: Some(request),
+            body,
             next_index: 0,
         }
     }
@@ -95,11 +109,25 @@ impl<'a> PipelineContext<'a> {
         let cloned = PipelineContext {
             pipeline: self.pipeline,
             request: clone_request,
+            body: self.body.clone(),
             next_index: self.next_index,
         };
         Some(cloned)
     }
 
+    /// Get the method and the URL of the underlying request, or `None` if the request cannot be
+    /// inspected, e.g. when a body that cannot be cloned is set on the request itself.
+    pub fn request_target(&self) -> Option<(Method, Url)> {
+        let request = self.request.as_ref()?.try_clone()?.build().ok()?;
+        Some((request.method().clone(), request.url().clone()))
+    }
+
+    /// Get the buffered body of the underlying request. Requests without a body give an empty
+    /// slice.
+    pub fn request_body(&self) -> &[u8] {
+        self.body.as_ref().map(|body| body.as_slice()).unwrap_or(&[])
+    }
+
     /// Execute the given callback on the underlying request builder.
     pub fn map_request<F>(&mut self, mapper: F)
         where F: FnOnce(RequestBuilder) -> RequestBuilder {
@@ -110,7 +138,11 @@ impl<'a> PipelineContext<'a> {
     pub fn invoke_next(mut self) -> Result<Response> {
         if self.next_index == self.pipeline.middlewares.len() {
             // All middleware has been invoked. Execute the request.
-            let response = self.take_request().send()?;
+            let mut request = self.take_request();
+            if let Some(body) = self.body.take() {
+                request = request.body(body);
+            }
+            let response = request.send()?;
             return Ok(response);
         }
 
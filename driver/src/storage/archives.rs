@@ -2,21 +2,24 @@
 //!
 
 use std::collections::HashMap;
-use std::convert::TryFrom;
 use std::fmt::{Display, Formatter};
 use std::fs::File;
-use std::io::{Read, Seek, SeekFrom};
-use std::path::{Path, PathBuf};
+use std::io::{BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Component, Path, PathBuf};
 use std::str::FromStr;
 use std::string::ToString;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{Receiver, SyncSender};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
+use flate2::read::GzDecoder;
+use nix::sys::time::{TimeVal, TimeValLike};
 use serde::{Serialize, Deserialize};
 use zip::ZipArchive;
-use zip::read::ZipFile;
 
+use crate::metrics::Metrics;
 use crate::restful::RestfulClient;
-use crate::restful::entities::ObjectId;
+use crate::restful::entities::{JudgeMode, ObjectId};
 use crate::sync::KeyLock;
 
 error_chain::error_chain! {
@@ -30,6 +33,7 @@ error_chain::error_chain! {
 
     foreign_links {
         IoError(::std::io::Error);
+        NixError(::nix::Error);
         ZipError(::zip::result::ZipError);
         SerdeJsonError(::serde_json::Error);
     }
@@ -39,6 +43,15 @@ error_chain::error_chain! {
             description("bad test archive"),
             display("bad test archive: {}", corruption)
         }
+
+        UnknownArchiveFormat {
+            description("unknown test archive format")
+        }
+
+        ArchiveNotCached(id: ObjectId) {
+            description("test archive is not cached"),
+            display("test archive is not cached: {}", id)
+        }
     }
 }
 
@@ -53,6 +66,9 @@ pub enum TestArchiveCorruption {
 
     /// Some entry cannot be categorized.
     UnknownEntry(PathBuf),
+
+    /// The archive contains no test cases, which is not permitted by the `EmptyTestSuitePolicy`.
+    NoTestCases,
 }
 
 impl Display for TestArchiveCorruption {
@@ -64,9 +80,44 @@ impl Display for TestArchiveCorruption {
             MissingAnswerFile(path) =>
                 f.write_fmt(format_args!("missing answer file for entry: {}", path.display())),
             UnknownEntry(path) =>
-                f.write_fmt(format_args!("unknown entry: {}", path.display()))
+                f.write_fmt(format_args!("unknown entry: {}", path.display())),
+            NoTestCases => f.write_str("no test cases")
+        }
+    }
+}
+
+/// Policy on test archives containing no test cases.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmptyTestSuitePolicy {
+    /// Test archives must contain at least one test case.
+    Forbid,
+
+    /// Test archives may contain no test cases.
+    Permit,
+}
+
+impl EmptyTestSuitePolicy {
+    /// Get the policy for problems judged in the given mode.
+    ///
+    /// Problems judged in standard mode or special judge mode check the output of the judgee
+    /// against the test cases, so an empty test suite would judge nothing. Interactive problems
+    /// may have their interactor generate test cases on the fly and thus are permitted to have an
+    /// empty test suite.
+    pub fn for_judge_mode(mode: JudgeMode) -> Self {
+        match mode {
+            JudgeMode::Standard | JudgeMode::SpecialJudge => EmptyTestSuitePolicy::Forbid,
+            JudgeMode::Interactive => EmptyTestSuitePolicy::Permit,
         }
     }
+
+    /// Check whether the given test archive metadata is acceptable under this policy.
+    fn check(self, metadata: &TestArchiveMetadata) -> Result<()> {
+        if self == EmptyTestSuitePolicy::Forbid && metadata.test_cases.is_empty() {
+            return Err(Error::from(ErrorKind::BadTestArchive(TestArchiveCorruption::NoTestCases)));
+        }
+
+        Ok(())
+    }
 }
 
 /// Extension of the input files inside a test archive.
@@ -75,6 +126,10 @@ const INPUT_FILE_EXTENSION: &'static str = "in";
 /// Extension of the answer files inside a test archive.
 const ANSWER_FILE_EXTENSION: &'static str = "ans";
 
+/// Name of the directory inside a test archive that contains grader files, i.e. helper files that
+/// are copied into the judge directory before the judgee runs.
+const GRADER_DIR_NAME: &'static str = "grader";
+
 /// Represent the kind of an entry in the test archive.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum TestArchiveEntryKind {
@@ -86,12 +141,18 @@ enum TestArchiveEntryKind {
 
     /// The entry represents an answer file.
     AnswerFile,
+
+    /// The entry represents a grader file under the grader directory.
+    GraderFile,
 }
 
 impl TestArchiveEntryKind {
-    /// Get the kind of the given entry.
-    fn get_kind<'a, 'b>(entry: &'a ZipFile<'b>) -> Self {
-        let entry_name = entry.sanitized_name();
+    /// Get the kind of the entry with the given path.
+    fn get_kind(entry_name: &Path) -> Self {
+        if entry_name.starts_with(GRADER_DIR_NAME) && entry_name != Path::new(GRADER_DIR_NAME) {
+            return TestArchiveEntryKind::GraderFile;
+        }
+
         if entry_name.extension()
             .and_then(|ext| Some(ext == INPUT_FILE_EXTENSION))
             .unwrap_or(false) {
@@ -148,7 +209,7 @@ impl TestCaseEntry {
     fn new<T>(name: T) -> Self
         where T: ToString {
         TestCaseEntry {
-            name: name.to_string()
+            name: name.to_string(),
         }
     }
 
@@ -173,21 +234,20 @@ struct TestArchiveMetadata {
     /// Test cases contained in the archive.
     #[serde(rename = "test_cases")]
     test_cases: Vec<TestCaseEntry>,
-}
 
-impl<'a, R> TryFrom<&'a mut ZipArchive<R>> for TestArchiveMetadata
-    where R: Read + Seek {
-    type Error = Error;
+    /// Paths to the grader files contained in the archive, relative to the root of the archive.
+    #[serde(default)]
+    grader_files: Vec<PathBuf>,
+}
 
-    fn try_from(archive: &'a mut ZipArchive<R>) -> Result<Self> {
+impl TestArchiveMetadata {
+    /// Build the metadata of the given test archive.
+    fn from_archive<A>(archive: &mut A, policy: EmptyTestSuitePolicy) -> Result<Self>
+        where A: ?Sized + TestArchiveReader {
         let mut builder = TestArchiveMetadataBuilder::new();
 
-        let archive_len = archive.len();
-        for i in 0..archive_len {
-            let archive_file = archive.by_index(i)?;
-            let archive_file_path = archive_file.sanitized_name();
-
-            match TestArchiveEntryKind::get_kind(&archive_file) {
+        for archive_file_path in archive.entry_paths()? {
+            match TestArchiveEntryKind::get_kind(&archive_file_path) {
                 TestArchiveEntryKind::Unknown => {
                     return Err(Error::from(
                         ErrorKind::BadTestArchive(
@@ -199,10 +259,13 @@ impl<'a, R> TryFrom<&'a mut ZipArchive<R>> for TestArchiveMetadata
                 TestArchiveEntryKind::AnswerFile => {
                     builder.add_answer_file(archive_file_path);
                 },
+                TestArchiveEntryKind::GraderFile => {
+                    builder.add_grader_file(archive_file_path);
+                },
             }
         }
 
-        builder.get_metadata()
+        builder.get_metadata(policy)
     }
 }
 
@@ -210,6 +273,9 @@ impl<'a, R> TryFrom<&'a mut ZipArchive<R>> for TestArchiveMetadata
 struct TestArchiveMetadataBuilder {
     /// The test cases maintained.
     test_cases: HashMap<String, (Option<PathBuf>, Option<PathBuf>)>,
+
+    /// The grader files maintained.
+    grader_files: Vec<PathBuf>,
 }
 
 impl TestArchiveMetadataBuilder {
@@ -217,9 +283,16 @@ impl TestArchiveMetadataBuilder {
     fn new() -> Self {
         TestArchiveMetadataBuilder {
             test_cases: HashMap::new(),
+            grader_files: Vec::new(),
         }
     }
 
+    /// Add a grader file to the metadata.
+    fn add_grader_file<T>(&mut self, grader_file: T)
+        where T: Into<PathBuf> {
+        self.grader_files.push(grader_file.into());
+    }
+
     /// Add an input file to the metadata.
     fn add_input_file<T>(&mut self, input_file: T)
         where T: Into<PathBuf> {
@@ -271,41 +344,198 @@ impl TestArchiveMetadataBuilder {
         Ok(())
     }
 
-    /// Build the metadata value.
-    fn get_metadata(self) -> Result<TestArchiveMetadata> {
+    /// Build the metadata value. Test cases in the metadata are sorted by their names. The given
+    /// policy decides whether the metadata may contain no test cases.
+    fn get_metadata(self, policy: EmptyTestSuitePolicy) -> Result<TestArchiveMetadata> {
         self.ensure_test_cases_integrity()?;
 
-        Ok(TestArchiveMetadata {
-            test_cases: self.test_cases.into_iter()
-                .map(|tc| TestCaseEntry::new(tc.0))
-                .collect()
+        let mut test_cases: Vec<TestCaseEntry> = self.test_cases.into_iter()
+            .map(|tc| TestCaseEntry::new(tc.0))
+            .collect();
+        test_cases.sort_by(|lhs, rhs| lhs.name.cmp(&rhs.name));
+
+        let mut grader_files = self.grader_files;
+        grader_files.sort();
+
+        let metadata = TestArchiveMetadata { test_cases, grader_files };
+        policy.check(&metadata)?;
+
+        Ok(metadata)
+    }
+}
+
+/// Represent the format of a test archive file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TestArchiveFormat {
+    /// The test archive is a zip archive.
+    Zip,
+
+    /// The test archive is a gzip compressed tar archive.
+    TarGz,
+}
+
+impl TestArchiveFormat {
+    /// Detect the format of the given test archive file by its magic bytes. The file pointer of
+    /// the given file is reset to the start of the file after detection.
+    fn detect<R>(source: &mut R) -> Result<Option<Self>>
+        where R: ?Sized + Read + Seek {
+        let mut magic = [0u8; 4];
+        source.seek(SeekFrom::Start(0))?;
+        let magic_len = source.read(&mut magic)?;
+        source.seek(SeekFrom::Start(0))?;
+
+        let magic = &magic[..magic_len];
+        if magic.starts_with(b"PK\x03\x04") || magic.starts_with(b"PK\x05\x06") {
+            Ok(Some(TestArchiveFormat::Zip))
+        } else if magic.starts_with(b"\x1f\x8b") {
+            Ok(Some(TestArchiveFormat::TarGz))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+/// Provide a trait for archives that contain test cases.
+trait TestArchiveReader: Extractable<Error = Error> {
+    /// Get the sanitized paths of all file entries contained in the archive.
+    fn entry_paths(&mut self) -> Result<Vec<PathBuf>>;
+
+    /// Get the total uncompressed size of all file entries contained in the archive, in bytes.
+    fn content_size(&mut self) -> Result<u64>;
+}
+
+impl<R> TestArchiveReader for ZipArchive<R>
+    where R: Read + Seek {
+    fn entry_paths(&mut self) -> Result<Vec<PathBuf>> {
+        let archive_len = self.len();
+        let mut paths = Vec::with_capacity(archive_len);
+        for i in 0..archive_len {
+            paths.push(self.by_index(i)?.sanitized_name());
+        }
+
+        Ok(paths)
+    }
+
+    fn content_size(&mut self) -> Result<u64> {
+        let mut size = 0;
+        for i in 0..self.len() {
+            size += self.by_index(i)?.size();
+        }
+
+        Ok(size)
+    }
+}
+
+/// Provide access to a gzip compressed tar archive.
+struct TarGzArchive<R>
+    where R: Read + Seek {
+    /// The underlying compressed archive data.
+    source: R,
+}
+
+impl<R> TarGzArchive<R>
+    where R: Read + Seek {
+    /// Create a new `TarGzArchive` value from the given compressed archive data.
+    fn new(source: R) -> Self {
+        TarGzArchive { source }
+    }
+
+    /// Rewind the underlying archive data and open the tar archive from its start. A tar archive
+    /// can only be walked through once, so every pass over the archive calls this function.
+    fn open(&mut self) -> Result<tar::Archive<GzDecoder<&mut R>>> {
+        self.source.seek(SeekFrom::Start(0))?;
+        Ok(tar::Archive::new(GzDecoder::new(&mut self.source)))
+    }
+}
+
+/// Sanitize the given path of an archive entry by removing any root, prefix, current directory
+/// and parent directory components from it.
+fn sanitize_entry_path(path: &Path) -> PathBuf {
+    path.components()
+        .filter_map(|comp| match comp {
+            Component::Normal(name) => Some(name),
+            _ => None
         })
+        .collect()
+}
+
+impl<R> TestArchiveReader for TarGzArchive<R>
+    where R: Read + Seek {
+    fn entry_paths(&mut self) -> Result<Vec<PathBuf>> {
+        let mut archive = self.open()?;
+        let mut paths = Vec::new();
+        for entry in archive.entries()? {
+            let entry = entry?;
+            // Directories and links inside the archive are not test case entries.
+            if !entry.header().entry_type().is_file() {
+                continue;
+            }
+
+            paths.push(sanitize_entry_path(&entry.path()?));
+        }
+
+        Ok(paths)
+    }
+
+    fn content_size(&mut self) -> Result<u64> {
+        let mut archive = self.open()?;
+        let mut size = 0;
+        for entry in archive.entries()? {
+            let entry = entry?;
+            if entry.header().entry_type().is_file() {
+                size += entry.header().size()?;
+            }
+        }
+
+        Ok(size)
+    }
+}
+
+impl<R> Extractable for TarGzArchive<R>
+    where R: Read + Seek {
+    type Error = Error;
+
+    fn extract_into<P>(&mut self, dir: &P) -> std::result::Result<(), Self::Error>
+        where P: ?Sized + AsRef<Path> {
+        let mut archive = self.open()?;
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            if !entry.header().entry_type().is_file() {
+                continue;
+            }
+
+            let mut archive_file_path = dir.as_ref().to_owned();
+            archive_file_path.push(sanitize_entry_path(&entry.path()?));
+            if let Some(parent) = archive_file_path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+
+            let mut output_file = File::create(&archive_file_path)?;
+            std::io::copy(&mut entry, &mut output_file)?;
+        }
+
+        Ok(())
     }
 }
 
 /// Provide information about a test archive.
 #[derive(Debug)]
-struct TestArchive<R>
-    where R: Read + Seek {
-    /// The `ZipArchive` representation about the test archive.
-    archive: ZipArchive<R>,
+struct TestArchive<A>
+    where A: TestArchiveReader {
+    /// The underlying archive.
+    archive: A,
 
     /// The metadata about the archive.
     metadata: TestArchiveMetadata,
 }
 
-impl<R> TestArchive<R>
-    where R: Read + Seek {
-    /// Create a new `TestArchive` value from the given zip archive.
-    fn new(mut archive: ZipArchive<R>) -> Result<Self> {
-        let metadata = TestArchiveMetadata::try_from(&mut archive)?;
+impl<A> TestArchive<A>
+    where A: TestArchiveReader {
+    /// Create a new `TestArchive` value from the given archive.
+    fn new(mut archive: A, policy: EmptyTestSuitePolicy) -> Result<Self> {
+        let metadata = TestArchiveMetadata::from_archive(&mut archive, policy)?;
         Ok(TestArchive { archive, metadata })
     }
-
-    /// Create a new `TestArchive` value from the given `Read` object.
-    fn new_from_read(source: R) -> Result<Self> {
-        TestArchive::new(ZipArchive::new(source)?)
-    }
 }
 
 /// Provide a trait for types whose contents can be extracted into a specific directory.
@@ -318,29 +548,146 @@ trait Extractable {
         where P: ?Sized + AsRef<Path>;
 }
 
+/// Number of threads writing extracted files to the disk when extracting zip archives in parallel.
+const EXTRACT_THREADS: usize = 4;
+
+/// Zip archives with fewer entries than this are extracted on the calling thread, since the cost
+/// of spawning writer threads outweighs the benefit of parallel writes.
+const PARALLEL_EXTRACT_MIN_ENTRIES: usize = 16;
+
+/// Size of the chunks in which the contents of a zip archive entry are sent to the writer threads.
+const EXTRACT_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Maximal number of chunks of a zip archive entry sent to a writer thread but not yet written.
+const EXTRACT_CHUNKS_IN_FLIGHT: usize = 4;
+
+/// A file to be extracted by a writer thread, given by the path to the file and the channel
+/// through which the contents of the file are received in chunks.
+type ExtractJob = (PathBuf, Receiver<Vec<u8>>);
+
+/// Provide a `Write` implementation that sends the data written to it as chunks through a channel.
+struct ChunkSender {
+    /// The channel through which the chunks are sent.
+    sender: SyncSender<Vec<u8>>,
+}
+
+impl Write for ChunkSender {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.sender.send(buf.to_vec())
+            .map_err(|_| std::io::Error::new(std::io::ErrorKind::BrokenPipe,
+                "extract writer thread exited"))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Create the given file, together with its parent directories, and write the chunks received
+/// from the given channel into it until the channel is closed.
+fn write_extracted_file(path: &Path, chunks: Receiver<Vec<u8>>) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let mut file = File::create(path)?;
+    for chunk in chunks {
+        file.write_all(&chunk)?;
+    }
+
+    Ok(())
+}
+
+/// Write the files received from the given channel until the channel is closed.
+fn extract_writer_entry(receiver: Arc<Mutex<Receiver<ExtractJob>>>) -> std::io::Result<()> {
+    loop {
+        let received = receiver.lock().expect("failed to lock mutex").recv();
+        match received {
+            Ok((path, chunks)) => write_extracted_file(&path, chunks)?,
+            Err(..) => return Ok(())
+        };
+    }
+}
+
 impl<R> Extractable for ZipArchive<R>
     where R: Seek + Read {
     type Error = Error;
 
+    /// Extract the entries of the zip archive into the specified directory.
+    ///
+    /// Large archives are extracted in parallel: the entries are decompressed in order on the
+    /// calling thread, since the underlying reader cannot be shared, and written to the disk by a
+    /// bounded number of writer threads. The contents of each entry are streamed to its writer
+    /// thread in chunks through a bounded channel, so that only a few chunks are kept in memory
+    /// at the same time regardless of the sizes of the entries.
     fn extract_into<P>(&mut self, dir: &P) -> std::result::Result<(), Self::Error>
         where P: ?Sized + AsRef<Path> {
         let num_files = self.len();
-        for i in 0..num_files {
-            let mut archive_file = self.by_index(i)?;
+        if num_files < PARALLEL_EXTRACT_MIN_ENTRIES {
+            for i in 0..num_files {
+                let mut archive_file = self.by_index(i)?;
+
+                let mut archive_file_path = dir.as_ref().to_owned();
+                archive_file_path.push(archive_file.sanitized_name());
+                if let Some(parent) = archive_file_path.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                let mut output_file = File::create(&archive_file_path)?;
 
-            let mut archive_file_path = dir.as_ref().to_owned();
-            archive_file_path.push(archive_file.sanitized_name());
-            let mut output_file = File::create(&archive_file_path)?;
+                std::io::copy(&mut archive_file, &mut output_file)?;
+            }
 
-            std::io::copy(&mut archive_file, &mut output_file)?;
+            return Ok(());
         }
 
-        Ok(())
+        let (sender, receiver) = std::sync::mpsc::sync_channel(EXTRACT_THREADS);
+        let receiver = Arc::new(Mutex::new(receiver));
+        let writers: Vec<_> = (0..EXTRACT_THREADS)
+            .map(|_| {
+                let receiver = receiver.clone();
+                std::thread::spawn(move || extract_writer_entry(receiver))
+            })
+            .collect();
+        // Only the writer threads hold the receiver from now on, so that sending fails once all of
+        // them have exited due to errors.
+        drop(receiver);
+
+        let mut read_entries = || -> Result<()> {
+            for i in 0..num_files {
+                let mut archive_file = self.by_index(i)?;
+
+                let mut archive_file_path = dir.as_ref().to_owned();
+                archive_file_path.push(archive_file.sanitized_name());
+                let (chunk_sender, chunk_receiver) =
+                    std::sync::mpsc::sync_channel(EXTRACT_CHUNKS_IN_FLIGHT);
+                if sender.send((archive_file_path, chunk_receiver)).is_err() {
+                    // All writer threads have failed. The error is reported below.
+                    break;
+                }
+
+                // The writer thread of this entry exits early if it fails, in which case sending
+                // fails and the error of the writer thread is reported below.
+                let mut writer = BufWriter::with_capacity(
+                    EXTRACT_CHUNK_SIZE, ChunkSender { sender: chunk_sender });
+                std::io::copy(&mut archive_file, &mut writer)?;
+                writer.flush()?;
+            }
+
+            Ok(())
+        };
+        let read_result = read_entries();
+        drop(sender);
+
+        for writer in writers {
+            writer.join().expect("extract writer thread panicked")?;
+        }
+        read_result
     }
 }
 
-impl<R> Extractable for TestArchive<R>
-    where R: Seek + Read {
+impl<A> Extractable for TestArchive<A>
+    where A: TestArchiveReader {
     type Error = Error;
 
     fn extract_into<P>(&mut self, dir: &P) -> std::result::Result<(), Self::Error>
@@ -352,7 +699,7 @@ impl<R> Extractable for TestArchive<R>
 /// Provide an interator over a test archive represented by `TestArchiveHandle`.
 pub struct TestArchiveEntryIterator<'a> {
     handle: &'a TestArchiveHandle,
-    inner: std::slice::Iter<'a, TestCaseEntry>
+    inner: std::iter::Enumerate<std::slice::Iter<'a, TestCaseEntry>>
 }
 
 impl<'a> TestArchiveEntryIterator<'a> {
@@ -361,7 +708,7 @@ impl<'a> TestArchiveEntryIterator<'a> {
     pub fn new(handle: &'a TestArchiveHandle) -> Self {
         TestArchiveEntryIterator {
             handle,
-            inner: handle.metadata.test_cases.iter()
+            inner: handle.metadata.test_cases.iter().enumerate()
         }
     }
 }
@@ -370,7 +717,7 @@ impl<'a> Iterator for TestArchiveEntryIterator<'a> {
     type Item = TestCaseInfo<'a>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        self.inner.next().map(|entry| TestCaseInfo::new(self.handle, entry))
+        self.inner.next().map(|(index, entry)| TestCaseInfo::new(self.handle, index, entry))
     }
 }
 
@@ -381,12 +728,22 @@ pub struct TestArchiveHandle {
 
     /// The metadata of the test archive.
     metadata: TestArchiveMetadata,
+
+    /// Number of leading test cases in the test archive that are public.
+    public_test_cases: usize,
+
+    /// Keeps the test archive from being evicted while this handle is alive.
+    _usage: ArchiveUsageGuard,
 }
 
 impl TestArchiveHandle {
     /// Create a new `TestArchiveHandle` value representing the test archive residing in the
     /// specific directory.
-    fn new<P1, P2>(dir: &P1, metadata_file_path: &P2) -> Result<Self>
+    fn new<P1, P2>(
+        dir: &P1,
+        metadata_file_path: &P2,
+        public_test_cases: usize,
+        usage: ArchiveUsageGuard) -> Result<Self>
         where P1: ?Sized + AsRef<Path>,
               P2: ?Sized + AsRef<Path> {
         let mut metadata_file = File::open(metadata_file_path)?;
@@ -394,7 +751,9 @@ impl TestArchiveHandle {
 
         Ok(TestArchiveHandle {
             dir: dir.as_ref().to_owned(),
-            metadata
+            metadata,
+            public_test_cases,
+            _usage: usage,
         })
     }
 
@@ -402,6 +761,19 @@ impl TestArchiveHandle {
     pub fn test_cases<'a>(&'a self) -> TestArchiveEntryIterator<'a> {
         TestArchiveEntryIterator::new(self)
     }
+
+    /// Get the grader files contained in this test archive, given by pairs of the path to the file
+    /// and the path relative to the grader directory, at which the file should be placed in the
+    /// judge directory.
+    pub fn grader_files(&self) -> Vec<(PathBuf, PathBuf)> {
+        self.metadata.grader_files.iter()
+            .map(|file| {
+                let dest = file.strip_prefix(GRADER_DIR_NAME)
+                    .expect("grader file is not under the grader directory");
+                (self.dir.join(file), dest.to_owned())
+            })
+            .collect()
+    }
 }
 
 /// Represent a test case in a test archive.
@@ -409,14 +781,18 @@ pub struct TestCaseInfo<'a> {
     /// The handle to the test archive containing this test case.
     handle: &'a TestArchiveHandle,
 
+    /// Index of the test case in the test archive.
+    index: usize,
+
     /// The test case entry in the test archive.
     test_case_entry: &'a TestCaseEntry,
 }
 
 impl<'a> TestCaseInfo<'a> {
     /// Create a new `TestCaseInfo` value.
-    fn new(handle: &'a TestArchiveHandle, test_case_entry: &'a TestCaseEntry) -> Self {
-        TestCaseInfo { handle, test_case_entry }
+    fn new(handle: &'a TestArchiveHandle, index: usize, test_case_entry: &'a TestCaseEntry)
+        -> Self {
+        TestCaseInfo { handle, index, test_case_entry }
     }
 
     /// Get the path to the input file of this test case.
@@ -432,6 +808,68 @@ impl<'a> TestCaseInfo<'a> {
         p.push(self.test_case_entry.answer_file_path());
         p
     }
+
+    /// Determine whether this test case is public, i.e. it is among the leading test cases that
+    /// are configured to be public in the archive store.
+    pub fn is_public(&self) -> bool {
+        self.index < self.handle.public_test_cases
+    }
+}
+
+/// Number of users of each test archive that is currently in use.
+type ArchiveUsage = Arc<Mutex<HashMap<ObjectId, usize>>>;
+
+/// Mark a test archive as in use during the lifetime of this value. Test archives in use are never
+/// evicted from the archive store.
+struct ArchiveUsageGuard {
+    /// The usage counters shared with the archive store.
+    usage: ArchiveUsage,
+
+    /// ID of the test archive in use.
+    id: ObjectId,
+}
+
+impl ArchiveUsageGuard {
+    /// Create a new `ArchiveUsageGuard` value that marks the specified test archive as in use.
+    fn new(usage: ArchiveUsage, id: ObjectId) -> Self {
+        *usage.lock().expect("failed to lock archive usage").entry(id).or_insert(0) += 1;
+        ArchiveUsageGuard { usage, id }
+    }
+}
+
+impl Drop for ArchiveUsageGuard {
+    fn drop(&mut self) {
+        let mut usage = self.usage.lock().expect("failed to lock archive usage");
+        let count = usage.get_mut(&self.id).expect("archive usage is not tracked");
+        *count -= 1;
+        if *count == 0 {
+            usage.remove(&self.id);
+        }
+    }
+}
+
+/// Information about a test archive cached on the local disk.
+struct CachedArchive {
+    /// ID of the test archive.
+    id: ObjectId,
+
+    /// Total size of the contents of the test archive, in bytes.
+    size: u64,
+
+    /// The last time the test archive was accessed.
+    last_access: SystemTime,
+}
+
+/// Set the last access time of the test archive residing in the given directory.
+fn set_last_access<P>(archive_dir: &P, time: SystemTime) -> Result<()>
+    where P: ?Sized + AsRef<Path> {
+    // The modification time of the archive directory is used as the last access time since it does
+    // not change after the archive is extracted.
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or(Duration::from_secs(0));
+    let time = TimeVal::microseconds(since_epoch.as_micros() as i64);
+    nix::sys::stat::utimes(archive_dir.as_ref(), &time, &time)?;
+
+    Ok(())
 }
 
 /// Provide access to local archive store.
@@ -442,18 +880,40 @@ pub struct ArchiveStore {
     /// The root directory of the archive store on the local disk.
     root_dir: PathBuf,
 
+    /// Number of leading test cases in each test archive that are public.
+    public_test_cases: usize,
+
+    /// Maximum total size of the cached test archives, in bytes. `None` means that the size of the
+    /// archive store is not limited.
+    max_size: Option<u64>,
+
+    /// Test archives currently in use.
+    usage: ArchiveUsage,
+
     /// The RESTful client connected to the judge board server.
     rest: Arc<RestfulClient>,
+
+    /// The metrics registry into which the sizes of downloaded test archives are recorded.
+    metrics: Arc<Metrics>,
 }
 
 impl ArchiveStore {
     /// Create a new `ArchiveStore` instance.
-    pub(super) fn new<P>(dir: P, rest: Arc<RestfulClient>) -> Result<ArchiveStore>
+    pub(super) fn new<P>(
+        dir: P,
+        public_test_cases: usize,
+        max_size: Option<u64>,
+        rest: Arc<RestfulClient>,
+        metrics: Arc<Metrics>) -> Result<ArchiveStore>
         where P: Into<PathBuf> {
         let store = ArchiveStore {
             lock: KeyLock::new(),
             root_dir: dir.into(),
-            rest
+            public_test_cases,
+            max_size,
+            usage: Arc::new(Mutex::new(HashMap::new())),
+            rest,
+            metrics
         };
 
         // Create dir if it does not exist.
@@ -478,9 +938,13 @@ impl ArchiveStore {
     }
 
     /// Extract the content of the given test archive into the specified directory.
-    fn extract_archive<R, T>(&self, mut archive: TestArchive<R>, archive_dir: &T) -> Result<()>
-        where R: Seek + Read,
+    fn extract_archive<A, T>(&self, mut archive: TestArchive<A>, archive_dir: &T) -> Result<()>
+        where A: TestArchiveReader,
               T: ?Sized + AsRef<Path> {
+        // Make room for the new archive before extracting it.
+        let archive_size = archive.archive.content_size()?;
+        self.reclaim(archive_size)?;
+
         let archive_metadata = &archive.metadata;
         log::debug!("Archive metadata extracted: {:?}", archive_metadata);
 
@@ -501,20 +965,33 @@ impl ArchiveStore {
     }
 
     /// Download the specified test archive, verify and extract to the specified archive directory.
-    fn download_archive<T>(&self, id: ObjectId, archive_dir: &T) -> Result<()>
+    fn download_archive<T>(&self, id: ObjectId, archive_dir: &T, policy: EmptyTestSuitePolicy)
+        -> Result<()>
         where T: ?Sized + AsRef<Path> {
         // Create a temporary file and download the test archive from the judge board server.
         log::info!("Downloading archive {}", id);
         let mut archive_file = tempfile::tempfile()?;
         self.rest.download_archive(id, &mut archive_file)?;
+        self.metrics.add_archive_download_bytes(archive_file.metadata()?.len());
 
         log::info!("Verifying archive {}", id);
-        archive_file.seek(SeekFrom::Start(0))?;
-        let archive = TestArchive::new_from_read(archive_file)?;
+        let format = TestArchiveFormat::detect(&mut archive_file)?;
+        log::debug!("Format of archive {}: {:?}", id, format);
 
         let archive_dir = archive_dir.as_ref();
-        log::info!("Extracting archive {} into {}", id, archive_dir.display());
-        self.extract_archive(archive, archive_dir)
+        match format {
+            Some(TestArchiveFormat::Zip) => {
+                let archive = TestArchive::new(ZipArchive::new(archive_file)?, policy)?;
+                log::info!("Extracting archive {} into {}", id, archive_dir.display());
+                self.extract_archive(archive, archive_dir)
+            },
+            Some(TestArchiveFormat::TarGz) => {
+                let archive = TestArchive::new(TarGzArchive::new(archive_file), policy)?;
+                log::info!("Extracting archive {} into {}", id, archive_dir.display());
+                self.extract_archive(archive, archive_dir)
+            },
+            None => Err(Error::from(ErrorKind::UnknownArchiveFormat))
+        }
     }
 
     /// Get archive with the given ID. If the archive does not exist on the local disk, this
@@ -523,18 +1000,121 @@ impl ArchiveStore {
     ///
     /// `rest` is a `RestfulClient` object that has connected to the judge board through which the
     /// missing archive will be downloaded.
-    pub fn get(&self, id: ObjectId) -> Result<TestArchiveHandle> {
+    ///
+    /// `policy` decides whether the archive may contain no test cases. Archives violating the
+    /// policy are rejected with a `TestArchiveCorruption::NoTestCases` error.
+    pub fn get(&self, id: ObjectId, policy: EmptyTestSuitePolicy) -> Result<TestArchiveHandle> {
+        // Mark the archive as in use before accessing it so that it cannot be evicted by other
+        // threads until the returned handle is dropped.
+        let usage = ArchiveUsageGuard::new(self.usage.clone(), id);
+
         let archive_dir = self.get_archive_dir(id);
         self.lock.lock_and_execute(id, |_| {
             if !archive_dir.exists() {
-                self.download_archive(id, &archive_dir)
+                self.download_archive(id, &archive_dir, policy)
             } else {
-                Ok(())
+                set_last_access(&archive_dir, SystemTime::now())
             }
         })?;
 
         let metadata_file_path = self.get_metadata_file_path(&archive_dir);
-        TestArchiveHandle::new(&archive_dir, &metadata_file_path)
+        let handle = TestArchiveHandle::new(
+            &archive_dir, &metadata_file_path, self.public_test_cases, usage)?;
+
+        // The archive might be cached under a more permissive policy.
+        policy.check(&handle.metadata)?;
+
+        Ok(handle)
+    }
+
+    /// Get information about all test archives cached on the local disk.
+    fn scan_cached(&self) -> Result<Vec<CachedArchive>> {
+        let mut archives = Vec::new();
+        for entry in std::fs::read_dir(&self.root_dir)? {
+            let entry = entry?;
+            if !entry.file_type()?.is_dir() {
+                continue;
+            }
+
+            // Directories not named after an archive ID are not maintained by the archive store.
+            let id = match entry.file_name().to_str().map(ObjectId::from_str) {
+                Some(Ok(id)) => id,
+                _ => continue
+            };
+
+            archives.push(CachedArchive {
+                id,
+                size: sandbox::dir_size(&entry.path())?,
+                last_access: entry.metadata()?.modified()?,
+            });
+        }
+
+        Ok(archives)
+    }
+
+    /// Evict the least recently used test archives that are not in use until the total size of the
+    /// archive store plus the given number of bytes does not exceed the maximum size of the archive
+    /// store.
+    fn reclaim(&self, required: u64) -> Result<()> {
+        let max_size = match self.max_size {
+            Some(size) => size,
+            None => return Ok(())
+        };
+
+        // The usage counters are locked during the whole process so that no archive can be put into
+        // use while it is being evicted.
+        let usage = self.usage.lock().expect("failed to lock archive usage");
+
+        let mut archives = self.scan_cached()?;
+        archives.sort_by_key(|archive| archive.last_access);
+
+        let mut total_size = archives.iter().map(|archive| archive.size).sum::<u64>() + required;
+        for archive in archives {
+            if total_size <= max_size {
+                break;
+            }
+            if usage.contains_key(&archive.id) {
+                continue;
+            }
+
+            log::info!("Evicting archive {} of {} bytes from the archive store",
+                archive.id, archive.size);
+            std::fs::remove_dir_all(self.get_archive_dir(archive.id))?;
+            total_size -= archive.size;
+        }
+
+        if total_size > max_size {
+            log::warn!("Archive store size {} bytes exceeds the limit of {} bytes since the \
+                remaining archives are in use.", total_size, max_size);
+        }
+
+        Ok(())
+    }
+
+    /// Get the IDs of all test archives cached on the local disk, together with the total size of
+    /// each archive's contents in bytes. The returned list is sorted by archive ID.
+    pub fn list_cached(&self) -> Result<Vec<(ObjectId, u64)>> {
+        let mut archives: Vec<(ObjectId, u64)> = self.scan_cached()?.into_iter()
+            .map(|archive| (archive.id, archive.size))
+            .collect();
+
+        archives.sort_by_key(|(id, _)| id.to_string());
+        Ok(archives)
+    }
+
+    /// Remove the specified test archive from the local disk. The archive will be downloaded again
+    /// the next time it is requested.
+    pub fn evict(&self, id: ObjectId) -> Result<()> {
+        let archive_dir = self.get_archive_dir(id);
+        self.lock.lock_and_execute(id, |_| {
+            if !archive_dir.exists() {
+                return Err(Error::from(ErrorKind::ArchiveNotCached(id)));
+            }
+
+            log::info!("Evicting archive {}", id);
+            std::fs::remove_dir_all(&archive_dir)?;
+            Ok(())
+        })
     }
 }
 
@@ -591,14 +1171,14 @@ mod tests {
         fn miss_input_file() {
             let mut builder = TestArchiveMetadataBuilder::new();
             builder.add_answer_file("path/to/answer.ans");
-            assert!(builder.get_metadata().is_err());
+            assert!(builder.get_metadata(EmptyTestSuitePolicy::Forbid).is_err());
         }
 
         #[test]
         fn miss_answer_file() {
             let mut builder = TestArchiveMetadataBuilder::new();
             builder.add_input_file("path/to/input.in");
-            assert!(builder.get_metadata().is_err());
+            assert!(builder.get_metadata(EmptyTestSuitePolicy::Forbid).is_err());
         }
 
         #[test]
@@ -608,7 +1188,7 @@ mod tests {
             builder.add_answer_file("tc1.ans");
             builder.add_input_file("subdir/tc2.in");
             builder.add_answer_file("subdir/tc2.ans");
-            let metadata = builder.get_metadata().unwrap();
+            let metadata = builder.get_metadata(EmptyTestSuitePolicy::Forbid).unwrap();
 
             let mut mask = 0u32;
             for tc in metadata.test_cases.iter() {
@@ -623,5 +1203,386 @@ mod tests {
 
             assert_eq!(3, mask);
         }
+
+        #[test]
+        fn empty_forbidden() {
+            let builder = TestArchiveMetadataBuilder::new();
+            match builder.get_metadata(EmptyTestSuitePolicy::Forbid) {
+                Err(Error(ErrorKind::BadTestArchive(TestArchiveCorruption::NoTestCases), _)) => (),
+                r => panic!("unexpected result: {:?}", r),
+            };
+        }
+
+        #[test]
+        fn empty_permitted() {
+            let builder = TestArchiveMetadataBuilder::new();
+            let metadata = builder.get_metadata(EmptyTestSuitePolicy::Permit).unwrap();
+            assert!(metadata.test_cases.is_empty());
+        }
+
+        #[test]
+        fn policy_for_judge_mode() {
+            assert_eq!(EmptyTestSuitePolicy::Forbid,
+                EmptyTestSuitePolicy::for_judge_mode(JudgeMode::Standard));
+            assert_eq!(EmptyTestSuitePolicy::Forbid,
+                EmptyTestSuitePolicy::for_judge_mode(JudgeMode::SpecialJudge));
+            assert_eq!(EmptyTestSuitePolicy::Permit,
+                EmptyTestSuitePolicy::for_judge_mode(JudgeMode::Interactive));
+        }
+
+        #[test]
+        fn grader_files() {
+            let mut builder = TestArchiveMetadataBuilder::new();
+            builder.add_input_file("tc1.in");
+            builder.add_answer_file("tc1.ans");
+            builder.add_grader_file("grader/lib/grader.h");
+            builder.add_grader_file("grader/data.txt");
+            let metadata = builder.get_metadata(EmptyTestSuitePolicy::Forbid).unwrap();
+
+            assert_eq!(1, metadata.test_cases.len());
+            assert_eq!(vec![PathBuf::from("grader/data.txt"), PathBuf::from("grader/lib/grader.h")],
+                metadata.grader_files);
+        }
+
+        #[test]
+        fn entry_kind() {
+            assert_eq!(TestArchiveEntryKind::GraderFile,
+                TestArchiveEntryKind::get_kind(Path::new("grader/grader.in")));
+            assert_eq!(TestArchiveEntryKind::InputFile,
+                TestArchiveEntryKind::get_kind(Path::new("subdir/grader.in")));
+            assert_eq!(TestArchiveEntryKind::Unknown,
+                TestArchiveEntryKind::get_kind(Path::new("grader")));
+        }
+
+        #[test]
+        fn deserialize_without_grader_files() {
+            let metadata: TestArchiveMetadata =
+                serde_json::from_str(r#"{"test_cases":[{"name":"tc1"}]}"#).unwrap();
+            assert!(metadata.grader_files.is_empty());
+        }
+
+        #[test]
+        fn deserialize_with_public() {
+            let metadata: TestArchiveMetadata =
+                serde_json::from_str(r#"{"test_cases":[{"name":"tc1","public":true}]}"#).unwrap();
+            assert_eq!("tc1", metadata.test_cases[0].name);
+        }
+    }
+
+    mod zip_archive_tests {
+        use super::*;
+
+        use std::io::{Cursor, Write};
+
+        fn make_entries(count: usize) -> Vec<(String, Vec<u8>)> {
+            (0..count)
+                .map(|i| {
+                    let name = format!("subdir{}/tc{}.in", i % 3, i);
+                    let data = (0..i * 1000).map(|j| (j * 31 + i) as u8).collect();
+                    (name, data)
+                })
+                .collect()
+        }
+
+        fn make_zip(entries: &[(String, Vec<u8>)]) -> ZipArchive<Cursor<Vec<u8>>> {
+            let mut zip_data = Cursor::new(Vec::new());
+            {
+                let mut writer = zip::ZipWriter::new(&mut zip_data);
+                for (name, data) in entries {
+                    writer.start_file(name.as_str(), zip::write::FileOptions::default()).unwrap();
+                    writer.write_all(data).unwrap();
+                }
+                writer.finish().unwrap();
+            }
+
+            ZipArchive::new(zip_data).unwrap()
+        }
+
+        fn check_extract(entries: &[(String, Vec<u8>)]) {
+            let mut archive = make_zip(entries);
+
+            let dir = tempfile::tempdir().unwrap();
+            archive.extract_into(dir.path()).unwrap();
+
+            for (name, data) in entries {
+                assert_eq!(*data, std::fs::read(dir.path().join(name)).unwrap());
+            }
+        }
+
+        #[test]
+        fn extract_sequential() {
+            check_extract(&make_entries(PARALLEL_EXTRACT_MIN_ENTRIES - 1));
+        }
+
+        #[test]
+        fn extract_parallel() {
+            check_extract(&make_entries(PARALLEL_EXTRACT_MIN_ENTRIES * 4));
+        }
+
+        #[test]
+        fn extract_parallel_large_entry() {
+            let mut entries = make_entries(PARALLEL_EXTRACT_MIN_ENTRIES);
+            let len = EXTRACT_CHUNK_SIZE * (EXTRACT_CHUNKS_IN_FLIGHT + 2) + 1;
+            entries.push((String::from("large.in"), (0..len).map(|i| (i % 251) as u8).collect()));
+            check_extract(&entries);
+        }
+
+        #[test]
+        fn extract_parallel_write_failure() {
+            let mut archive = make_zip(&make_entries(PARALLEL_EXTRACT_MIN_ENTRIES));
+
+            let dir = tempfile::tempdir().unwrap();
+            let not_dir = dir.path().join("file");
+            std::fs::write(&not_dir, "").unwrap();
+            assert!(archive.extract_into(&not_dir).is_err());
+        }
+    }
+
+    mod tar_gz_archive_tests {
+        use super::*;
+
+        use std::io::{Cursor, Write};
+
+        use flate2::Compression;
+        use flate2::write::GzEncoder;
+
+        fn make_tar_gz(entries: &[(&str, &str)]) -> Vec<u8> {
+            let mut builder = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+            for (path, content) in entries {
+                let mut header = tar::Header::new_gnu();
+                header.set_size(content.len() as u64);
+                header.set_mode(0o644);
+                header.set_cksum();
+                builder.append_data(&mut header, path, content.as_bytes()).unwrap();
+            }
+
+            builder.into_inner().unwrap().finish().unwrap()
+        }
+
+        fn open_tar_gz(tar_gz: Vec<u8>) -> Result<TestArchive<TarGzArchive<Cursor<Vec<u8>>>>> {
+            TestArchive::new(TarGzArchive::new(Cursor::new(tar_gz)), EmptyTestSuitePolicy::Forbid)
+        }
+
+        #[test]
+        fn detect_format() {
+            let tar_gz = make_tar_gz(&[("tc1.in", "1 2"), ("tc1.ans", "3")]);
+            assert_eq!(Some(TestArchiveFormat::TarGz),
+                TestArchiveFormat::detect(&mut Cursor::new(tar_gz)).unwrap());
+
+            let mut zip_data = Cursor::new(Vec::new());
+            {
+                let mut writer = zip::ZipWriter::new(&mut zip_data);
+                writer.start_file("tc1.in", zip::write::FileOptions::default()).unwrap();
+                writer.write_all(b"1 2").unwrap();
+                writer.finish().unwrap();
+            }
+            assert_eq!(Some(TestArchiveFormat::Zip),
+                TestArchiveFormat::detect(&mut zip_data).unwrap());
+
+            assert_eq!(None, TestArchiveFormat::detect(&mut Cursor::new(b"hello")).unwrap());
+        }
+
+        #[test]
+        fn metadata() {
+            let tar_gz = make_tar_gz(&[
+                ("./tc1.in", "1 2"), ("./tc1.ans", "3"),
+                ("subdir/tc2.in", "3 4"), ("subdir/tc2.ans", "7"),
+            ]);
+            let archive = open_tar_gz(tar_gz).unwrap();
+
+            let mut mask = 0u32;
+            for tc in archive.metadata.test_cases.iter() {
+                if tc.name == "tc1" {
+                    mask |= 1;
+                } else if tc.name == "subdir/tc2" {
+                    mask |= 2;
+                } else {
+                    assert!(false);
+                }
+            }
+
+            assert_eq!(3, mask);
+        }
+
+        #[test]
+        fn miss_answer_file() {
+            let tar_gz = make_tar_gz(&[("tc1.in", "1 2")]);
+            assert!(open_tar_gz(tar_gz).is_err());
+        }
+
+        #[test]
+        fn unknown_entry() {
+            let tar_gz = make_tar_gz(&[("tc1.in", "1 2"), ("tc1.ans", "3"), ("README", "")]);
+            assert!(open_tar_gz(tar_gz).is_err());
+        }
+
+        #[test]
+        fn extract() {
+            let tar_gz = make_tar_gz(&[
+                ("tc1.in", "1 2"), ("tc1.ans", "3"),
+                ("subdir/tc2.in", "3 4"), ("subdir/tc2.ans", "7"),
+            ]);
+            let mut archive = open_tar_gz(tar_gz).unwrap();
+
+            let dir = tempfile::tempdir().unwrap();
+            archive.extract_into(dir.path()).unwrap();
+
+            assert_eq!("1 2", std::fs::read_to_string(dir.path().join("tc1.in")).unwrap());
+            assert_eq!("7", std::fs::read_to_string(dir.path().join("subdir/tc2.ans")).unwrap());
+        }
+    }
+
+    mod archive_store_tests {
+        use super::*;
+
+        use openssl::rsa::Rsa;
+
+        fn make_store(dir: &Path) -> ArchiveStore {
+            let url = reqwest::Url::parse("http://localhost").unwrap();
+            let rest = RestfulClient::new(url, Rsa::generate(1024).unwrap());
+            ArchiveStore::new(dir, 0, Some(100), Arc::new(rest), Arc::new(Metrics::new())).unwrap()
+        }
+
+        fn make_archive(store: &ArchiveStore, id: ObjectId, data_len: usize) {
+            let archive_dir = store.get_archive_dir(id);
+            std::fs::create_dir_all(archive_dir.join("subdir")).unwrap();
+            std::fs::write(archive_dir.join("tc1.in"), vec![b'0'; data_len]).unwrap();
+            std::fs::write(archive_dir.join("subdir/tc2.in"), vec![b'0'; data_len]).unwrap();
+        }
+
+        #[test]
+        fn get_cached_without_network() {
+            let dir = tempfile::tempdir().unwrap();
+            let store = make_store(dir.path());
+
+            let id = ObjectId::from_str("000000000000000000000001").unwrap();
+            make_archive(&store, id, 10);
+            std::fs::write(store.get_archive_dir(id).join("subdir/tc2.ans"), "0").unwrap();
+            std::fs::write(store.get_metadata_file_path(&store.get_archive_dir(id)),
+                r#"{"test_cases":[{"name":"subdir/tc2"}]}"#).unwrap();
+
+            // No judge board server is listening on localhost, so any network I/O would fail.
+            let handle = store.get(id, EmptyTestSuitePolicy::Forbid).unwrap();
+            let test_cases: Vec<PathBuf> = handle.test_cases()
+                .map(|tc| tc.answer_file_path())
+                .collect();
+            assert_eq!(vec![store.get_archive_dir(id).join("subdir/tc2.ans")], test_cases);
+        }
+
+        #[test]
+        fn public_test_cases() {
+            let dir = tempfile::tempdir().unwrap();
+            let url = reqwest::Url::parse("http://localhost").unwrap();
+            let rest = RestfulClient::new(url, Rsa::generate(1024).unwrap());
+            let mut store = ArchiveStore::new(
+                dir.path(), 2, None, Arc::new(rest), Arc::new(Metrics::new())).unwrap();
+
+            // Archives cached before the setting changes must follow the current setting.
+            let id = ObjectId::from_str("000000000000000000000001").unwrap();
+            make_archive(&store, id, 10);
+            std::fs::write(store.get_metadata_file_path(&store.get_archive_dir(id)),
+                r#"{"test_cases":[{"name":"tc1","public":true},{"name":"tc2"},{"name":"tc3"}]}"#)
+                .unwrap();
+
+            let public = |store: &ArchiveStore| -> Vec<bool> {
+                let handle = store.get(id, EmptyTestSuitePolicy::Forbid).unwrap();
+                handle.test_cases().map(|tc| tc.is_public()).collect()
+            };
+            assert_eq!(vec![true, true, false], public(&store));
+
+            store.public_test_cases = 0;
+            assert_eq!(vec![false, false, false], public(&store));
+        }
+
+        #[test]
+        fn grader_files() {
+            let dir = tempfile::tempdir().unwrap();
+            let store = make_store(dir.path());
+
+            let id = ObjectId::from_str("000000000000000000000001").unwrap();
+            make_archive(&store, id, 10);
+            std::fs::write(store.get_metadata_file_path(&store.get_archive_dir(id)),
+                r#"{"test_cases":[],"grader_files":["grader/lib/grader.h"]}"#).unwrap();
+
+            let handle = store.get(id, EmptyTestSuitePolicy::Permit).unwrap();
+            assert_eq!(
+                vec![(store.get_archive_dir(id).join("grader/lib/grader.h"),
+                    PathBuf::from("lib/grader.h"))],
+                handle.grader_files());
+        }
+
+        #[test]
+        fn list_cached() {
+            let dir = tempfile::tempdir().unwrap();
+            let store = make_store(dir.path());
+
+            let id1 = ObjectId::from_str("000000000000000000000001").unwrap();
+            let id2 = ObjectId::from_str("000000000000000000000002").unwrap();
+            make_archive(&store, id2, 20);
+            make_archive(&store, id1, 10);
+            std::fs::create_dir(dir.path().join("not-an-archive")).unwrap();
+
+            assert_eq!(vec![(id1, 20), (id2, 40)], store.list_cached().unwrap());
+        }
+
+        #[test]
+        fn evict() {
+            let dir = tempfile::tempdir().unwrap();
+            let store = make_store(dir.path());
+
+            let id1 = ObjectId::from_str("000000000000000000000001").unwrap();
+            let id2 = ObjectId::from_str("000000000000000000000002").unwrap();
+            make_archive(&store, id1, 10);
+            make_archive(&store, id2, 10);
+
+            store.evict(id1).unwrap();
+            assert!(!store.get_archive_dir(id1).exists());
+            assert_eq!(vec![(id2, 20)], store.list_cached().unwrap());
+
+            match store.evict(id1).unwrap_err().kind() {
+                ErrorKind::ArchiveNotCached(id) if *id == id1 => (),
+                kind => panic!("unexpected error kind: {:?}", kind),
+            };
+        }
+
+        #[test]
+        fn reclaim_least_recently_used() {
+            let dir = tempfile::tempdir().unwrap();
+            let store = make_store(dir.path());
+
+            let ids: Vec<ObjectId> = (1..4)
+                .map(|i| ObjectId::from_str(&format!("{:024}", i)).unwrap())
+                .collect();
+            for (i, id) in ids.iter().enumerate() {
+                make_archive(&store, *id, 15);
+                let last_access = UNIX_EPOCH + Duration::from_secs(1000 - i as u64 * 100);
+                set_last_access(&store.get_archive_dir(*id), last_access).unwrap();
+            }
+
+            // Cached archives take 90 bytes; adding a 30-byte archive exceeds the limit of 100 bytes
+            // and evicts the archive accessed least recently.
+            store.reclaim(30).unwrap();
+            assert_eq!(vec![(ids[0], 30), (ids[1], 30)], store.list_cached().unwrap());
+        }
+
+        #[test]
+        fn reclaim_skips_archives_in_use() {
+            let dir = tempfile::tempdir().unwrap();
+            let store = make_store(dir.path());
+
+            let id1 = ObjectId::from_str("000000000000000000000001").unwrap();
+            let id2 = ObjectId::from_str("000000000000000000000002").unwrap();
+            make_archive(&store, id1, 20);
+            make_archive(&store, id2, 20);
+            set_last_access(&store.get_archive_dir(id1), UNIX_EPOCH).unwrap();
+
+            let usage = ArchiveUsageGuard::new(store.usage.clone(), id1);
+            store.reclaim(30).unwrap();
+            assert_eq!(vec![(id1, 40)], store.list_cached().unwrap());
+
+            drop(usage);
+            store.reclaim(80).unwrap();
+            assert!(store.list_cached().unwrap().is_empty());
+        }
     }
 }
@@ -16,6 +16,88 @@ error_chain::error_chain! {
     }
 }
 
+/// Represent an incremental change to the schema of a table in the sqlite database.
+#[derive(Debug, Clone, Copy)]
+pub struct Migration {
+    /// The schema version of the table after applying this migration. The first migration of a
+    /// table has version 1, which applies to the table as originally created.
+    pub version: u32,
+
+    /// The SQL statements applying this migration, e.g. `ALTER TABLE` statements.
+    pub sql: &'static str,
+
+    /// The column added to the table by this migration, if any. If the column already exists, e.g.
+    /// in databases altered before schema versions were recorded, `sql` is not executed and only
+    /// the new schema version is recorded.
+    pub added_column: Option<&'static str>,
+}
+
+/// Create the table recording the schema versions of the other tables, if it does not exist.
+fn create_schema_versions_table(conn: &Connection) -> sqlite::Result<()> {
+    conn.execute(r#"
+        CREATE TABLE IF NOT EXISTS schema_versions(
+            table_name  TEXT PRIMARY KEY,
+            version     INTEGER NOT NULL
+        );
+    "#)
+}
+
+/// Get the schema version of the specified table, or 0 if it is not recorded.
+fn read_schema_version(conn: &Connection, table: &str) -> sqlite::Result<u32> {
+    let mut cursor = conn.prepare("SELECT version FROM schema_versions WHERE table_name = ?")?
+        .cursor();
+    cursor.bind(&[sqlite::Value::String(String::from(table))])?;
+    match cursor.next()? {
+        Some(row) => Ok(row[0].as_integer().unwrap_or(0) as u32),
+        None => Ok(0)
+    }
+}
+
+/// Determine whether the specified table has a column with the given name.
+fn has_column(conn: &Connection, table: &str, column: &str) -> sqlite::Result<bool> {
+    let mut found = false;
+    conn.iterate(format!("PRAGMA table_info({})", table), |pairs| {
+        found = pairs.iter().any(|&(name, value)| name == "name" && value == Some(column));
+        !found
+    })?;
+    Ok(found)
+}
+
+/// Execute the SQL statements of the given migration on the specified table, unless the column
+/// added by the migration already exists.
+fn execute_migration(conn: &Connection, table: &str, migration: &Migration) -> sqlite::Result<()> {
+    if let Some(column) = migration.added_column {
+        if has_column(conn, table, column)? {
+            log::info!("Column `{}` already exists in table `{}`", column, table);
+            return Ok(());
+        }
+    }
+
+    conn.execute(migration.sql)
+}
+
+/// Apply the given migration to the specified table and record the new schema version, in a
+/// single transaction.
+fn apply_migration(conn: &Connection, table: &str, migration: &Migration) -> sqlite::Result<()> {
+    conn.execute("BEGIN")?;
+    let result = execute_migration(conn, table, migration).and_then(|_| {
+        let mut stmt = conn.prepare(
+            "INSERT OR REPLACE INTO schema_versions(table_name, version) VALUES (?, ?)")?;
+        stmt.bind(1, table)?;
+        stmt.bind(2, migration.version as i64)?;
+        stmt.next()?;
+        Ok(())
+    });
+
+    match result {
+        Ok(()) => conn.execute("COMMIT"),
+        Err(e) => {
+            conn.execute("ROLLBACK").ok();
+            Err(e)
+        }
+    }
+}
+
 /// Represent a database connection to the sqlite database.
 pub struct SqliteConnection {
     /// The raw connection protected by a `Mutex`.
@@ -54,4 +136,126 @@ impl SqliteConnection {
             Ok(names)
         })
     }
+
+    /// Get the schema version of the specified table, which is the version of the last migration
+    /// applied to it by `migrate`, or 0 if no migration has been applied.
+    pub fn get_schema_version(&self, table: &str) -> Result<u32> {
+        self.execute(|conn| {
+            create_schema_versions_table(conn)?;
+            Ok(read_schema_version(conn, table)?)
+        })
+    }
+
+    /// Bring the schema of the specified table up to date by applying the given migrations whose
+    /// versions are higher than the current schema version of the table, in the given order. The
+    /// migrations should be sorted by their versions. Each migration is applied in a transaction
+    /// together with the update of the schema version, so a failed migration leaves the table at
+    /// the version before it.
+    pub fn migrate(&self, table: &str, migrations: &[Migration]) -> Result<()> {
+        self.execute(|conn| {
+            create_schema_versions_table(conn)?;
+            let version = read_schema_version(conn, table)?;
+            for migration in migrations.iter().filter(|m| m.version > version) {
+                log::info!("Migrating table `{}` to schema version {}", table, migration.version);
+                apply_migration(conn, table, migration)?;
+            }
+
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    mod migrate {
+        use super::*;
+
+        const MIGRATIONS: &[Migration] = &[
+            Migration {
+                version: 1,
+                sql: "ALTER TABLE items ADD COLUMN color TEXT",
+                added_column: Some("color"),
+            },
+            Migration {
+                version: 2,
+                sql: "ALTER TABLE items ADD COLUMN weight INTEGER",
+                added_column: Some("weight"),
+            },
+        ];
+
+        fn make_db(dir: &tempfile::TempDir) -> SqliteConnection {
+            let db = SqliteConnection::new(&dir.path().join("cache.db")).unwrap();
+            db.execute(|conn| {
+                conn.execute("CREATE TABLE items(id TEXT PRIMARY KEY); \
+                    INSERT INTO items(id) VALUES ('a');")
+            }).unwrap();
+            db
+        }
+
+        fn count_items(db: &SqliteConnection) -> i64 {
+            db.execute(|conn| {
+                let mut cursor = conn.prepare("SELECT COUNT(*) FROM items").unwrap().cursor();
+                cursor.next().unwrap().unwrap()[0].as_integer().unwrap()
+            })
+        }
+
+        #[test]
+        fn pending_migrations_applied() {
+            let dir = tempfile::tempdir().unwrap();
+            let db = make_db(&dir);
+            assert_eq!(0, db.get_schema_version("items").unwrap());
+
+            db.migrate("items", &MIGRATIONS[..1]).unwrap();
+            assert_eq!(1, db.get_schema_version("items").unwrap());
+
+            // Migrations already applied are skipped.
+            db.migrate("items", MIGRATIONS).unwrap();
+            db.migrate("items", MIGRATIONS).unwrap();
+            assert_eq!(2, db.get_schema_version("items").unwrap());
+            assert_eq!(1, count_items(&db));
+            db.execute(|conn| {
+                conn.execute("UPDATE items SET color = 'red', weight = 3 WHERE id = 'a'")
+            }).unwrap();
+        }
+
+        #[test]
+        fn failed_migration_rolled_back() {
+            let dir = tempfile::tempdir().unwrap();
+            let db = make_db(&dir);
+            let migrations = [
+                MIGRATIONS[0],
+                Migration {
+                    version: 2,
+                    sql: "ALTER TABLE items ADD COLUMN weight INTEGER; \
+                        ALTER TABLE nonexistent ADD COLUMN size INTEGER;",
+                    added_column: None,
+                },
+            ];
+
+            assert!(db.migrate("items", &migrations).is_err());
+            assert_eq!(1, db.get_schema_version("items").unwrap());
+
+            // The failed migration does not leave the column behind.
+            db.migrate("items", MIGRATIONS).unwrap();
+            assert_eq!(2, db.get_schema_version("items").unwrap());
+        }
+
+        #[test]
+        fn existing_column_not_added_again() {
+            let dir = tempfile::tempdir().unwrap();
+            let db = make_db(&dir);
+            db.execute(|conn| conn.execute("ALTER TABLE items ADD COLUMN color TEXT")).unwrap();
+            db.execute(|conn| conn.execute("UPDATE items SET color = 'red'")).unwrap();
+
+            db.migrate("items", MIGRATIONS).unwrap();
+            assert_eq!(2, db.get_schema_version("items").unwrap());
+            let color = db.execute(|conn| {
+                let mut cursor = conn.prepare("SELECT color FROM items").unwrap().cursor();
+                cursor.next().unwrap().unwrap()[0].as_string().map(String::from)
+            });
+            assert_eq!(Some(String::from("red")), color);
+        }
+    }
 }
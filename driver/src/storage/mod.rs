@@ -9,6 +9,7 @@ use problems::ProblemStore;
 
 use crate::config::AppConfig;
 use crate::forkserver::ForkServerClient;
+use crate::metrics::Metrics;
 use crate::restful::RestfulClient;
 
 error_chain::error_chain! {
@@ -37,7 +38,8 @@ impl AppStorageFacade {
     pub fn new(
         config: &AppConfig,
         rest: Arc<RestfulClient>,
-        fork_server: Arc<ForkServerClient>) -> Result<Self> {
+        fork_server: Arc<ForkServerClient>,
+        metrics: Arc<Metrics>) -> Result<Self> {
         let db_conn = db::SqliteConnection::new(&config.storage.db_file)?;
 
         let arc_db = Arc::new(db_conn);
@@ -47,9 +49,15 @@ impl AppStorageFacade {
         let problem_rest = rest.clone();
 
         Ok(AppStorageFacade {
-            archives: ArchiveStore::new(&config.storage.archive_dir, archive_rest)?,
+            archives: ArchiveStore::new(
+                &config.storage.archive_dir,
+                config.storage.public_test_cases,
+                config.storage.archive_cache_max.map(|size| size * 1024 * 1024),
+                archive_rest,
+                metrics)?,
             problems: ProblemStore::new(
-                problem_db, problem_rest, fork_server, &config.storage.jury_dir)?,
+                problem_db, problem_rest, fork_server, &config.storage.jury_dir,
+                &config.engine)?,
         })
     }
 }
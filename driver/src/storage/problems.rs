@@ -1,17 +1,24 @@
 //! This module manages problem metadata.
 //!
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use std::string::ToString;
 use std::sync::Arc;
+use std::time::Duration;
 
+use judge::{CompilationResult, ResourceLimits};
+use openssl::sha::Sha256;
+use sandbox::MemorySize;
+
+use crate::config::{JudgeEngineConfig, ProblemLimitBounds, RealTimeMultipliers};
 use crate::forkserver::{ForkServerClient, ForkServerClientExt};
 use crate::restful::RestfulClient;
-use crate::restful::entities::{ObjectId, LanguageTriple, ProblemInfo, JudgeMode};
+use crate::restful::entities::{ObjectId, LanguageTriple, ProblemInfo, ProblemSubtask, JudgeMode};
 use crate::sync::KeyLock;
 
-use super::db::SqliteConnection;
+use super::archives::{ArchiveStore, EmptyTestSuitePolicy};
+use super::db::{Migration, SqliteConnection};
 
 error_chain::error_chain! {
     types {
@@ -19,6 +26,7 @@ error_chain::error_chain! {
     }
 
     links {
+        ArchivesError(super::archives::Error, super::archives::ErrorKind);
         DbError(super::db::Error, super::db::ErrorKind);
         RestfulError(crate::restful::Error, crate::restful::ErrorKind);
         ForkServerError(crate::forkserver::Error, crate::forkserver::ErrorKind);
@@ -28,6 +36,19 @@ error_chain::error_chain! {
         IoError(::std::io::Error);
         SqliteError(::sqlite::Error);
     }
+
+    errors {
+        JuryCompilationFailed(id: ObjectId) {
+            description("failed to compile the jury program")
+            display("failed to compile the jury program of problem \"{}\"", id)
+        }
+
+        JuryLanguageNotEnabled(id: ObjectId, lang: String) {
+            description("the language of the jury program is not enabled")
+            display("the language \"{}\" of the jury program of problem \"{}\" is not enabled",
+                lang, id)
+        }
+    }
 }
 
 /// Provide metadata about a problem.
@@ -56,11 +77,24 @@ pub struct ProblemMetadata {
     /// `JudgeMode::Interactive`.
     pub jury_exec_path: Option<PathBuf>,
 
+    /// Hash of the source code, the language and the judge mode of the jury program, given by
+    /// `hash_jury_src`.
+    /// The jury executable compiled for an earlier version of the problem is reused if the hash
+    /// does not change.
+    pub jury_src_hash: Option<String>,
+
     /// The ID of the test archive.
     pub archive_id: ObjectId,
 
     /// Timestamp of the last update time of this metadata.
     pub timestamp: u64,
+
+    /// Subtasks grouping the test cases of the problem, if the problem is scored by subtasks.
+    pub subtasks: Vec<ProblemSubtask>,
+
+    /// Whether a judgee exiting with a non-zero exit code gets the `RuntimeError` verdict on this
+    /// problem, or `None` to follow the policy given by the application configuration.
+    pub treat_nonzero_exit_as_runtime_error: Option<bool>,
 }
 
 impl ProblemMetadata {
@@ -121,6 +155,20 @@ impl ProblemMetadata {
             None => return None
         };
 
+        let jury_src_hash = row.get(11).and_then(|v| v.as_string()).map(String::from);
+
+        let subtasks = match row.get(12) {
+            Some(sqlite::Value::String(s)) => match serde_json::from_str(s) {
+                Ok(subtasks) => subtasks,
+                Err(..) => return None
+            },
+            _ => Vec::new()
+        };
+
+        let treat_nonzero_exit_as_runtime_error = row.get(13)
+            .and_then(|v| v.as_integer())
+            .map(|v| v != 0);
+
         Some(ProblemMetadata {
             id,
             judge_mode,
@@ -129,8 +177,11 @@ impl ProblemMetadata {
             jury_src,
             jury_lang,
             jury_exec_path,
+            jury_src_hash,
             archive_id,
-            timestamp
+            timestamp,
+            subtasks,
+            treat_nonzero_exit_as_runtime_error,
         })
     }
 
@@ -148,63 +199,96 @@ impl ProblemMetadata {
         self.jury_exec_path.is_some()
     }
 
-    /// Save the metadata into the sqlite database through the given database connection.
+    /// Determine whether a judgee exiting with a non-zero exit code gets the `RuntimeError`
+    /// verdict on this problem. `default` is the policy given by the application configuration,
+    /// which is used if the problem does not set its own policy.
+    pub fn treat_nonzero_exit_as_runtime_error(&self, default: bool) -> bool {
+        self.treat_nonzero_exit_as_runtime_error.unwrap_or(default)
+    }
+
+    /// Convert the time limit and the memory limit of this problem into the resource limits of the
+    /// judgee. The limits are first clamped into the given bounds, with a warning logged if any of
+    /// them is out of the bounds. The real time limit is the time limit scaled by the ratio given
+    /// for the judge mode of this problem.
+    pub fn to_resource_limits(&self,
+        real_time_multipliers: &RealTimeMultipliers,
+        bounds: &ProblemLimitBounds) -> ResourceLimits {
+        let time_limit = bounds.clamp_time_limit(self.time_limit);
+        if time_limit != self.time_limit {
+            log::warn!("time limit of problem \"{}\" clamped from {} ms to {} ms.",
+                self.id, self.time_limit, time_limit);
+        }
+        let memory_limit = bounds.clamp_memory_limit(self.memory_limit);
+        if memory_limit != self.memory_limit {
+            log::warn!("memory limit of problem \"{}\" clamped from {} MB to {} MB.",
+                self.id, self.memory_limit, memory_limit);
+        }
+
+        let time_limit = Duration::from_millis(time_limit);
+        ResourceLimits {
+            cpu_time_limit: time_limit,
+            real_time_limit: scale_time_limit(
+                time_limit, real_time_multipliers.for_judge_mode(self.judge_mode)),
+            memory_limit: MemorySize::MegaBytes(memory_limit as usize),
+        }
+    }
+
+    /// Save the metadata into the sqlite database through the given database connection. The
+    /// values are bound to the statement as parameters rather than formatted into it, since the
+    /// source code of the jury may contain arbitrary characters.
     fn save(&self, conn: &SqliteConnection) -> Result<()> {
-        let id = format!("'{}'", self.id.to_string());
-        let judge_mode = self.judge_mode as i32;
-        let time_limit = self.time_limit;
-        let memory_limit = self.memory_limit;
-        let jury_src = match &self.jury_src {
-            Some(s) => format!("'{}'", s),
-            None => String::from("NULL")
-        };
-        let (jury_lang_id, jury_lang_dialect, jury_lang_version) = match &self.jury_lang {
-            Some(lang) => (
-                format!("'{}'", lang.identifier),
-                format!("'{}'", lang.dialect),
-                format!("'{}'", lang.version)
-            ),
-            None => (String::from("NULL"), String::from("NULL"), String::from("NULL"))
-        };
-        let jury_exec_path = match &self.jury_exec_path {
-            Some(p) => format!("'{}'", p.display()),
-            None => String::from("NULL")
-        };
-        let archive_id = format!("'{}'", self.archive_id.to_string());
-        let timestamp = self.timestamp;
+        fn optional_string<T>(value: Option<T>) -> sqlite::Value
+            where T: ToString {
+            match value {
+                Some(v) => sqlite::Value::String(v.to_string()),
+                None => sqlite::Value::Null
+            }
+        }
 
-        let stmt = format!(r#"
-            INSERT OR REPLACE INTO problems(
-                id,
-                judge_mode,
-                time_limit,
-                memory_limit,
-                jury_src,
-                jury_lang_id,
-                jury_lang_dialect,
-                jury_lang_version,
-                jury_exec_path,
-                archive_id,
-                timestamp
-            ) VALUES (
-                {}, /* id */
-                {}, /* judge_mode */
-                {}, /* time_limit */
-                {}, /* memory_limit */
-                {}, /* jury_src */
-                {}, /* jury_lang_id */
-                {}, /* jury_lang_dialect */
-                {}, /* jury_lang_version */
-                {}, /* jury_exec_path */
-                {}, /* archive_id */
-                {}  /* timestamp */
-            )
-        "#, id, judge_mode, time_limit, memory_limit, jury_src,
-            jury_lang_id, jury_lang_dialect, jury_lang_version, jury_exec_path,
-            archive_id, timestamp);
-
-        conn.execute(|sqlite| {
-            sqlite.execute(stmt)
+        let integer = |v: u64| sqlite::Value::Integer(crate::utils::bitcast::<u64, i64>(v));
+        let values = [
+            sqlite::Value::String(self.id.to_string()),
+            sqlite::Value::Integer(self.judge_mode as i64),
+            integer(self.time_limit),
+            integer(self.memory_limit),
+            optional_string(self.jury_src.as_ref()),
+            optional_string(self.jury_lang.as_ref().map(|lang| &lang.identifier)),
+            optional_string(self.jury_lang.as_ref().map(|lang| &lang.dialect)),
+            optional_string(self.jury_lang.as_ref().map(|lang| &lang.version)),
+            optional_string(self.jury_exec_path.as_ref().map(|p| p.display())),
+            sqlite::Value::String(self.archive_id.to_string()),
+            integer(self.timestamp),
+            optional_string(self.jury_src_hash.as_ref()),
+            sqlite::Value::String(serde_json::to_string(&self.subtasks)
+                .expect("failed to serialize subtasks")),
+            match self.treat_nonzero_exit_as_runtime_error {
+                Some(treat) => sqlite::Value::Integer(treat as i64),
+                None => sqlite::Value::Null
+            },
+        ];
+
+        conn.execute(|sqlite| -> Result<()> {
+            let mut cursor = sqlite.prepare(r#"
+                INSERT OR REPLACE INTO problems(
+                    id,
+                    judge_mode,
+                    time_limit,
+                    memory_limit,
+                    jury_src,
+                    jury_lang_id,
+                    jury_lang_dialect,
+                    jury_lang_version,
+                    jury_exec_path,
+                    archive_id,
+                    timestamp,
+                    jury_src_hash,
+                    subtasks,
+                    treat_nonzero_exit_as_runtime_error
+                ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            "#)?.cursor();
+            cursor.bind(&values)?;
+            cursor.next()?;
+            Ok(())
         })?;
 
         Ok(())
@@ -221,6 +305,10 @@ impl From<ProblemInfo> for ProblemMetadata {
             JudgeMode::Standard => None,
             _ => Some(pi.jury_lang)
         };
+        let jury_src_hash = match (&jury_src, &jury_lang) {
+            (Some(src), Some(lang)) => Some(hash_jury_src(src, lang, pi.judge_mode)),
+            _ => None
+        };
 
         ProblemMetadata {
             id: pi.id,
@@ -230,8 +318,11 @@ impl From<ProblemInfo> for ProblemMetadata {
             jury_src,
             jury_lang,
             jury_exec_path: None,
+            jury_src_hash,
             archive_id: pi.archive_id,
             timestamp: pi.timestamp,
+            subtasks: pi.subtasks,
+            treat_nonzero_exit_as_runtime_error: pi.treat_nonzero_exit_as_runtime_error,
         }
     }
 }
@@ -252,24 +343,31 @@ pub struct ProblemStore {
 
     /// Path to the directory containing compiled jury programs.
     jury_dir: PathBuf,
+
+    /// Configuration of the judge engine, against which the languages of jury programs are
+    /// checked.
+    engine_config: JudgeEngineConfig,
 }
 
 impl ProblemStore {
-    /// Create a new `ProblemStore` instance.
+    /// Create a new `ProblemStore` instance. Jury programs written in languages that are not
+    /// enabled in the given judge engine configuration are rejected.
     pub(super) fn new<P>(
         db: Arc<SqliteConnection>,
         rest: Arc<RestfulClient>,
         fork_server: Arc<ForkServerClient>,
-        jury_dir: P) -> Result<Self>
+        jury_dir: P,
+        engine_config: &JudgeEngineConfig) -> Result<Self>
         where P: Into<PathBuf> {
         let store = ProblemStore {
             lock: KeyLock::new(),
             db,
             rest,
             fork_server,
-            jury_dir: jury_dir.into()
+            jury_dir: jury_dir.into(),
+            engine_config: engine_config.clone(),
         };
-        store.init_db()?;
+        init_db(&store.db)?;
 
         // Create jury_dir if it does not exist.
         std::fs::create_dir_all(&store.jury_dir)?;
@@ -277,35 +375,6 @@ impl ProblemStore {
         Ok(store)
     }
 
-    fn init_db(&self) -> Result<()> {
-        if self.db.get_table_names()?.contains(&String::from("problems")) {
-            log::debug!("Table `problems` already exists in the sqlite database.");
-            return Ok(());
-        }
-
-        log::info!("Creating table `problems` on sqlite database");
-        self.db.execute(|conn| {
-            conn.execute(r#"
-                CREATE TABLE problems(
-                    id                  TEXT PRIMARY KEY,
-                    judge_mode          INTEGER,
-                    time_limit          INTEGER,
-                    memory_limit        INTEGER,
-                    jury_src            TEXT,
-                    jury_lang_id        TEXT,
-                    jury_lang_dialect   TEXT,
-                    jury_lang_version   TEXT,
-                    jury_exec_path      TEXT,
-                    archive_id          TEXT,
-                    timestamp           INTEGER
-                );
-            "#)
-        })?;
-        log::info!("Successfully created table `problems`");
-
-        Ok(())
-    }
-
     /// Get the last update timestamp of the specified problem's metadata.
     fn get_timestamp(&self, id: ObjectId) -> Result<Option<u64>> {
         self.db.execute(move |conn| {
@@ -327,6 +396,18 @@ impl ProblemStore {
         Ok(self.rest.get_problem_timestamp(id)?)
     }
 
+    /// Check that the language of the jury program of the specified problem is enabled on this
+    /// judge node, so that a jury program in an unavailable language is reported clearly instead
+    /// of failing when it is compiled.
+    fn check_jury_lang(&self, id: ObjectId, jury_lang: &LanguageTriple) -> Result<()> {
+        if self.engine_config.is_language_enabled(&jury_lang.to_judge_language()) {
+            return Ok(());
+        }
+
+        let lang = format!("{}:{}:{}", jury_lang.identifier, jury_lang.dialect, jury_lang.version);
+        Err(Error::from(ErrorKind::JuryLanguageNotEnabled(id, lang)))
+    }
+
     /// Compile the jury program. This function returns `Err` to indicate judge errors occured to
     /// compile the jury program, returns `Ok(None)` to indicate the jury program cannot be compiled
     /// due to compilation errors.
@@ -340,24 +421,16 @@ impl ProblemStore {
         let result = self.fork_server.compile_source(
             jury_src,
             jury_lang.to_judge_language(),
-            kind)?;
-
-        if !result.succeeded {
-            log::error!("failed to compile jury: {}", result.compiler_out.unwrap_or_default());
-            return Ok(None);
-        }
+            kind,
+            None)?;
 
-        if result.compiler_out.is_none() {
-            log::error!("failed to compile jury: judge returned ok but no output file.");
-            return Ok(None);
-        }
-
-        Ok(Some(result.output_file.unwrap()))
+        Ok(get_jury_exec_path(result))
     }
 
-    /// Get the cached version of the metadata of the specified problem. The returned metadata
-    /// might be out of date.
-    fn get_cached(&self, id: ObjectId) -> Result<Option<ProblemMetadata>> {
+    /// Get the cached version of the metadata of the specified problem without sending any request
+    /// to the judge board server. The returned metadata might be out of date, so it should only be
+    /// used where stale metadata is acceptable, e.g. when the judge board server is unreachable.
+    pub fn get_cached(&self, id: ObjectId) -> Result<Option<ProblemMetadata>> {
         self.db.execute(|conn| -> Result<Option<ProblemMetadata>> {
             let mut cursor = conn
                 .prepare("SELECT * FROM problems WHERE id = ?")?
@@ -372,14 +445,32 @@ impl ProblemStore {
         })
     }
 
+    /// Get the cached metadata of all problems. The returned metadata might be out of date. Rows
+    /// that cannot be deserialized are skipped.
+    pub fn list_cached(&self) -> Result<Vec<ProblemMetadata>> {
+        self.db.execute(|conn| -> Result<Vec<ProblemMetadata>> {
+            let mut cursor = conn.prepare("SELECT * FROM problems")?.cursor();
+
+            let mut problems = Vec::new();
+            while let Some(row) = cursor.next()? {
+                match ProblemMetadata::from_db_row(row) {
+                    Some(metadata) => problems.push(metadata),
+                    None => log::warn!("Skipping malformed row in table `problems`")
+                };
+            }
+
+            Ok(problems)
+        })
+    }
+
     /// Get the problem metadata of the specified problem. The returned metadata is guaranteed to be
-    /// the latest version. This function will send a request to the judge board server if the
-    /// cached metadata is out of date.
+    /// the latest version. This function will send a request to the judge board server to check
+    /// whether the cached metadata is out of date, and fails if the judge board server cannot be
+    /// reached. Use `get_cached` to get the cached metadata without network I/O.
     pub fn get(&self, id: ObjectId) -> Result<ProblemMetadata> {
         self.lock.lock_and_execute(id, |_| {
             if let Some(timestamp) = self.get_timestamp(id)? {
-                let remote_ts = self.get_remote_timestamp(id)?;
-                if timestamp >= remote_ts {
+                if timestamp >= self.get_remote_timestamp(id)? {
                     if let Some(metadata) = self.get_cached(id)? {
                         return Ok(metadata);
                     }
@@ -388,6 +479,18 @@ impl ProblemStore {
 
             let mut metadata: ProblemMetadata = self.rest.get_problem_info(id)?.into();
             if metadata.has_jury() {
+                self.check_jury_lang(
+                    id, metadata.jury_lang.as_ref().expect("failed to get language of jury"))?;
+            }
+            let reusable_jury_exec = match self.get_cached(id)? {
+                Some(cached) => reusable_jury_exec(&cached, &metadata),
+                None => None
+            };
+            if let Some(jury_exec_path) = reusable_jury_exec {
+                log::info!("Jury program of problem \"{}\" is unchanged, skipping compilation",
+                    metadata.id);
+                metadata.jury_exec_path = Some(jury_exec_path);
+            } else if metadata.has_jury() {
                 // Compile jury program.
                 log::info!("Compiling jury program for problem \"{}\"", metadata.id);
 
@@ -398,23 +501,10 @@ impl ProblemStore {
                     metadata.jury_lang.as_ref().expect("failed to get language of jury"),
                     metadata.judge_mode)?;
 
-                if jury_exec_temp_path.is_some() {
+                if let Some(jury_exec_temp_path) = jury_exec_temp_path {
                     // Copy the jury executable to the jury directory.
-                    let jury_exec_temp_path = jury_exec_temp_path.unwrap();
-                    let jury_exec_ext = jury_exec_temp_path.extension();
-
-                    // The file name of the jury executable should be {problemId}.{extension} under the
-                    // jury executable directory. Build the jury executable's file name now.
-                    let mut jury_exec_path = self.jury_dir.clone();
-                    jury_exec_path.push(id.to_string());
-                    if jury_exec_ext.is_some() {
-                        jury_exec_path.set_extension(jury_exec_ext.unwrap());
-                    }
-
-                    // And do the copy.
-                    std::fs::copy(&jury_exec_temp_path, &jury_exec_path)?;
-
-                    metadata.jury_exec_path = Some(jury_exec_path);
+                    metadata.jury_exec_path = Some(
+                        copy_jury_exec(&jury_exec_temp_path, &self.jury_dir, id)?);
                 }
             }
 
@@ -423,6 +513,204 @@ impl ProblemStore {
             Ok(metadata)
         })
     }
+
+    /// Fetch the metadata, compile the jury program and download the test archive of the specified
+    /// problems ahead of time, so that the first submissions to these problems are not delayed.
+    /// Returns the result of prewarming each problem, in the same order as `ids`. Prewarming a
+    /// problem whose jury program fails to compile results in a `JuryCompilationFailed` error,
+    /// after its test archive is downloaded.
+    pub fn prewarm(&self, ids: &[ObjectId], archives: &ArchiveStore)
+        -> Vec<(ObjectId, Result<()>)> {
+        ids.iter()
+            .map(|id| {
+                log::info!("Prewarming problem \"{}\"", id);
+                let result = self.get(*id).and_then(|metadata| {
+                    let policy = EmptyTestSuitePolicy::for_judge_mode(metadata.judge_mode);
+                    archives.get(metadata.archive_id, policy)?;
+
+                    if metadata.has_jury() && !metadata.jury_compile_succeeded() {
+                        return Err(Error::from(ErrorKind::JuryCompilationFailed(*id)));
+                    }
+                    Ok(())
+                });
+
+                (*id, result)
+            })
+            .collect()
+    }
+}
+
+/// Migrations of the `problems` table, applied on top of the table created by `init_db`. New
+/// columns should be appended to the table through new migrations, since `from_db_row` reads the
+/// columns by their positions.
+const PROBLEMS_MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        sql: "ALTER TABLE problems ADD COLUMN jury_src_hash TEXT",
+        added_column: Some("jury_src_hash"),
+    },
+    // Cached problems are fetched again since their subtasks were not cached.
+    Migration {
+        version: 2,
+        sql: "ALTER TABLE problems ADD COLUMN subtasks TEXT; UPDATE problems SET timestamp = 0;",
+        added_column: Some("subtasks"),
+    },
+    // Cached problems are fetched again since their non-zero exit code policies were not cached.
+    Migration {
+        version: 3,
+        sql: "ALTER TABLE problems ADD COLUMN treat_nonzero_exit_as_runtime_error INTEGER; \
+            UPDATE problems SET timestamp = 0;",
+        added_column: Some("treat_nonzero_exit_as_runtime_error"),
+    },
+];
+
+/// Create the `problems` table in the given database if it does not exist, and bring its schema
+/// up to date.
+fn init_db(db: &SqliteConnection) -> Result<()> {
+    if db.get_table_names()?.contains(&String::from("problems")) {
+        log::debug!("Table `problems` already exists in the sqlite database.");
+    } else {
+        log::info!("Creating table `problems` on sqlite database");
+        db.execute(|conn| {
+            conn.execute(r#"
+                CREATE TABLE problems(
+                    id                  TEXT PRIMARY KEY,
+                    judge_mode          INTEGER,
+                    time_limit          INTEGER,
+                    memory_limit        INTEGER,
+                    jury_src            TEXT,
+                    jury_lang_id        TEXT,
+                    jury_lang_dialect   TEXT,
+                    jury_lang_version   TEXT,
+                    jury_exec_path      TEXT,
+                    archive_id          TEXT,
+                    timestamp           INTEGER
+                );
+            "#)
+        })?;
+        log::info!("Successfully created table `problems`");
+    }
+
+    db.migrate("problems", PROBLEMS_MIGRATIONS)?;
+    log::debug!("Table `problems` is at schema version {}", db.get_schema_version("problems")?);
+
+    Ok(())
+}
+
+/// Compute the hash of the given jury source code, language and judge mode, as a hexadecimal
+/// SHA-256 digest. The judge mode decides the kind of the jury program it is compiled as.
+fn hash_jury_src(jury_src: &str, jury_lang: &LanguageTriple, judge_mode: JudgeMode) -> String {
+    let mut hasher = Sha256::new();
+    let judge_mode = (judge_mode as i64).to_string();
+    // Fields are separated by nul bytes, which never appear in language triples.
+    for field in &[&judge_mode, &jury_lang.identifier, &jury_lang.dialect, &jury_lang.version] {
+        hasher.update(field.as_bytes());
+        hasher.update(b"\0");
+    }
+    hasher.update(jury_src.as_bytes());
+
+    hasher.finish().iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Get the path to the jury executable compiled for the cached metadata of a problem, if the jury
+/// program can be reused by the latest metadata of the problem. This is the case if the jury
+/// program compiled successfully, its source code and language are unchanged, and the executable
+/// still exists.
+fn reusable_jury_exec(cached: &ProblemMetadata, latest: &ProblemMetadata) -> Option<PathBuf> {
+    if !latest.has_jury() || latest.jury_src_hash.is_none() ||
+        cached.jury_src_hash != latest.jury_src_hash {
+        return None;
+    }
+
+    cached.jury_exec_path.clone().filter(|path| path.is_file())
+}
+
+/// Get the path to the jury executable from the result of compiling the jury program. Returns
+/// `None` if the compilation failed or produced no output file.
+fn get_jury_exec_path(result: CompilationResult) -> Option<PathBuf> {
+    if !result.succeeded {
+        log::error!("failed to compile jury: {}", result.compiler_out.unwrap_or_default());
+        if let Some(command) = result.command {
+            log::error!("jury compiler command: {}", command);
+        }
+        return None;
+    }
+
+    if result.output_file.is_none() {
+        log::error!("failed to compile jury: judge returned ok but no output file.");
+        return None;
+    }
+
+    result.output_file
+}
+
+/// Copy the compiled jury executable of the specified problem into the jury directory and returns
+/// the path to the copy.
+///
+/// The copy is placed at `{jury_dir}/{problemId}/{file name}`, which keeps the file name of the
+/// executable as is. Some language providers derive information from the file name of the
+/// executable, e.g. the Java provider runs the main class named after the `.jar` file, so the
+/// executable cannot simply be renamed after the problem.
+fn copy_jury_exec(jury_exec_temp_path: &Path, jury_dir: &Path, id: ObjectId) -> Result<PathBuf> {
+    let file_name = jury_exec_temp_path.file_name()
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidInput,
+            format!("invalid jury executable path: {}", jury_exec_temp_path.display())))?;
+
+    // Remove the executable compiled for an earlier version of the problem, which may have a
+    // different file name. Older versions of the judge node store the executable directly as a
+    // regular file at the path of the directory.
+    let jury_exec_dir = jury_dir.join(id.to_string());
+    match std::fs::symlink_metadata(&jury_exec_dir) {
+        Ok(metadata) if metadata.is_dir() => std::fs::remove_dir_all(&jury_exec_dir)?,
+        Ok(..) => std::fs::remove_file(&jury_exec_dir)?,
+        Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => (),
+        Err(e) => return Err(Error::from(e))
+    };
+    remove_legacy_jury_execs(jury_dir, id)?;
+    std::fs::create_dir_all(&jury_exec_dir)?;
+
+    let jury_exec_path = jury_exec_dir.join(file_name);
+    std::fs::copy(jury_exec_temp_path, &jury_exec_path)?;
+
+    Ok(jury_exec_path)
+}
+
+/// Remove the jury executables of the specified problem stored by older versions of the judge node
+/// directly under the jury directory as `{problemId}.{extension}`, which are superseded by the copy
+/// made by `copy_jury_exec`.
+fn remove_legacy_jury_execs(jury_dir: &Path, id: ObjectId) -> Result<()> {
+    let prefix = format!("{}.", id);
+    for entry in std::fs::read_dir(jury_dir)? {
+        let entry = entry?;
+        let is_legacy = entry.file_name().to_str()
+            .map(|name| name.starts_with(&prefix))
+            .unwrap_or(false);
+        if !is_legacy || entry.file_type()?.is_dir() {
+            continue;
+        }
+
+        log::debug!("Removing legacy jury executable {}", entry.path().display());
+        match std::fs::remove_file(entry.path()) {
+            Ok(..) => (),
+            Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => (),
+            Err(e) => return Err(Error::from(e))
+        };
+    }
+
+    Ok(())
+}
+
+/// Multiply the given time limit by the given real time multiplier. Multipliers that are not finite
+/// and positive, or that overflow the time limit, are ignored with a warning and the time limit is
+/// returned as is.
+fn scale_time_limit(time_limit: Duration, multiplier: f64) -> Duration {
+    let secs = time_limit.as_secs_f64() * multiplier;
+    if multiplier.is_finite() && multiplier > 0.0 && secs < u64::max_value() as f64 {
+        Duration::from_secs_f64(secs)
+    } else {
+        log::warn!("invalid real time multiplier {} ignored.", multiplier);
+        time_limit
+    }
 }
 
 /// Provide extension functions for `JudgeMode`.
@@ -440,3 +728,575 @@ impl JudgeModeExt for JudgeMode {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    mod to_resource_limits {
+        use super::*;
+
+        fn make_metadata(judge_mode: JudgeMode) -> ProblemMetadata {
+            let id = ObjectId::from_str("000000000000000000000001").unwrap();
+            ProblemMetadata {
+                id,
+                judge_mode,
+                time_limit: 1500,
+                memory_limit: 256,
+                jury_src: None,
+                jury_lang: None,
+                jury_exec_path: None,
+                jury_src_hash: None,
+                archive_id: id,
+                timestamp: 0,
+                subtasks: Vec::new(),
+                treat_nonzero_exit_as_runtime_error: None,
+            }
+        }
+
+        fn to_resource_limits(metadata: &ProblemMetadata, multipliers: &RealTimeMultipliers)
+            -> ResourceLimits {
+            metadata.to_resource_limits(multipliers, &ProblemLimitBounds::default())
+        }
+
+        #[test]
+        fn converted() {
+            let multipliers = RealTimeMultipliers::default();
+            let limits = to_resource_limits(&make_metadata(JudgeMode::Standard), &multipliers);
+            assert_eq!(Duration::from_millis(1500), limits.cpu_time_limit);
+            assert_eq!(Duration::from_millis(4500), limits.real_time_limit);
+            assert_eq!(256 * 1024 * 1024, limits.memory_limit.bytes());
+        }
+
+        #[test]
+        fn multiplier_per_judge_mode() {
+            let mut multipliers = RealTimeMultipliers::default();
+            multipliers.interactive = 2.0;
+            let limits = to_resource_limits(&make_metadata(JudgeMode::Interactive), &multipliers);
+            assert_eq!(Duration::from_millis(1500), limits.cpu_time_limit);
+            assert_eq!(Duration::from_millis(3000), limits.real_time_limit);
+        }
+
+        #[test]
+        fn invalid_multipliers_ignored() {
+            for multiplier in &[-1.0, 0.0, std::f64::NAN, std::f64::INFINITY, 1e300] {
+                let mut multipliers = RealTimeMultipliers::default();
+                multipliers.standard = *multiplier;
+                let limits = to_resource_limits(&make_metadata(JudgeMode::Standard), &multipliers);
+                assert_eq!(Duration::from_millis(1500), limits.real_time_limit);
+            }
+        }
+
+        #[test]
+        fn zero_limits_clamped() {
+            let mut metadata = make_metadata(JudgeMode::Standard);
+            metadata.time_limit = 0;
+            metadata.memory_limit = 0;
+
+            let limits = to_resource_limits(&metadata, &RealTimeMultipliers::default());
+            assert_eq!(Duration::from_millis(100), limits.cpu_time_limit);
+            assert_eq!(Duration::from_millis(300), limits.real_time_limit);
+            assert_eq!(16 * 1024 * 1024, limits.memory_limit.bytes());
+        }
+
+        #[test]
+        fn enormous_limits_clamped() {
+            let mut metadata = make_metadata(JudgeMode::Standard);
+            metadata.time_limit = u64::max_value();
+            metadata.memory_limit = 1 << 40;
+
+            let mut bounds = ProblemLimitBounds::default();
+            bounds.max_time_limit = 10000;
+            let limits = metadata.to_resource_limits(&RealTimeMultipliers::default(), &bounds);
+            assert_eq!(Duration::from_millis(10000), limits.cpu_time_limit);
+            assert_eq!(4096 * 1024 * 1024, limits.memory_limit.bytes());
+        }
+    }
+
+    mod copy_jury_exec {
+        use super::*;
+
+        fn check_copy(file_name: &str) {
+            let temp_dir = tempfile::tempdir().unwrap();
+            let jury_dir = tempfile::tempdir().unwrap();
+            let id = ObjectId::from_str("000000000000000000000001").unwrap();
+
+            let temp_path = temp_dir.path().join(file_name);
+            std::fs::write(&temp_path, file_name).unwrap();
+
+            let copied = copy_jury_exec(&temp_path, jury_dir.path(), id).unwrap();
+            assert_eq!(jury_dir.path().join("000000000000000000000001").join(file_name), copied);
+            assert_eq!(file_name, std::fs::read_to_string(&copied).unwrap());
+        }
+
+        #[test]
+        fn extensionless() {
+            check_copy("checker");
+        }
+
+        #[test]
+        fn jar() {
+            check_copy("Checker.jar");
+        }
+
+        #[test]
+        fn replace_earlier_version() {
+            let temp_dir = tempfile::tempdir().unwrap();
+            let jury_dir = tempfile::tempdir().unwrap();
+            let id = ObjectId::from_str("000000000000000000000001").unwrap();
+
+            let old_path = temp_dir.path().join("Old.jar");
+            let new_path = temp_dir.path().join("New.jar");
+            std::fs::write(&old_path, "").unwrap();
+            std::fs::write(&new_path, "").unwrap();
+
+            let old_copy = copy_jury_exec(&old_path, jury_dir.path(), id).unwrap();
+            let new_copy = copy_jury_exec(&new_path, jury_dir.path(), id).unwrap();
+            assert!(!old_copy.exists());
+            assert!(new_copy.exists());
+        }
+
+        #[test]
+        fn replace_legacy_file() {
+            let temp_dir = tempfile::tempdir().unwrap();
+            let jury_dir = tempfile::tempdir().unwrap();
+            let id = ObjectId::from_str("000000000000000000000001").unwrap();
+
+            // Executables were stored as regular files named after the problem ID.
+            std::fs::write(jury_dir.path().join("000000000000000000000001"), "legacy").unwrap();
+
+            let new_path = temp_dir.path().join("checker");
+            std::fs::write(&new_path, "new").unwrap();
+            let new_copy = copy_jury_exec(&new_path, jury_dir.path(), id).unwrap();
+            assert_eq!("new", std::fs::read_to_string(&new_copy).unwrap());
+        }
+
+        #[test]
+        fn remove_legacy_files_with_extension() {
+            let temp_dir = tempfile::tempdir().unwrap();
+            let jury_dir = tempfile::tempdir().unwrap();
+            let id = ObjectId::from_str("000000000000000000000001").unwrap();
+
+            // Executables were stored as regular files named `{problemId}.{extension}`.
+            let legacy = jury_dir.path().join("000000000000000000000001.jar");
+            let other = jury_dir.path().join("000000000000000000000002.jar");
+            std::fs::write(&legacy, "legacy").unwrap();
+            std::fs::write(&other, "other").unwrap();
+
+            let new_path = temp_dir.path().join("Checker.jar");
+            std::fs::write(&new_path, "new").unwrap();
+            let new_copy = copy_jury_exec(&new_path, jury_dir.path(), id).unwrap();
+            assert_eq!("new", std::fs::read_to_string(&new_copy).unwrap());
+            assert!(!legacy.exists());
+            assert!(other.exists());
+        }
+    }
+
+    mod get_jury_exec_path {
+        use super::*;
+
+        #[test]
+        fn succeeded() {
+            let result = CompilationResult::succeed("/path/to/jury");
+            assert_eq!(Some(PathBuf::from("/path/to/jury")), get_jury_exec_path(result));
+        }
+
+        #[test]
+        fn failed() {
+            let result = CompilationResult::fail("error: expected ';'");
+            assert_eq!(None, get_jury_exec_path(result));
+        }
+
+        #[test]
+        fn succeeded_without_output_file() {
+            let mut result = CompilationResult::succeed("/path/to/jury");
+            result.output_file = None;
+            assert_eq!(None, get_jury_exec_path(result));
+        }
+    }
+
+    mod init_db {
+        use super::*;
+
+        #[test]
+        fn old_schema_migrated() {
+            let dir = tempfile::tempdir().unwrap();
+            let db = SqliteConnection::new(&dir.path().join("cache.db")).unwrap();
+            // The `problems` table as created before schema versions were recorded.
+            db.execute(|conn| {
+                conn.execute(r#"
+                    CREATE TABLE problems(
+                        id TEXT PRIMARY KEY, judge_mode INTEGER, time_limit INTEGER,
+                        memory_limit INTEGER, jury_src TEXT, jury_lang_id TEXT,
+                        jury_lang_dialect TEXT, jury_lang_version TEXT, jury_exec_path TEXT,
+                        archive_id TEXT, timestamp INTEGER
+                    );
+                    INSERT INTO problems VALUES (
+                        '000000000000000000000001', 1, 1000, 256, 'int main() {}', 'cpp', 'gnu',
+                        'c++17', '/jury/checker', '000000000000000000000002', 42
+                    );
+                "#)
+            }).unwrap();
+
+            init_db(&db).unwrap();
+            init_db(&db).unwrap();
+            let version = PROBLEMS_MIGRATIONS.last().unwrap().version;
+            assert_eq!(version, db.get_schema_version("problems").unwrap());
+
+            let metadata = db.execute(|conn| {
+                let mut cursor = conn.prepare("SELECT * FROM problems").unwrap().cursor();
+                ProblemMetadata::from_db_row(cursor.next().unwrap().unwrap())
+            }).unwrap();
+            assert_eq!(JudgeMode::SpecialJudge, metadata.judge_mode);
+            assert_eq!(1000, metadata.time_limit);
+            assert_eq!(Some(String::from("int main() {}")), metadata.jury_src);
+            assert_eq!(Some(PathBuf::from("/jury/checker")), metadata.jury_exec_path);
+            assert_eq!(None, metadata.jury_src_hash);
+            // The problem is fetched again to get its subtasks.
+            assert_eq!(0, metadata.timestamp);
+            assert!(metadata.subtasks.is_empty());
+        }
+
+        #[test]
+        fn unversioned_column_kept() {
+            let dir = tempfile::tempdir().unwrap();
+            let db = SqliteConnection::new(&dir.path().join("cache.db")).unwrap();
+            // The `problems` table with the `jury_src_hash` column added before its schema
+            // version was recorded.
+            db.execute(|conn| {
+                conn.execute(r#"
+                    CREATE TABLE problems(
+                        id TEXT PRIMARY KEY, judge_mode INTEGER, time_limit INTEGER,
+                        memory_limit INTEGER, jury_src TEXT, jury_lang_id TEXT,
+                        jury_lang_dialect TEXT, jury_lang_version TEXT, jury_exec_path TEXT,
+                        archive_id TEXT, timestamp INTEGER, jury_src_hash TEXT
+                    );
+                "#)
+            }).unwrap();
+
+            init_db(&db).unwrap();
+            let version = PROBLEMS_MIGRATIONS.last().unwrap().version;
+            assert_eq!(version, db.get_schema_version("problems").unwrap());
+        }
+
+        #[test]
+        fn new_schema_created() {
+            let dir = tempfile::tempdir().unwrap();
+            let db = SqliteConnection::new(&dir.path().join("cache.db")).unwrap();
+            init_db(&db).unwrap();
+
+            let version = PROBLEMS_MIGRATIONS.last().unwrap().version;
+            assert_eq!(version, db.get_schema_version("problems").unwrap());
+        }
+    }
+
+    mod save {
+        use super::*;
+
+        fn load(db: &SqliteConnection) -> Vec<ProblemMetadata> {
+            db.execute(|conn| {
+                let mut cursor = conn.prepare("SELECT * FROM problems").unwrap().cursor();
+                let mut problems = Vec::new();
+                while let Some(row) = cursor.next().unwrap() {
+                    problems.push(ProblemMetadata::from_db_row(row).unwrap());
+                }
+                problems
+            })
+        }
+
+        #[test]
+        fn special_characters_preserved() {
+            let dir = tempfile::tempdir().unwrap();
+            let db = SqliteConnection::new(&dir.path().join("cache.db")).unwrap();
+            init_db(&db).unwrap();
+
+            let id = ObjectId::from_str("000000000000000000000001").unwrap();
+            let jury_src = concat!(
+                "#include <cstdio>\n",
+                "// It's a checker; \"quotes\", backslashes \\ and unicode: 判题\n",
+                "int main() { puts(\"'); DROP TABLE problems; --\"); }\n");
+            let jury_lang = LanguageTriple::new("cpp", "gnu", "c++17");
+            let metadata = ProblemMetadata {
+                id,
+                judge_mode: JudgeMode::SpecialJudge,
+                time_limit: 1000,
+                memory_limit: 256,
+                jury_src: Some(String::from(jury_src)),
+                jury_src_hash: Some(hash_jury_src(jury_src, &jury_lang, JudgeMode::SpecialJudge)),
+                jury_lang: Some(jury_lang),
+                jury_exec_path: Some(PathBuf::from("/jury/o'brien/checker")),
+                archive_id: id,
+                timestamp: u64::max_value(),
+                subtasks: vec![ProblemSubtask {
+                    id: 1,
+                    test_cases: vec![0, 2],
+                    score: 40.0,
+                    dependencies: Vec::new(),
+                }],
+                treat_nonzero_exit_as_runtime_error: Some(false),
+            };
+            metadata.save(&db).unwrap();
+            metadata.save(&db).unwrap();
+
+            let problems = load(&db);
+            assert_eq!(1, problems.len());
+            let loaded = &problems[0];
+            assert_eq!(metadata.jury_src, loaded.jury_src);
+            let jury_lang = loaded.jury_lang.as_ref().unwrap();
+            assert_eq!(("cpp", "gnu", "c++17"),
+                (&*jury_lang.identifier, &*jury_lang.dialect, &*jury_lang.version));
+            assert_eq!(metadata.jury_exec_path, loaded.jury_exec_path);
+            assert_eq!(metadata.jury_src_hash, loaded.jury_src_hash);
+            assert_eq!(metadata.timestamp, loaded.timestamp);
+            assert_eq!(metadata.subtasks, loaded.subtasks);
+            assert_eq!(Some(false), loaded.treat_nonzero_exit_as_runtime_error);
+        }
+
+        #[test]
+        fn standard_problem_saved() {
+            let dir = tempfile::tempdir().unwrap();
+            let db = SqliteConnection::new(&dir.path().join("cache.db")).unwrap();
+            init_db(&db).unwrap();
+
+            let id = ObjectId::from_str("000000000000000000000002").unwrap();
+            let metadata = ProblemMetadata {
+                id,
+                judge_mode: JudgeMode::Standard,
+                time_limit: 2000,
+                memory_limit: 512,
+                jury_src: None,
+                jury_src_hash: None,
+                jury_lang: None,
+                jury_exec_path: None,
+                archive_id: id,
+                timestamp: 7,
+                subtasks: Vec::new(),
+                treat_nonzero_exit_as_runtime_error: None,
+            };
+            metadata.save(&db).unwrap();
+
+            let problems = load(&db);
+            assert_eq!(1, problems.len());
+            assert_eq!(JudgeMode::Standard, problems[0].judge_mode);
+            assert_eq!(2000, problems[0].time_limit);
+            assert_eq!(None, problems[0].jury_src);
+            assert!(problems[0].jury_lang.is_none());
+            assert_eq!(None, problems[0].treat_nonzero_exit_as_runtime_error);
+        }
+    }
+
+    mod reusable_jury_exec {
+        use super::*;
+
+        fn make_metadata(jury_src: &str, jury_exec_path: Option<PathBuf>) -> ProblemMetadata {
+            let id = ObjectId::from_str("000000000000000000000001").unwrap();
+            let jury_lang = LanguageTriple::new("cpp", "gnu", "c++17");
+            ProblemMetadata {
+                id,
+                judge_mode: JudgeMode::SpecialJudge,
+                time_limit: 1000,
+                memory_limit: 256,
+                jury_src: Some(String::from(jury_src)),
+                jury_src_hash: Some(hash_jury_src(jury_src, &jury_lang, JudgeMode::SpecialJudge)),
+                jury_lang: Some(jury_lang),
+                jury_exec_path,
+                archive_id: id,
+                timestamp: 0,
+                subtasks: Vec::new(),
+                treat_nonzero_exit_as_runtime_error: None,
+            }
+        }
+
+        #[test]
+        fn unchanged_source_reused() {
+            let dir = tempfile::tempdir().unwrap();
+            let jury_exec_path = dir.path().join("checker");
+            std::fs::write(&jury_exec_path, b"").unwrap();
+
+            let cached = make_metadata("int main() {}", Some(jury_exec_path.clone()));
+            let mut latest = make_metadata("int main() {}", None);
+            latest.timestamp = 1;
+            latest.time_limit = 2000;
+            assert_eq!(Some(jury_exec_path), reusable_jury_exec(&cached, &latest));
+        }
+
+        #[test]
+        fn changed_source_recompiled() {
+            let dir = tempfile::tempdir().unwrap();
+            let jury_exec_path = dir.path().join("checker");
+            std::fs::write(&jury_exec_path, b"").unwrap();
+
+            let cached = make_metadata("int main() {}", Some(jury_exec_path));
+            let latest = make_metadata("int main() { return 0; }", None);
+            assert_eq!(None, reusable_jury_exec(&cached, &latest));
+        }
+
+        #[test]
+        fn changed_language_recompiled() {
+            let dir = tempfile::tempdir().unwrap();
+            let jury_exec_path = dir.path().join("checker");
+            std::fs::write(&jury_exec_path, b"").unwrap();
+
+            let cached = make_metadata("int main() {}", Some(jury_exec_path));
+            let mut latest = make_metadata("int main() {}", None);
+            let jury_lang = LanguageTriple::new("cpp", "gnu", "c++11");
+            latest.jury_src_hash =
+                Some(hash_jury_src("int main() {}", &jury_lang, JudgeMode::SpecialJudge));
+            assert_eq!(None, reusable_jury_exec(&cached, &latest));
+        }
+
+        #[test]
+        fn changed_judge_mode_recompiled() {
+            let dir = tempfile::tempdir().unwrap();
+            let jury_exec_path = dir.path().join("checker");
+            std::fs::write(&jury_exec_path, b"").unwrap();
+
+            let cached = make_metadata("int main() {}", Some(jury_exec_path));
+            let mut latest = make_metadata("int main() {}", None);
+            let jury_lang = latest.jury_lang.clone().unwrap();
+            latest.judge_mode = JudgeMode::Interactive;
+            latest.jury_src_hash =
+                Some(hash_jury_src("int main() {}", &jury_lang, JudgeMode::Interactive));
+            assert_eq!(None, reusable_jury_exec(&cached, &latest));
+        }
+
+        #[test]
+        fn missing_executable_recompiled() {
+            let dir = tempfile::tempdir().unwrap();
+            let cached = make_metadata("int main() {}", Some(dir.path().join("checker")));
+            let latest = make_metadata("int main() {}", None);
+            assert_eq!(None, reusable_jury_exec(&cached, &latest));
+
+            let cached = make_metadata("int main() {}", None);
+            assert_eq!(None, reusable_jury_exec(&cached, &latest));
+        }
+
+        #[test]
+        fn legacy_row_recompiled() {
+            let dir = tempfile::tempdir().unwrap();
+            let jury_exec_path = dir.path().join("checker");
+            std::fs::write(&jury_exec_path, b"").unwrap();
+
+            let mut cached = make_metadata("int main() {}", Some(jury_exec_path));
+            cached.jury_src_hash = None;
+            let latest = make_metadata("int main() {}", None);
+            assert_eq!(None, reusable_jury_exec(&cached, &latest));
+        }
+    }
+
+    mod prewarm {
+        use super::*;
+
+        use std::sync::atomic::Ordering;
+
+        use openssl::rsa::Rsa;
+        use tempfile::TempDir;
+
+        use crate::forkserver::{Command, CommandResult};
+        use crate::metrics::Metrics;
+        use crate::restful::mock::{start_mock_judge_board, MockServer, PROBLEM_ID};
+
+        /// Create a problem store and an archive store under the given directory connected to a
+        /// mock judge board server serving a problem in the given judge mode. Jury programs are
+        /// compiled by a mock fork server that responds with the given compilation result.
+        fn make_stores(dir: &TempDir, judge_mode: &str, jury_result: CompilationResult)
+            -> (Arc<MockServer>, ProblemStore, ArchiveStore) {
+            make_stores_with(dir, judge_mode, jury_result, None)
+        }
+
+        /// Same as `make_stores`, with the given list of enabled languages.
+        fn make_stores_with(dir: &TempDir, judge_mode: &str, jury_result: CompilationResult,
+            enabled_languages: Option<Vec<String>>)
+            -> (Arc<MockServer>, ProblemStore, ArchiveStore) {
+            let rsa_key = Rsa::generate(1024).unwrap();
+            let (server, url) = start_mock_judge_board(judge_mode, rsa_key.clone());
+            let rest = Arc::new(RestfulClient::new(url, rsa_key));
+
+            let fork_server = crate::forkserver::start_mock_fork_server(move |cmd| match cmd {
+                Command::Compile(..) => CommandResult::Compile(jury_result.clone()),
+                cmd => panic!("unexpected command: {:?}", cmd),
+            });
+            let mut engine_config: JudgeEngineConfig = serde_yaml::from_str(r#"
+                judge_dir: "/judge"
+                language_dylibs: []
+                judge_username: "nobody"
+                jury_cpu_time_limit: 1000
+                jury_real_time_limit: 10000
+                jury_memory_limit: 1024
+            "#).unwrap();
+            engine_config.enabled_languages = enabled_languages;
+            let db = SqliteConnection::new(&dir.path().join("cache.db")).unwrap();
+            let problems = ProblemStore::new(
+                Arc::new(db), rest.clone(), Arc::new(fork_server), dir.path().join("jury"),
+                &engine_config).unwrap();
+            let archives = ArchiveStore::new(
+                dir.path().join("archives"), 0, None, rest, Arc::new(Metrics::new())).unwrap();
+
+            (server, problems, archives)
+        }
+
+        #[test]
+        fn cached_without_network() {
+            let dir = tempfile::tempdir().unwrap();
+            let (server, problems, archives) = make_stores(
+                &dir, "Standard", CompilationResult::fail("unused"));
+
+            let id = ObjectId::from_str(PROBLEM_ID).unwrap();
+            let results = problems.prewarm(&[id], &archives);
+            assert_eq!(1, results.len());
+            results[0].1.as_ref().unwrap();
+
+            server.offline.store(true, Ordering::SeqCst);
+            let metadata = problems.get_cached(id).unwrap().unwrap();
+            assert_eq!(42, metadata.timestamp);
+            let handle = archives.get(metadata.archive_id, EmptyTestSuitePolicy::Forbid).unwrap();
+            assert_eq!(1, handle.test_cases().count());
+        }
+
+        #[test]
+        fn unreachable_server_not_up_to_date() {
+            let dir = tempfile::tempdir().unwrap();
+            let (server, problems, _archives) = make_stores(
+                &dir, "Standard", CompilationResult::fail("unused"));
+
+            let id = ObjectId::from_str(PROBLEM_ID).unwrap();
+            problems.get(id).unwrap();
+
+            // Cached metadata is not taken as up to date when its timestamp cannot be checked.
+            server.offline.store(true, Ordering::SeqCst);
+            assert!(problems.get(id).is_err());
+        }
+
+        #[test]
+        fn jury_compilation_failed() {
+            let dir = tempfile::tempdir().unwrap();
+            let (_server, problems, archives) = make_stores(
+                &dir, "SpecialJudge", CompilationResult::fail("error: expected `;`"));
+
+            let id = ObjectId::from_str(PROBLEM_ID).unwrap();
+            match problems.prewarm(&[id], &archives).pop().unwrap().1 {
+                Err(Error(ErrorKind::JuryCompilationFailed(failed_id), _)) =>
+                    assert_eq!(id, failed_id),
+                r => panic!("unexpected result: {:?}", r),
+            };
+        }
+
+        #[test]
+        fn jury_language_not_enabled() {
+            let dir = tempfile::tempdir().unwrap();
+            let (_server, problems, _archives) = make_stores_with(
+                &dir, "SpecialJudge", CompilationResult::succeed("/bin/true"),
+                Some(vec![String::from("java")]));
+
+            let id = ObjectId::from_str(PROBLEM_ID).unwrap();
+            match problems.get(id) {
+                Err(Error(ErrorKind::JuryLanguageNotEnabled(failed_id, lang), _)) => {
+                    assert_eq!(id, failed_id);
+                    assert_eq!("cpp:gnu:c++17", lang);
+                },
+                r => panic!("unexpected result: {:?}", r),
+            };
+            assert!(problems.get_cached(id).unwrap().is_none());
+        }
+    }
+}
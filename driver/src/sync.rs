@@ -3,7 +3,7 @@
 
 use std::collections::HashMap;
 use std::hash::Hash;
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Condvar, Mutex};
 
 /// Provide a lock associated with unique keys.
 pub struct KeyLock<K>
@@ -78,3 +78,110 @@ impl KeyLockEntry {
         }
     }
 }
+
+/// Provide a counting semaphore that limits the number of threads executing some action at the same
+/// time.
+pub struct Semaphore {
+    /// Number of available permits.
+    permits: Mutex<usize>,
+
+    /// Condition variable notified when a permit is released.
+    released: Condvar,
+}
+
+impl Semaphore {
+    /// Create a new `Semaphore` object with the given number of permits. The number of permits is
+    /// at least 1.
+    pub fn new(permits: usize) -> Self {
+        Semaphore {
+            permits: Mutex::new(std::cmp::max(permits, 1)),
+            released: Condvar::new(),
+        }
+    }
+
+    /// Acquire a permit, blocking until one is available. The permit is released when the returned
+    /// guard is dropped.
+    pub fn acquire(&self) -> SemaphoreGuard<'_> {
+        let mut permits = self.permits.lock().expect("failed to lock mutex");
+        while *permits == 0 {
+            permits = self.released.wait(permits).expect("failed to lock mutex");
+        }
+        *permits -= 1;
+
+        SemaphoreGuard { semaphore: self }
+    }
+}
+
+/// A permit acquired from a `Semaphore`, which is released when dropped.
+pub struct SemaphoreGuard<'a> {
+    /// The semaphore the permit is acquired from.
+    semaphore: &'a Semaphore,
+}
+
+impl<'a> Drop for SemaphoreGuard<'a> {
+    fn drop(&mut self) {
+        let mut permits = self.semaphore.permits.lock().expect("failed to lock mutex");
+        *permits += 1;
+        self.semaphore.released.notify_one();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    mod semaphore {
+        use super::*;
+
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::time::Duration;
+
+        /// Run 8 threads fetching concurrently through a semaphore with the given number of permits
+        /// and returns the maximal number of concurrent fetches observed.
+        fn max_concurrent_fetches(permits: usize) -> usize {
+            let semaphore = Arc::new(Semaphore::new(permits));
+            let active = Arc::new(AtomicUsize::new(0));
+            let max_active = Arc::new(Mutex::new(0));
+
+            let threads: Vec<_> = (0..8).map(|_| {
+                let semaphore = semaphore.clone();
+                let active = active.clone();
+                let max_active = max_active.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..5 {
+                        let _permit = semaphore.acquire();
+                        let current = active.fetch_add(1, Ordering::SeqCst) + 1;
+                        {
+                            let mut max_active = max_active.lock().unwrap();
+                            *max_active = std::cmp::max(*max_active, current);
+                        }
+                        std::thread::sleep(Duration::from_millis(2));
+                        active.fetch_sub(1, Ordering::SeqCst);
+                    }
+                })
+            }).collect();
+            for t in threads {
+                t.join().unwrap();
+            }
+
+            let max_active = *max_active.lock().unwrap();
+            max_active
+        }
+
+        #[test]
+        fn serialized_fetches() {
+            assert_eq!(1, max_concurrent_fetches(1));
+        }
+
+        #[test]
+        fn limited_fetches() {
+            let max = max_concurrent_fetches(3);
+            assert!(max >= 1 && max <= 3);
+        }
+
+        #[test]
+        fn zero_permits_fallback_to_one() {
+            assert_eq!(1, max_concurrent_fetches(0));
+        }
+    }
+}
@@ -2,16 +2,29 @@
 //!
 
 use std::any::Any;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::sync::mpsc::{Receiver, SyncSender, TrySendError};
 use std::thread::JoinHandle;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use rand::Rng;
 
 use crate::AppContext;
+use crate::config::{JudgeEngineConfig, MAX_WORKERS};
+use crate::sync::Semaphore;
 
 use crate::forkserver::{ForkServerClientExt, Command as ForkServerCommand};
-use crate::restful::entities::{SubmissionInfo, JudgeMode, SubmissionJudgeResult, Verdict};
+use crate::restful::entities::{
+    ObjectId,
+    SubmissionInfo,
+    JudgeMode,
+    SubmissionJudgeProgress,
+    SubmissionJudgeResult,
+    Verdict,
+};
+use crate::storage::archives::EmptyTestSuitePolicy;
+use crate::storage::problems::ErrorKind as ProblemsErrorKind;
 
 error_chain::error_chain! {
     types {
@@ -33,6 +46,12 @@ error_chain::error_chain! {
             description("Worker thread failed.")
             display("Worker thread #{} failed.", worker_id)
         }
+
+        DeadlineExceeded { phase: &'static str, elapsed: Duration } {
+            description("submission exceeded processing deadline.")
+            display("submission exceeded processing deadline after {} ({} ms elapsed).",
+                phase, elapsed.as_millis())
+        }
     }
 }
 
@@ -64,6 +83,8 @@ impl SubmissionJudgeResultExt for SubmissionJudgeResult {
             time: 0,
             memory: 0,
             test_cases: Vec::new(),
+            subtasks: Vec::new(),
+            subtask_score: None,
         }
     }
 
@@ -90,11 +111,232 @@ impl SubmissionJudgeResultExt for SubmissionJudgeResult {
     }
 }
 
-/// Execute judge task on the given submission and returns the judge result.
-fn handle_submission(submission: &SubmissionInfo, context: &AppContext)
-    -> Result<SubmissionJudgeResult> {
+/// The wall-clock deadline of processing a submission. The deadline is checked before each phase of
+/// processing the submission starts, and the time remaining is passed to the phases carried out by
+/// the fork server as their time budgets, so that the results of finished phases are never
+/// discarded.
+struct SubmissionDeadline {
+    /// The time at which processing the submission started.
+    start: Instant,
+
+    /// The time allowed for processing the submission, or `None` if there is no deadline.
+    limit: Option<Duration>,
+}
+
+impl SubmissionDeadline {
+    /// Create a new `SubmissionDeadline` value starting now, with the given limit.
+    fn new(limit: Option<Duration>) -> Self {
+        SubmissionDeadline {
+            start: Instant::now(),
+            limit,
+        }
+    }
+
+    /// Check whether the deadline has passed during the given phase. Returns
+    /// `ErrorKind::DeadlineExceeded` if it has.
+    fn check(&self, phase: &'static str) -> Result<()> {
+        let elapsed = self.start.elapsed();
+        match self.limit {
+            Some(limit) if elapsed > limit =>
+                Err(Error::from(ErrorKind::DeadlineExceeded { phase, elapsed })),
+            _ => Ok(())
+        }
+    }
+
+    /// Check whether the deadline interrupted the judge task that gave the given result, i.e. the
+    /// deadline has passed and the result covers fewer than `test_cases_total` test cases. Returns
+    /// `ErrorKind::DeadlineExceeded` if it has; complete judge results are always kept.
+    fn check_judged(&self, result: &judge::JudgeResult, test_cases_total: usize) -> Result<()> {
+        if result.test_suite.len() < test_cases_total {
+            self.check("judging")?;
+        }
+        Ok(())
+    }
+
+    /// Get the time remaining before the deadline, or `None` if there is no deadline.
+    fn remaining(&self) -> Option<Duration> {
+        self.limit.map(|limit| limit.checked_sub(self.start.elapsed()).unwrap_or_default())
+    }
+}
+
+/// Throttle the progress updates of a submission reported to the judge board, so that the judge
+/// board is not flooded by progress updates of submissions with many fast test cases.
+pub(crate) struct ProgressThrottle {
+    /// Minimal time interval between two adjacent progress updates.
+    interval: Duration,
+
+    /// The time at which the last progress update was reported, if any.
+    last_report: Option<Instant>,
+}
+
+impl ProgressThrottle {
+    /// Create a new `ProgressThrottle` value with the given minimal interval between progress
+    /// updates.
+    pub(crate) fn new(interval: Duration) -> Self {
+        ProgressThrottle {
+            interval,
+            last_report: None,
+        }
+    }
+
+    /// Determine whether a progress update should be reported at the given time. If so, the time
+    /// is recorded as the time of the last report.
+    fn should_report(&mut self, now: Instant) -> bool {
+        match self.last_report {
+            Some(last_report) if now.duration_since(last_report) < self.interval => false,
+            _ => {
+                self.last_report = Some(now);
+                true
+            }
+        }
+    }
+}
+
+/// Report the given judge progress of the given submission to the judge board. Failures are logged
+/// and otherwise ignored, since the judge result is reported after the judge finishes anyway.
+fn report_progress(context: &AppContext, submission_id: ObjectId, progress: &judge::JudgeProgress) {
+    let progress = SubmissionJudgeProgress::from(*progress);
+    if let Err(e) = context.rest.patch_judge_progress(submission_id, &progress) {
+        log::warn!("failed to patch judge progress of submission \"{}\": {}", submission_id, e);
+    }
+}
+
+/// Maximal number of progress updates waiting to be reported by a `ProgressReporter`.
+const PROGRESS_QUEUE_CAPACITY: usize = 1;
+
+/// Report progress updates of submissions to the judge board on a dedicated thread, so that a slow
+/// or unreachable judge board never stalls judging. Progress updates are throttled by a
+/// `ProgressThrottle`, and dropped if the reporting thread falls behind.
+pub(crate) struct ProgressReporter {
+    /// Minimal time interval between two adjacent progress updates of a submission.
+    interval: Duration,
+
+    /// Throttle of the progress updates of the current submission.
+    throttle: ProgressThrottle,
+
+    /// Sender of the queue drained by the reporting thread.
+    sender: SyncSender<(ObjectId, judge::JudgeProgress)>,
+}
+
+impl ProgressReporter {
+    /// Create a new `ProgressReporter` value with the given minimal interval between progress
+    /// updates, and get the receiver of the queued progress updates.
+    fn new(interval: Duration) -> (Self, Receiver<(ObjectId, judge::JudgeProgress)>) {
+        let (sender, receiver) = std::sync::mpsc::sync_channel(PROGRESS_QUEUE_CAPACITY);
+        let reporter = ProgressReporter {
+            interval,
+            throttle: ProgressThrottle::new(interval),
+            sender,
+        };
+        (reporter, receiver)
+    }
+
+    /// Create a new `ProgressReporter` value and start its reporting thread, which exits once the
+    /// reporter is dropped.
+    pub(crate) fn start(context: Arc<AppContext>, interval: Duration) -> Self {
+        let (reporter, receiver) = ProgressReporter::new(interval);
+        std::thread::spawn(move || {
+            for (submission_id, progress) in receiver {
+                report_progress(&context, submission_id, &progress);
+            }
+        });
+        reporter
+    }
+
+    /// Reset the throttle for a new submission.
+    fn reset(&mut self) {
+        self.throttle = ProgressThrottle::new(self.interval);
+    }
+
+    /// Queue the given progress of the given submission to be reported, unless it is throttled or
+    /// the queue is full. This function never blocks.
+    fn report(&mut self, submission_id: ObjectId, progress: &judge::JudgeProgress) {
+        if !self.throttle.should_report(Instant::now()) {
+            return;
+        }
+
+        if let Err(TrySendError::Full(..)) = self.sender.try_send((submission_id, *progress)) {
+            log::debug!("Progress update of submission \"{}\" dropped", submission_id);
+        }
+    }
+}
+
+/// Convert the outcome of `handle_submission` into the judge result to be reported to the judge
+/// board.
+fn submission_result(submission: &SubmissionInfo, outcome: Result<SubmissionJudgeResult>)
+    -> SubmissionJudgeResult {
+    match outcome {
+        Ok(r) => {
+            log::info!("Judge of submission \"{}\" finished. Verdict: {}",
+                submission.id, r.verdict);
+            log::debug!("Judge result detail: {:?}", r);
+            r
+        },
+        Err(e @ Error(ErrorKind::DeadlineExceeded { .. }, _)) => {
+            log::warn!("failed to handle submission \"{}\": {}", submission.id, e);
+            SubmissionJudgeResult::failure("submission exceeded processing deadline")
+        },
+        Err(Error(ErrorKind::ProblemsError(
+            ProblemsErrorKind::JuryLanguageNotEnabled(_, lang)), _)) => {
+            log::error!("the jury language \"{}\" of submission \"{}\" is not enabled.",
+                lang, submission.id);
+            SubmissionJudgeResult::failure(format!(
+                "Jury language \"{}\" is not enabled on this judge node.", lang))
+        },
+        Err(e) => {
+            log::error!("failed to handle submission \"{}\": {}", submission.id, e);
+            SubmissionJudgeResult::failure("")
+        }
+    }
+}
+
+/// Check whether the language of the given submission is accepted by this judge node. Returns the
+/// judge result to be reported if not, so that submissions in languages whose toolchains are not
+/// installed are rejected before their compilation is attempted.
+fn check_submission_language(submission: &SubmissionInfo, config: &JudgeEngineConfig)
+    -> Option<SubmissionJudgeResult> {
+    if config.is_language_enabled(&submission.language.to_judge_language()) {
+        return None;
+    }
+
+    let lang = &submission.language;
+    log::warn!("submission {} is written in the disabled language \"{}:{}:{}\".",
+        submission.id, lang.identifier, lang.dialect, lang.version);
+    Some(SubmissionJudgeResult::failure(format!(
+        "Language \"{}:{}:{}\" is not enabled on this judge node.",
+        lang.identifier, lang.dialect, lang.version)))
+}
+
+/// Execute judge task on the given submission and returns the judge result. The judge result is not
+/// reported to the judge board. `fetched` is the time when the submission was fetched from the
+/// judge board, from which the queue latency of the submission is measured. `judge_subdir` is the
+/// name of the subdirectory of the judge directory under which the judge task is performed, see
+/// `worker_judge_subdir`.
+///
+/// If `progress_reporter` is not `None`, the progress of the judge task is reported to the judge
+/// board through it after each test case.
+///
+/// `ErrorKind::DeadlineExceeded` is returned if the deadline set by
+/// `JudgeEngineConfig::submission_deadline` passes before the submission is judged. The remaining
+/// time is passed to the compiler and the judge task as their time budgets, so an overrun
+/// interrupts them.
+pub(crate) fn handle_submission(
+    submission: &SubmissionInfo,
+    fetched: Instant,
+    judge_subdir: Option<&str>,
+    mut progress_reporter: Option<&mut ProgressReporter>,
+    context: &AppContext) -> Result<SubmissionJudgeResult> {
+    let deadline = SubmissionDeadline::new(
+        context.config.engine.submission_deadline.map(Duration::from_millis));
+
+    if let Some(result) = check_submission_language(submission, &context.config.engine) {
+        return Ok(result);
+    }
+
     let problem = context.storage.problems.get(submission.problem_id)?;
-    let archive = context.storage.archives.get(problem.archive_id)?;
+    let archive = context.storage.archives.get(
+        problem.archive_id, EmptyTestSuitePolicy::for_judge_mode(problem.judge_mode))?;
+    deadline.check("downloading the test archive")?;
 
     if problem.has_jury() && !problem.jury_compile_succeeded() {
         log::error!("the checker of the problem \"{}\" did not compiled successfully.",
@@ -102,56 +344,144 @@ fn handle_submission(submission: &SubmissionInfo, context: &AppContext)
         return Ok(SubmissionJudgeResult::failure("Answer checker did not compiled successfully."));
     }
 
+    // Make sure the fork server is alive before dispatching work to it.
+    context.fork_server.ensure_alive()?;
+
     // Compile the submission program.
+    let compile_start = Instant::now();
+    context.metrics.observe_queue(compile_start - fetched);
     let compile_result = context.fork_server.compile_source(
         &submission.source,
         submission.language.to_judge_language(),
-        judge::ProgramKind::Judgee)?;
+        judge::ProgramKind::Judgee,
+        deadline.remaining())?;
+    context.metrics.observe_compile(compile_start.elapsed());
     if !compile_result.succeeded {
+        // The compiler is killed once the deadline passes, which fails the compilation.
+        deadline.check("compiling")?;
         return Ok(SubmissionJudgeResult::compilation_failed(
             compile_result.compiler_out.unwrap_or_default()));
     }
 
     // Prepare a `JudgeTaskDescriptor`.
-    let exec_path = compile_result.compiler_out
+    let exec_path = compile_result.output_file
         .expect("failed to get the path to the executable file of submission");
 
     let program = judge::Program::new(exec_path, submission.language.to_judge_language());
     let mut task = judge::JudgeTaskDescriptor::new(program);
-    task.limits.cpu_time_limit = Duration::from_millis(problem.time_limit);
-    task.limits.real_time_limit = Duration::from_millis(problem.time_limit * 3);
-    task.limits.memory_limit = sandbox::MemorySize::MegaBytes(problem.memory_limit as usize);
+    task.limits = problem.to_resource_limits(
+        &context.config.engine.real_time_multipliers, &context.config.engine.problem_limit_bounds);
+    task.judge_subdir = judge_subdir.map(String::from);
+    task.submission_id = Some(submission.id.to_string());
+    task.problem_id = Some(submission.problem_id.to_string());
+    task.time_limit = deadline.remaining();
+    task.treat_nonzero_exit_as_runtime_error = problem.treat_nonzero_exit_as_runtime_error(
+        context.config.engine.treat_nonzero_exit_as_runtime_error);
 
+    let jury_program = || {
+        let jury_lang = problem.jury_lang.as_ref().unwrap().to_judge_language();
+        let jury_exec = problem.jury_exec_path.as_ref().unwrap();
+        judge::Program::new(jury_exec, jury_lang)
+    };
     task.mode = match problem.judge_mode {
         JudgeMode::Standard => judge::JudgeMode::Standard(judge::BuiltinCheckers::Default),
-        JudgeMode::SpecialJudge | JudgeMode::Interactive => {
-            let jury_lang = problem.jury_lang.as_ref().unwrap().to_judge_language();
-            let jury_exec = problem.jury_exec_path.as_ref().unwrap();
-            let jury_program = judge::Program::new(jury_exec, jury_lang);
-
-            if problem.judge_mode == JudgeMode::SpecialJudge {
-                judge::JudgeMode::SpecialJudge(jury_program)
-            } else { // problem.judge_mode == JudgeMode::SpecialJudge
-                judge::JudgeMode::Interactive(jury_program)
-            }
-        }
+        JudgeMode::SpecialJudge => judge::JudgeMode::SpecialJudge(jury_program()),
+        JudgeMode::Interactive => judge::JudgeMode::Interactive(jury_program()),
     };
 
     for test_case in archive.test_cases() {
-        let test_case_desc = judge::TestCaseDescriptor::new(
+        let mut test_case_desc = judge::TestCaseDescriptor::new(
             test_case.input_file_path(), test_case.answer_file_path());
+        test_case_desc.public = test_case.is_public();
         task.test_suite.push(test_case_desc);
     }
+    task.grader_files = archive.grader_files();
+    task.subtasks = problem.subtasks.iter().map(|subtask| subtask.to_judge_subtask()).collect();
+    if task.test_suite.is_empty() {
+        // The interactor generates test cases on the fly, so run it once without test data.
+        task.test_suite.push(judge::TestCaseDescriptor::new("/dev/null", "/dev/null"));
+    }
 
     // Execute the judge task.
+    let test_cases_total = task.test_suite.len();
     let cmd = ForkServerCommand::Judge(task);
-    let judge_result = context.fork_server.execute_cmd(&cmd)?.unwrap_as_judge_result();
+    let judge_start = Instant::now();
+    // The callback runs while the connection to the fork server is locked, so it only queues the
+    // progress updates.
+    if let Some(ref mut reporter) = progress_reporter {
+        reporter.reset();
+    }
+    let judge_result = context.fork_server.execute_cmd_streaming(&cmd, |progress| {
+        if let Some(ref mut reporter) = progress_reporter {
+            reporter.report(submission.id, progress);
+        }
+    })?.unwrap_as_judge_result();
+    context.metrics.observe_judge(judge_start.elapsed());
+    deadline.check_judged(&judge_result, test_cases_total)?;
 
     Ok(SubmissionJudgeResult::from(judge_result))
 }
 
-/// The entry point of a worker thread.
-fn worker_entry(worker_id: u32, context: Arc<AppContext>) {
+/// Get the name of the subdirectory of the judge directory dedicated to the given worker. Judge
+/// tasks of different workers are performed under different subdirectories so that the files
+/// created by their judgees never collide.
+fn worker_judge_subdir(worker_id: u32) -> String {
+    format!("worker-{}", worker_id)
+}
+
+/// The judge subdirectory of a worker. The subdirectory is removed when this value is dropped,
+/// which happens when the worker thread exits, either normally or by panicking.
+struct WorkerJudgeDir {
+    /// Path to the subdirectory.
+    path: PathBuf,
+}
+
+impl WorkerJudgeDir {
+    /// Create a new `WorkerJudgeDir` value for the given worker. Files left in the subdirectory by
+    /// a previous run of the judge (e.g. after a crash) are removed.
+    fn new(judge_dir: &Path, worker_id: u32) -> Self {
+        let dir = WorkerJudgeDir {
+            path: judge_dir.join(worker_judge_subdir(worker_id)),
+        };
+        dir.clean();
+        dir
+    }
+
+    /// Remove the subdirectory and everything in it.
+    fn clean(&self) {
+        match std::fs::remove_dir_all(&self.path) {
+            Ok(..) => (),
+            Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => (),
+            Err(e) => log::warn!("failed to remove judge directory \"{}\": {}",
+                self.path.display(), e)
+        };
+    }
+}
+
+impl Drop for WorkerJudgeDir {
+    fn drop(&mut self) {
+        self.clean();
+    }
+}
+
+/// Determine whether a worker should sleep before requesting for submissions again, after a request
+/// that took the given duration returned no submission. `long_poll_wait` is the long polling wait
+/// duration, or `None` if long polling is disabled.
+///
+/// A judge board supporting long polling holds the request until the wait duration elapses, in
+/// which case the next request can be sent immediately. A request returning much earlier indicates
+/// that the judge board does not support long polling, and the worker falls back to polling at
+/// intervals.
+fn should_sleep_after_empty_poll(long_poll_wait: Option<Duration>, elapsed: Duration) -> bool {
+    match long_poll_wait {
+        Some(wait) => elapsed < wait / 2,
+        None => true
+    }
+}
+
+/// The entry point of a worker thread. `fetch_limiter` limits the number of workers requesting for
+/// submissions at the same time, which prevents the workers from overwhelming the judge board.
+fn worker_entry(worker_id: u32, context: Arc<AppContext>, fetch_limiter: Arc<Semaphore>) {
     log::info!("Worker thread #{} has started", worker_id);
 
     fn sleep_interval() {
@@ -163,32 +493,44 @@ fn worker_entry(worker_id: u32, context: Arc<AppContext>) {
         std::thread::sleep(Duration::from_secs_f64(interval));
     }
 
+    let judge_subdir = worker_judge_subdir(worker_id);
+    let _judge_dir = WorkerJudgeDir::new(&context.config.engine.judge_dir, worker_id);
+
+    let long_poll_wait = context.rest.long_poll_wait();
+    let mut progress_reporter = ProgressReporter::start(context.clone(),
+        Duration::from_millis(context.config.cluster.progress_report_interval));
     loop {
-        let submission = match context.rest.get_submission() {
-            Ok(Some(sub)) => sub,
-            Ok(None) => {
-                sleep_interval();
+        let fetch_result = {
+            let _permit = fetch_limiter.acquire();
+            let poll_start = Instant::now();
+            (context.rest.get_submission(), poll_start.elapsed())
+        };
+        let submission = match fetch_result {
+            (Ok(Some(sub)), _) => sub,
+            (Ok(None), elapsed) => {
+                if should_sleep_after_empty_poll(long_poll_wait, elapsed) {
+                    sleep_interval();
+                }
                 continue;
             },
-            Err(e) => {
+            (Err(e), _) => {
                 log::error!("failed to get submission: {}", e);
                 sleep_interval();
                 continue;
             }
         };
 
-        let result = match handle_submission(&submission, &*context) {
-            Ok(r) => {
-                log::info!("Judge of submission \"{}\" finished. Verdict: {}",
-                    submission.id, r.verdict);
-                log::debug!("Judge result detail: {:?}", r);
-                r
-            },
-            Err(e) => {
-                log::error!("failed to handle submission \"{}\": {}", submission.id, e);
-                SubmissionJudgeResult::failure("")
+        let fetched = Instant::now();
+
+        let result = submission_result(&submission, handle_submission(&submission, fetched,
+            Some(&judge_subdir), Some(&mut progress_reporter), &*context));
+
+        context.metrics.record_submission(result.verdict);
+        if let Some(ref metrics_file) = context.config.metrics_file {
+            if let Err(e) = context.metrics.write_to_file(metrics_file) {
+                log::error!("failed to write metrics into \"{}\": {}", metrics_file.display(), e);
             }
-        };
+        }
 
         let mut retry_count = 3;
         while let Err(e) = context.rest.patch_judge_result(submission.id, &result) {
@@ -205,15 +547,15 @@ fn worker_entry(worker_id: u32, context: Arc<AppContext>) {
                 "after 3 retries. The judge result will be discarded."), submission.id);
         }
 
-        sleep_interval();
+        if long_poll_wait.is_none() {
+            sleep_interval();
+        }
     }
 }
 
 /// Spawn and execute worker threads. This function will block until any of the worker threads
 /// exits.
 pub(crate) fn run(context: Arc<AppContext>) -> Result<()> {
-    const MAX_WORKERS: u32 = 10;
-
     if context.config.workers == 0 {
         log::error!("Number of workers cannot be 0.");
         return Err(Error::from(ErrorKind::InvalidNumberOfWorkers));
@@ -226,11 +568,16 @@ pub(crate) fn run(context: Arc<AppContext>) -> Result<()> {
         context.config.workers
     };
 
+    let max_concurrent_fetches = context.config.cluster.max_concurrent_fetches.unwrap_or(1);
+    let fetch_limiter = Arc::new(Semaphore::new(max_concurrent_fetches as usize));
+
     log::info!("Spawning {} worker threads", num_workers);
     let mut worker_threads: Vec<JoinHandle<()>> = Vec::with_capacity(num_workers as usize);
     for worker_id in 1..=num_workers {
         let context_clone = context.clone();
-        let handle = std::thread::spawn(move || worker_entry(worker_id, context_clone));
+        let fetch_limiter_clone = fetch_limiter.clone();
+        let handle = std::thread::spawn(
+            move || worker_entry(worker_id, context_clone, fetch_limiter_clone));
         worker_threads.push(handle);
     }
     drop(context);
@@ -248,3 +595,461 @@ pub(crate) fn run(context: Arc<AppContext>) -> Result<()> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    mod check_submission_language {
+        use super::*;
+
+        use crate::restful::entities::{LanguageTriple, ObjectId};
+
+        fn submission(lang: &str) -> SubmissionInfo {
+            SubmissionInfo {
+                id: ObjectId::default(),
+                problem_id: ObjectId::default(),
+                source: String::from("int main() { }"),
+                language: lang.parse::<LanguageTriple>().unwrap(),
+            }
+        }
+
+        fn config_with(enabled_languages: &[&str]) -> JudgeEngineConfig {
+            let mut config: JudgeEngineConfig = serde_yaml::from_str(r#"
+                judge_dir: "/judge"
+                language_dylibs: []
+                judge_username: "nobody"
+                jury_cpu_time_limit: 1000
+                jury_real_time_limit: 10000
+                jury_memory_limit: 1024
+            "#).unwrap();
+            config.enabled_languages = Some(
+                enabled_languages.iter().map(|lang| String::from(*lang)).collect());
+            config
+        }
+
+        #[test]
+        fn disabled_language_rejected() {
+            let config = config_with(&["cpp"]);
+            let result = check_submission_language(&submission("java:java:8"), &config).unwrap();
+            assert_eq!(Verdict::JudgeFailed, result.verdict);
+            assert!(result.compiler_message.contains("java:java:8"));
+            assert!(result.test_cases.is_empty());
+        }
+
+        #[test]
+        fn enabled_language_accepted() {
+            let config = config_with(&["cpp", "java:java:8"]);
+            assert!(check_submission_language(&submission("cpp:gnu:17"), &config).is_none());
+            assert!(check_submission_language(&submission("java:java:8"), &config).is_none());
+        }
+
+        #[test]
+        fn jury_language_not_enabled() {
+            let outcome = Err(Error::from(crate::storage::problems::Error::from(
+                ProblemsErrorKind::JuryLanguageNotEnabled(
+                    ObjectId::default(), String::from("cpp:gnu:c++17")))));
+            let result = submission_result(&submission("java:java:8"), outcome);
+            assert_eq!(Verdict::JudgeFailed, result.verdict);
+            assert_eq!("Jury language \"cpp:gnu:c++17\" is not enabled on this judge node.",
+                result.compiler_message);
+        }
+    }
+
+    mod submission_deadline {
+        use super::*;
+
+        use crate::restful::entities::ObjectId;
+
+        fn submission() -> SubmissionInfo {
+            SubmissionInfo {
+                id: ObjectId::default(),
+                problem_id: ObjectId::default(),
+                source: String::from("int main() { }"),
+                language: "cpp:gnu:17".parse().unwrap(),
+            }
+        }
+
+        /// Simulate processing a submission whose phases take the given durations.
+        fn slow_submission(deadline: &SubmissionDeadline, phases: &[(&'static str, u64)])
+            -> Result<SubmissionJudgeResult> {
+            for (phase, millis) in phases {
+                std::thread::sleep(Duration::from_millis(*millis));
+                deadline.check(phase)?;
+            }
+            Ok(SubmissionJudgeResult {
+                verdict: Verdict::Accepted,
+                ..SubmissionJudgeResult::failure("")
+            })
+        }
+
+        #[test]
+        fn no_deadline() {
+            let deadline = SubmissionDeadline::new(None);
+            let outcome = slow_submission(&deadline, &[("compiling", 20), ("judging", 20)]);
+            assert_eq!(Verdict::Accepted, submission_result(&submission(), outcome).verdict);
+        }
+
+        #[test]
+        fn within_deadline() {
+            let deadline = SubmissionDeadline::new(Some(Duration::from_secs(10)));
+            let outcome = slow_submission(&deadline, &[("compiling", 20), ("judging", 20)]);
+            assert_eq!(Verdict::Accepted, submission_result(&submission(), outcome).verdict);
+        }
+
+        #[test]
+        fn deadline_exceeded() {
+            let deadline = SubmissionDeadline::new(Some(Duration::from_millis(50)));
+            let outcome = slow_submission(&deadline, &[("compiling", 100), ("judging", 0)]);
+            match outcome {
+                Err(Error(ErrorKind::DeadlineExceeded { phase, .. }, _)) =>
+                    assert_eq!("compiling", phase),
+                _ => panic!("unexpected outcome: {:?}", outcome)
+            };
+
+            let result = submission_result(&submission(), outcome);
+            assert_eq!(Verdict::JudgeFailed, result.verdict);
+            assert_eq!("submission exceeded processing deadline", result.compiler_message);
+        }
+
+        #[test]
+        fn interrupted_judge_failed() {
+            let deadline = SubmissionDeadline::new(Some(Duration::from_millis(10)));
+            std::thread::sleep(Duration::from_millis(20));
+            let mut judge_result = judge::JudgeResult::new();
+            judge_result.verdict = judge::Verdict::JudgeTimeLimitExceeded;
+            judge_result.add_test_case_result(judge::TestCaseResult::new());
+
+            let outcome = deadline.check_judged(&judge_result, 2)
+                .map(|_| SubmissionJudgeResult::from(judge_result.clone()));
+            let result = submission_result(&submission(), outcome);
+            assert_eq!(Verdict::JudgeFailed, result.verdict);
+            assert_eq!("submission exceeded processing deadline", result.compiler_message);
+
+            // The judge task finished before the deadline passed.
+            assert!(deadline.check_judged(&judge_result, 1).is_ok());
+        }
+
+        #[test]
+        fn remaining_budget() {
+            assert_eq!(None, SubmissionDeadline::new(None).remaining());
+
+            let deadline = SubmissionDeadline::new(Some(Duration::from_secs(10)));
+            let remaining = deadline.remaining().unwrap();
+            assert!(remaining > Duration::from_secs(9) && remaining <= Duration::from_secs(10));
+
+            let deadline = SubmissionDeadline::new(Some(Duration::from_millis(10)));
+            std::thread::sleep(Duration::from_millis(20));
+            assert_eq!(Some(Duration::from_secs(0)), deadline.remaining());
+        }
+    }
+
+    mod progress_throttle {
+        use super::*;
+
+        #[test]
+        fn throttled() {
+            let mut throttle = ProgressThrottle::new(Duration::from_millis(1000));
+            let start = Instant::now();
+            assert!(throttle.should_report(start));
+            assert!(!throttle.should_report(start + Duration::from_millis(500)));
+            assert!(throttle.should_report(start + Duration::from_millis(1000)));
+            assert!(!throttle.should_report(start + Duration::from_millis(1999)));
+        }
+
+        #[test]
+        fn zero_interval() {
+            let mut throttle = ProgressThrottle::new(Duration::from_millis(0));
+            let now = Instant::now();
+            assert!(throttle.should_report(now));
+            assert!(throttle.should_report(now));
+        }
+    }
+
+    mod progress_reporter {
+        use super::*;
+
+        fn progress(test_cases_done: usize) -> judge::JudgeProgress {
+            judge::JudgeProgress {
+                test_cases_done,
+                test_cases_total: 3,
+                verdict: judge::Verdict::Accepted,
+            }
+        }
+
+        #[test]
+        fn dropped_when_behind() {
+            let (mut reporter, receiver) = ProgressReporter::new(Duration::from_millis(0));
+            let id = ObjectId::default();
+            for i in 1..=3 {
+                reporter.report(id, &progress(i));
+            }
+
+            assert_eq!(vec![(id, progress(1))], receiver.try_iter().collect::<Vec<_>>());
+            reporter.report(id, &progress(3));
+            assert_eq!(vec![(id, progress(3))], receiver.try_iter().collect::<Vec<_>>());
+        }
+
+        #[test]
+        fn throttled_per_submission() {
+            let (mut reporter, receiver) = ProgressReporter::new(Duration::from_secs(1000));
+            let id = ObjectId::default();
+            reporter.report(id, &progress(1));
+            reporter.report(id, &progress(2));
+            assert_eq!(1, receiver.try_iter().count());
+
+            reporter.reset();
+            reporter.report(id, &progress(1));
+            assert_eq!(1, receiver.try_iter().count());
+        }
+    }
+
+    mod worker_judge_dir {
+        use super::*;
+
+        #[test]
+        fn removed_on_drop() {
+            let judge_dir = tempfile::tempdir().unwrap();
+            let path = judge_dir.path().join(worker_judge_subdir(3));
+            {
+                let _dir = WorkerJudgeDir::new(judge_dir.path(), 3);
+                std::fs::create_dir_all(path.join("tmp1234")).unwrap();
+                std::fs::write(path.join("tmp1234").join("output"), "1\n").unwrap();
+            }
+            assert!(!path.exists());
+        }
+
+        #[test]
+        fn stale_files_removed() {
+            let judge_dir = tempfile::tempdir().unwrap();
+            let path = judge_dir.path().join(worker_judge_subdir(1));
+            std::fs::create_dir_all(&path).unwrap();
+            std::fs::write(path.join("output"), "1\n").unwrap();
+
+            let _dir = WorkerJudgeDir::new(judge_dir.path(), 1);
+            assert!(!path.join("output").exists());
+        }
+    }
+
+    mod should_sleep_after_empty_poll {
+        use super::*;
+
+        #[test]
+        fn interval_polling() {
+            assert!(should_sleep_after_empty_poll(None, Duration::from_secs(0)));
+            assert!(should_sleep_after_empty_poll(None, Duration::from_secs(60)));
+        }
+
+        #[test]
+        fn long_polling() {
+            let wait = Some(Duration::from_secs(30));
+            assert!(!should_sleep_after_empty_poll(wait, Duration::from_secs(30)));
+            assert!(!should_sleep_after_empty_poll(wait, Duration::from_secs(20)));
+        }
+
+        #[test]
+        fn long_polling_unsupported() {
+            let wait = Some(Duration::from_secs(30));
+            assert!(should_sleep_after_empty_poll(wait, Duration::from_millis(50)));
+        }
+    }
+
+    mod handle_submission {
+        use super::*;
+
+        use std::str::FromStr;
+        use std::sync::Mutex;
+
+        use openssl::rsa::Rsa;
+
+        use crate::config::AppConfig;
+        use crate::forkserver::{Command, CommandResult};
+        use crate::metrics::Metrics;
+        use crate::restful::RestfulClient;
+        use crate::restful::entities::LanguageTriple;
+        use crate::restful::mock::{start_mock_judge_board_with_fields, MockServer, PROBLEM_ID};
+        use crate::storage::AppStorageFacade;
+
+        /// Create an application context under the given directory connected to a mock judge
+        /// board server serving a problem in standard judge mode, and a mock fork server
+        /// responding to every compilation with the given result. `engine_config` is appended to
+        /// the `engine` section of the application configuration, and `problem_fields` to the
+        /// fields of the problem served. The commands received by the mock fork server are
+        /// recorded into the returned list.
+        fn make_context(dir: &std::path::Path, compile_result: judge::CompilationResult,
+            engine_config: &str, problem_fields: &str)
+            -> (Arc<MockServer>, AppContext, Arc<Mutex<Vec<Command>>>) {
+            let rsa_key = Rsa::generate(1024).unwrap();
+            let (server, url) = start_mock_judge_board_with_fields(
+                "Standard", problem_fields, rsa_key.clone());
+            let rest = Arc::new(RestfulClient::new(url.clone(), rsa_key));
+
+            let config: AppConfig = serde_yaml::from_str(&format!(r#"
+                workers: 1
+                cluster:
+                    judge_board_url: "{url}"
+                    heartbeat_interval: 5
+                    authenticate_key_file: "{dir}/key.pem"
+                storage:
+                    db_file: "{dir}/cache.db"
+                    archive_dir: "{dir}/archives"
+                    jury_dir: "{dir}/jury"
+                engine:
+                    judge_dir: "{dir}/judge"
+                    language_dylibs: []
+                    judge_username: "nobody"
+                    jury_cpu_time_limit: 1000
+                    jury_real_time_limit: 10000
+                    jury_memory_limit: 1024
+                    {engine}
+            "#, url = url, dir = dir.display(), engine = engine_config)).unwrap();
+
+            let commands = Arc::new(Mutex::new(Vec::new()));
+            let commands_clone = commands.clone();
+            let fork_server = Arc::new(crate::forkserver::start_mock_fork_server(move |cmd| {
+                let result = match cmd {
+                    Command::Compile(..) => CommandResult::Compile(compile_result.clone()),
+                    Command::Judge(..) => CommandResult::Judge(judge::JudgeResult::new()),
+                    Command::Ping => CommandResult::Pong,
+                };
+                commands_clone.lock().unwrap().push(cmd);
+                result
+            }));
+
+            let metrics = Arc::new(Metrics::new());
+            let storage = AppStorageFacade::new(
+                &config, rest.clone(), fork_server.clone(), metrics.clone()).unwrap();
+            let context = AppContext {
+                config: Arc::new(config),
+                fork_server,
+                rest,
+                storage,
+                metrics,
+            };
+            (server, context, commands)
+        }
+
+        /// Create a submission of the problem served by the mock judge board server.
+        fn make_submission() -> SubmissionInfo {
+            SubmissionInfo {
+                id: ObjectId::default(),
+                problem_id: ObjectId::from_str(PROBLEM_ID).unwrap(),
+                source: String::from("int main() { return 0 }"),
+                language: "cpp:gnu:c++17".parse::<LanguageTriple>().unwrap(),
+            }
+        }
+
+        /// Judge the submission given by `make_submission` as if it was fetched just now.
+        fn judge_submission(context: &AppContext) -> SubmissionJudgeResult {
+            handle_submission(&make_submission(), Instant::now(), None, None, context).unwrap()
+        }
+
+        /// Get the judge tasks among the given commands.
+        fn judge_tasks(commands: &[Command]) -> Vec<&judge::JudgeTaskDescriptor> {
+            commands.iter()
+                .filter_map(|cmd| match cmd {
+                    Command::Judge(task) => Some(task),
+                    _ => None
+                })
+                .collect()
+        }
+
+        #[test]
+        fn compilation_failure_not_judged() {
+            let dir = tempfile::tempdir().unwrap();
+            let (_server, context, commands) = make_context(
+                dir.path(), judge::CompilationResult::fail("error: expected `;`"), "", "");
+
+            let result = judge_submission(&context);
+            assert_eq!(Verdict::CompilationFailed, result.verdict);
+            assert_eq!("error: expected `;`", result.compiler_message);
+            assert!(result.test_cases.is_empty());
+            let commands = commands.lock().unwrap();
+            assert!(commands.iter().any(|cmd| match cmd {
+                Command::Compile(..) => true,
+                _ => false
+            }));
+            assert!(judge_tasks(&commands).is_empty());
+        }
+
+        #[test]
+        fn nonzero_exit_policy_from_config() {
+            for &policy in &[true, false] {
+                let dir = tempfile::tempdir().unwrap();
+                let (_server, context, commands) = make_context(
+                    dir.path(), judge::CompilationResult::succeed("/bin/true"),
+                    &format!("treat_nonzero_exit_as_runtime_error: {}", policy), "");
+
+                judge_submission(&context);
+                let commands = commands.lock().unwrap();
+                let tasks = judge_tasks(&commands);
+                assert_eq!(1, tasks.len());
+                assert_eq!(policy, tasks[0].treat_nonzero_exit_as_runtime_error);
+            }
+        }
+
+        #[test]
+        fn nonzero_exit_policy_from_problem() {
+            for &policy in &[true, false] {
+                let dir = tempfile::tempdir().unwrap();
+                let (_server, context, commands) = make_context(
+                    dir.path(), judge::CompilationResult::succeed("/bin/true"),
+                    &format!("treat_nonzero_exit_as_runtime_error: {}", !policy),
+                    &format!(r#""treatNonzeroExitAsRuntimeError": {}"#, policy));
+
+                judge_submission(&context);
+                let commands = commands.lock().unwrap();
+                let tasks = judge_tasks(&commands);
+                assert_eq!(1, tasks.len());
+                assert_eq!(policy, tasks[0].treat_nonzero_exit_as_runtime_error);
+            }
+        }
+
+        #[test]
+        fn queue_latency_measured_from_fetch() {
+            let dir = tempfile::tempdir().unwrap();
+            let (_server, context, _commands) = make_context(
+                dir.path(), judge::CompilationResult::succeed("/bin/true"), "", "");
+
+            let fetched = Instant::now() - Duration::from_secs(20);
+            handle_submission(&make_submission(), fetched, None, None, &context).unwrap();
+            let lines = context.metrics.render();
+            assert!(lines.contains("wave_judge_queue_seconds_bucket{le=\"10\"} 0\n"));
+            assert!(lines.contains("wave_judge_queue_seconds_bucket{le=\"30\"} 1\n"));
+        }
+
+        #[test]
+        fn remaining_deadline_passed_to_judge_task() {
+            let dir = tempfile::tempdir().unwrap();
+            let (_server, context, commands) = make_context(
+                dir.path(), judge::CompilationResult::succeed("/bin/true"),
+                "submission_deadline: 60000", "");
+
+            judge_submission(&context);
+            let commands = commands.lock().unwrap();
+            let tasks = judge_tasks(&commands);
+            assert_eq!(1, tasks.len());
+            let time_limit = tasks[0].time_limit.unwrap();
+            assert!(time_limit > Duration::from_secs(30) && time_limit < Duration::from_secs(60));
+        }
+
+        #[test]
+        fn subtasks_from_problem() {
+            let dir = tempfile::tempdir().unwrap();
+            let (_server, context, commands) = make_context(
+                dir.path(), judge::CompilationResult::succeed("/bin/true"), "",
+                r#""subtasks": [
+                    {"id": 1, "testCases": [0], "score": 40},
+                    {"id": 2, "testCases": [0], "score": 60, "dependencies": [1]}
+                ]"#);
+
+            judge_submission(&context);
+            let commands = commands.lock().unwrap();
+            let tasks = judge_tasks(&commands);
+            assert_eq!(1, tasks.len());
+            let mut dependent = judge::Subtask::new(2, vec![0], 60.0);
+            dependent.dependencies.push(1);
+            assert_eq!(vec![judge::Subtask::new(1, vec![0], 40.0), dependent], tasks[0].subtasks);
+        }
+    }
+}
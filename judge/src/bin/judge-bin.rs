@@ -2,20 +2,30 @@ extern crate error_chain;
 extern crate stderrlog;
 extern crate clap;
 extern crate judge;
+extern crate sandbox;
 
 use std::path::PathBuf;
 use std::str::FromStr;
+use std::time::Duration;
 
 use error_chain::ChainedError;
 
+use sandbox::SystemCall;
+
 use judge::{
     Program,
     ProgramKind,
     CompilationTaskDescriptor,
+    JudgeTaskDescriptor,
+    JudgeMode,
+    BuiltinCheckers,
+    MemorySize,
+    TestCaseDescriptor,
 };
 use judge::engine::{
     JudgeEngine,
     JudgeEngineConfig,
+    JudgeEngineConfigBuilder,
 };
 use judge::languages::{
     LanguageIdentifier,
@@ -29,6 +39,7 @@ error_chain::error_chain! {
 
     links {
         JudgeError(::judge::Error, ::judge::ErrorKind);
+        SandboxError(::sandbox::Error, ::sandbox::ErrorKind);
         DylibLoaderError(
             ::judge::languages::LoadDylibError, ::judge::languages::LoadDylibErrorKind);
     }
@@ -37,10 +48,33 @@ error_chain::error_chain! {
         InvalidLanguageIdentifier {
             description("invalid language identifier")
         }
+
+        InvalidArgument(name: &'static str, value: String) {
+            description("invalid argument")
+            display("invalid value of argument {}: \"{}\"", name, value)
+        }
+
+        InvalidTestCase(spec: String) {
+            description("invalid test case")
+            display("invalid test case: \"{}\", expected /path/to/input:/path/to/answer", spec)
+        }
+
+        InvalidCheckerType(name: String) {
+            description("invalid checker type")
+            display("invalid checker type: \"{}\", valid values are: {}", name,
+                BuiltinCheckers::ALL.iter()
+                    .map(|checker| checker.name())
+                    .collect::<Vec<_>>()
+                    .join(", "))
+        }
     }
 }
 
 fn get_arg_matches() -> clap::ArgMatches<'static> {
+    make_app().get_matches()
+}
+
+fn make_app() -> clap::App<'static, 'static> {
     clap::App::new("judge-bin")
         .version("0.1.0")
         .author("Lancern <msrlancern@126.com>")
@@ -105,8 +139,15 @@ fn get_arg_matches() -> clap::ArgMatches<'static> {
                 .takes_value(true)
                 .value_name("JUDGE_MODE")
                 .default_value("STANDARD")
-                .possible_values(&["STANDARD", "SPECIAL_JUDGE", "INTERACTIVE"])
+                .possible_values(&["STANDARD", "SPECIAL_JUDGE", "INTERACTIVE", "AGAINST_REFERENCE"])
                 .help("judge mode"))
+            .arg(clap::Arg::with_name("checker_type")
+                .long("checker-type")
+                .multiple(false)
+                .takes_value(true)
+                .value_name("CHECKER_TYPE")
+                .default_value("default")
+                .help("built-in checker used in STANDARD and AGAINST_REFERENCE judge mode"))
             .arg(clap::Arg::with_name("cpu_time_limit")
                 .short("t")
                 .long("cpu")
@@ -170,6 +211,13 @@ fn get_arg_matches() -> clap::ArgMatches<'static> {
                 .takes_value(true)
                 .value_name("CHECKER_MEMORY_LIMIT")
                 .help("memory limit of the checker"))
+            .arg(clap::Arg::with_name("reference")
+                .long("reference")
+                .required_if("mode", "AGAINST_REFERENCE")
+                .multiple(false)
+                .takes_value(true)
+                .value_name("REFERENCE")
+                .help("path to the reference solution"))
             .arg(clap::Arg::with_name("interactor")
                 .long("interactor")
                 .required_if("mode", "INTERACTIVE")
@@ -210,7 +258,6 @@ fn get_arg_matches() -> clap::ArgMatches<'static> {
                 .takes_value(true)
                 .value_name("PROGRAM")
                 .help("path to the program executable file to be judged")))
-        .get_matches()
 }
 
 fn parse_lang(lang: &str) -> Result<LanguageIdentifier> {
@@ -252,8 +299,114 @@ fn do_compile(matches: &clap::ArgMatches<'_>, engine: &mut JudgeEngine) -> Resul
     Ok(())
 }
 
+fn get_judge_mode(matches: &clap::ArgMatches<'_>) -> Result<JudgeMode> {
+    let checker_type = matches.value_of("checker_type").unwrap();
+    let checker = BuiltinCheckers::from_str(checker_type)
+        .map_err(|_| Error::from(ErrorKind::InvalidCheckerType(String::from(checker_type))))?;
+
+    // The jury programs are assumed to be written in the same language as the judgee.
+    let lang = parse_lang(matches.value_of("lang").unwrap())?;
+    let mode = match matches.value_of("mode").unwrap() {
+        "STANDARD" => JudgeMode::Standard(checker),
+        "SPECIAL_JUDGE" => JudgeMode::SpecialJudge(
+            Program::new(matches.value_of("checker").unwrap(), lang)),
+        "INTERACTIVE" => JudgeMode::Interactive(
+            Program::new(matches.value_of("interactor").unwrap(), lang)),
+        "AGAINST_REFERENCE" => JudgeMode::AgainstReference {
+            reference: Program::new(matches.value_of("reference").unwrap(), lang),
+            checker,
+        },
+        _ => unreachable!()
+    };
+
+    Ok(mode)
+}
+
+fn parse_arg<T>(matches: &clap::ArgMatches<'_>, name: &'static str) -> Result<Option<T>>
+    where T: FromStr {
+    match matches.value_of(name) {
+        Some(value) => value.parse().map(Some)
+            .map_err(|_| Error::from(ErrorKind::InvalidArgument(name, String::from(value)))),
+        None => Ok(None)
+    }
+}
+
+fn get_engine_config(matches: &clap::ArgMatches<'_>) -> Result<JudgeEngineConfig> {
+    let mut builder = JudgeEngineConfigBuilder::new();
+    if let Some(uid) = parse_arg(matches, "uid")? {
+        builder.judge_uid(uid);
+    }
+    if let Some(syscalls) = matches.values_of("allowed_syscalls") {
+        let whitelist = syscalls
+            .map(SystemCall::from_name)
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        builder.judgee_syscall_whitelist(whitelist);
+    }
+
+    // The limits of the jury program are given by the options of the interactor in interactive
+    // judge mode, and by the options of the checker otherwise.
+    let jury = if matches.value_of("mode") == Some("INTERACTIVE") {
+        ["interactor_cpu_time_limit", "interactor_real_time_limit", "interactor_memory_limit"]
+    } else {
+        ["checker_cpu_time_limit", "checker_real_time_limit", "checker_memory_limit"]
+    };
+    if let Some(limit) = parse_arg(matches, jury[0])? {
+        builder.jury_cpu_time_limit(Duration::from_millis(limit))?;
+    }
+    if let Some(limit) = parse_arg(matches, jury[1])? {
+        builder.jury_real_time_limit(Duration::from_millis(limit))?;
+    }
+    if let Some(limit) = parse_arg(matches, jury[2])? {
+        builder.jury_memory_limit(MemorySize::MegaBytes(limit))?;
+    }
+
+    Ok(builder.build()?)
+}
+
+fn parse_test_case(spec: &str) -> Result<TestCaseDescriptor> {
+    let files = spec.split(':').collect::<Vec<&'_ str>>();
+    if files.len() != 2 {
+        return Err(Error::from(ErrorKind::InvalidTestCase(String::from(spec))));
+    }
+
+    Ok(TestCaseDescriptor::new(files[0], files[1]))
+}
+
+fn get_judge_task(matches: &clap::ArgMatches<'_>) -> Result<JudgeTaskDescriptor> {
+    let lang = parse_lang(matches.value_of("lang").unwrap())?;
+    let program = Program::new(matches.value_of("program").unwrap(), lang);
+    let mut task = JudgeTaskDescriptor::new(program);
+    task.mode = get_judge_mode(matches)?;
+    task.limits.cpu_time_limit = Duration::from_millis(
+        parse_arg(matches, "cpu_time_limit")?.unwrap());
+    task.limits.real_time_limit = Duration::from_millis(
+        parse_arg(matches, "real_time_limit")?.unwrap());
+    task.limits.memory_limit = MemorySize::MegaBytes(parse_arg(matches, "memory_limit")?.unwrap());
+    task.test_suite = matches.values_of("test_suite").unwrap()
+        .map(parse_test_case)
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(task)
+}
+
 fn do_judge(matches: &clap::ArgMatches<'_>, engine: &mut JudgeEngine) -> Result<()> {
-    unimplemented!()
+    let task = get_judge_task(matches)?;
+    log::debug!("Judge mode: {:?}", task.mode);
+
+    let res = engine.judge(task).chain_err(|| Error::from("Judge failed"))?;
+
+    println!("Verdict: {:?}", res.verdict);
+    for (index, tc_result) in res.test_suite.iter().enumerate() {
+        println!("Test case #{}: {:?}", index + 1, tc_result.verdict);
+        if let Some(ref comment) = tc_result.comment {
+            println!("{}", comment);
+        }
+    }
+    if let Some(ref comment) = res.comment {
+        println!("{}", comment);
+    }
+
+    Ok(())
 }
 
 fn do_main() -> Result<()> {
@@ -264,8 +417,14 @@ fn do_main() -> Result<()> {
         .unwrap();
     let matches = get_arg_matches();
 
+    // The engine configuration is given by the options of the judge subcommand.
+    let engine_config = match matches.subcommand() {
+        ("judge", Some(judge_matches)) => get_engine_config(judge_matches)?,
+        _ => JudgeEngineConfig::new()
+    };
+
     // Load dynamic linking libraries that contains definitions for language proviers, if any.
-    let mut engine = JudgeEngine::new();
+    let mut engine = JudgeEngine::with_config(engine_config);
     match matches.values_of("lang_so") {
         Some(sos) => {
             for so in sos {
@@ -301,3 +460,92 @@ fn main() {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    mod judge_mode {
+        use super::*;
+
+        fn get_judge_mode_from(args: &[&str]) -> Result<JudgeMode> {
+            let mut argv = vec![
+                "judge-bin", "judge", "--lang", "cpp:gnu:c++17", "program", "--tc", "in:ans"];
+            argv.extend_from_slice(args);
+
+            let matches = make_app().get_matches_from(argv);
+            get_judge_mode(matches.subcommand_matches("judge").unwrap())
+        }
+
+        #[test]
+        fn default_checker() {
+            match get_judge_mode_from(&[]).unwrap() {
+                JudgeMode::Standard(BuiltinCheckers::Default) => (),
+                mode => panic!("unexpected judge mode: {:?}", mode),
+            };
+        }
+
+        #[test]
+        fn checker_type() {
+            match get_judge_mode_from(&["--checker-type", "floating-point"]).unwrap() {
+                JudgeMode::Standard(BuiltinCheckers::FloatingPointAware) => (),
+                mode => panic!("unexpected judge mode: {:?}", mode),
+            };
+            match get_judge_mode_from(&["--checker-type", "case-insensitive"]).unwrap() {
+                JudgeMode::Standard(BuiltinCheckers::CaseInsensitive) => (),
+                mode => panic!("unexpected judge mode: {:?}", mode),
+            };
+        }
+
+        #[test]
+        fn against_reference() {
+            let args = ["--mode", "AGAINST_REFERENCE", "--reference", "ref", "--checker-type",
+                "floating-point"];
+            match get_judge_mode_from(&args).unwrap() {
+                JudgeMode::AgainstReference {
+                    ref reference, checker: BuiltinCheckers::FloatingPointAware
+                } if reference.file == PathBuf::from("ref") => (),
+                mode => panic!("unexpected judge mode: {:?}", mode),
+            };
+        }
+
+        #[test]
+        fn invalid_checker_type() {
+            let err = get_judge_mode_from(&["--checker-type", "exact"]).unwrap_err();
+            assert_eq!(
+                "invalid checker type: \"exact\", valid values are: default, floating-point-aware, \
+                case-insensitive, sorted-lines, exact-bytes",
+                err.to_string());
+        }
+    }
+
+    mod judge_task {
+        use super::*;
+
+        #[test]
+        fn parsed() {
+            let argv = ["judge-bin", "judge", "--lang", "cpp:gnu:c++17", "-t", "2000", "-m", "64",
+                "--tc", "1.in:1.ans", "2.in:2.ans", "--", "program"];
+            let matches = make_app().get_matches_from(argv.iter());
+            let task = get_judge_task(matches.subcommand_matches("judge").unwrap()).unwrap();
+            assert_eq!(PathBuf::from("program"), task.program.file);
+            assert_eq!(Duration::from_secs(2), task.limits.cpu_time_limit);
+            assert_eq!(Duration::from_secs(3), task.limits.real_time_limit);
+            assert_eq!(MemorySize::MegaBytes(64), task.limits.memory_limit);
+            assert_eq!(2, task.test_suite.len());
+            assert_eq!(PathBuf::from("2.in"), task.test_suite[1].input_file);
+            assert_eq!(PathBuf::from("2.ans"), task.test_suite[1].answer_file);
+        }
+
+        #[test]
+        fn invalid_test_case() {
+            let argv = ["judge-bin", "judge", "--lang", "cpp:gnu:c++17", "--tc", "1.in", "--",
+                "program"];
+            let matches = make_app().get_matches_from(argv.iter());
+            let err = get_judge_task(matches.subcommand_matches("judge").unwrap()).unwrap_err();
+            assert_eq!(
+                "invalid test case: \"1.in\", expected /path/to/input:/path/to/answer",
+                err.to_string());
+        }
+    }
+}
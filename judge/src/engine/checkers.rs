@@ -1,13 +1,15 @@
 //! This module implements built-in answer checkers used in standard judge mode.
 //!
 
+use std::collections::{HashMap, VecDeque};
 use std::fs::File;
+use std::os::unix::fs::FileExt;
 use std::str::FromStr;
 
 #[cfg(feature = "serde")]
 use serde::{Serialize, Deserialize};
 
-use crate::BuiltinCheckers;
+use crate::{BuiltinCheckers, BuiltinCheckerOptions, DiffView, DIFF_SNIPPET_LEN};
 use super::io::{TokenizedRead, TokenizedReader};
 
 
@@ -23,7 +25,10 @@ pub struct CheckerContext {
     pub answer: TokenizedReader<File>,
 
     /// Judgee's output file.
-    pub user_output: TokenizedReader<File>
+    pub user_output: TokenizedReader<File>,
+
+    /// Whether the judgee's output must follow the trailing newline convention of the answer file.
+    pub require_trailing_newline: bool,
 }
 
 impl CheckerContext {
@@ -35,9 +40,49 @@ impl CheckerContext {
         CheckerContext {
             input,
             answer,
-            user_output
+            user_output,
+            require_trailing_newline: false,
         }
     }
+
+    /// Apply the given built-in checker options to the answer and user output streams.
+    pub fn apply_options(&mut self, options: &BuiltinCheckerOptions) {
+        self.answer.set_ignore_line_prefix(options.ignore_line_prefix.clone());
+        self.user_output.set_ignore_line_prefix(options.ignore_line_prefix.clone());
+        self.answer.set_normalize_line_endings(options.normalize_line_endings);
+        self.user_output.set_normalize_line_endings(options.normalize_line_endings);
+        self.require_trailing_newline = options.require_trailing_newline;
+    }
+
+    /// Check whether the judgee's output ends with a newline if and only if the answer file does,
+    /// if required. Returns the rejected result if the check fails.
+    fn check_trailing_newline(&self) -> std::io::Result<Option<CheckerResult>> {
+        if !self.require_trailing_newline {
+            return Ok(None);
+        }
+
+        let expected = ends_with_newline(self.answer.get_ref())?;
+        let actual = ends_with_newline(self.user_output.get_ref())?;
+        let comment = match (expected, actual) {
+            (true, false) => "expected a trailing newline at the end of the output.",
+            (false, true) => "unexpected trailing newline at the end of the output.",
+            _ => return Ok(None)
+        };
+
+        Ok(Some(CheckerResult::rejected(Some(String::from(comment)))))
+    }
+}
+
+/// Determine whether the given file ends with a newline. The position of the file is not changed.
+fn ends_with_newline(file: &File) -> std::io::Result<bool> {
+    let len = file.metadata()?.len();
+    if len == 0 {
+        return Ok(false);
+    }
+
+    let mut last = [0u8; 1];
+    file.read_exact_at(&mut last, len - 1)?;
+    Ok(last[0] == b'\n')
 }
 
 /// Represent the result of a checker.
@@ -48,7 +93,10 @@ pub struct CheckerResult {
     pub accepted: bool,
 
     /// Comment by the checker, if any.
-    pub comment: Option<String>
+    pub comment: Option<String>,
+
+    /// Structured view of the first difference found by the checker, if any.
+    pub diff: Option<DiffView>,
 }
 
 impl CheckerResult {
@@ -56,7 +104,8 @@ impl CheckerResult {
     pub fn accepted(comment: Option<String>) -> CheckerResult {
         CheckerResult {
             accepted: true,
-            comment
+            comment,
+            diff: None
         }
     }
 
@@ -64,9 +113,17 @@ impl CheckerResult {
     pub fn rejected(comment: Option<String>) -> CheckerResult {
         CheckerResult {
             accepted: false,
-            comment
+            comment,
+            diff: None
         }
     }
+
+    /// Set the diff view of this result to the difference between the given expected and actual
+    /// contents.
+    pub fn with_diff(mut self, expected: &str, actual: &str) -> CheckerResult {
+        self.diff = Some(DiffView::new(expected, actual));
+        self
+    }
 }
 
 /// A boilerplate function that executes some common logic of all built-in checkers, such as
@@ -81,12 +138,13 @@ fn builtin_checker_exec<C>(context: &mut CheckerContext, token_checker: C)
         let user_token = match context.user_output.read_token()? {
             Some(t) => t,
             None => return Ok(CheckerResult::rejected(
-                Some(format!("expect \"{}\", but found EOF", expected_token))))
+                Some(format!("expect \"{}\", but found EOF", expected_token)))
+                .with_diff(&expected_token, ""))
         };
 
         let (accepted, comment) = token_checker(&expected_token, &user_token);
         if !accepted {
-            return Ok(CheckerResult::rejected(comment));
+            return Ok(CheckerResult::rejected(comment).with_diff(&expected_token, &user_token));
         }
 
         token_counter += 1;
@@ -95,7 +153,12 @@ fn builtin_checker_exec<C>(context: &mut CheckerContext, token_checker: C)
     // Check if we can hit EOF on the user's output stream.
     if let Some(user_token) = context.user_output.read_token()? {
         return Ok(CheckerResult::rejected(
-            Some(format!("expect EOF, but found \"{}\"", user_token))));
+            Some(format!("expect EOF, but found \"{}\"", user_token)))
+            .with_diff("", &user_token));
+    }
+
+    if let Some(res) = context.check_trailing_newline()? {
+        return Ok(res);
     }
 
     Ok(CheckerResult::accepted(Some(format!("OK: {} tokens.", token_counter))))
@@ -172,11 +235,408 @@ fn case_insensitive_checker(context: &mut CheckerContext) -> std::io::Result<Che
     })
 }
 
+/// Read all non-blank lines from the given reader, with trailing whitespaces removed.
+fn read_nonblank_lines(reader: &mut TokenizedReader<File>) -> std::io::Result<Vec<String>> {
+    let mut lines = Vec::new();
+    while let Some(line) = reader.read_line()? {
+        let line = line.trim_end();
+        if !line.is_empty() {
+            lines.push(String::from(line));
+        }
+    }
+
+    Ok(lines)
+}
+
+/// This function implements the sorted lines checker's logic. The lines of the answer and the
+/// judgee's output are compared as multisets.
+fn sorted_lines_checker(context: &mut CheckerContext) -> std::io::Result<CheckerResult> {
+    let mut expected_lines = read_nonblank_lines(&mut context.answer)?;
+    let mut user_lines = read_nonblank_lines(&mut context.user_output)?;
+    expected_lines.sort();
+    user_lines.sort();
+
+    // Walk through both sorted lists simultaneously to find the first difference.
+    let mut expected_iter = expected_lines.iter().peekable();
+    let mut user_iter = user_lines.iter().peekable();
+    loop {
+        match (expected_iter.peek(), user_iter.peek()) {
+            (None, None) => break,
+            (Some(expected), Some(user)) if expected == user => {
+                expected_iter.next();
+                user_iter.next();
+            },
+            (Some(expected), Some(user)) if expected < user =>
+                return Ok(CheckerResult::rejected(
+                    Some(format!("line \"{}\" is missing from the output.", expected)))
+                    .with_diff(expected, user)),
+            (Some(expected), None) =>
+                return Ok(CheckerResult::rejected(
+                    Some(format!("line \"{}\" is missing from the output.", expected)))
+                    .with_diff(expected, "")),
+            (expected, Some(user)) =>
+                return Ok(CheckerResult::rejected(
+                    Some(format!("unexpected line \"{}\" in the output.", user)))
+                    .with_diff(expected.map(|line| line.as_str()).unwrap_or_default(), user)),
+        };
+    }
+
+    if let Some(res) = context.check_trailing_newline()? {
+        return Ok(res);
+    }
+
+    Ok(CheckerResult::accepted(Some(format!("OK: {} lines.", expected_lines.len()))))
+}
+
+/// Read the snippet of the given reader starting at the first difference found by the exact bytes
+/// checker, for the diff view. `recent` contains the bytes preceding the first difference and
+/// `first` is the differing byte read from the reader.
+fn read_diff_snippet(recent: &VecDeque<u8>, first: Option<u8>, reader: &mut TokenizedReader<File>)
+    -> std::io::Result<String> {
+    let mut snippet: Vec<u8> = recent.iter().cloned().collect();
+    if let Some(first) = first {
+        snippet.push(first);
+        while snippet.len() < recent.len() + DIFF_SNIPPET_LEN {
+            match reader.read_raw_byte()? {
+                Some(b) => snippet.push(b),
+                None => break
+            };
+        }
+    }
+
+    Ok(String::from_utf8_lossy(&snippet).into_owned())
+}
+
+/// This function implements the exact bytes checker's logic. The answer and the judgee's output are
+/// compared byte by byte as they are read, and the offset of the first differing byte is reported.
+fn exact_bytes_checker(context: &mut CheckerContext) -> std::io::Result<CheckerResult> {
+    let mut offset: u64 = 0;
+    // The last bytes read before the current one, kept as the context of the diff view.
+    let mut recent = VecDeque::with_capacity(DIFF_SNIPPET_LEN);
+    loop {
+        let expected_byte = context.answer.read_raw_byte()?;
+        let user_byte = context.user_output.read_raw_byte()?;
+        let comment = match (expected_byte, user_byte) {
+            (None, None) => break,
+            (Some(expected), Some(user)) if expected == user => {
+                offset += 1;
+                if recent.len() == DIFF_SNIPPET_LEN {
+                    recent.pop_front();
+                }
+                recent.push_back(expected);
+                continue;
+            },
+            (Some(expected), Some(user)) =>
+                format!("expected byte 0x{:02x}, but found byte 0x{:02x}", expected, user),
+            (Some(expected), None) => format!("expected byte 0x{:02x}, but found EOF", expected),
+            (None, Some(user)) => format!("expected EOF, but found byte 0x{:02x}", user),
+        };
+
+        let expected = read_diff_snippet(&recent, expected_byte, &mut context.answer)?;
+        let user = read_diff_snippet(&recent, user_byte, &mut context.user_output)?;
+        return Ok(CheckerResult::rejected(
+            Some(format!("first difference at byte offset {}: {}.", offset, comment)))
+            .with_diff(&expected, &user));
+    }
+
+    Ok(CheckerResult::accepted(Some(format!("OK: {} bytes.", offset))))
+}
+
 /// Get the corresponding built-in checker specified by the `BuiltinCheckers` enum.
 pub fn get_checker(checker: BuiltinCheckers) -> Checker {
     match checker {
         BuiltinCheckers::Default => default_checker,
         BuiltinCheckers::FloatingPointAware => floating_point_aware_checker,
-        BuiltinCheckers::CaseInsensitive => case_insensitive_checker
+        BuiltinCheckers::CaseInsensitive => case_insensitive_checker,
+        BuiltinCheckers::SortedLines => sorted_lines_checker,
+        BuiltinCheckers::ExactBytes => exact_bytes_checker,
+    }
+}
+
+/// Provide a registry of in-process answer checkers that can be looked up by name.
+///
+/// A newly created registry contains the built-in checkers under the names `default`,
+/// `floating-point-aware`, `case-insensitive`, `sorted-lines` and `exact-bytes`.
+pub struct CheckerRegistry {
+    /// The registered checkers, indexed by their names.
+    checkers: HashMap<String, Checker>,
+}
+
+impl CheckerRegistry {
+    /// Create a new `CheckerRegistry` instance with all built-in checkers registered.
+    pub fn new() -> Self {
+        let mut registry = CheckerRegistry::empty();
+        for checker in BuiltinCheckers::ALL.iter() {
+            registry.register(checker.name(), get_checker(*checker));
+        }
+        registry
+    }
+
+    /// Create a new `CheckerRegistry` instance that contains no checkers.
+    pub fn empty() -> Self {
+        CheckerRegistry {
+            checkers: HashMap::new(),
+        }
+    }
+
+    /// Register the given checker under the given name. If a checker with the same name has
+    /// already been registered, it will be replaced and returned.
+    pub fn register<T>(&mut self, name: T, checker: Checker) -> Option<Checker>
+        where T: Into<String> {
+        self.checkers.insert(name.into(), checker)
+    }
+
+    /// Find the checker registered under the given name.
+    pub fn find(&self, name: &str) -> Option<Checker> {
+        self.checkers.get(name).cloned()
+    }
+
+    /// Get the names of all registered checkers.
+    pub fn names(&self) -> Vec<String> {
+        self.checkers.keys().cloned().collect()
+    }
+}
+
+impl Default for CheckerRegistry {
+    fn default() -> Self {
+        CheckerRegistry::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::{Seek, SeekFrom, Write};
+
+    fn make_file(content: &str) -> File {
+        let mut file = tempfile::tempfile().unwrap();
+        file.write_all(content.as_bytes()).unwrap();
+        file.seek(SeekFrom::Start(0)).unwrap();
+        file
+    }
+
+    fn make_context(input: &str, answer: &str, user_output: &str) -> CheckerContext {
+        CheckerContext::new(
+            TokenizedReader::new(make_file(input)),
+            TokenizedReader::new(make_file(answer)),
+            TokenizedReader::new(make_file(user_output)))
+    }
+
+    mod checker_options {
+        use super::*;
+
+        #[test]
+        fn ignore_line_prefix() {
+            let mut options = BuiltinCheckerOptions::new();
+            options.ignore_line_prefix = Some(String::from("#"));
+
+            let mut context = make_context("", "# seed 1\n1 2\n3\n", "1 2\n# took 3ms\n3\n");
+            context.apply_options(&options);
+            assert!(default_checker(&mut context).unwrap().accepted);
+
+            let mut context = make_context("", "# seed 1\n1 2\n3\n", "1 2\n# took 3ms\n3\n");
+            assert!(!default_checker(&mut context).unwrap().accepted);
+        }
+
+        #[test]
+        fn normalize_line_endings() {
+            let mut options = BuiltinCheckerOptions::new();
+            let mut context = make_context("", "1 2\n3\n", "1 2\r\n3\r\n");
+            context.apply_options(&options);
+            assert!(default_checker(&mut context).unwrap().accepted);
+
+            let mut context = make_context("", "1 2\n3\n", "3\r\n1 2\r\n");
+            context.apply_options(&options);
+            assert!(sorted_lines_checker(&mut context).unwrap().accepted);
+
+            options.normalize_line_endings = false;
+            let mut context = make_context("", "1 2\n3\n", "1 2\r\n3\r\n");
+            context.apply_options(&options);
+            assert!(!default_checker(&mut context).unwrap().accepted);
+        }
+
+        fn check(options: &BuiltinCheckerOptions, answer: &str, user_output: &str)
+            -> CheckerResult {
+            let mut context = make_context("", answer, user_output);
+            context.apply_options(options);
+            default_checker(&mut context).unwrap()
+        }
+
+        #[test]
+        fn trailing_newline_ignored() {
+            let options = BuiltinCheckerOptions::new();
+            assert!(check(&options, "1 2\n3\n", "1 2\n3").accepted);
+            assert!(check(&options, "1 2\n3", "1 2\n3\n").accepted);
+            assert!(check(&options, "1 2\n3\n", "1 2\n3\n").accepted);
+        }
+
+        #[test]
+        fn trailing_newline_required() {
+            let mut options = BuiltinCheckerOptions::new();
+            options.require_trailing_newline = true;
+            assert!(check(&options, "1 2\n3\n", "1 2\n3\n").accepted);
+            assert!(check(&options, "1 2\n3", "1 2\n3").accepted);
+            assert!(check(&options, "", "").accepted);
+
+            let res = check(&options, "1 2\n3\n", "1 2\n3");
+            assert!(!res.accepted);
+            assert_eq!(Some(String::from("expected a trailing newline at the end of the output.")),
+                res.comment);
+            let res = check(&options, "1 2\n3", "1 2\n3\n");
+            assert!(!res.accepted);
+            assert_eq!(Some(String::from("unexpected trailing newline at the end of the output.")),
+                res.comment);
+
+            // Wrong answers are still reported as such.
+            let res = check(&options, "1 2\n3\n", "1 2\n4");
+            assert_eq!(Some(String::from("expected \"3\", but found \"4\".")), res.comment);
+        }
+    }
+
+    mod sorted_lines {
+        use super::*;
+
+        #[test]
+        fn different_order() {
+            let mut context = make_context("",
+                "apple\nbanana\ncherry\n", "cherry\napple  \n\nbanana");
+            let res = sorted_lines_checker(&mut context).unwrap();
+            assert!(res.accepted);
+        }
+
+        #[test]
+        fn missing_line() {
+            let mut context = make_context("", "apple\nbanana\ncherry\n", "cherry\napple\n");
+            let res = sorted_lines_checker(&mut context).unwrap();
+            assert!(!res.accepted);
+            assert_eq!(Some(String::from("line \"banana\" is missing from the output.")),
+                res.comment);
+        }
+
+        #[test]
+        fn extra_line() {
+            let mut context = make_context("", "apple\napple\n", "apple\napple\napple\n");
+            let res = sorted_lines_checker(&mut context).unwrap();
+            assert!(!res.accepted);
+            assert_eq!(Some(String::from("unexpected line \"apple\" in the output.")),
+                res.comment);
+        }
+    }
+
+    mod exact_bytes {
+        use super::*;
+
+        #[test]
+        fn identical() {
+            let mut context = make_context("", "1 2\r\n\t3\n", "1 2\r\n\t3\n");
+            let res = exact_bytes_checker(&mut context).unwrap();
+            assert!(res.accepted);
+            assert_eq!(Some(String::from("OK: 8 bytes.")), res.comment);
+        }
+
+        #[test]
+        fn one_byte_different() {
+            let mut context = make_context("", "1 2\n3\n", "1 2 3\n");
+            let res = exact_bytes_checker(&mut context).unwrap();
+            assert!(!res.accepted);
+            assert_eq!(Some(String::from(
+                "first difference at byte offset 3: expected byte 0x0a, but found byte 0x20.")),
+                res.comment);
+        }
+
+        #[test]
+        fn whitespace_significant() {
+            let mut context = make_context("", "1 2\n", "1 2");
+            let res = exact_bytes_checker(&mut context).unwrap();
+            assert!(!res.accepted);
+            assert_eq!(Some(String::from(
+                "first difference at byte offset 3: expected byte 0x0a, but found EOF.")),
+                res.comment);
+
+            let mut context = make_context("", "1 2\n", "1 2\r\n");
+            let res = exact_bytes_checker(&mut context).unwrap();
+            assert!(!res.accepted);
+        }
+    }
+
+    mod diff_view {
+        use super::*;
+
+        #[test]
+        fn wrong_answer() {
+            let mut context = make_context("", "1 2\n3\n", "1 2\n4\n");
+            let res = default_checker(&mut context).unwrap();
+            assert!(!res.accepted);
+            assert_eq!(Some(DiffView::new("3", "4")), res.diff);
+            assert_eq!(0, res.diff.unwrap().first_diff_offset);
+        }
+
+        #[test]
+        fn unexpected_eof() {
+            let mut context = make_context("", "1 2\n3\n", "1 2\n");
+            let diff = default_checker(&mut context).unwrap().diff.unwrap();
+            assert_eq!("3", diff.expected_snippet);
+            assert_eq!("", diff.actual_snippet);
+            assert_eq!(0, diff.first_diff_offset);
+        }
+
+        #[test]
+        fn accepted() {
+            let mut context = make_context("", "1 2\n3\n", "1 2\n3\n");
+            assert!(default_checker(&mut context).unwrap().diff.is_none());
+        }
+
+        #[test]
+        fn exact_bytes() {
+            let mut context = make_context("", "1 2\n3\n", "1 2 3\n");
+            let diff = exact_bytes_checker(&mut context).unwrap().diff.unwrap();
+            assert_eq!("1 2\n3\n", diff.expected_snippet);
+            assert_eq!("1 2 3\n", diff.actual_snippet);
+            assert_eq!(3, diff.first_diff_offset);
+        }
+    }
+
+    mod checker_registry {
+        use super::*;
+
+        fn token_count_checker(context: &mut CheckerContext) -> std::io::Result<CheckerResult> {
+            let mut expected = 0;
+            while context.answer.read_token()?.is_some() {
+                expected += 1;
+            }
+
+            let mut actual = 0;
+            while context.user_output.read_token()?.is_some() {
+                actual += 1;
+            }
+
+            if expected == actual {
+                Ok(CheckerResult::accepted(None))
+            } else {
+                Ok(CheckerResult::rejected(None))
+            }
+        }
+
+        #[test]
+        fn builtin_registered() {
+            let registry = CheckerRegistry::new();
+            assert!(registry.find("default").is_some());
+            assert!(registry.find("floating-point-aware").is_some());
+            assert!(registry.find("case-insensitive").is_some());
+            assert!(registry.find("sorted-lines").is_some());
+            assert!(registry.find("exact-bytes").is_some());
+            assert!(registry.find("token-count").is_none());
+        }
+
+        #[test]
+        fn register_custom() {
+            let mut registry = CheckerRegistry::new();
+            assert!(registry.register("token-count", token_count_checker).is_none());
+
+            let checker = registry.find("token-count").unwrap();
+            assert!(checker(&mut make_context("", "1 2 3", "a b c")).unwrap().accepted);
+            assert!(!checker(&mut make_context("", "1 2 3", "a b")).unwrap().accepted);
+        }
     }
 }
@@ -0,0 +1,197 @@
+//! This module provides the spans around the key steps of the judge engine, namely compilations,
+//! judge tasks and the test cases in judge tasks. When the `tracing` feature is enabled, the spans
+//! are `tracing` spans carrying the identifiers of the submission and the problem, and the
+//! verdicts as fields. Otherwise the spans do nothing, and the engine reports through `log` only.
+//!
+
+#[cfg(feature = "tracing")]
+use tracing::field::{display, Empty};
+
+use crate::{CompilationTaskDescriptor, JudgeTaskDescriptor, Verdict};
+
+/// A span around a step of the judge engine.
+pub(super) struct Span {
+    /// The underlying `tracing` span.
+    #[cfg(feature = "tracing")]
+    inner: tracing::Span,
+}
+
+impl Span {
+    /// Create a span around the given compilation task.
+    #[cfg(feature = "tracing")]
+    pub(super) fn compile(task: &CompilationTaskDescriptor) -> Self {
+        let inner = tracing::info_span!("compile",
+            language = %task.program.language,
+            kind = ?task.kind,
+            succeeded = Empty);
+        Span { inner }
+    }
+
+    /// Create a span around the given compilation task.
+    #[cfg(not(feature = "tracing"))]
+    pub(super) fn compile(_task: &CompilationTaskDescriptor) -> Self {
+        Span { }
+    }
+
+    /// Create a span around the given judge task.
+    #[cfg(feature = "tracing")]
+    pub(super) fn judge(task: &JudgeTaskDescriptor) -> Self {
+        let inner = tracing::info_span!("judge",
+            submission_id = Empty,
+            problem_id = Empty,
+            language = %task.program.language,
+            verdict = Empty);
+        if let Some(ref id) = task.submission_id {
+            inner.record("submission_id", &display(id));
+        }
+        if let Some(ref id) = task.problem_id {
+            inner.record("problem_id", &display(id));
+        }
+
+        Span { inner }
+    }
+
+    /// Create a span around the given judge task.
+    #[cfg(not(feature = "tracing"))]
+    pub(super) fn judge(_task: &JudgeTaskDescriptor) -> Self {
+        Span { }
+    }
+
+    /// Create a span around the test case at the given index of a judge task. The span should be
+    /// created inside the span of the judge task.
+    #[cfg(feature = "tracing")]
+    pub(super) fn test_case(index: usize) -> Self {
+        Span { inner: tracing::info_span!("test_case", index, verdict = Empty) }
+    }
+
+    /// Create a span around the test case at the given index of a judge task.
+    #[cfg(not(feature = "tracing"))]
+    pub(super) fn test_case(_index: usize) -> Self {
+        Span { }
+    }
+
+    /// Run the given function inside this span.
+    pub(super) fn in_scope<F, R>(&self, f: F) -> R
+        where F: FnOnce() -> R {
+        #[cfg(feature = "tracing")]
+        let _entered = self.inner.enter();
+        f()
+    }
+
+    /// Record whether the compilation succeeded in this span.
+    #[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+    pub(super) fn record_succeeded(&self, succeeded: bool) {
+        #[cfg(feature = "tracing")]
+        self.inner.record("succeeded", &succeeded);
+    }
+
+    /// Record the given verdict in this span.
+    #[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+    pub(super) fn record_verdict(&self, verdict: Verdict) {
+        #[cfg(feature = "tracing")]
+        self.inner.record("verdict", &tracing::field::debug(verdict));
+    }
+}
+
+#[cfg(all(test, feature = "tracing"))]
+mod tests {
+    use super::*;
+
+    use std::fmt::Debug;
+    use std::sync::{Arc, Mutex};
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    use tracing::{Event, Metadata, Subscriber};
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+
+    use crate::Program;
+    use crate::languages::{LanguageIdentifier, LanguageBranch};
+
+    /// A span recorded by `RecordingSubscriber`, given by its name and its recorded fields.
+    type RecordedSpan = (&'static str, Vec<(String, String)>);
+
+    /// A `Subscriber` recording the spans created and the fields recorded in them.
+    struct RecordingSubscriber {
+        next_id: AtomicU64,
+        spans: Arc<Mutex<Vec<RecordedSpan>>>,
+    }
+
+    impl RecordingSubscriber {
+        fn new(spans: Arc<Mutex<Vec<RecordedSpan>>>) -> Self {
+            RecordingSubscriber { next_id: AtomicU64::new(1), spans }
+        }
+    }
+
+    /// A `Visit` implementation collecting the visited fields.
+    struct FieldCollector<'a>(&'a mut Vec<(String, String)>);
+
+    impl<'a> Visit for FieldCollector<'a> {
+        fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+            self.0.push((String::from(field.name()), format!("{:?}", value)));
+        }
+    }
+
+    impl Subscriber for RecordingSubscriber {
+        fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, span: &Attributes<'_>) -> Id {
+            let mut fields = Vec::new();
+            span.record(&mut FieldCollector(&mut fields));
+            self.spans.lock().unwrap().push((span.metadata().name(), fields));
+            Id::from_u64(self.next_id.fetch_add(1, Ordering::SeqCst))
+        }
+
+        fn record(&self, span: &Id, values: &Record<'_>) {
+            let mut spans = self.spans.lock().unwrap();
+            let index = span.into_u64() as usize - 1;
+            values.record(&mut FieldCollector(&mut spans[index].1));
+        }
+
+        fn record_follows_from(&self, _span: &Id, _follows: &Id) { }
+
+        fn event(&self, _event: &Event<'_>) { }
+
+        fn enter(&self, _span: &Id) { }
+
+        fn exit(&self, _span: &Id) { }
+    }
+
+    fn field<'a>(span: &'a RecordedSpan, name: &str) -> Option<&'a str> {
+        span.1.iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    #[test]
+    fn judge_span_emitted() {
+        let spans = Arc::new(Mutex::new(Vec::new()));
+        let subscriber = RecordingSubscriber::new(spans.clone());
+
+        let language = LanguageIdentifier::new("cpp", LanguageBranch::new("gnu", "17"));
+        let mut task = JudgeTaskDescriptor::new(Program::new("main", language));
+        task.submission_id = Some(String::from("5e1f"));
+        task.problem_id = Some(String::from("5e20"));
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span = Span::judge(&task);
+            span.in_scope(|| {
+                let tc_span = Span::test_case(0);
+                tc_span.record_verdict(Verdict::WrongAnswer);
+            });
+            span.record_verdict(Verdict::WrongAnswer);
+        });
+
+        let spans = spans.lock().unwrap();
+        assert_eq!(2, spans.len());
+        assert_eq!("judge", spans[0].0);
+        assert_eq!(Some("5e1f"), field(&spans[0], "submission_id"));
+        assert_eq!(Some("5e20"), field(&spans[0], "problem_id"));
+        assert_eq!(Some("WrongAnswer"), field(&spans[0], "verdict"));
+        assert_eq!("test_case", spans[1].0);
+        assert_eq!(Some("0"), field(&spans[1], "index"));
+        assert_eq!(Some("WrongAnswer"), field(&spans[1], "verdict"));
+    }
+}
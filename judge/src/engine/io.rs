@@ -1,9 +1,11 @@
 //! This module defines IO related facilities used in the judge engine, such as pipes.
 //!
 
+use std::collections::VecDeque;
 use std::fs::File;
-use std::io::Read;
+use std::io::{Read, Write, Seek, SeekFrom};
 use std::path::Path;
+use std::thread::JoinHandle;
 
 use std::os::unix::io::{FromRawFd, AsRawFd};
 
@@ -19,6 +21,42 @@ pub fn pipe() -> Result<(File, File)> {
     ))
 }
 
+/// Read all contents from a file on a separate thread, keeping at most a given number of leading
+/// bytes of the contents.
+///
+/// The pipes connected to the standard streams of child processes should be drained while the
+/// child processes are running: a child process writing more than the pipe buffer (64 KiB on
+/// Linux) blocks until the contents are read, and thus never exits if the judge reads the pipe
+/// only after the child process exits.
+pub struct BackgroundReader {
+    /// Handle to the reading thread.
+    handle: JoinHandle<std::io::Result<Vec<u8>>>,
+}
+
+impl BackgroundReader {
+    /// Start reading the given file on a new thread until the end of the file is reached. For a
+    /// pipe, this is when all write ends of the pipe are closed. Only the first `max_len` bytes
+    /// are kept; the remaining contents are read and discarded.
+    pub fn start(file: File, max_len: usize) -> Self {
+        let handle = std::thread::spawn(move || {
+            let mut contents = Vec::new();
+            let mut file = file.take(max_len as u64);
+            file.read_to_end(&mut contents)?;
+            std::io::copy(&mut file.into_inner(), &mut std::io::sink())?;
+            Ok(contents)
+        });
+
+        BackgroundReader { handle }
+    }
+
+    /// Wait for the reading thread to reach the end of the file and get the contents kept. Invalid
+    /// UTF-8 sequences in the contents are replaced with `U+FFFD REPLACEMENT CHARACTER`.
+    pub fn finish(self) -> std::io::Result<String> {
+        let contents = self.handle.join().expect("background reader thread panicked")?;
+        Ok(String::from_utf8_lossy(&contents).into_owned())
+    }
+}
+
 /// Provide a `read_token` method on `Read` taits where tokens are separated by blank characters.
 pub trait TokenizedRead {
     /// Read next token from the underlying device. Tokens are separated by blank characters.
@@ -37,7 +75,38 @@ pub struct TokenizedReader<R: Read> {
     buffer_size: usize,
 
     /// The read head of this reader into the buffer.
-    ptr: usize
+    ptr: usize,
+
+    /// Lines starting with this prefix are skipped entirely, if any.
+    ignore_line_prefix: Option<Vec<u8>>,
+
+    /// Bytes that have been read ahead from the buffer and should be returned before reading more.
+    pending: VecDeque<u8>,
+
+    /// Is the next byte to be read at the start of a line?
+    at_line_start: bool,
+
+    /// Whether `\r` bytes are stripped from the underlying data, so that CRLF line endings are read
+    /// as LF line endings.
+    normalize_line_endings: bool,
+
+    /// The token read ahead by `peek_token` together with the line it starts on, if any.
+    peeked: Option<(String, usize)>,
+
+    /// The number of tokens read so far, excluding the peeked token.
+    tokens_read: usize,
+
+    /// The number of line terminators consumed so far, including those of skipped lines.
+    lines_read: usize,
+
+    /// The 1-based line number on which the last token read starts.
+    last_token_line: Option<usize>,
+
+    /// The maximal length of the data view recorded by this reader.
+    view_len: usize,
+
+    /// The leading bytes of the underlying data, recorded as they are read into the buffer.
+    view: Vec<u8>,
 }
 
 impl<R: Read> TokenizedReader<R> {
@@ -49,22 +118,215 @@ impl<R: Read> TokenizedReader<R> {
             inner,
             buffer: vec![0; TokenizedReader::<R>::BUFFER_SIZE],
             buffer_size: 0,
-            ptr: 0
+            ptr: 0,
+            ignore_line_prefix: None,
+            pending: VecDeque::new(),
+            at_line_start: true,
+            normalize_line_endings: true,
+            peeked: None,
+            tokens_read: 0,
+            lines_read: 0,
+            last_token_line: None,
+            view_len: 0,
+            view: Vec::new(),
         }
     }
 
+    /// Get a reference to the underlying reader. Reading from it directly desynchronizes this
+    /// reader, so it should be used for positional reads only.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Record the first `len` bytes of the underlying data as a data view while reading, so that
+    /// the view and the tokens are produced by a single pass over the data. This is disabled by
+    /// default and must be set before any read.
+    pub fn set_view_len(&mut self, len: usize) {
+        self.view_len = len;
+    }
+
+    /// Take the data view recorded by this reader. The view is completed from the underlying
+    /// reader if the reads so far stopped early, e.g. when a checker rejects the first token.
+    /// Invalid UTF-8 sequences are replaced, as `read_file_view` does.
+    pub fn take_view(&mut self) -> std::io::Result<String> {
+        let mut buffer = vec![0u8; TokenizedReader::<R>::BUFFER_SIZE];
+        while self.view.len() < self.view_len {
+            let max_len = std::cmp::min(buffer.len(), self.view_len - self.view.len());
+            let bytes_read = self.inner.read(&mut buffer[..max_len])?;
+            if bytes_read == 0 {
+                break;
+            }
+            self.view.extend_from_slice(&buffer[..bytes_read]);
+        }
+
+        let view = String::from_utf8_lossy(&self.view).into_owned();
+        self.view.clear();
+        Ok(view)
+    }
+
+    /// Set whether `\r` bytes are stripped from the underlying data. This is enabled by default.
+    /// If disabled, `\r` bytes are ordinary bytes that are neither blank characters separating
+    /// tokens nor part of line terminators.
+    pub fn set_normalize_line_endings(&mut self, normalize: bool) {
+        self.normalize_line_endings = normalize;
+    }
+
+    /// Skip all lines starting with the given prefix. Passing `None` or an empty prefix disables
+    /// line skipping.
+    pub fn set_ignore_line_prefix<T>(&mut self, prefix: Option<T>)
+        where T: Into<Vec<u8>> {
+        self.ignore_line_prefix = prefix.map(|p| p.into()).filter(|p| !p.is_empty());
+    }
+
+    /// Get the next token without consuming it. The next call to `read_token` returns the same
+    /// token.
+    ///
+    /// This function returns `Ok(None)` if there are no more tokens.
+    pub fn peek_token(&mut self) -> std::io::Result<Option<&str>> {
+        if self.peeked.is_none() {
+            self.peeked = self.read_next_token()?;
+        }
+
+        Ok(self.peeked.as_ref().map(|(token, _)| token.as_str()))
+    }
+
+    /// Get the number of tokens read so far. Tokens that are only peeked are not counted.
+    pub fn token_count_read(&self) -> usize {
+        self.tokens_read
+    }
+
+    /// Get the 1-based line number on which the last token read starts, or `None` if no token has
+    /// been read yet. Skipped lines are counted as well, so the line number refers to the
+    /// underlying data.
+    pub fn line_of_last_token(&self) -> Option<usize> {
+        self.last_token_line
+    }
+
+    /// Read the next line from the underlying reader, skipping any lines starting with the ignored
+    /// line prefix. The line terminator `\n` is not included in the returned line.
+    ///
+    /// A token peeked by `peek_token` is discarded by this function, so reading lines and peeking
+    /// tokens should not be mixed.
+    ///
+    /// This function returns `Ok(None)` if EOF is hit before any byte is read.
+    pub fn read_line(&mut self) -> std::io::Result<Option<String>> {
+        self.peeked = None;
+        let mut buffer = Vec::<u8>::new();
+        let mut eof = true;
+        while let Some(byte) = self.read_byte()? {
+            eof = false;
+            if byte == b'\n' {
+                break;
+            }
+            buffer.push(byte);
+        }
+
+        if eof {
+            return Ok(None);
+        }
+
+        let line = String::from_utf8(buffer)
+            .map_err(|_| std::io::Error::from(std::io::ErrorKind::InvalidData))
+            ?;
+        Ok(Some(line))
+    }
+
     /// Read next block of bytes into the internal buffer.
     fn read_block(&mut self) -> std::io::Result<()> {
         self.buffer_size = self.inner.read(self.buffer.as_mut())?;
         self.ptr = 0;
+
+        if self.view.len() < self.view_len {
+            let len = std::cmp::min(self.buffer_size, self.view_len - self.view.len());
+            self.view.extend_from_slice(&self.buffer[..len]);
+        }
+
         Ok(())
     }
 
-    /// Read a single byte from the underlying reader.
+    /// Read a single byte from the underlying reader, skipping any lines starting with the ignored
+    /// line prefix.
     ///
     /// This function returns `Ok(Some(..))` if one byte is successfully read, returns `Ok(None)` if
     /// EOF is hit, returns `Err(..)` on IO errors.
     fn read_byte(&mut self) -> std::io::Result<Option<u8>> {
+        loop {
+            if let Some(byte) = self.pending.pop_front() {
+                self.at_line_start = byte == b'\n';
+                if self.at_line_start {
+                    self.lines_read += 1;
+                }
+                return Ok(Some(byte));
+            }
+
+            let prefix_len = match self.ignore_line_prefix {
+                Some(ref prefix) if self.at_line_start => prefix.len(),
+                _ => {
+                    let byte = self.read_data_byte()?;
+                    if let Some(b) = byte {
+                        self.at_line_start = b == b'\n';
+                        if self.at_line_start {
+                            self.lines_read += 1;
+                        }
+                    }
+                    return Ok(byte);
+                }
+            };
+
+            // We're at the start of a line. Read ahead to check whether the line starts with the
+            // ignored prefix.
+            while self.pending.len() < prefix_len {
+                match self.read_data_byte()? {
+                    Some(b) => {
+                        self.pending.push_back(b);
+                        if b == b'\n' {
+                            break;
+                        }
+                    },
+                    None => break
+                };
+            }
+
+            let prefix = self.ignore_line_prefix.as_ref().unwrap();
+            if self.pending.iter().eq(prefix.iter()) {
+                // Skip the rest of this line, including the line terminator.
+                self.pending.clear();
+                while let Some(b) = self.read_data_byte()? {
+                    if b == b'\n' {
+                        self.lines_read += 1;
+                        break;
+                    }
+                }
+            } else {
+                self.at_line_start = false;
+                if self.pending.is_empty() {
+                    return Ok(None);
+                }
+            }
+        }
+    }
+
+    /// Read a single byte from the underlying reader, stripping `\r` bytes if line endings are
+    /// normalized.
+    ///
+    /// This function returns `Ok(Some(..))` if one byte is successfully read, returns `Ok(None)` if
+    /// EOF is hit, returns `Err(..)` on IO errors.
+    fn read_data_byte(&mut self) -> std::io::Result<Option<u8>> {
+        loop {
+            match self.read_raw_byte()? {
+                Some(b'\r') if self.normalize_line_endings => continue,
+                byte => return Ok(byte)
+            };
+        }
+    }
+
+    /// Read a single byte from the underlying reader without any filtering. Neither line endings
+    /// are normalized nor lines with the ignored prefix are skipped, so raw reads should not be
+    /// mixed with the other reads.
+    ///
+    /// This function returns `Ok(Some(..))` if one byte is successfully read, returns `Ok(None)` if
+    /// EOF is hit, returns `Err(..)` on IO errors.
+    pub fn read_raw_byte(&mut self) -> std::io::Result<Option<u8>> {
         if self.ptr >= self.buffer_size {
             self.read_block()?;
             if self.ptr >= self.buffer_size {
@@ -76,11 +338,11 @@ impl<R: Read> TokenizedReader<R> {
         self.ptr += 1;
         Ok(Some(byte))
     }
-}
 
-impl<R: Read> TokenizedRead for TokenizedReader<R> {
-    fn read_token(&mut self) -> std::io::Result<Option<String>> {
-        static SEPERATE_BYTES: &'static [u8] = &[b' ', b'\r', b'\n', b'\t'];
+    /// Read the next token from the underlying reader, together with the 1-based line number on
+    /// which the token starts. The peeked token, if any, is not considered.
+    fn read_next_token(&mut self) -> std::io::Result<Option<(String, usize)>> {
+        static SEPERATE_BYTES: &'static [u8] = &[b' ', b'\n', b'\t'];
 
         // Skip any leading whitespace characters.
         let mut byte = SEPERATE_BYTES[0];
@@ -92,6 +354,7 @@ impl<R: Read> TokenizedRead for TokenizedReader<R> {
         }
 
         // First non-whitespace character has been hit and stored in `byte`.
+        let line = self.lines_read + 1;
         let mut buffer = Vec::<u8>::new();
         while !SEPERATE_BYTES.contains(&byte) {
             buffer.push(byte);
@@ -104,7 +367,22 @@ impl<R: Read> TokenizedRead for TokenizedReader<R> {
         let token = String::from_utf8(buffer)
             .map_err(|_| std::io::Error::from(std::io::ErrorKind::InvalidData))
             ?;
-        Ok(Some(token))
+        Ok(Some((token, line)))
+    }
+}
+
+impl<R: Read> TokenizedRead for TokenizedReader<R> {
+    fn read_token(&mut self) -> std::io::Result<Option<String>> {
+        let next = match self.peeked.take() {
+            Some(peeked) => Some(peeked),
+            None => self.read_next_token()?
+        };
+
+        Ok(next.map(|(token, line)| {
+            self.tokens_read += 1;
+            self.last_token_line = Some(line);
+            token
+        }))
     }
 }
 
@@ -146,6 +424,23 @@ pub fn read_file_view<P>(path: &P, max_len: usize) -> std::io::Result<String>
     Ok(view)
 }
 
+/// Write the whole content of the given file to the given writer, framed by a header line
+/// containing the length of the content in bytes. The file is read from its start regardless of
+/// its current position. This is the framing format used by `CheckerIoMode::StdinDelimited`.
+pub fn write_length_prefixed<W>(writer: &mut W, file: &mut File) -> std::io::Result<()>
+    where W: ?Sized + Write {
+    let len = file.metadata()?.len();
+    file.seek(SeekFrom::Start(0))?;
+
+    writeln!(writer, "{}", len)?;
+    let copied = std::io::copy(&mut file.take(len), writer)?;
+    if copied != len {
+        return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof));
+    }
+
+    Ok(())
+}
+
 /// Provide extension functions to `File`.
 pub trait FileExt {
     /// Duplicate a `File` instance by duplicating its underlying file descriptor using the `dup`
@@ -163,3 +458,185 @@ impl FileExt for File {
         Ok(unsafe { File::from_raw_fd(dup_fd) })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    mod tokenized_reader {
+        use super::*;
+
+        fn read_all_tokens<R: Read>(reader: &mut TokenizedReader<R>) -> Vec<String> {
+            let mut tokens = Vec::new();
+            while let Some(token) = reader.read_token().unwrap() {
+                tokens.push(token);
+            }
+            tokens
+        }
+
+        #[test]
+        fn read_tokens() {
+            let mut reader = TokenizedReader::new("1 2\r\n\t3\n".as_bytes());
+            assert_eq!(vec!["1", "2", "3"], read_all_tokens(&mut reader));
+        }
+
+        #[test]
+        fn peek_token() {
+            let mut reader = TokenizedReader::new("1 2\n3".as_bytes());
+            assert_eq!(Some("1"), reader.peek_token().unwrap());
+            assert_eq!(Some("1"), reader.peek_token().unwrap());
+            assert_eq!(0, reader.token_count_read());
+            assert_eq!(vec!["1", "2", "3"], read_all_tokens(&mut reader));
+            assert_eq!(None, reader.peek_token().unwrap());
+        }
+
+        #[test]
+        fn token_count_and_line() {
+            let mut reader = TokenizedReader::new("# header\n1 2\n\n  3\n#4\n5".as_bytes());
+            reader.set_ignore_line_prefix(Some("#"));
+            assert_eq!(None, reader.line_of_last_token());
+
+            let mut lines = Vec::new();
+            while reader.read_token().unwrap().is_some() {
+                lines.push(reader.line_of_last_token().unwrap());
+            }
+            assert_eq!(vec![2, 2, 4, 6], lines);
+            assert_eq!(4, reader.token_count_read());
+        }
+
+        #[test]
+        fn view_recorded() {
+            let mut reader = TokenizedReader::new("1 2\n3\n".as_bytes());
+            reader.set_view_len(4);
+            assert_eq!(vec!["1", "2", "3"], read_all_tokens(&mut reader));
+            assert_eq!("1 2\n", reader.take_view().unwrap());
+
+            let mut reader = TokenizedReader::new("1 2\n".as_bytes());
+            assert_eq!(Some(String::from("1")), reader.read_token().unwrap());
+            assert_eq!("", reader.take_view().unwrap());
+        }
+
+        #[test]
+        fn view_completed_after_early_stop() {
+            let data = "1 ".repeat(TokenizedReader::<&[u8]>::BUFFER_SIZE);
+            let mut reader = TokenizedReader::new(data.as_bytes());
+            reader.set_view_len(data.len() - 1);
+            assert_eq!(Some(String::from("1")), reader.read_token().unwrap());
+            assert_eq!(&data[..data.len() - 1], reader.take_view().unwrap());
+        }
+
+        #[test]
+        fn strict_line_endings() {
+            let mut reader = TokenizedReader::new("1 2\r\n\t3\n".as_bytes());
+            reader.set_normalize_line_endings(false);
+            assert_eq!(vec!["1", "2\r", "3"], read_all_tokens(&mut reader));
+
+            let mut reader = TokenizedReader::new("1 2\r\n".as_bytes());
+            reader.set_normalize_line_endings(false);
+            assert_eq!(Some(String::from("1 2\r")), reader.read_line().unwrap());
+        }
+
+        #[test]
+        fn ignore_line_prefix() {
+            let mut reader = TokenizedReader::new("# header\n1 2\n#debug 5\n3 #4\n#".as_bytes());
+            reader.set_ignore_line_prefix(Some("#"));
+            assert_eq!(vec!["1", "2", "3", "#4"], read_all_tokens(&mut reader));
+        }
+
+        #[test]
+        fn ignore_line_prefix_partial_match() {
+            let mut reader = TokenizedReader::new("//x\n/ 1\n/\n2".as_bytes());
+            reader.set_ignore_line_prefix(Some("//"));
+            assert_eq!(vec!["/", "1", "/", "2"], read_all_tokens(&mut reader));
+        }
+
+        #[test]
+        fn read_lines() {
+            let mut reader = TokenizedReader::new("1 2\r\n# 3\n\n4".as_bytes());
+            reader.set_ignore_line_prefix(Some("#"));
+
+            let mut lines = Vec::new();
+            while let Some(line) = reader.read_line().unwrap() {
+                lines.push(line);
+            }
+            assert_eq!(vec!["1 2", "", "4"], lines);
+        }
+    }
+
+    mod background_reader {
+        use super::*;
+
+        #[test]
+        fn pipe_drained() {
+            let (read, mut write) = pipe().unwrap();
+            let reader = BackgroundReader::start(read, 1 << 20);
+
+            // Writing more than the pipe buffer would block forever without the reader.
+            let contents = "x".repeat(1 << 20);
+            write.write_all(contents.as_bytes()).unwrap();
+            drop(write);
+            assert_eq!(contents, reader.finish().unwrap());
+        }
+
+        #[test]
+        fn contents_truncated() {
+            let (read, mut write) = pipe().unwrap();
+            let reader = BackgroundReader::start(read, 4);
+
+            write.write_all("x".repeat(1 << 20).as_bytes()).unwrap();
+            drop(write);
+            assert_eq!("xxxx", reader.finish().unwrap());
+        }
+
+        #[test]
+        fn invalid_utf8_replaced() {
+            let (read, mut write) = pipe().unwrap();
+            let reader = BackgroundReader::start(read, 1024);
+
+            write.write_all(b"a\xffb").unwrap();
+            drop(write);
+            assert_eq!("a\u{fffd}b", reader.finish().unwrap());
+        }
+    }
+
+    mod length_prefixed {
+        use super::*;
+
+        use std::io::BufRead;
+
+        use tempfile::tempfile;
+
+        /// A reference parser of the framing format, as a checker author would write it.
+        fn read_frame<R: BufRead>(reader: &mut R) -> Vec<u8> {
+            let mut header = String::new();
+            reader.read_line(&mut header).unwrap();
+            let len: u64 = header.trim_end().parse().unwrap();
+
+            let mut content = Vec::new();
+            reader.take(len).read_to_end(&mut content).unwrap();
+            assert_eq!(len as usize, content.len());
+            content
+        }
+
+        fn make_file(content: &[u8]) -> File {
+            let mut file = tempfile().unwrap();
+            file.write_all(content).unwrap();
+            file
+        }
+
+        #[test]
+        fn round_trip() {
+            let streams: [&[u8]; 3] = [b"3\n1 2 3\n", b"", b"6\n\n12\n\x00"];
+            let mut framed = Vec::new();
+            for content in streams.iter() {
+                write_length_prefixed(&mut framed, &mut make_file(content)).unwrap();
+            }
+
+            let mut reader = std::io::BufReader::new(&framed[..]);
+            for content in streams.iter() {
+                assert_eq!(*content, &read_frame(&mut reader)[..]);
+            }
+            assert!(reader.fill_buf().unwrap().is_empty());
+        }
+    }
+}
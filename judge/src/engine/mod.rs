@@ -5,19 +5,25 @@
 //!
 
 mod checkers;
+mod instrument;
+mod subtasks;
 mod io;
 
+use std::borrow::Cow;
 use std::fs::File;
-use std::io::{Read, Seek, SeekFrom};
-use std::path::PathBuf;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Component, Path, PathBuf};
 use std::sync::Arc;
-use std::os::unix::io::AsRawFd;
-use std::time::Duration;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::time::{Duration, Instant};
 
 #[cfg(feature = "serde")]
 use serde::{Serialize, Deserialize};
 
+use nix::errno::Errno;
 use sandbox::{
+    IoPriority,
+    MemoryLimitPolicy,
     MemorySize,
     UserId,
     SystemCall,
@@ -37,9 +43,13 @@ use super::{
     JudgeTaskDescriptor,
     JudgeMode,
     BuiltinCheckers,
+    CheckerIoMode,
+    ResourceLimits,
     TestCaseDescriptor,
     JudgeResult,
+    JudgeProgress,
     TestCaseResult,
+    FailureReason,
     Verdict
 };
 use super::languages::{
@@ -49,40 +59,163 @@ use super::languages::{
     ExecutionInfo,
     CompilationInfo,
 };
-use checkers::{Checker, CheckerContext};
-use io::{
-    FileExt,
-    TokenizedReader,
-};
+use instrument::Span;
+use subtasks::SubtaskTracker;
+use io::{BackgroundReader, FileExt};
+
+pub use checkers::{Checker, CheckerContext, CheckerResult, CheckerRegistry};
+pub use io::{TokenizedRead, TokenizedReader};
 
-/// Configuration for a judge engine instance.
+/// Configuration for a judge engine instance. `new` gives the default configuration; other
+/// configurations are built by `JudgeEngineConfigBuilder`, which validates the values. Deserialized
+/// configurations are validated in the same way.
 #[derive(Debug)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+// The derived implementations become inherent functions, which are wrapped by the trait
+// implementations below so that deserialized configurations are validated.
+#[cfg_attr(feature = "serde", serde(remote = "Self"))]
 pub struct JudgeEngineConfig {
     /// The effective user ID of the judgee, answer checker and interactor.
-    pub judge_uid: Option<UserId>,
+    judge_uid: Option<UserId>,
 
     /// The directory inside which the judge task will be executed. Every judge task will create a
     /// temporary directory inside this directory and thus every judge task is independent from
     /// each other in the file system's perspective.
-    pub judge_dir: Option<PathBuf>,
+    judge_dir: Option<PathBuf>,
 
     /// System call whitelist for the judgee process.
-    pub judgee_syscall_whitelist: Vec<SystemCall>,
+    judgee_syscall_whitelist: Vec<SystemCall>,
+
+    /// Limit on the total size of the files the judgee can write into the judge directory.
+    judgee_disk_quota: Option<MemorySize>,
+
+    /// Limit on the number of system calls the judgee can invoke. Judgees with a system call budget
+    /// are traced while running, which slows down system call heavy judgees considerably.
+    judgee_syscall_budget: Option<u64>,
+
+    /// Limit on the number of files the judgee can open. Defaults to
+    /// `DEFAULT_JUDGEE_OPEN_FILES_LIMIT`, which is enough for the runtimes of the supported
+    /// languages while stopping the judgee from exhausting the file descriptors of the system.
+    judgee_open_files_limit: Option<u32>,
+
+    /// How the memory limit of the judgee is enforced. Defaults to `MemoryLimitPolicy::Daemon`.
+    /// `MemoryLimitPolicy::Native` additionally lets the kernel bound the address space of the
+    /// judgee, which stops judgees from allocating memory unboundedly between two samplings of the
+    /// daemon thread. The memory limit enforced by the daemon thread stays authoritative.
+    judgee_memory_limit_policy: MemoryLimitPolicy,
+
+    /// The nice value of the judgee, or `None` to inherit the nice value of the judge. Running the
+    /// judgee at a lower priority than the judge keeps the daemon threads measuring the judgee
+    /// responsive on busy judge nodes, which makes the measurements fairer.
+    judgee_nice: Option<i32>,
+
+    /// The I/O priority of the judgee, or `None` to inherit the I/O priority of the judge.
+    judgee_io_priority: Option<IoPriority>,
+
+    /// Environment variables set for both the judgee and the jury. By default this contains
+    /// `LC_ALL=C` so that numbers are parsed and printed in the same way regardless of the system
+    /// locale of the judge node. Environment variables passed through `jury_env` of the judge task
+    /// take precedence over these for the jury.
+    judge_env: Vec<(String, String)>,
+
+    /// Whether the contents written by the judgee to its stderr are captured into the `error_view`
+    /// of test case results. Disable this to discard the judgee's stderr for performance.
+    capture_judgee_stderr: bool,
+
+    /// Directory into which the outputs of judgees failing on test cases (e.g. killed on exceeding
+    /// the time limit or crashed) are preserved, so that problem setters can inspect the output
+    /// produced before the failure. Every judge task preserves its outputs into a subdirectory of
+    /// its own, and only the most recent `MAX_PRESERVED_OUTPUT_TASK_DIRS` subdirectories are kept.
+    /// At most `MAX_PRESERVED_OUTPUT_LEN` bytes are preserved per test case and
+    /// `MAX_PRESERVED_OUTPUT_TOTAL_LEN` bytes per judge task. Only judgees in standard and special
+    /// judge modes are covered. `None` (the default) disables preserving outputs.
+    preserved_output_dir: Option<PathBuf>,
 
     /// CPU time limit of answer checkers and interactors.
-    pub jury_cpu_time_limit: Option<Duration>,
+    jury_cpu_time_limit: Option<Duration>,
 
     /// Real time limit of checkers and interactors.
-    pub jury_real_time_limit: Option<Duration>,
+    jury_real_time_limit: Option<Duration>,
 
     /// Memory limit of answer checkers and interactors.
-    pub jury_memory_limit: Option<MemorySize>,
+    jury_memory_limit: Option<MemorySize>,
 
     /// System call whitelist of answer checkers and interactors.
-    pub jury_syscall_whitelist: Vec<SystemCall>,
+    jury_syscall_whitelist: Vec<SystemCall>,
+
+    /// If `true`, answer checkers and interactors are trusted and run without a seccomp filter,
+    /// ignoring `jury_syscall_whitelist` and the whitelist provided by their language providers.
+    /// Resource limits and the effective user ID still apply to them.
+    ///
+    /// This saves the effort of maintaining a jury whitelist for every language, and allows jury
+    /// programs that depend on unusual system calls (e.g. testlib based interactors spawning
+    /// threads). On the other hand, a malicious or buggy jury program gains the full system call
+    /// surface of the judge user, so enable this only if every problem is uploaded by trusted
+    /// problem setters.
+    trust_jury: bool,
+
+    /// If `true`, the judgee and the jury fail to start if they have system call whitelists but
+    /// seccomp is not available on this system. If `false` (the default), they run without system
+    /// call filtering in that case and a warning is logged. See
+    /// `sandbox::ProcessBuilder::require_seccomp`.
+    require_seccomp: bool,
+
+    /// If `true` (the default), the answer checker of special judge mode runs whenever the judgee
+    /// exits normally, including when the judgee writes nothing to its stdout, and decides the
+    /// verdict on its own. Some checkers accept empty output, e.g. when no solution exists. If
+    /// `false`, empty output gets the `WrongAnswer` verdict without running the checker.
+    run_checker_on_empty_output: bool,
+
+    /// If `true`, the command line of the compiler is recorded into the compilation results of
+    /// successful compilations as well. This is useful when debugging language providers. The
+    /// command line is always recorded for failed compilations.
+    record_compile_command: bool,
+
+    /// Number of times a compilation is retried after a transient infrastructure error, i.e. a
+    /// system call failing with `EAGAIN`, `EINTR`, `ENOMEM` or `EMFILE`. Retries are delayed by an
+    /// exponential backoff. Other failures, including compilation errors, are never retried.
+    /// Defaults to 0, i.e. no retries.
+    compile_retries: u32,
+
+    /// Maximum size of the source code of judgees accepted by `compile_source`. Larger source code
+    /// is rejected before it is written to the disk, which stops huge source code from exhausting
+    /// the disk or the compiler. Jury programs are trusted and not limited. Defaults to
+    /// `DEFAULT_MAX_SOURCE_SIZE`; `None` means that the size of the source code is not limited.
+    max_source_size: Option<MemorySize>,
+
+    /// Maximum number of test cases in the test suite of a single judge task. `None` means that
+    /// the number of test cases is not limited.
+    max_test_cases: Option<usize>,
+
+    /// If `true`, judge tasks whose test suite exceeds `max_test_cases` are judged on the first
+    /// `max_test_cases` test cases only and a warning is logged; otherwise these tasks are rejected
+    /// with a `TooManyTestCases` error before any test case is executed.
+    truncate_test_suite: bool,
+
+    /// Wall-clock time budget of a single judge task. The budget is checked between test cases;
+    /// once it is exhausted, the remaining test cases are skipped and the judge result is marked
+    /// with `Verdict::JudgeTimeLimitExceeded`. `None` means that the budget is not limited.
+    total_judge_time_limit: Option<Duration>,
 }
 
+/// The default limit on the number of files the judgee can open.
+pub const DEFAULT_JUDGEE_OPEN_FILES_LIMIT: u32 = 256;
+
+/// The default maximum size of the source code accepted by `JudgeEngine::compile_source`.
+pub const DEFAULT_MAX_SOURCE_SIZE: MemorySize = MemorySize::KiloBytes(256);
+
+/// Maximum number of bytes kept from the messages of compilers and the comments of checkers.
+const MAX_MESSAGE_LEN: usize = 64 * 1024;
+
+/// Maximum number of bytes preserved from the output of a judgee on a single test case.
+pub const MAX_PRESERVED_OUTPUT_LEN: u64 = 1024 * 1024;
+
+/// Maximum total number of bytes preserved from the outputs of the judgee in a single judge task.
+pub const MAX_PRESERVED_OUTPUT_TOTAL_LEN: u64 = 16 * 1024 * 1024;
+
+/// Maximum number of judge task subdirectories kept in the preserved output directory.
+pub const MAX_PRESERVED_OUTPUT_TASK_DIRS: usize = 64;
+
 impl JudgeEngineConfig {
     /// Create a new `JudgeEngineConfig` instance.
     pub fn new() -> Self {
@@ -90,11 +223,320 @@ impl JudgeEngineConfig {
             judge_uid: None,
             judge_dir: None,
             judgee_syscall_whitelist: Vec::new(),
+            judgee_disk_quota: None,
+            judgee_syscall_budget: None,
+            judgee_open_files_limit: Some(DEFAULT_JUDGEE_OPEN_FILES_LIMIT),
+            judgee_memory_limit_policy: MemoryLimitPolicy::Daemon,
+            judgee_nice: None,
+            judgee_io_priority: None,
+            judge_env: vec![(String::from("LC_ALL"), String::from("C"))],
+            capture_judgee_stderr: true,
+            preserved_output_dir: None,
             jury_cpu_time_limit: None,
             jury_real_time_limit: None,
             jury_memory_limit: None,
             jury_syscall_whitelist: Vec::new(),
+            trust_jury: false,
+            require_seccomp: false,
+            run_checker_on_empty_output: true,
+            record_compile_command: false,
+            compile_retries: 0,
+            max_source_size: Some(DEFAULT_MAX_SOURCE_SIZE),
+            max_test_cases: None,
+            truncate_test_suite: false,
+            total_judge_time_limit: None,
+        }
+    }
+
+    /// Check the invariants across the configuration values. Fails with an `InvalidEngineConfig`
+    /// error if any of them is violated.
+    fn validate(&self) -> Result<()> {
+        if let Some(ref judge_dir) = self.judge_dir {
+            if !judge_dir.is_dir() {
+                return invalid_engine_config("judge directory does not exist");
+            }
+        }
+
+        if let (Some(cpu_time_limit), Some(real_time_limit)) =
+            (self.jury_cpu_time_limit, self.jury_real_time_limit) {
+            if real_time_limit < cpu_time_limit {
+                return invalid_engine_config(
+                    "jury real time limit is less than jury CPU time limit");
+            }
+        }
+
+        if self.trust_jury && !self.jury_syscall_whitelist.is_empty() {
+            // Existing configurations may set both, so this is not rejected.
+            log::warn!("Jury system call whitelist is ignored since the jury is trusted.");
+        }
+
+        if self.truncate_test_suite && self.max_test_cases.is_none() {
+            return invalid_engine_config(
+                "test suites cannot be truncated without a maximum number of test cases");
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Serialize for JudgeEngineConfig {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+        where S: serde::Serializer {
+        JudgeEngineConfig::serialize(self, serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> Deserialize<'de> for JudgeEngineConfig {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+        where D: serde::Deserializer<'de> {
+        let config = JudgeEngineConfig::deserialize(deserializer)?;
+        config.validate().map_err(serde::de::Error::custom)?;
+        Ok(config)
+    }
+}
+
+/// Create an `InvalidEngineConfig` error with the given message.
+fn invalid_engine_config<T>(message: &str) -> Result<T> {
+    Err(Error::from(ErrorKind::InvalidEngineConfig(String::from(message))))
+}
+
+/// Provide a builder for `JudgeEngineConfig` values. Setters of values that cannot be valid on
+/// their own (e.g. zero limits) fail immediately, and invariants across multiple values are checked
+/// by `build`.
+#[derive(Debug)]
+pub struct JudgeEngineConfigBuilder {
+    /// The configuration being built.
+    config: JudgeEngineConfig,
+}
+
+impl JudgeEngineConfigBuilder {
+    /// Create a new `JudgeEngineConfigBuilder` instance, starting from the default configuration
+    /// given by `JudgeEngineConfig::new`.
+    pub fn new() -> Self {
+        JudgeEngineConfigBuilder {
+            config: JudgeEngineConfig::new(),
+        }
+    }
+
+    /// Set the effective user ID of the judgee, answer checker and interactor.
+    pub fn judge_uid(&mut self, uid: UserId) -> &mut Self {
+        self.config.judge_uid = Some(uid);
+        self
+    }
+
+    /// Set the directory inside which judge tasks are executed. The directory should exist when
+    /// `build` is called.
+    pub fn judge_dir<P>(&mut self, dir: P) -> &mut Self
+        where P: Into<PathBuf> {
+        self.config.judge_dir = Some(dir.into());
+        self
+    }
+
+    /// Set the system call whitelist for the judgee process.
+    pub fn judgee_syscall_whitelist(&mut self, whitelist: Vec<SystemCall>) -> &mut Self {
+        self.config.judgee_syscall_whitelist = whitelist;
+        self
+    }
+
+    /// Set the limit on the total size of the files the judgee can write into the judge
+    /// directory. The quota should not be zero.
+    pub fn judgee_disk_quota(&mut self, quota: MemorySize) -> Result<&mut Self> {
+        if quota.bytes() == 0 {
+            return invalid_engine_config("judgee disk quota is zero");
+        }
+
+        self.config.judgee_disk_quota = Some(quota);
+        Ok(self)
+    }
+
+    /// Set the limit on the number of system calls the judgee can invoke.
+    pub fn judgee_syscall_budget(&mut self, budget: u64) -> &mut Self {
+        self.config.judgee_syscall_budget = Some(budget);
+        self
+    }
+
+    /// Set the limit on the number of files the judgee can open. The limit should be large enough
+    /// for the standard streams.
+    pub fn judgee_open_files_limit(&mut self, limit: u32) -> Result<&mut Self> {
+        if limit <= libc::STDERR_FILENO as u32 {
+            return invalid_engine_config("judgee open files limit is too small");
+        }
+
+        self.config.judgee_open_files_limit = Some(limit);
+        Ok(self)
+    }
+
+    /// Set how the memory limit of the judgee is enforced.
+    pub fn judgee_memory_limit_policy(&mut self, policy: MemoryLimitPolicy) -> &mut Self {
+        self.config.judgee_memory_limit_policy = policy;
+        self
+    }
+
+    /// Set the nice value of the judgee, which should range from -20 to 19.
+    pub fn judgee_nice(&mut self, nice: i32) -> Result<&mut Self> {
+        if nice < -20 || nice > 19 {
+            return invalid_engine_config("judgee nice value is out of range");
+        }
+
+        self.config.judgee_nice = Some(nice);
+        Ok(self)
+    }
+
+    /// Set the I/O priority of the judgee. The level of the best-effort class should range from 0
+    /// to 7.
+    pub fn judgee_io_priority(&mut self, priority: IoPriority) -> Result<&mut Self> {
+        if let IoPriority::BestEffort(level) = priority {
+            if level > 7 {
+                return invalid_engine_config("judgee I/O priority level is out of range");
+            }
+        }
+
+        self.config.judgee_io_priority = Some(priority);
+        Ok(self)
+    }
+
+    /// Set the environment variables set for both the judgee and the jury, replacing the default
+    /// ones. Names should be non-empty and should not contain `=`, and neither names nor values
+    /// should contain NUL characters.
+    pub fn judge_env(&mut self, env: Vec<(String, String)>) -> Result<&mut Self> {
+        for (name, value) in &env {
+            if name.is_empty() || name.contains('=') || name.contains('\0') ||
+                value.contains('\0') {
+                return invalid_engine_config("invalid environment variable");
+            }
+        }
+
+        self.config.judge_env = env;
+        Ok(self)
+    }
+
+    /// Set whether the stderr of the judgee is captured.
+    pub fn capture_judgee_stderr(&mut self, capture: bool) -> &mut Self {
+        self.config.capture_judgee_stderr = capture;
+        self
+    }
+
+    /// Set the directory into which the outputs of judgees failing on test cases are preserved.
+    pub fn preserved_output_dir<T>(&mut self, dir: T) -> &mut Self
+        where T: Into<PathBuf> {
+        self.config.preserved_output_dir = Some(dir.into());
+        self
+    }
+
+    /// Set the CPU time limit of answer checkers and interactors. The limit should not be zero.
+    pub fn jury_cpu_time_limit(&mut self, limit: Duration) -> Result<&mut Self> {
+        if limit == Duration::new(0, 0) {
+            return invalid_engine_config("jury CPU time limit is zero");
+        }
+
+        self.config.jury_cpu_time_limit = Some(limit);
+        Ok(self)
+    }
+
+    /// Set the real time limit of answer checkers and interactors. The limit should not be zero.
+    pub fn jury_real_time_limit(&mut self, limit: Duration) -> Result<&mut Self> {
+        if limit == Duration::new(0, 0) {
+            return invalid_engine_config("jury real time limit is zero");
+        }
+
+        self.config.jury_real_time_limit = Some(limit);
+        Ok(self)
+    }
+
+    /// Set the memory limit of answer checkers and interactors. The limit should not be zero.
+    pub fn jury_memory_limit(&mut self, limit: MemorySize) -> Result<&mut Self> {
+        if limit.bytes() == 0 {
+            return invalid_engine_config("jury memory limit is zero");
+        }
+
+        self.config.jury_memory_limit = Some(limit);
+        Ok(self)
+    }
+
+    /// Set the system call whitelist of answer checkers and interactors.
+    pub fn jury_syscall_whitelist(&mut self, whitelist: Vec<SystemCall>) -> &mut Self {
+        self.config.jury_syscall_whitelist = whitelist;
+        self
+    }
+
+    /// Set whether answer checkers and interactors are trusted. See
+    /// `JudgeEngineConfig::trust_jury` for details.
+    pub fn trust_jury(&mut self, trust: bool) -> &mut Self {
+        self.config.trust_jury = trust;
+        self
+    }
+
+    /// Set whether seccomp is required to run the judgee and the jury. See
+    /// `JudgeEngineConfig::require_seccomp` for details.
+    pub fn require_seccomp(&mut self, require: bool) -> &mut Self {
+        self.config.require_seccomp = require;
+        self
+    }
+
+    /// Set whether the answer checker of special judge mode runs on empty output. See
+    /// `JudgeEngineConfig::run_checker_on_empty_output` for details.
+    pub fn run_checker_on_empty_output(&mut self, run: bool) -> &mut Self {
+        self.config.run_checker_on_empty_output = run;
+        self
+    }
+
+    /// Set whether the command line of the compiler is recorded for successful compilations.
+    pub fn record_compile_command(&mut self, record: bool) -> &mut Self {
+        self.config.record_compile_command = record;
+        self
+    }
+
+    /// Set the number of times a compilation is retried after an infrastructure error.
+    pub fn compile_retries(&mut self, retries: u32) -> &mut Self {
+        self.config.compile_retries = retries;
+        self
+    }
+
+    /// Set the maximum size of the source code accepted by `JudgeEngine::compile_source`, or
+    /// remove the limit if `None` is given. The maximum should not be zero.
+    pub fn max_source_size(&mut self, size: Option<MemorySize>) -> Result<&mut Self> {
+        if size.map(|size| size.bytes() == 0).unwrap_or(false) {
+            return invalid_engine_config("maximum source size is zero");
+        }
+
+        self.config.max_source_size = size;
+        Ok(self)
+    }
+
+    /// Set the maximum number of test cases in the test suite of a single judge task. The maximum
+    /// should not be zero.
+    pub fn max_test_cases(&mut self, max: usize) -> Result<&mut Self> {
+        if max == 0 {
+            return invalid_engine_config("maximum number of test cases is zero");
+        }
+
+        self.config.max_test_cases = Some(max);
+        Ok(self)
+    }
+
+    /// Set whether test suites exceeding the maximum number of test cases are truncated rather
+    /// than rejected. This requires the maximum number of test cases to be set.
+    pub fn truncate_test_suite(&mut self, truncate: bool) -> &mut Self {
+        self.config.truncate_test_suite = truncate;
+        self
+    }
+
+    /// Set the wall-clock time budget of a single judge task. The budget should not be zero.
+    pub fn total_judge_time_limit(&mut self, limit: Duration) -> Result<&mut Self> {
+        if limit == Duration::new(0, 0) {
+            return invalid_engine_config("total judge time limit is zero");
         }
+
+        self.config.total_judge_time_limit = Some(limit);
+        Ok(self)
+    }
+
+    /// Check the invariants across the configuration values and build the `JudgeEngineConfig`.
+    pub fn build(self) -> Result<JudgeEngineConfig> {
+        self.config.validate()?;
+        Ok(self.config)
     }
 }
 
@@ -103,6 +545,22 @@ impl JudgeEngineConfig {
 trait ExecutionInfoExt {
     /// Create a `ProcessBuilder` instance from this value.
     fn build(&self) -> Result<ProcessBuilder>;
+
+    /// Get the command line described by this value, quoted for a POSIX shell. Environment
+    /// variables are not included.
+    fn command_line(&self) -> String;
+}
+
+/// Quote the given word for a POSIX shell. Words consisting of safe characters only are returned
+/// as is.
+fn shell_quote(word: &str) -> Cow<'_, str> {
+    let safe = !word.is_empty() &&
+        word.chars().all(|c| c.is_ascii_alphanumeric() || "-_./=:,+@%".contains(c));
+    if safe {
+        Cow::Borrowed(word)
+    } else {
+        Cow::Owned(format!("'{}'", word.replace('\'', "'\\''")))
+    }
 }
 
 impl ExecutionInfoExt for ExecutionInfo {
@@ -120,12 +578,159 @@ impl ExecutionInfoExt for ExecutionInfo {
 
         Ok(builder)
     }
+
+    fn command_line(&self) -> String {
+        let executable = self.executable.to_string_lossy();
+        std::iter::once(&*executable)
+            .chain(self.args.iter().map(|arg| arg.as_str()))
+            .map(shell_quote)
+            .collect::<Vec<Cow<str>>>()
+            .join(" ")
+    }
 }
 
 impl ExecutionInfoExt for CompilationInfo {
     fn build(&self) -> Result<ProcessBuilder> {
         self.compiler.build()
     }
+
+    fn command_line(&self) -> String {
+        self.compiler.command_line()
+    }
+}
+
+/// Copy the given grader files into the judge directory. Each grader file is given by a pair of the
+/// path to the file and the destination path relative to the judge directory. Destinations that
+/// are absolute or contain `..` are rejected with `ErrorKind::InvalidGraderFile`.
+///
+/// This is called before every test case, so that changes made by the judgee to the grader files
+/// do not affect later test cases. Whatever the judgee left at the destinations, including
+/// symbolic links in place of the files or their parent directories, is removed rather than
+/// written through.
+fn seed_judge_dir(judge_dir: &Path, grader_files: &[(PathBuf, PathBuf)]) -> Result<()> {
+    for (file, dest) in grader_files {
+        let valid = dest.components().count() > 0 &&
+            dest.components().all(|c| match c {
+                Component::Normal(..) => true,
+                _ => false
+            });
+        if !valid {
+            return Err(Error::from(ErrorKind::InvalidGraderFile(dest.clone())));
+        }
+
+        let mut path = judge_dir.to_owned();
+        let mut components = dest.components().peekable();
+        while let Some(component) = components.next() {
+            path.push(component);
+            let is_file = components.peek().is_none();
+            match std::fs::symlink_metadata(&path) {
+                Ok(ref metadata) if metadata.is_dir() && !is_file => continue,
+                Ok(ref metadata) if metadata.is_dir() => std::fs::remove_dir_all(&path)?,
+                Ok(..) => std::fs::remove_file(&path)?,
+                Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => (),
+                Err(e) => return Err(Error::from(e)),
+            };
+            if !is_file {
+                std::fs::create_dir(&path)?;
+            }
+        }
+        std::fs::copy(file, &path)?;
+    }
+
+    Ok(())
+}
+
+/// Create the temporary directory of a judge task under the given judge directory, or under the
+/// system temporary directory if no judge directory is configured. If `subdir` is given, the
+/// temporary directory is created under that subdirectory of the judge directory instead. Names of
+/// subdirectories that are not a single path component are rejected with
+/// `ErrorKind::InvalidJudgeSubdir`.
+fn create_judge_dir(judge_dir: Option<&Path>, subdir: Option<&str>) -> Result<TempDir> {
+    let parent = match judge_dir {
+        Some(dir) => dir.to_owned(),
+        None => std::env::temp_dir()
+    };
+    let parent = match subdir {
+        Some(name) => {
+            let mut components = Path::new(name).components();
+            let valid = match (components.next(), components.next()) {
+                (Some(Component::Normal(..)), None) => true,
+                _ => false
+            };
+            if !valid {
+                return Err(Error::from(ErrorKind::InvalidJudgeSubdir(name.to_owned())));
+            }
+            parent.join(name)
+        },
+        None => parent
+    };
+
+    // Make sure that the parent directory exists, and create a temporary directory under it.
+    std::fs::create_dir_all(&parent)?;
+    Ok(tempfile::tempdir_in(&parent)?)
+}
+
+/// Error numbers of the errors caused by the judge node running short of resources for a moment,
+/// which may not occur again if the operation is retried.
+const TRANSIENT_ERRNOS: &[Errno] = &[
+    Errno::EAGAIN, Errno::EINTR, Errno::ENOMEM, Errno::EMFILE, Errno::ENOSPC];
+
+/// Delay before the first retry of an operation failing with a transient error. The delay doubles
+/// after each retry.
+const RETRY_BACKOFF: Duration = Duration::from_millis(100);
+
+/// Get the error number of the given error, if it is caused by a failed system call.
+fn get_errno(err: &Error) -> Option<i32> {
+    match err.kind() {
+        ErrorKind::Io(ref e) |
+        ErrorKind::Sandbox(sandbox::ErrorKind::Io(ref e)) => e.raw_os_error(),
+        ErrorKind::Nix(nix::Error::Sys(errno)) |
+        ErrorKind::Sandbox(sandbox::ErrorKind::Nix(nix::Error::Sys(errno))) =>
+            Some(*errno as i32),
+        ErrorKind::Sandbox(sandbox::ErrorKind::ChildSetupFailed(_, errno)) => Some(*errno),
+        _ => None
+    }
+}
+
+/// Check whether the given error is caused by the judge node running short of resources for a
+/// moment rather than by the task itself, which may not occur again if the operation is retried.
+/// The system call failures listed in `TRANSIENT_ERRNOS` are transient, and so are failures to
+/// launch a child process, e.g. the compiler, that are not caused by a failed system call.
+fn is_transient_error(err: &Error) -> bool {
+    if let ErrorKind::Sandbox(sandbox::ErrorKind::ChildStartupFailed) = err.kind() {
+        return true;
+    }
+
+    match get_errno(err) {
+        Some(errno) => TRANSIENT_ERRNOS.iter().any(|e| *e as i32 == errno),
+        None => false
+    }
+}
+
+/// Call the given function until it succeeds or returns a non-transient error, retrying at most
+/// `retries` times after transient errors. The first retry is delayed by `backoff`, which doubles
+/// after each retry. The last error is returned if all attempts fail.
+fn retry_transient<T, F>(retries: u32, backoff: Duration, mut f: F) -> Result<T>
+    where F: FnMut() -> Result<T> {
+    let mut attempt = 0;
+    let mut delay = backoff;
+    loop {
+        match f() {
+            Err(ref e) if attempt < retries && is_transient_error(e) => {
+                attempt += 1;
+                log::warn!("Transient error occurred, retrying ({}/{}) in {} ms: {}",
+                    attempt, retries, delay.as_millis(), e);
+                std::thread::sleep(delay);
+                delay *= 2;
+            },
+            res => return res
+        };
+    }
+}
+
+/// Add the memory overhead of a language runtime to the given memory limit.
+fn add_memory_overhead(limit: MemorySize, overhead: MemorySize) -> MemorySize {
+    MemorySize::Bytes(limit.bytes().saturating_add(overhead.bytes()))
 }
 
 /// A judge engine instance.
@@ -133,6 +738,9 @@ pub struct JudgeEngine {
     /// Atomic shared reference to the singleton `LanguageManager` instance.
     languages: Arc<LanguageManager>,
 
+    /// Registry of in-process answer checkers available in standard judge mode.
+    checkers: CheckerRegistry,
+
     /// Configuration of the judge engine.
     pub config: JudgeEngineConfig,
 }
@@ -143,14 +751,22 @@ impl JudgeEngine {
     pub fn new() -> Self {
         JudgeEngine {
             languages: Arc::new(LanguageManager::new()),
+            checkers: CheckerRegistry::new(),
             config: JudgeEngineConfig::new(),
         }
     }
 
     /// Create a new `JudgeEngine` object using the given configuration.
     pub fn with_config(config: JudgeEngineConfig) -> Self {
+        JudgeEngine::with_checkers(config, CheckerRegistry::new())
+    }
+
+    /// Create a new `JudgeEngine` object using the given configuration, whose in-process answer
+    /// checkers are looked up in the given checker registry.
+    pub fn with_checkers(config: JudgeEngineConfig, checkers: CheckerRegistry) -> Self {
         JudgeEngine {
             languages: Arc::new(LanguageManager::new()),
+            checkers,
             config,
         }
     }
@@ -159,6 +775,17 @@ impl JudgeEngine {
     pub fn languages<'s>(&'s self) -> &'s LanguageManager {
         &self.languages
     }
+
+    /// Get the checker registry contained in this judge engine.
+    pub fn checkers<'s>(&'s self) -> &'s CheckerRegistry {
+        &self.checkers
+    }
+
+    /// Get the mutable checker registry contained in this judge engine. Custom in-process checkers
+    /// can be registered through the returned reference.
+    pub fn checkers_mut<'s>(&'s mut self) -> &'s mut CheckerRegistry {
+        &mut self.checkers
+    }
 }
 
 // This implementation block implements some common facilities used in judge engine.
@@ -177,21 +804,104 @@ impl JudgeEngine {
     /// Execute the given compilation task.
     pub fn compile(&self, task: CompilationTaskDescriptor) -> Result<CompilationResult> {
         log::trace!("Compilation task: {:?}", task);
+        let span = Span::compile(&task);
+
+        let result = span.in_scope(|| {
+            let compile_info = self.get_compile_info(
+                &task.program, task.kind, task.output_dir, &task.compile_flags)?;
+            log::trace!("Compilation info: {:?}", compile_info);
+
+            match compile_info {
+                Some(info) => self.execute_compiler(info, task.time_limit),
+                None => Ok(CompilationResult::succeed(task.program.file))
+            }
+        })?;
+
+        span.record_succeeded(result.succeeded);
+        Ok(result)
+    }
+
+    /// Compile the given source code written in the given language. The source code is saved into a
+    /// temporary file named with the source file extension reported by the language provider, and
+    /// then compiled by `compile`. The temporary file is created in `output_dir`, or in the system's
+    /// temporary directory if `output_dir` is `None`.
+    ///
+    /// The saved source file is removed after compilation unless the language is interpreted, in
+    /// which case the source file itself is the output file and is kept.
+    ///
+    /// Source code of judgees larger than `JudgeEngineConfig::max_source_size` is rejected with a
+    /// failed compilation result without being saved.
+    pub fn compile_source(&self,
+        source: &str,
+        lang: LanguageIdentifier,
+        kind: ProgramKind,
+        output_dir: Option<PathBuf>) -> Result<CompilationResult> {
+        if let (Some(max_size), false) = (self.config.max_source_size, kind.is_jury()) {
+            if source.len() > max_size.bytes() {
+                return Ok(CompilationResult::source_too_large(source.len(), max_size.bytes()));
+            }
+        }
 
-        let compile_info = self.get_compile_info(&task.program, task.kind, task.output_dir)?;
-        log::trace!("Compilation info: {:?}", compile_info);
+        let lang_provider = self.find_language_provider(&lang)?;
+        let suffix = match lang_provider.source_extension() {
+            "" => String::new(),
+            ext => format!(".{}", ext)
+        };
+
+        let src_dir = output_dir.clone().unwrap_or_else(std::env::temp_dir);
+        let mut src_file = tempfile::Builder::new()
+            .prefix("source")
+            .suffix(&suffix)
+            .tempfile_in(&src_dir)?;
+        src_file.write_all(source.as_bytes())?;
 
-        match compile_info {
-            Some(info) => self.execute_compiler(info),
-            None => Ok(CompilationResult::succeed(task.program.file))
+        let mut task = CompilationTaskDescriptor::new(Program::new(src_file.path(), lang));
+        task.kind = kind;
+        task.output_dir = output_dir;
+        let result = self.compile(task)?;
+
+        if lang_provider.metadata().interpreted {
+            // Persisting the temporary file onto its own path keeps it from being removed.
+            let src_path = src_file.path().to_owned();
+            src_file.persist(&src_path).map_err(|e| e.error)?;
         }
+
+        Ok(result)
+    }
+
+    /// Check whether the given program compiles without running it, e.g. to validate a submission
+    /// before it is judged. The syntax check mode of the language provider is used if available;
+    /// otherwise the program is compiled into a temporary directory as `compile` does.
+    ///
+    /// The compiled program, if any, is discarded, so `output_file` of the returned result is
+    /// always `None`.
+    pub fn check_compiles(&self, program: &Program, kind: ProgramKind)
+        -> Result<CompilationResult> {
+        let lang_provider = self.find_language_provider(&program.language)?;
+        let check_info = lang_provider.syntax_check(program, kind)
+            .map_err(|e| Error::from(ErrorKind::LanguageError(format!("{}", e))))?;
+        log::trace!("Syntax check info: {:?}", check_info);
+
+        let mut result = match check_info {
+            Some(info) => self.execute_compiler(info, None)?,
+            None => {
+                let output_dir = tempfile::tempdir()?;
+                let mut task = CompilationTaskDescriptor::new(program.clone());
+                task.kind = kind;
+                task.output_dir = Some(output_dir.path().to_owned());
+                self.compile(task)?
+            }
+        };
+        result.output_file = None;
+
+        Ok(result)
     }
 
     /// Get necessary compilation information for compiling the given program of the given kind.
     /// This function can return `Ok(None)` to indicate that the given program need not to be
     /// compiled before execution.
     fn get_compile_info(&self,
-        program: &Program, kind: ProgramKind, output_dir: Option<PathBuf>)
+        program: &Program, kind: ProgramKind, output_dir: Option<PathBuf>, flags: &[String])
         -> Result<Option<CompilationInfo>> {
         let lang_provider = self.find_language_provider(&program.language)?;
         if lang_provider.metadata().interpreted {
@@ -199,37 +909,62 @@ impl JudgeEngine {
             // before execution.
             Ok(None)
         } else {
-            lang_provider.compile(program, kind, output_dir)
+            lang_provider.compile_with_flags(program, kind, output_dir, flags)
                 .map(|info| Some(info))
                 .map_err(|e| Error::from(ErrorKind::LanguageError(format!("{}", e))))
         }
     }
 
-    /// Execute the compiler configuration specified in the given `CompilationInfo` instance.
-    fn execute_compiler(&self, compile_info: CompilationInfo) -> Result<CompilationResult> {
+    /// Execute the compiler configuration specified in the given `CompilationInfo` instance, under
+    /// the given wall-clock time limit if any. The compiler is executed again on infrastructure
+    /// errors, up to `compile_retries` times.
+    fn execute_compiler(&self, compile_info: CompilationInfo, time_limit: Option<Duration>)
+        -> Result<CompilationResult> {
+        // Report a missing compiler early; the sandbox would only report that the child process
+        // failed to look up its executable.
+        compile_info.check_compiler()?;
+
+        retry_transient(self.config.compile_retries, RETRY_BACKOFF,
+            || self.execute_compiler_once(&compile_info, time_limit))
+    }
+
+    /// Execute the compiler once. Errors in the compiled program are reported by a failed
+    /// `CompilationResult`, while `Err` is returned on infrastructure errors.
+    fn execute_compiler_once(&self, compile_info: &CompilationInfo, time_limit: Option<Duration>)
+        -> Result<CompilationResult> {
         let mut process_builder = compile_info.build()?;
         process_builder.inherit_envs();
+        if time_limit.is_some() {
+            process_builder.limits.real_time_limit = time_limit;
+        }
 
-        // Redirect `stderr` of the compiler to a pipe.
-        let (mut stderr_pipe_read, stderr_pipe_write) = io::pipe()?;
+        // Redirect `stderr` of the compiler to a pipe, which is drained while the compiler is
+        // running since compilers may print lots of error messages. The messages are drained even
+        // if the compilation succeeds, in which case they are discarded.
+        let (stderr_pipe_read, stderr_pipe_write) = io::pipe()?;
         process_builder.redirections.stderr = Some(stderr_pipe_write);
 
         // Launch the compiler process.
         let mut process_handle = process_builder.start()?;
+        let stderr_reader = BackgroundReader::start(stderr_pipe_read, MAX_MESSAGE_LEN);
         process_handle.wait_for_exit()?;
+        let err_msg = stderr_reader.finish()?;
 
         let exit_status = process_handle.exit_status();
         log::trace!("Compiler exited with status: {:?}", exit_status);
 
         match exit_status {
-            ProcessExitStatus::Normal(0) =>
-                Ok(CompilationResult::succeed(compile_info.output_file.clone())),
+            ProcessExitStatus::Normal(0) => {
+                let mut result = CompilationResult::succeed(compile_info.output_file.clone());
+                if self.config.record_compile_command {
+                    result.command = Some(compile_info.command_line());
+                }
+                Ok(result)
+            },
             _ => {
-                // Read all contents from stderr of the compiler.
-                let mut err_msg = String::new();
-                stderr_pipe_read.read_to_string(&mut err_msg)?;
-
-                Ok(CompilationResult::fail(err_msg))
+                let mut result = CompilationResult::fail(err_msg);
+                result.command = Some(compile_info.command_line());
+                Ok(result)
             }
         }
     }
@@ -239,6 +974,108 @@ impl JudgeEngine {
 impl JudgeEngine {
     /// Execute the given judge task.
     pub fn judge(&self, task: JudgeTaskDescriptor) -> Result<JudgeResult> {
+        self.judge_streaming(task, |_| ())
+    }
+
+    /// Execute the given judge task, calling the given function with the progress of the judge
+    /// task after each test case is judged or skipped.
+    pub fn judge_streaming<F>(&self, task: JudgeTaskDescriptor, mut on_progress: F)
+        -> Result<JudgeResult>
+        where F: FnMut(&JudgeProgress) {
+        let span = Span::judge(&task);
+        let result = span.in_scope(|| {
+            let jury_bdr_mem = self.build_jury_bdr(&task.mode, &task.jury_env)?;
+            self.judge_with_jury(&task, jury_bdr_mem.as_ref(), &mut on_progress)
+        })?;
+
+        span.record_verdict(result.verdict);
+        Ok(result)
+    }
+
+    /// Judge each of the given programs against the same test suite, using the same judge mode and
+    /// resource limits. The results are returned in the same order as the given programs.
+    /// `jury_env` is passed to the jury program and the answer generator of every judge task.
+    ///
+    /// Compared to calling `judge` once per program, the jury program (if any) is prepared only
+    /// once and its process builder memento is shared across all judgee runs.
+    pub fn judge_batch(&self,
+        programs: Vec<Program>,
+        shared_mode: JudgeMode,
+        limits: ResourceLimits,
+        test_suite: Vec<TestCaseDescriptor>,
+        jury_env: Vec<(String, String)>) -> Result<Vec<JudgeResult>> {
+        let jury_bdr_mem = self.build_jury_bdr(&shared_mode, &jury_env)?;
+
+        let mut results = Vec::with_capacity(programs.len());
+        for program in programs {
+            let mut task = JudgeTaskDescriptor::new(program);
+            task.mode = shared_mode.clone();
+            task.limits = limits;
+            task.test_suite = test_suite.clone();
+            task.jury_env = jury_env.clone();
+
+            results.push(self.judge_with_jury(&task, jury_bdr_mem.as_ref(), &mut |_| ())?);
+        }
+
+        Ok(results)
+    }
+
+    /// Build the process builder memento of the jury program required by the given judge mode. This
+    /// function returns `Ok(None)` if the judge mode does not require a jury program.
+    fn build_jury_bdr(&self, mode: &JudgeMode, jury_env: &[(String, String)])
+        -> Result<Option<ProcessBuilderMemento>> {
+        let jury_exec_info = match mode {
+            JudgeMode::SpecialJudge(ref checker) =>
+                self.get_execution_info(checker, ProgramKind::Checker)?,
+            JudgeMode::Interactive(ref interactor) =>
+                self.get_execution_info(interactor, ProgramKind::Interactor)?,
+            JudgeMode::AgainstReference { ref reference, .. } =>
+                self.get_execution_info(reference, ProgramKind::Judgee)?,
+            _ => return Ok(None)
+        };
+        log::trace!("Jury execution info: {:?}", jury_exec_info);
+
+        let mut jury_bdr = jury_exec_info.build()?;
+        self.apply_jury_bdr_config(&mut jury_bdr, jury_env)?;
+
+        let jury_bdr_mem: ProcessBuilderMemento = jury_bdr.into();
+        log::trace!("Jury process builder memento built: {:?}", jury_bdr_mem);
+
+        Ok(Some(jury_bdr_mem))
+    }
+
+    /// Build the process builder memento of the given answer generator. The answer generator is
+    /// trusted and runs under the same configuration as the jury programs.
+    fn build_answer_generator_bdr(&self, generator: &Program, jury_env: &[(String, String)])
+        -> Result<ProcessBuilderMemento> {
+        let exec_info = self.get_execution_info(generator, ProgramKind::Checker)?;
+        log::trace!("Answer generator execution info: {:?}", exec_info);
+
+        let mut generator_bdr = exec_info.build()?;
+        self.apply_jury_bdr_config(&mut generator_bdr, jury_env)?;
+
+        Ok(generator_bdr.into())
+    }
+
+    /// Get the wall-clock time budget of the given judge task, which is the smaller one of
+    /// `JudgeEngineConfig::total_judge_time_limit` and the budget of the task itself.
+    fn judge_time_limit(&self, task: &JudgeTaskDescriptor) -> Option<Duration> {
+        match (self.config.total_judge_time_limit, task.time_limit) {
+            (Some(lhs), Some(rhs)) => Some(std::cmp::min(lhs, rhs)),
+            (lhs, rhs) => lhs.or(rhs)
+        }
+    }
+
+    /// Execute the given judge task using the given jury process builder memento. The memento
+    /// should be built by `build_jury_bdr` from the judge mode of the task. `on_progress` is called
+    /// after each test case is judged or skipped.
+    fn judge_with_jury(&self,
+        task: &JudgeTaskDescriptor,
+        jury_bdr_mem: Option<&ProcessBuilderMemento>,
+        on_progress: &mut dyn FnMut(&JudgeProgress)) -> Result<JudgeResult> {
+        let test_suite = self.limit_test_suite(&task.test_suite)?;
+        subtasks::validate(&task.subtasks, task.test_suite.len())?;
+
         let judgee_lang_prov = self.find_language_provider(&task.program.language)?;
 
         // Get execution information of the judgee.
@@ -246,27 +1083,23 @@ impl JudgeEngine {
         let judgee_exec_info = judgee_lang_prov.execute(&task.program, ProgramKind::Judgee)
             .map_err(|e| Error::from(ErrorKind::LanguageError(format!("{}", e))))
             ?;
+        judgee_exec_info.check_interpreter(&task.program)?;
         log::trace!("Judgee execution info returned by language provider: {:?}", judgee_exec_info);
 
         // Apply judge engine configuration to the judgee's builder.
         let mut judgee_bdr = judgee_exec_info.build()?;
-        self.apply_judgee_bdr_config(&mut judgee_bdr);
+        self.apply_judgee_bdr_config(&mut judgee_bdr)?;
 
         // Set judgee's resource limits.
         judgee_bdr.limits.cpu_time_limit = Some(task.limits.cpu_time_limit);
         judgee_bdr.limits.real_time_limit = Some(task.limits.real_time_limit);
-        judgee_bdr.limits.memory_limit = Some(task.limits.memory_limit);
+        judgee_bdr.limits.memory_limit = Some(add_memory_overhead(
+            task.limits.memory_limit, judgee_lang_prov.memory_overhead()));
 
         // Create a temporary directory for this judge task.
-        let judge_dir = match self.config.judge_dir {
-            Some(ref parent) => {
-                // Make sure that judge_dir exists.
-                std::fs::create_dir_all(parent)?;
-                // And create a temporary directory under judge_dir.
-                tempfile::tempdir_in(parent)?
-            },
-            None => tempfile::tempdir()?
-        };
+        let judge_dir = create_judge_dir(
+            self.config.judge_dir.as_ref().map(|dir| dir.as_path()),
+            task.judge_subdir.as_ref().map(|name| name.as_str()))?;
         // And set the judge directory to the judgee's process builder.
         judgee_bdr.dir.root_dir = Some(judge_dir.path().to_owned());
         judgee_bdr.dir.working_dir = Some(judge_dir.path().to_owned());
@@ -276,42 +1109,83 @@ impl JudgeEngine {
         log::trace!("Judgee process builder memento built: {:?}", judgee_bdr_mem);
 
         // Create judge context.
-        let context = match task.mode {
+        let mut context = match task.mode {
             JudgeMode::Standard(checker) => {
                 let builtin_checker = self.get_builtin_checker(checker);
-                JudgeContext::standard(&task, judge_dir, judgee_bdr_mem, builtin_checker)
+                JudgeContext::standard(
+                    task, test_suite, judge_dir, judgee_bdr_mem, builtin_checker)
+            },
+            JudgeMode::BuiltinNamed(ref name) => {
+                let checker = self.checkers.find(name)
+                    .ok_or_else(|| Error::from(ErrorKind::CheckerNotFound(name.clone())))?;
+                JudgeContext::standard(task, test_suite, judge_dir, judgee_bdr_mem, checker)
             },
             JudgeMode::SpecialJudge(..) | JudgeMode::Interactive(..) => {
-                let jury_exec_info = match task.mode {
-                    JudgeMode::SpecialJudge(ref checker) =>
-                        self.get_execution_info(checker, ProgramKind::Checker)?,
-                    JudgeMode::Interactive(ref interactor) =>
-                        self.get_execution_info(interactor, ProgramKind::Interactor)?,
-                    _ => unreachable!()
-                };
-                log::trace!("Jury execution info: {:?}", jury_exec_info);
+                let jury_bdr_mem = jury_bdr_mem
+                    .expect("failed to unwrap jury process builder memento");
+                JudgeContext::with_jury(task, test_suite, judge_dir, judgee_bdr_mem, jury_bdr_mem)
+            },
+            JudgeMode::AgainstReference { checker, .. } => {
+                let jury_bdr_mem = jury_bdr_mem
+                    .expect("failed to unwrap jury process builder memento");
+                let mut context = JudgeContext::with_jury(
+                    task, test_suite, judge_dir, judgee_bdr_mem, jury_bdr_mem);
+                context.builtin_checker = Some(self.get_builtin_checker(checker));
+                context
+            }
+        };
+        context.time_limit = self.judge_time_limit(task);
+
+        let has_jury_output = match task.mode {
+            JudgeMode::AgainstReference { .. } => true,
+            _ => task.answer_generator.is_some()
+        };
+        if has_jury_output {
+            context.jury_output_dir = Some(create_judge_dir(
+                self.config.judge_dir.as_ref().map(|dir| dir.as_path()), None)?);
+        }
 
-                let mut jury_bdr = jury_exec_info.build()?;
-                self.apply_jury_bdr_config(&mut jury_bdr);
-                jury_bdr.dir.working_dir = Some(judge_dir.path().to_owned());
-                jury_bdr.dir.root_dir = Some(judge_dir.path().to_owned());
+        if let Some(ref generator) = task.answer_generator {
+            match task.mode {
+                JudgeMode::AgainstReference { .. } =>
+                    log::warn!("Answer generator is ignored in reference judge mode."),
+                _ => context.answer_generator_bdr = Some(
+                    self.build_answer_generator_bdr(generator, &task.jury_env)?)
+            };
+        }
 
-                let jury_bdr_mem: ProcessBuilderMemento = jury_bdr.into();
-                log::trace!("Jury process builder memento built: {:?}", jury_bdr_mem);
+        let mut judge_exec = JudgeEngineExecutor::new(self.config.capture_judgee_stderr);
+        judge_exec.run_checker_on_empty_output = self.config.run_checker_on_empty_output;
+        judge_exec.preserved_output_dir = self.config.preserved_output_dir.clone();
+        context.execute_streaming(&mut judge_exec, on_progress)
+    }
 
-                JudgeContext::with_jury(&task, judge_dir, judgee_bdr_mem, jury_bdr_mem)
-            }
+    /// Apply the `max_test_cases` configuration to the given test suite and returns the test cases
+    /// that should be judged.
+    fn limit_test_suite<'t>(&self, test_suite: &'t [TestCaseDescriptor])
+        -> Result<&'t [TestCaseDescriptor]> {
+        let max = match self.config.max_test_cases {
+            Some(max) if test_suite.len() > max => max,
+            _ => return Ok(test_suite)
         };
 
-        let mut judge_exec = JudgeEngineExecutor::new();
-        context.execute(&mut judge_exec)
+        if !self.config.truncate_test_suite {
+            return Err(Error::from(ErrorKind::TooManyTestCases(test_suite.len(), max)));
+        }
+
+        log::warn!("Test suite contains {} test cases, only the first {} will be judged.",
+            test_suite.len(), max);
+        Ok(&test_suite[..max])
     }
 
     /// Apply judgee related configurations to the given `ProcessBuilder` that builds the judgee
     /// process.
-    fn apply_judgee_bdr_config(&self, judgee_bdr: &mut ProcessBuilder) {
+    fn apply_judgee_bdr_config(&self, judgee_bdr: &mut ProcessBuilder) -> Result<()> {
         judgee_bdr.add_env("ONLINE_JUDGE", "YES")
             .expect("failed to set ONLINE_JUDGE environment variable for judgee.");
+        for (name, value) in &self.config.judge_env {
+            judgee_bdr.add_env(name.clone(), value.clone())?;
+        }
 
         if self.config.judge_uid.is_some() {
             judgee_bdr.uid = Some(self.config.judge_uid.unwrap());
@@ -320,15 +1194,33 @@ impl JudgeEngine {
         for syscall in &self.config.judgee_syscall_whitelist {
             judgee_bdr.syscall_whitelist.push(syscall.clone());
         }
+
+        judgee_bdr.require_seccomp = self.config.require_seccomp;
+        judgee_bdr.limits.disk_quota = self.config.judgee_disk_quota;
+        judgee_bdr.syscall_budget = self.config.judgee_syscall_budget;
+        judgee_bdr.limits.open_files_limit = self.config.judgee_open_files_limit;
+        judgee_bdr.memory_limit_policy = self.config.judgee_memory_limit_policy;
+        judgee_bdr.nice = self.config.judgee_nice;
+        judgee_bdr.io_priority = self.config.judgee_io_priority;
+
+        Ok(())
     }
 
     /// Apply jury related configurations to the given `ProcessBuilder` that builds the jury
-    /// process.
-    fn apply_jury_bdr_config(&self, jury_bdr: &mut ProcessBuilder) {
+    /// process. The given extra environment variables are passed to the jury process only.
+    fn apply_jury_bdr_config(&self, jury_bdr: &mut ProcessBuilder, jury_env: &[(String, String)])
+        -> Result<()> {
         jury_bdr.add_env("ONLINE_JUDGE", "YES")
             .expect("failed to set ONLINE_JUDGE environment variable for jury.");
+        // Duplicate environment variables are resolved to the first occurrence by most C libraries,
+        // so variables overridden by the judge task are skipped here.
+        let judge_env = self.config.judge_env.iter()
+            .filter(|(name, _)| !jury_env.iter().any(|(n, _)| n == name));
+        for (name, value) in judge_env.chain(jury_env) {
+            jury_bdr.add_env(name.clone(), value.clone())?;
+        }
 
-        if self.config.jury_cpu_time_limit.is_none() {
+        if self.config.jury_cpu_time_limit.is_some() {
             jury_bdr.limits.cpu_time_limit = self.config.jury_cpu_time_limit;
         }
         if self.config.jury_real_time_limit.is_some() {
@@ -338,9 +1230,17 @@ impl JudgeEngine {
             jury_bdr.limits.memory_limit = self.config.jury_memory_limit;
         }
 
-        for syscall in &self.config.jury_syscall_whitelist {
-            jury_bdr.syscall_whitelist.push(syscall.clone());
-        }
+        jury_bdr.require_seccomp = self.config.require_seccomp;
+        if self.config.trust_jury {
+            // An empty whitelist disables the seccomp filter of the sandbox.
+            jury_bdr.syscall_whitelist.clear();
+        } else {
+            for syscall in &self.config.jury_syscall_whitelist {
+                jury_bdr.syscall_whitelist.push(syscall.clone());
+            }
+        }
+
+        Ok(())
     }
 
     /// Get a `Checker` trait object corresponding to the given builtin checker indicator.
@@ -352,8 +1252,10 @@ impl JudgeEngine {
     fn get_execution_info(&self, program: &Program, kind: ProgramKind)
         -> Result<ExecutionInfo> {
         let lang_provider = self.find_language_provider(&program.language)?;
-        lang_provider.execute(program, kind)
-            .map_err(|e| Error::from(ErrorKind::LanguageError(format!("{}", e))))
+        let exec_info = lang_provider.execute(program, kind)
+            .map_err(|e| Error::from(ErrorKind::LanguageError(format!("{}", e))))?;
+        exec_info.check_interpreter(program)?;
+        Ok(exec_info)
     }
 }
 
@@ -362,6 +1264,9 @@ struct JudgeContext<'a> {
     /// The judge task under execution.
     task: &'a JudgeTaskDescriptor,
 
+    /// The test cases to be judged, which may be a prefix of the task's test suite.
+    test_suite: &'a [TestCaseDescriptor],
+
     /// Path to the directory inside which the judge task will be executed.
     judge_dir: TempDir,
 
@@ -371,8 +1276,21 @@ struct JudgeContext<'a> {
     /// The built-in checker to be used.
     builtin_checker: Option<Checker>,
 
-    /// Process builder memento for the jury process.
-    jury_bdr: Option<ProcessBuilderMemento>,
+    /// Process builder memento for the jury process. The memento may be shared across multiple
+    /// judge tasks, so the judge directory is not set in it.
+    jury_bdr: Option<&'a ProcessBuilderMemento>,
+
+    /// Wall-clock time budget of the whole judge task, if any.
+    time_limit: Option<Duration>,
+
+    /// Process builder memento for the answer generator, if any. The judge directory is not set in
+    /// it either.
+    answer_generator_bdr: Option<ProcessBuilderMemento>,
+
+    /// Directory holding the outputs of the reference solution and the answer generator, which is
+    /// outside the judge directory so that the judgee can neither read them nor be charged for
+    /// them against its disk quota. The system temporary directory is used if this is `None`.
+    jury_output_dir: Option<TempDir>,
 }
 
 impl<'a> JudgeContext<'a> {
@@ -380,15 +1298,20 @@ impl<'a> JudgeContext<'a> {
     /// `Standard`.
     fn standard(
         task: &'a JudgeTaskDescriptor,
+        test_suite: &'a [TestCaseDescriptor],
         judge_dir: TempDir,
         judgee_bdr: ProcessBuilderMemento,
         builtin_checker: Checker) -> Self {
         JudgeContext {
             task,
+            test_suite,
             judge_dir,
             judgee_bdr,
             builtin_checker: Some(builtin_checker),
             jury_bdr: None,
+            time_limit: None,
+            answer_generator_bdr: None,
+            jury_output_dir: None,
         }
     }
 
@@ -396,47 +1319,123 @@ impl<'a> JudgeContext<'a> {
     /// program.
     fn with_jury(
         task: &'a JudgeTaskDescriptor,
+        test_suite: &'a [TestCaseDescriptor],
         judge_dir: TempDir,
         judgee_bdr: ProcessBuilderMemento,
-        jury_bdr: ProcessBuilderMemento) -> Self {
+        jury_bdr: &'a ProcessBuilderMemento) -> Self {
         JudgeContext {
             task,
+            test_suite,
             judge_dir,
             judgee_bdr,
             builtin_checker: None,
             jury_bdr: Some(jury_bdr),
+            time_limit: None,
+            answer_generator_bdr: None,
+            jury_output_dir: None,
         }
     }
 
-    /// Execute the judge task contained in this `JudgeContext` using the given executor.
+    /// Create a temporary file to hold the output of the reference solution or the answer
+    /// generator, outside the judge directory.
+    fn create_jury_output_file(&self) -> Result<NamedTempFile> {
+        let file = match self.jury_output_dir {
+            Some(ref dir) => NamedTempFile::new_in(dir.path())?,
+            None => NamedTempFile::new()?
+        };
+        Ok(file)
+    }
+
+    /// Execute the judge task contained in this `JudgeContext` using the given executor, without
+    /// reporting progress.
+    #[cfg(test)]
     fn execute<E>(&self, executor: &mut E) -> Result<JudgeResult>
+        where E: ?Sized + TestCaseExecutor {
+        self.execute_streaming(executor, &mut |_| ())
+    }
+
+    /// Execute the judge task contained in this `JudgeContext` using the given executor, calling
+    /// `on_progress` after each test case is judged or skipped.
+    fn execute_streaming<E>(&self, executor: &mut E, on_progress: &mut dyn FnMut(&JudgeProgress))
+        -> Result<JudgeResult>
         where E: ?Sized + TestCaseExecutor {
         let mut res = JudgeResult::new();
+        let report_progress = |res: &JudgeResult, on_progress: &mut dyn FnMut(&JudgeProgress)| {
+            on_progress(&JudgeProgress {
+                test_cases_done: res.test_suite.len(),
+                test_cases_total: self.test_suite.len(),
+                verdict: res.verdict,
+            });
+        };
+        let start_time = Instant::now();
+        let mut subtasks = SubtaskTracker::new(&self.task.subtasks);
+
+        for (index, tc) in self.test_suite.iter().enumerate() {
+            if let Some(time_limit) = self.time_limit {
+                if start_time.elapsed() > time_limit {
+                    log::warn!("Judge task exceeded its time budget of {:?} after {} test cases.",
+                        time_limit, index);
+                    res.verdict &= Verdict::JudgeTimeLimitExceeded;
+                    res.comment = Some(format!(
+                        "judge time limit of {} ms exceeded, {} of {} test cases were judged.",
+                        time_limit.as_millis(), index, self.test_suite.len()));
+                    break;
+                }
+            }
+
+            if subtasks.should_skip(index) {
+                log::trace!("Skipping test case {} since its subtasks have failed.", index);
+                res.add_test_case_result(TestCaseResult::skipped());
+                report_progress(&res, on_progress);
+                continue;
+            }
 
-        for tc in &self.task.test_suite {
             log::trace!("Judging on test case: (\"{}\", \"{}\")",
                 tc.input_file.display(), tc.answer_file.display());
-            let mut tc_ctx = TestCaseContext::new(self, tc);
-
-            executor.before(&mut tc_ctx)?;
-            match self.task.mode {
-                JudgeMode::Standard(..) => {
-                    executor.judge_std(&mut tc_ctx)?;
-                },
-                JudgeMode::SpecialJudge(..) => {
-                    executor.judge_spj(&mut tc_ctx)?;
-                },
-                JudgeMode::Interactive(..) => {
-                    executor.judge_interactive(&mut tc_ctx)?;
-                }
-            };
-            executor.after(&mut tc_ctx)?;
+            seed_judge_dir(self.judge_dir.path(), &self.task.grader_files)?;
+            let span = Span::test_case(index);
+            let tc_result = span.in_scope(|| self.execute_test_case(executor, tc))?;
+            span.record_verdict(tc_result.verdict);
 
-            res.add_test_case_result(tc_ctx.result);
+            subtasks.record(index, tc_result.verdict);
+            res.add_test_case_result(tc_result);
+            report_progress(&res, on_progress);
         }
 
+        res.subtasks = subtasks.results(&res.test_suite);
         Ok(res)
     }
+
+    /// Execute the given test case using the given executor.
+    fn execute_test_case<E>(&self, executor: &mut E, tc: &TestCaseDescriptor)
+        -> Result<TestCaseResult>
+        where E: ?Sized + TestCaseExecutor {
+        let mut tc_ctx = TestCaseContext::new(self, tc);
+
+        if self.answer_generator_bdr.is_some() && !executor.generate_answer(&mut tc_ctx)? {
+            // The answer generator failed and the verdict of the test case has been set.
+            return Ok(tc_ctx.result);
+        }
+
+        executor.before(&mut tc_ctx)?;
+        match self.task.mode {
+            JudgeMode::Standard(..) | JudgeMode::BuiltinNamed(..) => {
+                executor.judge_std(&mut tc_ctx)?;
+            },
+            JudgeMode::SpecialJudge(..) => {
+                executor.judge_spj(&mut tc_ctx)?;
+            },
+            JudgeMode::Interactive(..) => {
+                executor.judge_interactive(&mut tc_ctx)?;
+            },
+            JudgeMode::AgainstReference { .. } => {
+                executor.judge_against_reference(&mut tc_ctx)?;
+            }
+        };
+        executor.after(&mut tc_ctx)?;
+
+        Ok(tc_ctx.result)
+    }
 }
 
 /// Provide judge context on a specific test case.
@@ -449,6 +1448,10 @@ struct TestCaseContext<'a, 'b> {
 
     /// The judge result on this test case.
     result: TestCaseResult,
+
+    /// The answer generated by the answer generator, if any, which supersedes the answer file of
+    /// the test case.
+    generated_answer: Option<NamedTempFile>,
 }
 
 impl<'a, 'b> TestCaseContext<'a, 'b> {
@@ -458,6 +1461,15 @@ impl<'a, 'b> TestCaseContext<'a, 'b> {
             judge_context,
             test_case,
             result: TestCaseResult::new(),
+            generated_answer: None,
+        }
+    }
+
+    /// Get the path to the answer file of the test case, which is the generated answer if any.
+    fn answer_file(&self) -> &Path {
+        match self.generated_answer {
+            Some(ref answer) => answer.path(),
+            None => &self.test_case.answer_file
         }
     }
 }
@@ -470,14 +1482,34 @@ trait TestCaseExecutor {
     /// Called before a test case is executed.
     fn before<'s, 'a, 'b, 'c>(&'s mut self, context: &'c mut TestCaseContext<'a, 'b>)
         -> Result<()> {
+        if !context.test_case.public {
+            return Ok(());
+        }
+
         let input_view = io::read_file_view(&context.test_case.input_file, DATA_VIEW_LEN)?;
-        let answer_view = io::read_file_view(&context.test_case.answer_file, DATA_VIEW_LEN)?;
         context.result.input_view = Some(input_view);
-        context.result.answer_view = Some(answer_view);
+
+        // In standard judge mode the answer view is recorded while the answer is checked, so that
+        // the answer file is read only once. In reference judge mode the answer file is not used.
+        match context.judge_context.task.mode {
+            JudgeMode::Standard(..) |
+            JudgeMode::BuiltinNamed(..) |
+            JudgeMode::AgainstReference { .. } => (),
+            _ => {
+                let answer_view = io::read_file_view(context.answer_file(), DATA_VIEW_LEN)?;
+                context.result.answer_view = Some(answer_view);
+            }
+        };
 
         Ok(())
     }
 
+    /// Run the answer generator of the judge context on the input of the current test case and
+    /// keep the generated answer in the given context. This function returns `Ok(false)` and sets
+    /// the verdict of the current test case if the answer generator failed.
+    fn generate_answer<'s, 'a, 'b, 'c>(&'s mut self, context: &'c mut TestCaseContext<'a, 'b>)
+        -> Result<bool>;
+
     /// Execute standard judge mode on the given judge context.
     fn judge_std<'s, 'a, 'b, 'c>(&'s mut self, context: &'c mut TestCaseContext<'a, 'b>)
         -> Result<()>;
@@ -490,6 +1522,10 @@ trait TestCaseExecutor {
     fn judge_interactive<'s, 'a, 'b, 'c>(&'s mut self, context: &'c mut TestCaseContext<'a, 'b>)
         -> Result<()>;
 
+    /// Execute reference judge mode on the given judge context.
+    fn judge_against_reference<'s, 'a, 'b, 'c>(&'s mut self,
+        context: &'c mut TestCaseContext<'a, 'b>) -> Result<()>;
+
     /// Called after a test case is executed.
     fn after<'s, 'a, 'b, 'c>(&'s mut self, _context: &'c mut TestCaseContext<'a, 'b>)
         -> Result<()> {
@@ -497,23 +1533,190 @@ trait TestCaseExecutor {
     }
 }
 
+/// Get the command line arguments passed to the answer checker in the `FileDescriptors` I/O mode.
+/// The 3 command line arguments are:
+/// 1. fd of the input file of the current test case;
+/// 2. fd of the answer file of the current test case;
+/// 3. fd of the user's output file on the current test case.
+///
+/// The arguments are plain decimal numbers. They are not quoted since they are passed to the checker
+/// directly rather than through a shell.
+fn checker_fd_args(input_fd: RawFd, answer_fd: RawFd, output_fd: RawFd) -> Vec<String> {
+    vec![input_fd.to_string(), answer_fd.to_string(), output_fd.to_string()]
+}
+
+/// Get the command line arguments passed to the interactor. The 3 command line arguments are:
+/// 1. fd of the input file of the current test case;
+/// 2. fd of the answer file of the current test case;
+/// 3. fd of the report, into which the interactor writes its comment and score directive.
+///
+/// The standard input and the standard output of the interactor are connected to the standard
+/// output and the standard input of the judgee, respectively.
+fn interactor_fd_args(input_fd: RawFd, answer_fd: RawFd, report_fd: RawFd) -> Vec<String> {
+    vec![input_fd.to_string(), answer_fd.to_string(), report_fd.to_string()]
+}
+
 /// Provide an `Executor` for the judge engine.
-struct JudgeEngineExecutor;
+struct JudgeEngineExecutor {
+    /// Whether the stderr of the judgee should be captured.
+    capture_stderr: bool,
+
+    /// Whether the answer checker of special judge mode runs on empty output of the judgee.
+    run_checker_on_empty_output: bool,
+
+    /// Directory into which the outputs of failed judgees are preserved, if any.
+    preserved_output_dir: Option<PathBuf>,
+
+    /// Subdirectory of `preserved_output_dir` holding the outputs preserved in the current judge
+    /// task. It is created when the first output is preserved.
+    preserved_output_task_dir: Option<PathBuf>,
+
+    /// Maximum number of bytes preserved from the output of the judgee on a single test case.
+    max_preserved_output_len: u64,
+
+    /// Number of bytes that can still be preserved in the current judge task.
+    preserved_output_budget: u64,
+}
 
 impl JudgeEngineExecutor {
     /// Create a new `JudgeEngineExecutor` value.
-    fn new() -> Self {
-        JudgeEngineExecutor { }
+    fn new(capture_stderr: bool) -> Self {
+        JudgeEngineExecutor {
+            capture_stderr,
+            run_checker_on_empty_output: true,
+            preserved_output_dir: None,
+            preserved_output_task_dir: None,
+            max_preserved_output_len: MAX_PRESERVED_OUTPUT_LEN,
+            preserved_output_budget: MAX_PRESERVED_OUTPUT_TOTAL_LEN,
+        }
+    }
+}
+
+/// Remove the oldest judge task subdirectories under the given preserved output directory, so that
+/// at most `keep` of them are left. Subdirectories removed concurrently by other judge tasks are
+/// skipped.
+fn remove_old_preserved_output_dirs(dir: &Path, keep: usize) -> Result<()> {
+    let mut task_dirs = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        if !entry.file_name().to_string_lossy().starts_with("task") {
+            continue;
+        }
+        match entry.metadata() {
+            Ok(ref metadata) if metadata.is_dir() =>
+                task_dirs.push((metadata.modified()?, entry.path())),
+            Ok(..) => (),
+            Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => (),
+            Err(e) => return Err(Error::from(e)),
+        };
     }
+
+    if task_dirs.len() <= keep {
+        return Ok(());
+    }
+    task_dirs.sort();
+    for (_, path) in &task_dirs[..task_dirs.len() - keep] {
+        match std::fs::remove_dir_all(path) {
+            Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => (),
+            res => res?,
+        };
+    }
+
+    Ok(())
+}
+
+/// Create a new subdirectory under the given preserved output directory for the outputs of a judge
+/// task, removing the oldest ones so that at most `MAX_PRESERVED_OUTPUT_TASK_DIRS` are kept.
+/// Returns the path to the new subdirectory.
+fn create_preserved_output_task_dir(dir: &Path) -> Result<PathBuf> {
+    std::fs::create_dir_all(dir)?;
+    remove_old_preserved_output_dirs(dir, MAX_PRESERVED_OUTPUT_TASK_DIRS - 1)?;
+    let task_dir = tempfile::Builder::new().prefix("task").tempdir_in(dir)?;
+    Ok(task_dir.into_path())
+}
+
+/// Copy at most `max_len` bytes of the output of a judgee into a new file under the given
+/// directory, which is kept after the judge task completes. Returns the path to the new file and
+/// the number of bytes copied.
+fn preserve_output(output_file: &mut NamedTempFile, dir: &Path, max_len: u64)
+    -> Result<(PathBuf, u64)> {
+    let mut preserved = tempfile::Builder::new().prefix("output").tempfile_in(dir)?;
+
+    output_file.as_file_mut().seek(SeekFrom::Start(0))?;
+    let len = std::io::copy(&mut output_file.as_file_mut().take(max_len),
+        preserved.as_file_mut())?;
+
+    let (_, path) = preserved.keep().map_err(|e| e.error)?;
+    Ok((path, len))
 }
 
 impl JudgeEngineExecutor {
+    /// Preserve the output of the judgee on the current test case into the subdirectory of the
+    /// current judge task. Returns the path to the preserved output, or `None` if preserving
+    /// outputs is disabled or the budget of the judge task is exhausted.
+    fn preserve_judgee_output(&mut self, output_file: &mut NamedTempFile)
+        -> Result<Option<PathBuf>> {
+        let dir = match self.preserved_output_dir {
+            Some(ref dir) => dir,
+            None => return Ok(None)
+        };
+        if self.preserved_output_budget == 0 {
+            log::trace!("Judgee output is not preserved since the budget is used up.");
+            return Ok(None);
+        }
+
+        if self.preserved_output_task_dir.is_none() {
+            self.preserved_output_task_dir = Some(create_preserved_output_task_dir(dir)?);
+        }
+        let task_dir = self.preserved_output_task_dir.as_ref().unwrap();
+
+        let max_len = std::cmp::min(self.max_preserved_output_len, self.preserved_output_budget);
+        let (path, len) = preserve_output(output_file, task_dir, max_len)?;
+        self.preserved_output_budget -= len;
+        Ok(Some(path))
+    }
+
+    /// Redirect stderr of the judgee to a temporary file if it should be captured, so that users
+    /// can see runtime error messages (e.g. stack traces). Returns the temporary file, if any.
+    /// Otherwise stderr of the judgee is discarded.
+    fn redirect_judgee_stderr<'a, 'b, 'c>(&self, judgee_bdr: &mut ProcessBuilder,
+        context: &'c TestCaseContext<'a, 'b>) -> Result<Option<NamedTempFile>> {
+        if !self.capture_stderr {
+            judgee_bdr.redirections.ignore_stderr()?;
+            return Ok(None);
+        }
+
+        let error_file = NamedTempFile::new_in(&context.judge_context.judge_dir)?;
+        judgee_bdr.redirections.stderr = Some(error_file.as_file().duplicate()?);
+        Ok(Some(error_file))
+    }
+
+    /// Set the exit status of the judgee on the current test case, and record the view of its
+    /// captured stderr in the given error file, if any. The view is omitted for hidden test cases
+    /// since it may reveal the contents of the test data.
+    fn set_judgee_exit_status<'a, 'b, 'c>(&self, context: &'c mut TestCaseContext<'a, 'b>,
+        status: ProcessExitStatus, error_file: Option<&NamedTempFile>) -> Result<()> {
+        let task = context.judge_context.task;
+        context.result.set_judgee_exit_status(status,
+            task.treat_nonzero_exit_as_runtime_error, &task.signal_verdicts);
+
+        if let (true, Some(error_file)) = (context.test_case.public, error_file) {
+            let error_view = io::read_file_view(error_file.path(), DATA_VIEW_LEN)?;
+            context.result.error_view = Some(error_view);
+        }
+
+        Ok(())
+    }
+
     /// Execute the judgee program and returns the output file generated by the judgee program.
     /// This function returns `Err` to indicate any errors in the judge, returns `Ok(None)` to
     /// indicate that the judgee program itself failed. The file pointer of the returned
     /// `NamedTempFile` is properly reset to the start of the file.
-    fn execute_judgee<'s, 'a, 'b, 'c>(&'s mut self, context: &'c mut TestCaseContext<'a, 'b>)
-        -> Result<Option<NamedTempFile>> {
+    ///
+    /// If `defer_output_view` is set, the view of the output data is not read when the output file
+    /// is returned, leaving it to the caller that reads the output anyway.
+    fn execute_judgee<'s, 'a, 'b, 'c>(&'s mut self, context: &'c mut TestCaseContext<'a, 'b>,
+        defer_output_view: bool) -> Result<Option<NamedTempFile>> {
         // Redirect input and answer file.
         let input_file = File::open(&context.test_case.input_file)?;
         let mut output_file = NamedTempFile::new_in(&context.judge_context.judge_dir)?;
@@ -521,49 +1724,68 @@ impl JudgeEngineExecutor {
         let mut judgee_bdr = context.judge_context.judgee_bdr.restore();
         judgee_bdr.redirections.stdin = Some(input_file);
         judgee_bdr.redirections.stdout = Some(output_file.as_file().duplicate()?);
-        judgee_bdr.redirections.ignore_stderr()?;
+        let error_file = self.redirect_judgee_stderr(&mut judgee_bdr, context)?;
 
         // Execute the judgee.
         let mut judgee_handle = judgee_bdr.start()?;
         judgee_handle.wait_for_exit()?;
         log::trace!("Judgee exited with status: {:?}", judgee_handle.exit_status());
+        self.set_judgee_exit_status(context, judgee_handle.exit_status(), error_file.as_ref())?;
 
-        // Read view of output data.
-        output_file.as_file_mut().seek(SeekFrom::Start(0))?;
-        let output_view = io::read_file_view(output_file.path(), DATA_VIEW_LEN)?;
-        context.result.output_view = Some(output_view);
-
-        context.result.set_judgee_exit_status(judgee_handle.exit_status());
+        // Read views of output data. Views are omitted for hidden test cases since they may reveal
+        // the contents of the test data.
+        let view_output = !defer_output_view || !context.result.verdict.is_accepted();
+        if context.test_case.public && view_output {
+            let output_view = io::read_file_view(output_file.path(), DATA_VIEW_LEN)?;
+            context.result.output_view = Some(output_view);
+        }
 
         if context.result.verdict.is_accepted() {
             output_file.as_file_mut().seek(SeekFrom::Start(0))?;
-            Ok(Some(output_file))
-        } else {
-            Ok(None)
+            return Ok(Some(output_file));
         }
-    }
-}
 
-impl TestCaseExecutor for JudgeEngineExecutor {
-    fn judge_std<'s, 'a, 'b, 'c>(&'s mut self, context: &'c mut TestCaseContext<'a, 'b>)
-        -> Result<()> {
-        let output_file = match self.execute_judgee(context)? {
-            Some(f)=> f,
-            None => return Ok(())
+        // Failures to preserve the output only affect debugging, so the judge goes on.
+        match self.preserve_judgee_output(&mut output_file) {
+            Ok(path) => context.result.preserved_output = path,
+            Err(e) => log::warn!("failed to preserve judgee output: {}", e)
         };
+        Ok(None)
+    }
 
-        // Open input and answer file of the current test case.
+    /// Check the given output of the judgee against the given answer with the built-in checker of
+    /// the judge context, and set the verdict of the current test case accordingly. The views of
+    /// the answer and the output are recorded by the same pass over the data for public test
+    /// cases.
+    fn check_output<'s, 'a, 'b, 'c>(&'s mut self,
+        context: &'c mut TestCaseContext<'a, 'b>,
+        answer_file: File,
+        output_file: NamedTempFile) -> Result<()> {
+        let public = context.test_case.public;
         let input_file = File::open(&context.test_case.input_file)?;
-        let answer_file = File::open(&context.test_case.answer_file)?;
 
         let mut checker_context = CheckerContext::new(
             TokenizedReader::new(input_file),
             TokenizedReader::new(answer_file),
             TokenizedReader::new(output_file.into_file()));
+        checker_context.apply_options(&context.judge_context.task.checker_options);
+        if public {
+            // The views of the answer data and the output data are recorded by the same pass over
+            // the data that checks the output.
+            checker_context.answer.set_view_len(DATA_VIEW_LEN);
+            checker_context.user_output.set_view_len(DATA_VIEW_LEN);
+        }
+
         let checker = context.judge_context.builtin_checker
             .expect("failed to unwrap built-in checker pointer");
         let checker_res = checker(&mut checker_context)?;
 
+        if public {
+            context.result.answer_view = Some(checker_context.answer.take_view()?);
+            context.result.output_view = Some(checker_context.user_output.take_view()?);
+            context.result.diff = checker_res.diff;
+        }
+
         context.result.comment = checker_res.comment;
         context.result.verdict = if checker_res.accepted {
             Verdict::Accepted
@@ -574,43 +1796,185 @@ impl TestCaseExecutor for JudgeEngineExecutor {
         Ok(())
     }
 
+    /// Execute the reference solution on the input of the current test case and returns the
+    /// output file generated by it. This function returns `Ok(None)` and sets the verdict of the
+    /// current test case to `CheckerFailed` if the reference solution failed. The file pointer of
+    /// the returned `NamedTempFile` is properly reset to the start of the file.
+    fn execute_reference<'s, 'a, 'b, 'c>(&'s mut self, context: &'c mut TestCaseContext<'a, 'b>)
+        -> Result<Option<NamedTempFile>> {
+        let reference_bdr = context.judge_context.jury_bdr
+            .expect("failed to unwrap jury process builder as reference process builder");
+        let (output_file, status) = execute_on_input(reference_bdr, context)?;
+        log::trace!("Reference solution exited with status: {:?}", status);
+
+        match status {
+            ProcessExitStatus::Normal(0) => Ok(Some(output_file)),
+            status => {
+                context.result.verdict = Verdict::CheckerFailed;
+                context.result.comment = Some(format!("reference solution failed: {:?}", status));
+                context.result.failure_reason = Some(FailureReason::ReferenceFailed);
+                Ok(None)
+            }
+        }
+    }
+}
+
+/// Execute the trusted program built by the given process builder memento inside the judge
+/// directory, with its stdin redirected to the input file of the current test case. Returns the
+/// output file generated by the program and its exit status. The output file is created outside
+/// the judge directory so that the judgee cannot read it. The file pointer of the returned
+/// `NamedTempFile` is properly reset to the start of the file.
+fn execute_on_input<'a, 'b, 'c>(bdr_mem: &ProcessBuilderMemento,
+    context: &'c TestCaseContext<'a, 'b>) -> Result<(NamedTempFile, ProcessExitStatus)> {
+    let mut bdr = bdr_mem.restore();
+    let judge_dir = context.judge_context.judge_dir.path();
+    bdr.dir.working_dir = Some(judge_dir.to_owned());
+    bdr.dir.root_dir = Some(judge_dir.to_owned());
+
+    let input_file = File::open(&context.test_case.input_file)?;
+    let mut output_file = context.judge_context.create_jury_output_file()?;
+    bdr.redirections.stdin = Some(input_file);
+    bdr.redirections.stdout = Some(output_file.as_file().duplicate()?);
+    bdr.redirections.ignore_stderr()?;
+
+    let mut handle = bdr.start()?;
+    handle.wait_for_exit()?;
+
+    output_file.as_file_mut().seek(SeekFrom::Start(0))?;
+    Ok((output_file, handle.exit_status()))
+}
+
+impl TestCaseExecutor for JudgeEngineExecutor {
+    fn generate_answer<'s, 'a, 'b, 'c>(&'s mut self, context: &'c mut TestCaseContext<'a, 'b>)
+        -> Result<bool> {
+        let generator_bdr = context.judge_context.answer_generator_bdr.as_ref()
+            .expect("failed to unwrap answer generator process builder");
+        let (answer_file, status) = execute_on_input(generator_bdr, context)?;
+        log::trace!("Answer generator exited with status: {:?}", status);
+
+        match status {
+            ProcessExitStatus::Normal(0) => {
+                context.generated_answer = Some(answer_file);
+                Ok(true)
+            },
+            status => {
+                context.result.verdict = Verdict::CheckerFailed;
+                context.result.comment = Some(format!("answer generator failed: {:?}", status));
+                context.result.failure_reason = Some(FailureReason::AnswerGeneratorFailed);
+                Ok(false)
+            }
+        }
+    }
+
+    fn judge_std<'s, 'a, 'b, 'c>(&'s mut self, context: &'c mut TestCaseContext<'a, 'b>)
+        -> Result<()> {
+        let public = context.test_case.public;
+        let output_file = match self.execute_judgee(context, true)? {
+            Some(f)=> f,
+            None => {
+                if public {
+                    let answer_view = io::read_file_view(context.answer_file(), DATA_VIEW_LEN)?;
+                    context.result.answer_view = Some(answer_view);
+                }
+                return Ok(());
+            }
+        };
+
+        let answer_file = File::open(context.answer_file())?;
+        self.check_output(context, answer_file, output_file)
+    }
+
+    fn judge_against_reference<'s, 'a, 'b, 'c>(&'s mut self,
+        context: &'c mut TestCaseContext<'a, 'b>) -> Result<()> {
+        // The judgee is executed first, so that the reference solution need not be executed if the
+        // judgee failed.
+        let output_file = match self.execute_judgee(context, true)? {
+            Some(f) => f,
+            None => return Ok(())
+        };
+
+        let answer_file = match self.execute_reference(context)? {
+            Some(f) => f,
+            None => {
+                if context.test_case.public {
+                    let output_view = io::read_file_view(output_file.path(), DATA_VIEW_LEN)?;
+                    context.result.output_view = Some(output_view);
+                }
+                return Ok(());
+            }
+        };
+
+        self.check_output(context, answer_file.into_file(), output_file)
+    }
+
     fn judge_spj<'s, 'a, 'b, 'c>(&'s mut self, context: &'c mut TestCaseContext<'a, 'b>)
         -> Result<()> {
-        let output_file = match self.execute_judgee(context)? {
+        let mut output_file = match self.execute_judgee(context, false)? {
             Some(f) => f,
             None => return Ok(())
         };
 
-        let mut checker_bdr = context.judge_context.jury_bdr.as_ref()
+        if !self.run_checker_on_empty_output && output_file.as_file().metadata()?.len() == 0 {
+            context.result.verdict = Verdict::WrongAnswer;
+            context.result.comment = Some(String::from("empty output"));
+            return Ok(());
+        }
+
+        let mut checker_bdr = context.judge_context.jury_bdr
             .expect("failed to unwrap jury process builder as checker process builder")
             .restore();
+        let judge_dir = context.judge_context.judge_dir.path();
+        checker_bdr.dir.working_dir = Some(judge_dir.to_owned());
+        checker_bdr.dir.root_dir = Some(judge_dir.to_owned());
 
-        // Add answer checker specific command line arguments to the process builder.
-        // The 3 command line arguments passed to the answer checker are:
-        // 1. fd of the input file of the current test case;
-        // 2. fd of the answer file of the current test case;
-        // 3. fd of the user's output file on the current test case.
-        let input_file = File::open(&context.test_case.input_file)?;
-        let answer_file = File::open(&context.test_case.answer_file)?;
-        checker_bdr.add_arg(format!("\"{}\"", input_file.as_raw_fd()))?;
-        checker_bdr.add_arg(format!("\"{}\"", answer_file.as_raw_fd()))?;
-        checker_bdr.add_arg(format!("\"{}\"", output_file.as_raw_fd()))?;
+        let mut input_file = File::open(&context.test_case.input_file)?;
+        let mut answer_file = File::open(context.answer_file())?;
+        match context.judge_context.task.checker_io_mode {
+            CheckerIoMode::FileDescriptors => {
+                // Add answer checker specific command line arguments to the process builder. The
+                // files are passed to the answer checker by file descriptors, so the answer checker
+                // need not access any path outside of the judge directory.
+                let output_file = output_file.as_file().duplicate()?;
+                let args = checker_fd_args(
+                    input_file.as_raw_fd(), answer_file.as_raw_fd(), output_file.as_raw_fd());
+                for arg in args {
+                    checker_bdr.add_arg(arg)?;
+                }
+
+                checker_bdr.redirections.inherited_files.push(input_file);
+                checker_bdr.redirections.inherited_files.push(answer_file);
+                checker_bdr.redirections.inherited_files.push(output_file);
+            },
+            CheckerIoMode::StdinDelimited => {
+                // Concatenate the 3 streams into a temporary file and redirect it to the stdin of
+                // the answer checker. A file is used instead of a pipe so that the judge does not
+                // block on a checker that stops reading early.
+                let mut checker_input = NamedTempFile::new_in(&context.judge_context.judge_dir)?;
+                io::write_length_prefixed(checker_input.as_file_mut(), &mut input_file)?;
+                io::write_length_prefixed(checker_input.as_file_mut(), &mut answer_file)?;
+                io::write_length_prefixed(checker_input.as_file_mut(), output_file.as_file_mut())?;
+                checker_input.as_file_mut().seek(SeekFrom::Start(0))?;
+                checker_bdr.redirections.stdin = Some(checker_input.into_file());
+            }
+        };
 
-        let (mut comment_read, comment_write) = io::pipe()?;
+        let (comment_read, comment_write) = io::pipe()?;
         checker_bdr.redirections.stdout = Some(comment_write);
 
-        // Start the checker process.
+        // Start the checker process. The comment is read while the checker is running so that a
+        // checker writing a long comment does not block on the full pipe. The comment is discarded
+        // if the checker does not exit normally.
         let mut checker_handle = checker_bdr.start()?;
+        let comment_reader = BackgroundReader::start(comment_read, MAX_MESSAGE_LEN);
         checker_handle.wait_for_exit()?;
+        let comment = comment_reader.finish()?;
         log::trace!("Answer checker exited with status: {:?}", checker_handle.exit_status());
 
         let status = checker_handle.exit_status();
+        context.result.checker_exit_status = Some(status.clone());
+        context.result.failure_reason = FailureReason::of_checker(&status);
         match status {
             ProcessExitStatus::Normal(..) => {
-                // Read the checker's comment.
-                let mut comment = String::new();
-                comment_read.read_to_string(&mut comment)?;
-
                 match status {
                     ProcessExitStatus::Normal(0) => {
                         // Accepted.
@@ -645,6 +2009,14 @@ impl TestCaseExecutor for JudgeEngineExecutor {
                 context.result.verdict = Verdict::CheckerFailed;
                 context.result.comment = Some(String::from("checker invokes banned system call"));
             },
+            ProcessExitStatus::SyscallBudgetExceeded => {
+                context.result.verdict = Verdict::CheckerFailed;
+                context.result.comment = Some(String::from("checker system call budget exceeded"));
+            },
+            ProcessExitStatus::DiskQuotaExceeded => {
+                context.result.verdict = Verdict::CheckerFailed;
+                context.result.comment = Some(String::from("checker disk quota exceeded"));
+            },
             _ => unreachable!()
         };
 
@@ -653,6 +2025,2163 @@ impl TestCaseExecutor for JudgeEngineExecutor {
 
     fn judge_interactive<'s, 'a, 'b, 'c>(&'s mut self, context: &'c mut TestCaseContext<'a, 'b>)
         -> Result<()> {
-        unimplemented!()
+        // The judgee and the interactor talk to each other through a pair of pipes.
+        let (judgee_stdin, interactor_stdout) = io::pipe()?;
+        let (interactor_stdin, judgee_stdout) = io::pipe()?;
+
+        let mut judgee_bdr = context.judge_context.judgee_bdr.restore();
+        judgee_bdr.redirections.stdin = Some(judgee_stdin);
+        judgee_bdr.redirections.stdout = Some(judgee_stdout);
+        let error_file = self.redirect_judgee_stderr(&mut judgee_bdr, context)?;
+
+        let mut interactor_bdr = context.judge_context.jury_bdr
+            .expect("failed to unwrap jury process builder as interactor process builder")
+            .restore();
+        let judge_dir = context.judge_context.judge_dir.path();
+        interactor_bdr.dir.working_dir = Some(judge_dir.to_owned());
+        interactor_bdr.dir.root_dir = Some(judge_dir.to_owned());
+        interactor_bdr.redirections.stdin = Some(interactor_stdin);
+        interactor_bdr.redirections.stdout = Some(interactor_stdout);
+        interactor_bdr.redirections.ignore_stderr()?;
+
+        // The input file and the answer file are passed to the interactor by file descriptors, so
+        // the interactor need not access any path outside of the judge directory.
+        let input_file = File::open(&context.test_case.input_file)?;
+        let answer_file = File::open(context.answer_file())?;
+        let (report_read, report_write) = io::pipe()?;
+        let args = interactor_fd_args(
+            input_file.as_raw_fd(), answer_file.as_raw_fd(), report_write.as_raw_fd());
+        for arg in args {
+            interactor_bdr.add_arg(arg)?;
+        }
+        interactor_bdr.redirections.inherited_files.push(input_file);
+        interactor_bdr.redirections.inherited_files.push(answer_file);
+        interactor_bdr.redirections.inherited_files.push(report_write);
+
+        // The ends of the pipes are closed in the judge once the processes are started, so that
+        // each process reads EOF after the other one exits. The report is read while the
+        // interactor is running so that an interactor writing a long report does not block on the
+        // full pipe.
+        let mut interactor_handle = interactor_bdr.start()?;
+        let report_reader = BackgroundReader::start(report_read, MAX_MESSAGE_LEN);
+        let mut judgee_handle = judgee_bdr.start()?;
+
+        judgee_handle.wait_for_exit()?;
+        log::trace!("Judgee exited with status: {:?}", judgee_handle.exit_status());
+        interactor_handle.wait_for_exit()?;
+        log::trace!("Interactor exited with status: {:?}", interactor_handle.exit_status());
+        let report = report_reader.finish()?;
+
+        self.set_judgee_exit_status(context, judgee_handle.exit_status(), error_file.as_ref())?;
+        context.result.set_interactor_result(interactor_handle.exit_status(), &report);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Compile the given C source code into a statically linked program at `dest`. Programs
+    /// running with the judge directory as their root directory, e.g. checkers and interactors,
+    /// must be linked statically since no shared libraries are available there.
+    fn compile_static_program(source: &str, dest: &Path) {
+        let source_file = dest.with_extension("c");
+        std::fs::write(&source_file, source).unwrap();
+        let status = std::process::Command::new("gcc")
+            .arg("-static")
+            .arg("-o").arg(dest)
+            .arg(&source_file)
+            .status()
+            .unwrap();
+        assert!(status.success());
+    }
+
+    /// The role of the jury program in `judge_with_static_jury`.
+    #[derive(Clone, Copy, Debug)]
+    enum StaticJury {
+        /// The jury program is an answer checker in special judge mode.
+        Checker,
+
+        /// The jury program is an interactor in interactive judge mode.
+        Interactor,
+
+        /// The jury program is a reference solution in reference judge mode.
+        Reference,
+
+        /// The jury program is an answer generator in standard judge mode.
+        AnswerGenerator,
+    }
+
+    /// Judge a shell script judgee on the given test suite with a jury program compiled from the
+    /// given C source code, which plays the given role. The default built-in checker is used where
+    /// the jury program is not a checker. Both programs get a real time limit of 5 seconds, which
+    /// can be changed together with other settings of the jury program and the executor by
+    /// `setup`.
+    fn judge_with_static_jury<F>(jury_source: &str, judgee_script: &str, role: StaticJury,
+        test_suite: Vec<TestCaseDescriptor>, setup: F) -> JudgeResult
+        where F: FnOnce(&mut ProcessBuilder, &mut JudgeEngineExecutor) {
+        // The jury program runs with the judge directory as its root directory, so it is linked
+        // statically and copied to the same path under the judge directory.
+        let jury_dir = tempfile::tempdir().unwrap();
+        let jury = jury_dir.path().join("jury");
+        compile_static_program(jury_source, &jury);
+
+        let judge_dir = tempfile::tempdir().unwrap();
+        let chrooted_jury = judge_dir.path().join(jury.strip_prefix("/").unwrap());
+        std::fs::create_dir_all(chrooted_jury.parent().unwrap()).unwrap();
+        std::fs::copy(&jury, &chrooted_jury).unwrap();
+
+        let lang = LanguageIdentifier::new(
+            "c", crate::languages::LanguageBranch::new("gnu", "c11"));
+        let jury_program = Program::new(jury.clone(), lang.clone());
+        let mut task = JudgeTaskDescriptor::new(Program::new("/bin/sh", lang));
+        match role {
+            StaticJury::Checker => task.mode = JudgeMode::SpecialJudge(jury_program),
+            StaticJury::Interactor => task.mode = JudgeMode::Interactive(jury_program),
+            StaticJury::Reference => task.mode = JudgeMode::AgainstReference {
+                reference: jury_program,
+                checker: BuiltinCheckers::Default,
+            },
+            StaticJury::AnswerGenerator => task.answer_generator = Some(jury_program),
+        };
+        task.test_suite = test_suite;
+
+        let mut judgee_bdr = ProcessBuilder::new("/bin/sh");
+        judgee_bdr.add_arg("-c").unwrap();
+        judgee_bdr.add_arg(judgee_script).unwrap();
+        judgee_bdr.dir.working_dir = Some(judge_dir.path().to_owned());
+        judgee_bdr.limits.real_time_limit = Some(Duration::from_secs(5));
+        let mut jury_bdr = ProcessBuilder::new(&jury);
+        jury_bdr.limits.real_time_limit = Some(Duration::from_secs(5));
+        let mut executor = JudgeEngineExecutor::new(true);
+        setup(&mut jury_bdr, &mut executor);
+
+        let jury_mem = jury_bdr.memento();
+        let context = match role {
+            StaticJury::AnswerGenerator => {
+                let mut context = JudgeContext::standard(&task, &task.test_suite, judge_dir,
+                    judgee_bdr.memento(), checkers::get_checker(BuiltinCheckers::Default));
+                context.answer_generator_bdr = Some(jury_mem.clone());
+                context
+            },
+            _ => {
+                let mut context = JudgeContext::with_jury(&task, &task.test_suite, judge_dir,
+                    judgee_bdr.memento(), &jury_mem);
+                if let StaticJury::Reference = role {
+                    context.builtin_checker =
+                        Some(checkers::get_checker(BuiltinCheckers::Default));
+                }
+                context
+            }
+        };
+
+        context.execute(&mut executor).unwrap()
+    }
+
+    mod jury_env {
+        use super::*;
+
+        fn has_env(bdr: &ProcessBuilder, name: &str, value: &str) -> bool {
+            bdr.envs().iter().any(|(n, v)| n == name && v == value)
+        }
+
+        #[test]
+        fn passed_to_jury_only() {
+            let engine = JudgeEngine::new();
+            let jury_env = vec![(String::from("EPS"), String::from("1e-9"))];
+
+            let mut jury_bdr = ProcessBuilder::new("/bin/true");
+            engine.apply_jury_bdr_config(&mut jury_bdr, &jury_env).unwrap();
+            assert!(has_env(&jury_bdr, "EPS", "1e-9"));
+            assert!(has_env(&jury_bdr, "ONLINE_JUDGE", "YES"));
+
+            let mut judgee_bdr = ProcessBuilder::new("/bin/true");
+            engine.apply_judgee_bdr_config(&mut judgee_bdr).unwrap();
+            assert!(!judgee_bdr.envs().iter().any(|(n, _)| n == "EPS"));
+        }
+
+        #[test]
+        fn default_judge_env() {
+            let engine = JudgeEngine::new();
+
+            let mut jury_bdr = ProcessBuilder::new("/bin/true");
+            engine.apply_jury_bdr_config(&mut jury_bdr, &[]).unwrap();
+            assert!(has_env(&jury_bdr, "LC_ALL", "C"));
+
+            let mut judgee_bdr = ProcessBuilder::new("/bin/true");
+            engine.apply_judgee_bdr_config(&mut judgee_bdr).unwrap();
+            assert!(has_env(&judgee_bdr, "LC_ALL", "C"));
+        }
+
+        #[test]
+        fn judge_env_overridden() {
+            let mut config = JudgeEngineConfig::new();
+            config.judge_env = vec![(String::from("LC_ALL"), String::from("en_US.UTF-8"))];
+            let engine = JudgeEngine::with_config(config);
+
+            let mut judgee_bdr = ProcessBuilder::new("/bin/true");
+            engine.apply_judgee_bdr_config(&mut judgee_bdr).unwrap();
+            assert!(has_env(&judgee_bdr, "LC_ALL", "en_US.UTF-8"));
+            assert!(!has_env(&judgee_bdr, "LC_ALL", "C"));
+
+            let engine = JudgeEngine::new();
+            let jury_env = vec![(String::from("LC_ALL"), String::from("de_DE.UTF-8"))];
+            let mut jury_bdr = ProcessBuilder::new("/bin/true");
+            engine.apply_jury_bdr_config(&mut jury_bdr, &jury_env).unwrap();
+            assert!(has_env(&jury_bdr, "LC_ALL", "de_DE.UTF-8"));
+            assert!(!has_env(&jury_bdr, "LC_ALL", "C"));
+        }
+
+        #[test]
+        fn invalid_pair() {
+            let engine = JudgeEngine::new();
+            let jury_env = vec![(String::from("EPS"), String::from("1e-9\0"))];
+
+            let mut jury_bdr = ProcessBuilder::new("/bin/true");
+            assert!(engine.apply_jury_bdr_config(&mut jury_bdr, &jury_env).is_err());
+        }
+    }
+
+    mod judgee_limits {
+        use super::*;
+
+        #[test]
+        fn default_open_files_limit() {
+            let engine = JudgeEngine::new();
+            let mut judgee_bdr = ProcessBuilder::new("/bin/true");
+            engine.apply_judgee_bdr_config(&mut judgee_bdr).unwrap();
+            assert_eq!(Some(DEFAULT_JUDGEE_OPEN_FILES_LIMIT), judgee_bdr.limits.open_files_limit);
+        }
+
+        #[test]
+        fn memory_limit_policy() {
+            let engine = JudgeEngine::new();
+            let mut judgee_bdr = ProcessBuilder::new("/bin/true");
+            engine.apply_judgee_bdr_config(&mut judgee_bdr).unwrap();
+            assert_eq!(MemoryLimitPolicy::Daemon, judgee_bdr.memory_limit_policy);
+
+            let mut builder = JudgeEngineConfigBuilder::new();
+            builder.judgee_memory_limit_policy(MemoryLimitPolicy::Native);
+            let engine = JudgeEngine::with_config(builder.build().unwrap());
+            let mut judgee_bdr = ProcessBuilder::new("/bin/true");
+            engine.apply_judgee_bdr_config(&mut judgee_bdr).unwrap();
+            assert_eq!(MemoryLimitPolicy::Native, judgee_bdr.memory_limit_policy);
+        }
+    }
+
+    mod jury_limits {
+        use super::*;
+
+        #[test]
+        fn configured_limits_applied() {
+            let mut config = JudgeEngineConfig::new();
+            config.jury_cpu_time_limit = Some(Duration::from_secs(3));
+            config.jury_real_time_limit = Some(Duration::from_secs(5));
+            config.jury_memory_limit = Some(MemorySize::MegaBytes(256));
+            let engine = JudgeEngine::with_config(config);
+
+            let mut jury_bdr = ProcessBuilder::new("/bin/true");
+            engine.apply_jury_bdr_config(&mut jury_bdr, &[]).unwrap();
+            assert_eq!(Some(Duration::from_secs(3)), jury_bdr.limits.cpu_time_limit);
+            assert_eq!(Some(Duration::from_secs(5)), jury_bdr.limits.real_time_limit);
+            assert_eq!(Some(MemorySize::MegaBytes(256)), jury_bdr.limits.memory_limit);
+        }
+
+        #[test]
+        fn cpu_time_limit_applied_alone() {
+            let mut config = JudgeEngineConfig::new();
+            config.jury_cpu_time_limit = Some(Duration::from_secs(2));
+            let engine = JudgeEngine::with_config(config);
+
+            let mut jury_bdr = ProcessBuilder::new("/bin/true");
+            engine.apply_jury_bdr_config(&mut jury_bdr, &[]).unwrap();
+            assert_eq!(Some(Duration::from_secs(2)), jury_bdr.limits.cpu_time_limit);
+            assert_eq!(None, jury_bdr.limits.real_time_limit);
+        }
+
+        #[test]
+        fn unconfigured_limits_kept() {
+            let engine = JudgeEngine::new();
+
+            let mut jury_bdr = ProcessBuilder::new("/bin/true");
+            jury_bdr.limits.cpu_time_limit = Some(Duration::from_secs(1));
+            engine.apply_jury_bdr_config(&mut jury_bdr, &[]).unwrap();
+            assert_eq!(Some(Duration::from_secs(1)), jury_bdr.limits.cpu_time_limit);
+        }
+    }
+
+    mod config_builder {
+        use super::*;
+
+        fn assert_invalid<T>(res: Result<T>) {
+            match res.err().expect("expect an invalid configuration").kind() {
+                ErrorKind::InvalidEngineConfig(..) => (),
+                kind => panic!("unexpected error kind: {:?}", kind),
+            };
+        }
+
+        #[test]
+        fn default_config() {
+            let config = JudgeEngineConfigBuilder::new().build().unwrap();
+            assert_eq!(JudgeEngineConfig::new().judge_env, config.judge_env);
+            assert!(config.capture_judgee_stderr);
+            assert_eq!(None, config.jury_cpu_time_limit);
+        }
+
+        #[test]
+        fn valid_config() {
+            let mut builder = JudgeEngineConfigBuilder::new();
+            builder.jury_cpu_time_limit(Duration::from_secs(1)).unwrap()
+                .jury_real_time_limit(Duration::from_secs(2)).unwrap()
+                .max_test_cases(10).unwrap()
+                .truncate_test_suite(true)
+                .capture_judgee_stderr(false);
+            let config = builder.build().unwrap();
+
+            assert_eq!(Some(Duration::from_secs(1)), config.jury_cpu_time_limit);
+            assert_eq!(Some(Duration::from_secs(2)), config.jury_real_time_limit);
+            assert_eq!(Some(10), config.max_test_cases);
+            assert!(config.truncate_test_suite);
+            assert!(!config.capture_judgee_stderr);
+        }
+
+        #[test]
+        fn invalid_values_rejected() {
+            let mut builder = JudgeEngineConfigBuilder::new();
+            assert_invalid(builder.jury_cpu_time_limit(Duration::new(0, 0)));
+            assert_invalid(builder.jury_memory_limit(MemorySize::MegaBytes(0)));
+            assert_invalid(builder.judgee_disk_quota(MemorySize::Bytes(0)));
+            assert_invalid(builder.max_test_cases(0));
+            assert_invalid(builder.judgee_open_files_limit(2));
+            assert_invalid(builder.judgee_nice(20));
+            assert_invalid(builder.judgee_io_priority(IoPriority::BestEffort(8)));
+            assert_invalid(builder.judge_env(vec![(String::from("A=B"), String::from("C"))]));
+        }
+
+        #[test]
+        fn inconsistent_values_rejected() {
+            let mut builder = JudgeEngineConfigBuilder::new();
+            builder.jury_cpu_time_limit(Duration::from_secs(2)).unwrap()
+                .jury_real_time_limit(Duration::from_secs(1)).unwrap();
+            assert_invalid(builder.build());
+
+            let mut builder = JudgeEngineConfigBuilder::new();
+            builder.truncate_test_suite(true);
+            assert_invalid(builder.build());
+
+            let mut builder = JudgeEngineConfigBuilder::new();
+            builder.judge_dir("/nonexistent/judge/dir");
+            assert_invalid(builder.build());
+        }
+
+        #[test]
+        fn trusted_jury_with_whitelist_accepted() {
+            let mut builder = JudgeEngineConfigBuilder::new();
+            builder.trust_jury(true)
+                .jury_syscall_whitelist(vec![SystemCall::from_name("read").unwrap()]);
+            let config = builder.build().unwrap();
+            assert!(config.trust_jury);
+            assert_eq!(1, config.jury_syscall_whitelist.len());
+        }
+
+        #[test]
+        #[cfg(feature = "serde")]
+        fn deserialized_config_validated() {
+            let mut config = JudgeEngineConfig::new();
+            config.max_test_cases = Some(10);
+            config.truncate_test_suite = true;
+            let data = serde_json::to_string(&config).unwrap();
+            let config: JudgeEngineConfig = serde_json::from_str(&data).unwrap();
+            assert_eq!(Some(10), config.max_test_cases);
+
+            let mut config = JudgeEngineConfig::new();
+            config.truncate_test_suite = true;
+            let data = serde_json::to_string(&config).unwrap();
+            assert!(serde_json::from_str::<JudgeEngineConfig>(&data).is_err());
+        }
+    }
+
+    mod trust_jury {
+        use super::*;
+
+        /// Run a jury process that creates a pipe, which is not on the jury system call whitelist.
+        fn run_jury(trust_jury: bool) -> ProcessExitStatus {
+            let mut config = JudgeEngineConfig::new();
+            // The system calls needed to run `/bin/true` only.
+            config.jury_syscall_whitelist = ProcessBuilder::new("/bin/true")
+                .trace_syscalls().unwrap()
+                .syscalls;
+            config.trust_jury = trust_jury;
+            let engine = JudgeEngine::with_config(config);
+
+            let mut jury_bdr = ProcessBuilder::new("/bin/sh");
+            jury_bdr.add_arg("-c").unwrap();
+            jury_bdr.add_arg("/bin/true | /bin/true").unwrap();
+            engine.apply_jury_bdr_config(&mut jury_bdr, &[]).unwrap();
+
+            let mut process = jury_bdr.start().unwrap();
+            process.wait_for_exit().unwrap();
+            process.exit_status()
+        }
+
+        #[test]
+        fn banned_syscall_killed() {
+            assert_ne!(Some(0), run_jury(false).exit_code());
+        }
+
+        #[test]
+        fn banned_syscall_allowed_when_trusted() {
+            assert_eq!(Some(0), run_jury(true).exit_code());
+        }
+    }
+
+    mod judge_batch {
+        use super::*;
+
+        use std::sync::Mutex;
+
+        use crate::languages::{LanguageBranch, LanguageProviderMetadata};
+
+        /// A language provider that records the kind of every program it is asked to execute.
+        pub(super) struct RecordingLanguageProvider {
+            pub(super) metadata: &'static LanguageProviderMetadata,
+            pub(super) executed: Arc<Mutex<Vec<ProgramKind>>>,
+        }
+
+        impl LanguageProvider for RecordingLanguageProvider {
+            fn metadata(&self) -> &'static LanguageProviderMetadata {
+                self.metadata
+            }
+
+            fn compile(&self, _program: &Program, _kind: ProgramKind, _output_dir: Option<PathBuf>)
+                -> std::result::Result<CompilationInfo, Box<dyn std::error::Error>> {
+                unreachable!()
+            }
+
+            fn execute(&self, program: &Program, kind: ProgramKind)
+                -> std::result::Result<ExecutionInfo, Box<dyn std::error::Error>> {
+                self.executed.lock().unwrap().push(kind);
+                Ok(ExecutionInfo::new(program.file.clone()))
+            }
+        }
+
+        pub(super) fn language() -> LanguageIdentifier {
+            LanguageIdentifier::new("recording", LanguageBranch::new("test", "1"))
+        }
+
+        #[test]
+        fn jury_built_once() {
+            let mut metadata = LanguageProviderMetadata::new("recording", true);
+            metadata.branches.push(language().branch().clone());
+            let executed = Arc::new(Mutex::new(Vec::new()));
+
+            let engine = JudgeEngine::new();
+            engine.languages().register(Box::new(RecordingLanguageProvider {
+                metadata: Box::leak(Box::new(metadata)),
+                executed: executed.clone(),
+            }));
+
+            let programs = vec![
+                Program::new("/bin/true", language()),
+                Program::new("/bin/false", language()),
+            ];
+            let checker = Program::new("/bin/true", language());
+            let results = engine.judge_batch(
+                programs, JudgeMode::SpecialJudge(checker), ResourceLimits::default(), Vec::new(),
+                Vec::new())
+                .unwrap();
+
+            assert_eq!(2, results.len());
+            assert_eq!(
+                vec![ProgramKind::Checker, ProgramKind::Judgee, ProgramKind::Judgee],
+                *executed.lock().unwrap());
+        }
+
+        #[test]
+        fn jury_env_applied() {
+            let mut metadata = LanguageProviderMetadata::new("recording", true);
+            metadata.branches.push(language().branch().clone());
+
+            let engine = JudgeEngine::new();
+            engine.languages().register(Box::new(RecordingLanguageProvider {
+                metadata: Box::leak(Box::new(metadata)),
+                executed: Arc::new(Mutex::new(Vec::new())),
+            }));
+
+            let programs = vec![Program::new("/bin/true", language())];
+            let checker = Program::new("/bin/true", language());
+            let jury_env = vec![(String::from("EPS"), String::from("1e-9\0"))];
+            assert!(engine.judge_batch(
+                programs, JudgeMode::SpecialJudge(checker), ResourceLimits::default(), Vec::new(),
+                jury_env)
+                .is_err());
+        }
+    }
+
+    mod max_test_cases {
+        use super::*;
+
+        fn make_test_suite(count: usize) -> Vec<TestCaseDescriptor> {
+            (0..count)
+                .map(|i| TestCaseDescriptor::new(format!("{}.in", i), format!("{}.ans", i)))
+                .collect()
+        }
+
+        #[test]
+        fn unlimited() {
+            let engine = JudgeEngine::new();
+            let test_suite = make_test_suite(3);
+            assert_eq!(3, engine.limit_test_suite(&test_suite).unwrap().len());
+        }
+
+        #[test]
+        fn rejected() {
+            let mut config = JudgeEngineConfig::new();
+            config.max_test_cases = Some(2);
+            let engine = JudgeEngine::with_config(config);
+
+            assert_eq!(2, engine.limit_test_suite(&make_test_suite(2)).unwrap().len());
+            let err = engine.limit_test_suite(&make_test_suite(3)).unwrap_err();
+            match err.kind() {
+                ErrorKind::TooManyTestCases(3, 2) => (),
+                kind => panic!("unexpected error kind: {:?}", kind),
+            };
+        }
+
+        #[test]
+        fn truncated() {
+            let mut config = JudgeEngineConfig::new();
+            config.max_test_cases = Some(2);
+            config.truncate_test_suite = true;
+            let engine = JudgeEngine::with_config(config);
+
+            let test_suite = make_test_suite(3);
+            let limited = engine.limit_test_suite(&test_suite).unwrap();
+            assert_eq!(2, limited.len());
+            assert_eq!(PathBuf::from("1.in"), limited[1].input_file);
+        }
+    }
+
+    mod judgee_stderr {
+        use super::*;
+
+        use std::io::Write;
+
+        fn make_file(dir: &TempDir, name: &str, content: &str) -> PathBuf {
+            let path = dir.path().join(name);
+            File::create(&path).unwrap().write_all(content.as_bytes()).unwrap();
+            path
+        }
+
+        fn judge_crashing_judgee(capture_stderr: bool, public: bool) -> TestCaseResult {
+            let data_dir = tempfile::tempdir().unwrap();
+            let mut task = JudgeTaskDescriptor::new(Program::new("/bin/sh",
+                LanguageIdentifier::new("sh", crate::languages::LanguageBranch::new("sh", "1"))));
+            let mut test_case = TestCaseDescriptor::new(
+                make_file(&data_dir, "input", "1\n"),
+                make_file(&data_dir, "answer", "1\n"));
+            test_case.public = public;
+            task.test_suite.push(test_case);
+
+            // The judge directory is not used as the root directory here so that the shell is
+            // available to the judgee.
+            let mut judgee_bdr = ProcessBuilder::new("/bin/sh");
+            judgee_bdr.add_arg("-c").unwrap();
+            judgee_bdr.add_arg("echo 'division by zero' >&2; kill -SEGV $$").unwrap();
+            let context = JudgeContext::standard(&task, &task.test_suite,
+                tempfile::tempdir().unwrap(), judgee_bdr.memento(),
+                checkers::get_checker(BuiltinCheckers::Default));
+
+            let mut executor = JudgeEngineExecutor::new(capture_stderr);
+            let mut res = context.execute(&mut executor).unwrap();
+            res.test_suite.pop().unwrap()
+        }
+
+        #[test]
+        fn captured() {
+            let res = judge_crashing_judgee(true, true);
+            match res.verdict {
+                Verdict::RuntimeError => (),
+                verdict => panic!("unexpected verdict: {:?}", verdict),
+            };
+            assert_eq!(Some(String::from("division by zero\n")), res.error_view);
+        }
+
+        #[test]
+        fn discarded() {
+            let res = judge_crashing_judgee(false, true);
+            assert_eq!(None, res.error_view);
+        }
+
+        #[test]
+        fn hidden_test_case() {
+            let res = judge_crashing_judgee(true, false);
+            match res.verdict {
+                Verdict::RuntimeError => (),
+                verdict => panic!("unexpected verdict: {:?}", verdict),
+            };
+            assert_eq!(None, res.input_view);
+            assert_eq!(None, res.answer_view);
+            assert_eq!(None, res.output_view);
+            assert_eq!(None, res.error_view);
+        }
+
+        #[test]
+        fn public_test_case() {
+            let res = judge_crashing_judgee(true, true);
+            assert_eq!(Some(String::from("1\n")), res.input_view);
+            assert_eq!(Some(String::from("1\n")), res.answer_view);
+            assert_eq!(Some(String::new()), res.output_view);
+        }
+    }
+
+    mod total_judge_time_limit {
+        use super::*;
+
+        /// A `TestCaseExecutor` that sleeps for a fixed duration on every test case.
+        struct SleepingExecutor {
+            duration: Duration,
+        }
+
+        impl TestCaseExecutor for SleepingExecutor {
+            fn generate_answer<'s, 'a, 'b, 'c>(&'s mut self,
+                _context: &'c mut TestCaseContext<'a, 'b>) -> Result<bool> {
+                unreachable!()
+            }
+
+            fn judge_std<'s, 'a, 'b, 'c>(&'s mut self, _context: &'c mut TestCaseContext<'a, 'b>)
+                -> Result<()> {
+                std::thread::sleep(self.duration);
+                Ok(())
+            }
+
+            fn judge_spj<'s, 'a, 'b, 'c>(&'s mut self, _context: &'c mut TestCaseContext<'a, 'b>)
+                -> Result<()> {
+                unreachable!()
+            }
+
+            fn judge_interactive<'s, 'a, 'b, 'c>(&'s mut self,
+                _context: &'c mut TestCaseContext<'a, 'b>) -> Result<()> {
+                unreachable!()
+            }
+
+            fn judge_against_reference<'s, 'a, 'b, 'c>(&'s mut self,
+                _context: &'c mut TestCaseContext<'a, 'b>) -> Result<()> {
+                unreachable!()
+            }
+        }
+
+        fn judge(time_limit: Option<Duration>) -> JudgeResult {
+            let task = JudgeTaskDescriptor::new(Program::new("/bin/true",
+                LanguageIdentifier::new("sh", crate::languages::LanguageBranch::new("sh", "1"))));
+            let test_suite: Vec<TestCaseDescriptor> = (0..5)
+                .map(|i| TestCaseDescriptor::new(format!("{}.in", i), format!("{}.ans", i)))
+                .collect();
+
+            let mut context = JudgeContext::standard(&task, &test_suite,
+                tempfile::tempdir().unwrap(), ProcessBuilder::new("/bin/true").memento(),
+                checkers::get_checker(BuiltinCheckers::Default));
+            context.time_limit = time_limit;
+
+            let mut executor = SleepingExecutor { duration: Duration::from_millis(40) };
+            context.execute(&mut executor).unwrap()
+        }
+
+        #[test]
+        fn exceeded() {
+            let res = judge(Some(Duration::from_millis(100)));
+            match res.verdict {
+                Verdict::JudgeTimeLimitExceeded => (),
+                verdict => panic!("unexpected verdict: {:?}", verdict),
+            };
+            assert!(res.test_suite.len() < 5);
+            assert!(res.comment.is_some());
+        }
+
+        #[test]
+        fn unlimited() {
+            let res = judge(None);
+            assert!(res.verdict.is_accepted());
+            assert_eq!(5, res.test_suite.len());
+            assert!(res.comment.is_none());
+        }
+
+        #[test]
+        fn task_budget_passed_through() {
+            let mut task = JudgeTaskDescriptor::new(Program::new("/bin/true",
+                LanguageIdentifier::new("sh", crate::languages::LanguageBranch::new("sh", "1"))));
+            task.time_limit = Some(Duration::from_millis(300));
+
+            let engine = JudgeEngine::new();
+            assert_eq!(Some(Duration::from_millis(300)), engine.judge_time_limit(&task));
+
+            let mut builder = JudgeEngineConfigBuilder::new();
+            builder.total_judge_time_limit(Duration::from_millis(200)).unwrap();
+            let engine = JudgeEngine::with_config(builder.build().unwrap());
+            assert_eq!(Some(Duration::from_millis(200)), engine.judge_time_limit(&task));
+
+            let mut builder = JudgeEngineConfigBuilder::new();
+            builder.total_judge_time_limit(Duration::from_secs(10)).unwrap();
+            let engine = JudgeEngine::with_config(builder.build().unwrap());
+            assert_eq!(Some(Duration::from_millis(300)), engine.judge_time_limit(&task));
+
+            task.time_limit = None;
+            assert_eq!(Some(Duration::from_secs(10)), engine.judge_time_limit(&task));
+        }
+    }
+
+    mod subtask_skipping {
+        use super::*;
+
+        use crate::Subtask;
+
+        /// A `TestCaseExecutor` that records the judged test cases and rejects the test cases at
+        /// the given indices.
+        struct RejectingExecutor {
+            rejected: Vec<usize>,
+            judged: Vec<usize>,
+        }
+
+        impl TestCaseExecutor for RejectingExecutor {
+            fn generate_answer<'s, 'a, 'b, 'c>(&'s mut self,
+                _context: &'c mut TestCaseContext<'a, 'b>) -> Result<bool> {
+                unreachable!()
+            }
+
+            fn judge_std<'s, 'a, 'b, 'c>(&'s mut self, context: &'c mut TestCaseContext<'a, 'b>)
+                -> Result<()> {
+                let index: usize = context.test_case.input_file.file_stem().unwrap()
+                    .to_str().unwrap().parse().unwrap();
+                self.judged.push(index);
+                if self.rejected.contains(&index) {
+                    context.result.verdict = Verdict::WrongAnswer;
+                }
+                Ok(())
+            }
+
+            fn judge_spj<'s, 'a, 'b, 'c>(&'s mut self, _context: &'c mut TestCaseContext<'a, 'b>)
+                -> Result<()> {
+                unreachable!()
+            }
+
+            fn judge_interactive<'s, 'a, 'b, 'c>(&'s mut self,
+                _context: &'c mut TestCaseContext<'a, 'b>) -> Result<()> {
+                unreachable!()
+            }
+
+            fn judge_against_reference<'s, 'a, 'b, 'c>(&'s mut self,
+                _context: &'c mut TestCaseContext<'a, 'b>) -> Result<()> {
+                unreachable!()
+            }
+        }
+
+        /// Judge a task of 6 test cases with the given subtasks, rejecting the test cases at the
+        /// given indices. Returns the judge result and the indices of the judged test cases.
+        fn judge(subtasks: Vec<Subtask>, rejected: &[usize]) -> (JudgeResult, Vec<usize>) {
+            judge_streaming(subtasks, rejected, &mut |_| ())
+        }
+
+        /// Same as `judge`, reporting the progress of the judge task to `on_progress`.
+        fn judge_streaming(subtasks: Vec<Subtask>, rejected: &[usize],
+            on_progress: &mut dyn FnMut(&JudgeProgress)) -> (JudgeResult, Vec<usize>) {
+            let mut task = JudgeTaskDescriptor::new(Program::new("/bin/true",
+                LanguageIdentifier::new("sh", crate::languages::LanguageBranch::new("sh", "1"))));
+            task.test_suite = (0..6)
+                .map(|i| TestCaseDescriptor::new(format!("{}.in", i), format!("{}.ans", i)))
+                .collect();
+            task.subtasks = subtasks;
+
+            let context = JudgeContext::standard(&task, &task.test_suite,
+                tempfile::tempdir().unwrap(), ProcessBuilder::new("/bin/true").memento(),
+                checkers::get_checker(BuiltinCheckers::Default));
+            let mut executor = RejectingExecutor {
+                rejected: rejected.to_vec(),
+                judged: Vec::new(),
+            };
+            let res = context.execute_streaming(&mut executor, on_progress).unwrap();
+            (res, executor.judged)
+        }
+
+        #[test]
+        fn all_accepted() {
+            let subtasks = vec![Subtask::new(1, vec![0, 1, 2], 40.0),
+                Subtask::new(2, vec![3, 4, 5], 60.0)];
+            let (res, judged) = judge(subtasks, &[]);
+            assert!(res.verdict.is_accepted());
+            assert_eq!(vec![0, 1, 2, 3, 4, 5], judged);
+            assert_eq!(100.0, res.subtask_score());
+        }
+
+        #[test]
+        fn skipped_on_failure() {
+            let subtasks = vec![Subtask::new(1, vec![0, 1, 2], 40.0),
+                Subtask::new(2, vec![3, 4, 5], 60.0)];
+            let (res, judged) = judge(subtasks, &[1]);
+            assert_eq!(Verdict::WrongAnswer, res.verdict);
+            assert_eq!(vec![0, 1, 3, 4, 5], judged);
+            assert_eq!(6, res.test_suite.len());
+            assert_eq!(Verdict::Skipped, res.test_suite[2].verdict);
+
+            assert_eq!(Verdict::WrongAnswer, res.subtasks[0].verdict);
+            assert_eq!(0.0, res.subtasks[0].score);
+            assert_eq!(Verdict::Accepted, res.subtasks[1].verdict);
+            assert_eq!(60.0, res.subtask_score());
+        }
+
+        #[test]
+        fn progress_reported_per_test_case() {
+            let subtasks = vec![Subtask::new(1, vec![0, 1, 2], 40.0),
+                Subtask::new(2, vec![3, 4, 5], 60.0)];
+            let mut reports = Vec::new();
+            judge_streaming(subtasks, &[1], &mut |progress| reports.push(*progress));
+
+            assert_eq!(6, reports.len());
+            for (index, progress) in reports.iter().enumerate() {
+                assert_eq!(index + 1, progress.test_cases_done);
+                assert_eq!(6, progress.test_cases_total);
+            }
+            assert_eq!(Verdict::Accepted, reports[0].verdict);
+            assert_eq!(Verdict::WrongAnswer, reports[1].verdict);
+            assert_eq!(Verdict::WrongAnswer, reports[5].verdict);
+        }
+
+        #[test]
+        fn dependencies_skipped() {
+            let mut second = Subtask::new(2, vec![2, 3], 30.0);
+            second.dependencies.push(1);
+            let mut third = Subtask::new(3, vec![4, 5], 50.0);
+            third.dependencies.push(2);
+            let subtasks = vec![Subtask::new(1, vec![0, 1], 20.0), second, third];
+
+            let (res, judged) = judge(subtasks, &[0]);
+            assert_eq!(vec![0], judged);
+            assert_eq!(Verdict::WrongAnswer, res.verdict);
+            assert_eq!(vec![Verdict::WrongAnswer, Verdict::Skipped, Verdict::Skipped],
+                res.subtasks.iter().map(|subtask| subtask.verdict).collect::<Vec<_>>());
+            assert_eq!(0.0, res.subtask_score());
+        }
+
+        #[test]
+        fn independent_test_cases_judged() {
+            let (res, judged) = judge(vec![Subtask::new(1, vec![0, 1], 100.0)], &[0]);
+            assert_eq!(vec![0, 2, 3, 4, 5], judged);
+            assert_eq!(Verdict::Skipped, res.test_suite[1].verdict);
+        }
+    }
+
+    mod compile_source {
+        use super::*;
+
+        use crate::languages::{LanguageBranch, LanguageProviderMetadata};
+
+        /// A language provider that compiles C++ source code with `g++`.
+        struct GxxLanguageProvider {
+            metadata: &'static LanguageProviderMetadata,
+
+            /// Path to the compiler executable.
+            compiler: &'static str,
+        }
+
+        impl LanguageProvider for GxxLanguageProvider {
+            fn metadata(&self) -> &'static LanguageProviderMetadata {
+                self.metadata
+            }
+
+            fn source_extension(&self) -> &'static str {
+                "cpp"
+            }
+
+            fn compile(&self, program: &Program, _kind: ProgramKind, output_dir: Option<PathBuf>)
+                -> std::result::Result<CompilationInfo, Box<dyn std::error::Error>> {
+                let output_file = output_dir.unwrap().join("program");
+                let mut ci = CompilationInfo::new(self.compiler, &output_file);
+                ci.compiler.add_arg("-o")?;
+                ci.compiler.add_arg(format!("{}", output_file.display()))?;
+                ci.compiler.add_arg(format!("{}", program.file.display()))?;
+                Ok(ci)
+            }
+
+            fn execute(&self, _program: &Program, _kind: ProgramKind)
+                -> std::result::Result<ExecutionInfo, Box<dyn std::error::Error>> {
+                unreachable!()
+            }
+        }
+
+        pub(super) fn make_engine() -> (JudgeEngine, LanguageIdentifier) {
+            make_engine_with(JudgeEngineConfig::new())
+        }
+
+        fn make_engine_with(config: JudgeEngineConfig) -> (JudgeEngine, LanguageIdentifier) {
+            make_engine_with_compiler(config, "/usr/bin/g++")
+        }
+
+        fn make_engine_with_compiler(config: JudgeEngineConfig, compiler: &'static str)
+            -> (JudgeEngine, LanguageIdentifier) {
+            let language = LanguageIdentifier::new("cpp", LanguageBranch::new("gnu", "c++11"));
+            let mut metadata = LanguageProviderMetadata::new("cpp", false);
+            metadata.branches.push(language.branch().clone());
+
+            let engine = JudgeEngine::with_config(config);
+            engine.languages().register(Box::new(GxxLanguageProvider {
+                metadata: Box::leak(Box::new(metadata)),
+                compiler,
+            }));
+            (engine, language)
+        }
+
+        #[test]
+        fn compiled() {
+            let (engine, language) = make_engine();
+            let output_dir = tempfile::tempdir().unwrap();
+
+            let res = engine.compile_source("#include <cstdio>\nint main() { puts(\"hi\"); }\n",
+                language, ProgramKind::Judgee, Some(output_dir.path().to_owned())).unwrap();
+            assert!(res.succeeded, "compiler output: {:?}", res.compiler_out);
+
+            let output_file = res.output_file.unwrap();
+            assert_eq!(output_dir.path().join("program"), output_file);
+            let output = std::process::Command::new(&output_file).output().unwrap();
+            assert_eq!(b"hi\n", output.stdout.as_slice());
+
+            // The temporary source file has been removed.
+            assert_eq!(1, std::fs::read_dir(output_dir.path()).unwrap().count());
+        }
+
+        #[test]
+        fn compiler_not_found() {
+            let (engine, language) = make_engine_with_compiler(
+                JudgeEngineConfig::new(), "no-such-g++");
+            let output_dir = tempfile::tempdir().unwrap();
+
+            let err = engine.compile_source("int main() { }",
+                language, ProgramKind::Judgee, Some(output_dir.path().to_owned())).unwrap_err();
+            match err.kind() {
+                ErrorKind::LanguageError(message) =>
+                    assert_eq!("compiler 'no-such-g++' not found in PATH", message),
+                kind => panic!("unexpected error kind: {:?}", kind),
+            };
+        }
+
+        #[test]
+        fn compilation_error() {
+            let (engine, language) = make_engine();
+            let output_dir = tempfile::tempdir().unwrap();
+
+            let res = engine.compile_source("int main() { return }",
+                language, ProgramKind::Judgee, Some(output_dir.path().to_owned())).unwrap();
+            assert!(!res.succeeded);
+            assert!(res.compiler_out.unwrap().contains("error"));
+
+            let command = res.command.unwrap();
+            let output_file = output_dir.path().join("program");
+            let prefix = format!("/usr/bin/g++ -o {} ", output_file.display());
+            assert!(command.starts_with(&prefix), "command: {}", command);
+            assert!(command.ends_with(".cpp"), "command: {}", command);
+        }
+
+        #[test]
+        fn command_recorded_on_success() {
+            let source = "int main() { }";
+            let output_dir = tempfile::tempdir().unwrap();
+            let (engine, language) = make_engine();
+            let res = engine.compile_source(source, language, ProgramKind::Judgee,
+                Some(output_dir.path().to_owned())).unwrap();
+            assert!(res.succeeded);
+            assert_eq!(None, res.command);
+
+            let mut config = JudgeEngineConfig::new();
+            config.record_compile_command = true;
+            let (engine, language) = make_engine_with(config);
+            let res = engine.compile_source(source, language, ProgramKind::Judgee,
+                Some(output_dir.path().to_owned())).unwrap();
+            assert!(res.succeeded);
+            assert!(res.command.unwrap().starts_with("/usr/bin/g++ -o "));
+        }
+
+        #[test]
+        fn source_too_large() {
+            let mut config = JudgeEngineConfig::new();
+            config.max_source_size = Some(MemorySize::KiloBytes(1));
+            let (engine, language) = make_engine_with(config);
+            let output_dir = tempfile::tempdir().unwrap();
+
+            let source = format!("int main() {{ }}\n{}", "//\n".repeat(1024));
+            let res = engine.compile_source(&source, language.clone(), ProgramKind::Judgee,
+                Some(output_dir.path().to_owned())).unwrap();
+            assert!(!res.succeeded);
+            assert!(res.compiler_out.unwrap().starts_with("source too large"));
+            assert!(res.command.is_none());
+            // The source code is not saved.
+            assert_eq!(0, std::fs::read_dir(output_dir.path()).unwrap().count());
+
+            let res = engine.compile_source("int main() { }", language.clone(),
+                ProgramKind::Judgee, Some(output_dir.path().to_owned())).unwrap();
+            assert!(res.succeeded);
+
+            // Jury programs are not limited.
+            let res = engine.compile_source(&source, language, ProgramKind::Checker,
+                Some(output_dir.path().to_owned())).unwrap();
+            assert!(res.succeeded);
+        }
+    }
+
+    mod retry_transient {
+        use super::*;
+
+        /// Call `retry_transient` with the given number of retries on a function returning the
+        /// given results in order, and returns the result and the number of calls.
+        fn call(retries: u32, mut results: Vec<Result<CompilationResult>>)
+            -> (Result<CompilationResult>, usize) {
+            results.reverse();
+            let mut calls = 0;
+            let res = retry_transient(retries, Duration::from_millis(1), || {
+                calls += 1;
+                results.pop().unwrap()
+            });
+            (res, calls)
+        }
+
+        fn launch_failure() -> Result<CompilationResult> {
+            Err(Error::from(sandbox::Error::from(nix::Error::Sys(Errno::EAGAIN))))
+        }
+
+        #[test]
+        fn transient_launch_failure() {
+            let (res, calls) = call(2, vec![
+                launch_failure(),
+                Ok(CompilationResult::succeed("a.out"))]);
+            assert!(res.unwrap().succeeded);
+            assert_eq!(2, calls);
+        }
+
+        #[test]
+        fn retries_exhausted() {
+            let (res, calls) = call(1, vec![launch_failure(), launch_failure()]);
+            match res.unwrap_err().kind() {
+                ErrorKind::Sandbox(..) => (),
+                kind => panic!("unexpected error kind: {:?}", kind),
+            };
+            assert_eq!(2, calls);
+
+            let (res, calls) = call(0, vec![launch_failure()]);
+            assert!(res.is_err());
+            assert_eq!(1, calls);
+        }
+
+        #[test]
+        fn compilation_error_not_retried() {
+            let (res, calls) = call(2, vec![Ok(CompilationResult::fail("error: expected ';'"))]);
+            assert!(!res.unwrap().succeeded);
+            assert_eq!(1, calls);
+        }
+
+        #[test]
+        fn transient_errnos() {
+            for errno in TRANSIENT_ERRNOS {
+                let err = Error::from(std::io::Error::from_raw_os_error(*errno as i32));
+                assert!(is_transient_error(&err), "{:?} is not transient", errno);
+            }
+
+            let stage = sandbox::ChildSetupStage::Execve;
+            let err = Error::from(sandbox::Error::from(
+                sandbox::ErrorKind::ChildSetupFailed(stage, libc::EMFILE)));
+            assert!(is_transient_error(&err));
+
+            let err = Error::from(std::io::Error::from_raw_os_error(libc::ENOSPC));
+            assert!(is_transient_error(&err));
+        }
+
+        #[test]
+        fn compiler_launch_failure_retried() {
+            let (res, calls) = call(2, vec![
+                Err(Error::from(sandbox::Error::from(sandbox::ErrorKind::ChildStartupFailed))),
+                Ok(CompilationResult::succeed("a.out"))]);
+            assert!(res.unwrap().succeeded);
+            assert_eq!(2, calls);
+        }
+
+        #[test]
+        fn permanent_errors_not_retried() {
+            let stage = sandbox::ChildSetupStage::Execve;
+            let errors = vec![
+                Error::from(std::io::Error::from_raw_os_error(libc::ENOENT)),
+                Error::from(nix::Error::Sys(Errno::EACCES)),
+                Error::from(sandbox::Error::from(
+                    sandbox::ErrorKind::ChildSetupFailed(stage, libc::EACCES))),
+            ];
+            for err in errors {
+                let (res, calls) = call(2, vec![Err(err)]);
+                assert!(res.is_err());
+                assert_eq!(1, calls);
+            }
+        }
+
+        #[test]
+        fn language_error_not_retried() {
+            let (res, calls) = call(2, vec![
+                Err(Error::from(ErrorKind::LanguageError(String::from("compiler not found"))))]);
+            assert!(res.is_err());
+            assert_eq!(1, calls);
+        }
+    }
+
+    mod check_compiles {
+        use super::*;
+
+        use crate::languages::{LanguageBranch, LanguageProviderMetadata};
+
+        /// A language provider of shell scripts whose syntax is checked by `sh -n`.
+        struct ShellLanguageProvider {
+            metadata: &'static LanguageProviderMetadata,
+        }
+
+        impl LanguageProvider for ShellLanguageProvider {
+            fn metadata(&self) -> &'static LanguageProviderMetadata {
+                self.metadata
+            }
+
+            fn compile(&self, _program: &Program, _kind: ProgramKind, _output_dir: Option<PathBuf>)
+                -> std::result::Result<CompilationInfo, Box<dyn std::error::Error>> {
+                unreachable!()
+            }
+
+            fn syntax_check(&self, program: &Program, _kind: ProgramKind)
+                -> std::result::Result<Option<CompilationInfo>, Box<dyn std::error::Error>> {
+                let mut ci = CompilationInfo::new("/bin/sh", &program.file);
+                ci.compiler.add_arg("-n")?;
+                ci.compiler.add_arg(format!("{}", program.file.display()))?;
+                Ok(Some(ci))
+            }
+
+            fn execute(&self, _program: &Program, _kind: ProgramKind)
+                -> std::result::Result<ExecutionInfo, Box<dyn std::error::Error>> {
+                unreachable!()
+            }
+        }
+
+        fn check_file(engine: &JudgeEngine, language: LanguageIdentifier, source: &str,
+            extension: &str) -> CompilationResult {
+            let src_dir = tempfile::tempdir().unwrap();
+            let src_file = src_dir.path().join(format!("source.{}", extension));
+            std::fs::write(&src_file, source).unwrap();
+
+            let result = engine.check_compiles(&Program::new(&src_file, language),
+                ProgramKind::Judgee).unwrap();
+            // Only the source file is left in the directory.
+            assert_eq!(1, std::fs::read_dir(src_dir.path()).unwrap().count());
+            result
+        }
+
+        #[test]
+        fn compiled_language() {
+            let (engine, language) = super::compile_source::make_engine();
+
+            let res = check_file(&engine, language.clone(), "int main() { }", "cpp");
+            assert!(res.succeeded, "compiler output: {:?}", res.compiler_out);
+            assert_eq!(None, res.output_file);
+
+            let res = check_file(&engine, language, "int main() { return }", "cpp");
+            assert!(!res.succeeded);
+            assert!(res.compiler_out.unwrap().contains("error"));
+        }
+
+        #[test]
+        fn interpreted_language() {
+            let language = LanguageIdentifier::new("sh", LanguageBranch::new("posix", "1"));
+            let mut metadata = LanguageProviderMetadata::new("sh", true);
+            metadata.branches.push(language.branch().clone());
+
+            let engine = JudgeEngine::new();
+            engine.languages().register(Box::new(ShellLanguageProvider {
+                metadata: Box::leak(Box::new(metadata)),
+            }));
+
+            let res = check_file(&engine, language.clone(), "echo hello\n", "sh");
+            assert!(res.succeeded, "compiler output: {:?}", res.compiler_out);
+            assert_eq!(None, res.output_file);
+
+            let res = check_file(&engine, language, "if true; then echo hello\n", "sh");
+            assert!(!res.succeeded);
+            assert!(res.command.unwrap().starts_with("/bin/sh -n "));
+        }
+    }
+
+    mod shell_quote {
+        use super::*;
+
+        #[test]
+        fn safe_words_kept() {
+            assert_eq!("-std=c++11", shell_quote("-std=c++11"));
+            assert_eq!("/tmp/a.out", shell_quote("/tmp/a.out"));
+        }
+
+        #[test]
+        fn unsafe_words_quoted() {
+            assert_eq!("''", shell_quote(""));
+            assert_eq!("'a b'", shell_quote("a b"));
+            assert_eq!("'it'\\''s'", shell_quote("it's"));
+        }
+    }
+
+    mod seed_judge_dir {
+        use super::*;
+
+        #[test]
+        fn copied() {
+            let data_dir = tempfile::tempdir().unwrap();
+            let grader = data_dir.path().join("grader.h");
+            std::fs::write(&grader, "int query(int);\n").unwrap();
+
+            let judge_dir = tempfile::tempdir().unwrap();
+            let grader_files = vec![
+                (grader.clone(), PathBuf::from("grader.h")),
+                (grader, PathBuf::from("lib/grader.h")),
+            ];
+            seed_judge_dir(judge_dir.path(), &grader_files).unwrap();
+
+            for dest in &["grader.h", "lib/grader.h"] {
+                assert_eq!("int query(int);\n",
+                    std::fs::read_to_string(judge_dir.path().join(dest)).unwrap());
+            }
+        }
+
+        #[test]
+        fn links_replaced() {
+            let data_dir = tempfile::tempdir().unwrap();
+            let grader = data_dir.path().join("grader.h");
+            std::fs::write(&grader, "int query(int);\n").unwrap();
+            let outside = tempfile::tempdir().unwrap();
+
+            let judge_dir = tempfile::tempdir().unwrap();
+            std::os::unix::fs::symlink(outside.path(), judge_dir.path().join("lib")).unwrap();
+            std::os::unix::fs::symlink(outside.path().join("grader.h"),
+                judge_dir.path().join("grader.h")).unwrap();
+            let grader_files = vec![
+                (grader.clone(), PathBuf::from("grader.h")),
+                (grader, PathBuf::from("lib/grader.h")),
+            ];
+            seed_judge_dir(judge_dir.path(), &grader_files).unwrap();
+
+            assert_eq!(0, std::fs::read_dir(outside.path()).unwrap().count());
+            for dest in &["grader.h", "lib/grader.h"] {
+                let path = judge_dir.path().join(dest);
+                assert!(!std::fs::symlink_metadata(&path).unwrap().file_type().is_symlink());
+                assert_eq!("int query(int);\n", std::fs::read_to_string(&path).unwrap());
+            }
+        }
+
+        #[test]
+        fn restored_for_each_test_case() {
+            let data_dir = tempfile::tempdir().unwrap();
+            let grader = data_dir.path().join("grader.h");
+            std::fs::write(&grader, "int query(int);\n").unwrap();
+            let input_file = data_dir.path().join("input");
+            std::fs::write(&input_file, "").unwrap();
+
+            let mut task = JudgeTaskDescriptor::new(Program::new("/bin/sh",
+                LanguageIdentifier::new("sh", crate::languages::LanguageBranch::new("sh", "1"))));
+            task.grader_files.push((grader.clone(), PathBuf::from("lib/grader.h")));
+            for _ in 0..2 {
+                task.test_suite.push(TestCaseDescriptor::new(input_file.clone(), grader.clone()));
+            }
+
+            let judge_dir = tempfile::tempdir().unwrap();
+            let mut judgee_bdr = ProcessBuilder::new("/bin/sh");
+            judgee_bdr.add_arg("-c").unwrap();
+            judgee_bdr.add_arg("cat lib/grader.h; echo 'int query(long);' > lib/grader.h").unwrap();
+            judgee_bdr.dir.working_dir = Some(judge_dir.path().to_owned());
+            let context = JudgeContext::standard(&task, &task.test_suite, judge_dir,
+                judgee_bdr.memento(), checkers::get_checker(BuiltinCheckers::Default));
+
+            let res = context.execute(&mut JudgeEngineExecutor::new(true)).unwrap();
+            assert_eq!(2, res.test_suite.len());
+            assert!(res.verdict.is_accepted());
+        }
+
+        #[test]
+        fn escaping_destination_rejected() {
+            let judge_dir = tempfile::tempdir().unwrap();
+            for dest in &["../grader.h", "/etc/grader.h", ""] {
+                let grader_files = vec![(PathBuf::from("/dev/null"), PathBuf::from(dest))];
+                let err = seed_judge_dir(judge_dir.path(), &grader_files).unwrap_err();
+                match err.kind() {
+                    ErrorKind::InvalidGraderFile(path) => assert_eq!(PathBuf::from(dest), *path),
+                    kind => panic!("unexpected error kind: {:?}", kind),
+                };
+            }
+        }
+    }
+
+    mod create_judge_dir {
+        use super::*;
+
+        use std::sync::{Arc, Barrier};
+
+        #[test]
+        fn isolated_by_subdir() {
+            let parent = tempfile::tempdir().unwrap();
+            let barrier = Arc::new(Barrier::new(2));
+
+            // Two judges write a file with the same name into their judge directories at the same
+            // time, and each of them should read back its own content.
+            let handles = (1..=2).map(|worker_id| {
+                let parent = parent.path().to_owned();
+                let barrier = barrier.clone();
+                std::thread::spawn(move || {
+                    let subdir = format!("worker-{}", worker_id);
+                    let judge_dir = create_judge_dir(Some(&parent), Some(&subdir)).unwrap();
+                    assert_eq!(parent.join(&subdir), judge_dir.path().parent().unwrap());
+
+                    let output = judge_dir.path().join("output");
+                    barrier.wait();
+                    std::fs::write(&output, subdir.as_bytes()).unwrap();
+                    barrier.wait();
+                    assert_eq!(subdir, std::fs::read_to_string(&output).unwrap());
+                })
+            }).collect::<Vec<_>>();
+
+            for handle in handles {
+                handle.join().unwrap();
+            }
+        }
+
+        #[test]
+        fn without_subdir() {
+            let parent = tempfile::tempdir().unwrap();
+            let judge_dir = create_judge_dir(Some(parent.path()), None).unwrap();
+            assert_eq!(parent.path(), judge_dir.path().parent().unwrap());
+        }
+
+        #[test]
+        fn invalid_subdir_rejected() {
+            let parent = tempfile::tempdir().unwrap();
+            for name in &["", "..", "../worker-1", "/worker-1", "worker/1"] {
+                let err = create_judge_dir(Some(parent.path()), Some(name)).unwrap_err();
+                match err.kind() {
+                    ErrorKind::InvalidJudgeSubdir(subdir) => assert_eq!(name, subdir),
+                    kind => panic!("unexpected error kind: {:?}", kind),
+                };
+            }
+        }
+    }
+
+    mod add_memory_overhead {
+        use super::*;
+
+        #[test]
+        fn no_overhead() {
+            assert_eq!(MemorySize::MegaBytes(64),
+                add_memory_overhead(MemorySize::MegaBytes(64), MemorySize::Bytes(0)));
+        }
+
+        #[test]
+        fn overhead_added() {
+            assert_eq!(MemorySize::MegaBytes(96),
+                add_memory_overhead(MemorySize::MegaBytes(64), MemorySize::MegaBytes(32)));
+        }
+    }
+
+    mod checker_fd_args {
+        use super::*;
+
+        #[test]
+        fn plain_fds_in_order() {
+            assert_eq!(vec!["3", "4", "10"], checker_fd_args(3, 4, 10));
+        }
+    }
+
+    mod interactor_fd_args {
+        use super::*;
+
+        #[test]
+        fn plain_fds_in_order() {
+            assert_eq!(vec!["3", "4", "10"], interactor_fd_args(3, 4, 10));
+        }
+    }
+
+    mod test_suite_execution {
+        use super::*;
+
+        fn make_file(dir: &TempDir, name: &str, content: &str) -> PathBuf {
+            let path = dir.path().join(name);
+            std::fs::write(&path, content).unwrap();
+            path
+        }
+
+        /// Judge a shell script judgee on test cases given by pairs of input and answer, using the
+        /// default built-in checker.
+        fn judge_script(script: &str, test_cases: &[(&str, &str)]) -> JudgeResult {
+            judge_script_with(script, test_cases, |_| ())
+        }
+
+        /// Same as `judge_script`, but the judge task is modified by the given function before
+        /// the judge starts.
+        fn judge_script_with<F>(script: &str, test_cases: &[(&str, &str)], setup: F) -> JudgeResult
+            where F: FnOnce(&mut JudgeTaskDescriptor) {
+            let data_dir = tempfile::tempdir().unwrap();
+            let mut task = JudgeTaskDescriptor::new(Program::new("/bin/sh",
+                LanguageIdentifier::new("sh", crate::languages::LanguageBranch::new("sh", "1"))));
+            for (index, (input, answer)) in test_cases.iter().enumerate() {
+                task.test_suite.push(TestCaseDescriptor::new(
+                    make_file(&data_dir, &format!("{}.in", index), input),
+                    make_file(&data_dir, &format!("{}.ans", index), answer)));
+            }
+            setup(&mut task);
+
+            // The judge directory is not used as the root directory here so that the shell is
+            // available to the judgee.
+            let mut judgee_bdr = ProcessBuilder::new("/bin/sh");
+            judgee_bdr.add_arg("-c").unwrap();
+            judgee_bdr.add_arg(script).unwrap();
+            let context = JudgeContext::standard(&task, &task.test_suite,
+                tempfile::tempdir().unwrap(), judgee_bdr.memento(),
+                checkers::get_checker(BuiltinCheckers::Default));
+
+            let mut executor = JudgeEngineExecutor::new(true);
+            context.execute(&mut executor).unwrap()
+        }
+
+        #[test]
+        fn accepted() {
+            let res = judge_script("read x; echo $((x + 1))", &[("1\n", "2\n"), ("5\n", "6\n")]);
+            assert!(res.verdict.is_accepted());
+            assert_eq!(2, res.test_suite.len());
+        }
+
+        #[test]
+        fn wrong_answer() {
+            let res = judge_script("read x; echo $x", &[("1\n", "2\n")]);
+            match res.verdict {
+                Verdict::WrongAnswer => (),
+                verdict => panic!("unexpected verdict: {:?}", verdict),
+            };
+        }
+
+        #[test]
+        fn nonzero_exit() {
+            let res = judge_script("read x; echo $x; exit 3", &[("1\n", "1\n")]);
+            match res.verdict {
+                Verdict::RuntimeError => (),
+                verdict => panic!("unexpected verdict: {:?}", verdict),
+            };
+        }
+
+        #[test]
+        fn nonzero_exit_ignored() {
+            let res = judge_script_with("read x; echo $x; exit 3", &[("1\n", "1\n")],
+                |task| task.treat_nonzero_exit_as_runtime_error = false);
+            assert!(res.verdict.is_accepted());
+
+            let res = judge_script_with("read x; echo 2; exit 3", &[("1\n", "1\n")],
+                |task| task.treat_nonzero_exit_as_runtime_error = false);
+            match res.verdict {
+                Verdict::WrongAnswer => (),
+                verdict => panic!("unexpected verdict: {:?}", verdict),
+            };
+        }
+
+        #[test]
+        fn views_recorded_while_checking() {
+            let res = judge_script_with("read x; echo $x", &[("1\n", "1\n"), ("2\n", "3\n")],
+                |task| task.test_suite.iter_mut().for_each(|tc| tc.public = true));
+            assert_eq!(2, res.test_suite.len());
+
+            let accepted = &res.test_suite[0];
+            assert!(accepted.verdict.is_accepted());
+            assert_eq!(Some(String::from("1\n")), accepted.answer_view);
+            assert_eq!(Some(String::from("1\n")), accepted.output_view);
+
+            let rejected = &res.test_suite[1];
+            assert_eq!(Verdict::WrongAnswer, rejected.verdict);
+            assert_eq!(Some(String::from("3\n")), rejected.answer_view);
+            assert_eq!(Some(String::from("2\n")), rejected.output_view);
+        }
+
+        /// Judge a C program compiled from the given source code, which is started by a shell
+        /// script judgee, on a single test case.
+        fn judge_c_program(source: &str) -> TestCaseResult {
+            let program_dir = tempfile::tempdir().unwrap();
+            let source_file = make_file(&program_dir, "program.c", source);
+            let program = program_dir.path().join("program");
+            let status = std::process::Command::new("gcc")
+                .arg("-o").arg(&program)
+                .arg(&source_file)
+                .arg("-lm")
+                .status()
+                .unwrap();
+            assert!(status.success());
+
+            let mut res = judge_script(&format!("exec {}", program.display()), &[("1\n", "1\n")]);
+            res.test_suite.remove(0)
+        }
+
+        #[test]
+        fn segmentation_fault_described() {
+            let res = judge_c_program("int main(void) { *(volatile int *)0 = 1; return 0; }");
+            match res.verdict {
+                Verdict::RuntimeError => (),
+                verdict => panic!("unexpected verdict: {:?}", verdict),
+            };
+            assert_eq!(Some(String::from("killed by signal 11 (SIGSEGV): segmentation fault")),
+                res.comment);
+        }
+
+        #[test]
+        fn division_by_zero_described() {
+            // Floating point division by zero traps once the exception is enabled, which is more
+            // portable than integer division by zero across virtualized CPUs.
+            let res = judge_c_program("#define _GNU_SOURCE\n#include <fenv.h>\n\
+                int main(void) { volatile double zero = 0.0; feenableexcept(FE_DIVBYZERO); \
+                return (int)(1.0 / zero); }");
+            match res.verdict {
+                Verdict::RuntimeError => (),
+                verdict => panic!("unexpected verdict: {:?}", verdict),
+            };
+            assert_eq!(Some(String::from("killed by signal 8 (SIGFPE): floating point exception")),
+                res.comment);
+        }
+
+        #[test]
+        fn not_fail_fast() {
+            // The judgee fails on the first test case only. All test cases are judged and the
+            // verdict of the first failed test case becomes the verdict of the judge task.
+            let res = judge_script("read x; [ $x -eq 1 ] && kill -SEGV $$; echo $x",
+                &[("1\n", "1\n"), ("2\n", "3\n"), ("4\n", "4\n")]);
+            match res.verdict {
+                Verdict::RuntimeError => (),
+                verdict => panic!("unexpected verdict: {:?}", verdict),
+            };
+
+            assert_eq!(3, res.test_suite.len());
+            assert_eq!(Verdict::WrongAnswer, res.test_suite[1].verdict);
+            assert!(res.test_suite[2].verdict.is_accepted());
+        }
+    }
+
+    mod against_reference {
+        use super::*;
+
+        /// Source code of a reference solution that prints the double of the input number.
+        const REFERENCE_SOURCE: &str = r#"
+            #include <stdio.h>
+
+            int main(void) {
+                int x;
+                scanf("%d", &x);
+                printf("%d\n", x * 2);
+                return 0;
+            }
+        "#;
+
+        /// Judge a shell script judgee on the given inputs against the reference solution.
+        fn judge_script(script: &str, inputs: &[&str]) -> JudgeResult {
+            let data_dir = tempfile::tempdir().unwrap();
+            let mut test_suite = Vec::new();
+            for (index, input) in inputs.iter().enumerate() {
+                let input_file = data_dir.path().join(format!("{}.in", index));
+                std::fs::write(&input_file, input).unwrap();
+                // The answer files are not used in reference judge mode.
+                let mut test_case = TestCaseDescriptor::new(input_file, "/nonexistent");
+                test_case.public = true;
+                test_suite.push(test_case);
+            }
+
+            judge_with_static_jury(
+                REFERENCE_SOURCE, script, StaticJury::Reference, test_suite, |_, _| ())
+        }
+
+        #[test]
+        fn matching_submission() {
+            let res = judge_script("read x; echo $((x + x))", &["3\n", "5\n"]);
+            assert!(res.verdict.is_accepted());
+            assert_eq!(2, res.test_suite.len());
+            assert_eq!(Some(String::from("10\n")), res.test_suite[1].answer_view);
+        }
+
+        #[test]
+        fn diverging_submission() {
+            let res = judge_script("read x; echo $((x + 2))", &["2\n", "5\n"]);
+            match res.verdict {
+                Verdict::WrongAnswer => (),
+                verdict => panic!("unexpected verdict: {:?}", verdict),
+            };
+            assert!(res.test_suite[0].verdict.is_accepted());
+            assert_eq!(Some(String::from("10\n")), res.test_suite[1].answer_view);
+            assert_eq!(Some(String::from("7\n")), res.test_suite[1].output_view);
+        }
+    }
+
+    mod answer_generator {
+        use super::*;
+
+        /// Source code of an answer generator that prints the double of the input number, and
+        /// fails on negative numbers.
+        const GENERATOR_SOURCE: &str = r#"
+            #include <stdio.h>
+
+            int main(void) {
+                int x;
+                scanf("%d", &x);
+                if (x < 0) {
+                    return 1;
+                }
+                printf("%d\n", x * 2);
+                return 0;
+            }
+        "#;
+
+        /// Judge a shell script judgee on the given inputs with answers generated by the answer
+        /// generator.
+        fn judge_script(script: &str, inputs: &[&str]) -> JudgeResult {
+            let data_dir = tempfile::tempdir().unwrap();
+            let mut test_suite = Vec::new();
+            for (index, input) in inputs.iter().enumerate() {
+                let input_file = data_dir.path().join(format!("{}.in", index));
+                std::fs::write(&input_file, input).unwrap();
+                // The answer files are superseded by the generated answers.
+                let mut test_case = TestCaseDescriptor::new(input_file, "/nonexistent");
+                test_case.public = true;
+                test_suite.push(test_case);
+            }
+
+            judge_with_static_jury(
+                GENERATOR_SOURCE, script, StaticJury::AnswerGenerator, test_suite, |_, _| ())
+        }
+
+        #[test]
+        fn executed_as_jury() {
+            use std::sync::Mutex;
+
+            use crate::languages::LanguageProviderMetadata;
+            use super::judge_batch::{RecordingLanguageProvider, language};
+
+            let mut metadata = LanguageProviderMetadata::new("recording", true);
+            metadata.branches.push(language().branch().clone());
+            let executed = Arc::new(Mutex::new(Vec::new()));
+
+            let mut config = JudgeEngineConfig::new();
+            config.jury_syscall_whitelist = vec![SystemCall::from_name("read").unwrap()];
+            let engine = JudgeEngine::with_config(config);
+            engine.languages().register(Box::new(RecordingLanguageProvider {
+                metadata: Box::leak(Box::new(metadata)),
+                executed: executed.clone(),
+            }));
+
+            let generator = Program::new("/bin/true", language());
+            let generator_bdr = engine.build_answer_generator_bdr(&generator, &[]).unwrap();
+            assert_eq!(vec![ProgramKind::Checker], *executed.lock().unwrap());
+            assert_eq!(1, generator_bdr.restore().syscall_whitelist.len());
+        }
+
+        #[test]
+        fn matching_submission() {
+            let res = judge_script("read x; echo $((x + x))", &["3\n", "5\n"]);
+            assert!(res.verdict.is_accepted());
+            assert_eq!(2, res.test_suite.len());
+            assert_eq!(Some(String::from("10\n")), res.test_suite[1].answer_view);
+        }
+
+        #[test]
+        fn diverging_submission() {
+            let res = judge_script("read x; echo $((x + 2))", &["2\n", "5\n"]);
+            match res.verdict {
+                Verdict::WrongAnswer => (),
+                verdict => panic!("unexpected verdict: {:?}", verdict),
+            };
+            assert!(res.test_suite[0].verdict.is_accepted());
+            assert_eq!(Some(String::from("10\n")), res.test_suite[1].answer_view);
+            assert_eq!(Some(String::from("7\n")), res.test_suite[1].output_view);
+        }
+
+        #[test]
+        fn answer_hidden_from_judgee() {
+            // The judgee finds its own output and error files only, unless the generated answer is
+            // placed inside the judge directory as well.
+            let res = judge_script(
+                "read x; n=$(ls -a | grep -c '^\\.tmp'); echo $((x + x + n - 2))", &["3\n"]);
+            assert!(res.verdict.is_accepted(), "unexpected verdict: {:?}", res.verdict);
+        }
+
+        #[test]
+        fn generator_failed() {
+            let res = judge_script("read x; echo $((x + x))", &["-1\n"]);
+            match res.test_suite[0].verdict {
+                Verdict::CheckerFailed => (),
+                verdict => panic!("unexpected verdict: {:?}", verdict),
+            };
+            assert_eq!(Some(FailureReason::AnswerGeneratorFailed),
+                res.test_suite[0].failure_reason);
+            assert!(res.test_suite[0].comment.as_ref().unwrap()
+                .starts_with("answer generator failed"));
+        }
+    }
+
+    mod checker_isolation {
+        use super::*;
+
+        /// Source code of an answer checker that tries to open the file given by its first
+        /// argument, and reports the first line of the judgee's output read from the file
+        /// descriptor given by its fourth argument.
+        const CHECKER_SOURCE: &str = r#"
+            #include <stdio.h>
+            #include <stdlib.h>
+
+            int main(int argc, char **argv) {
+                char line[64];
+                FILE *output;
+                if (fopen(argv[1], "r")) {
+                    puts("secret file opened");
+                    return 1;
+                }
+                output = fdopen(atoi(argv[4]), "r");
+                if (!output || !fgets(line, sizeof(line), output)) {
+                    puts("cannot read output");
+                    return 1;
+                }
+                printf("output: %s", line);
+                return 0;
+            }
+        "#;
+
+        #[test]
+        fn checker_cannot_open_files_outside_judge_dir() {
+            let data_dir = tempfile::tempdir().unwrap();
+            let input_file = data_dir.path().join("input");
+            let answer_file = data_dir.path().join("answer");
+            let secret_file = data_dir.path().join("secret");
+            std::fs::write(&input_file, "1\n").unwrap();
+            std::fs::write(&answer_file, "42\n").unwrap();
+            std::fs::write(&secret_file, "secret\n").unwrap();
+
+            let test_suite = vec![TestCaseDescriptor::new(input_file, answer_file)];
+            let mut res = judge_with_static_jury(CHECKER_SOURCE, "echo 42", StaticJury::Checker,
+                test_suite, |checker_bdr, _| {
+                    checker_bdr.add_arg(format!("{}", secret_file.display())).unwrap();
+                });
+            let res = res.test_suite.pop().unwrap();
+            assert_eq!(Some(String::from("output: 42\n")), res.comment);
+            assert!(res.verdict.is_accepted());
+        }
+    }
+
+    mod empty_output {
+        use super::*;
+
+        /// Source code of an answer checker that accepts empty output only. The judgee's output is
+        /// read from the file descriptor given by its third argument.
+        const CHECKER_SOURCE: &str = r#"
+            #include <stdlib.h>
+            #include <unistd.h>
+
+            int main(int argc, char **argv) {
+                char c;
+                if (read(atoi(argv[3]), &c, 1) != 0) {
+                    return 1;
+                }
+                write(STDOUT_FILENO, "empty output accepted", 21);
+                return 0;
+            }
+        "#;
+
+        /// Judge a judgee that prints nothing with the checker above.
+        fn judge_empty_output(run_checker_on_empty_output: bool) -> TestCaseResult {
+            let data_dir = tempfile::tempdir().unwrap();
+            let input_file = data_dir.path().join("input");
+            let answer_file = data_dir.path().join("answer");
+            std::fs::write(&input_file, "1\n").unwrap();
+            std::fs::write(&answer_file, "").unwrap();
+
+            let test_suite = vec![TestCaseDescriptor::new(input_file, answer_file)];
+            let mut res = judge_with_static_jury(CHECKER_SOURCE, "true", StaticJury::Checker,
+                test_suite, |_, executor| {
+                    executor.run_checker_on_empty_output = run_checker_on_empty_output;
+                });
+            res.test_suite.pop().unwrap()
+        }
+
+        #[test]
+        fn checker_decides() {
+            let res = judge_empty_output(true);
+            assert!(res.verdict.is_accepted());
+            assert_eq!(Some(String::from("empty output accepted")), res.comment);
+        }
+
+        #[test]
+        fn rejected_without_checker() {
+            let res = judge_empty_output(false);
+            match res.verdict {
+                Verdict::WrongAnswer => (),
+                verdict => panic!("unexpected verdict: {:?}", verdict),
+            };
+            assert_eq!(Some(String::from("empty output")), res.comment);
+        }
+    }
+
+    mod chatty_checker {
+        use super::*;
+
+        /// Source code of an answer checker that writes a comment far larger than the pipe buffer
+        /// before accepting the output.
+        const CHECKER_SOURCE: &str = r#"
+            #include <string.h>
+            #include <unistd.h>
+
+            int main(void) {
+                static char buf[256 * 1024];
+                memset(buf, 'x', sizeof(buf));
+                size_t written = 0;
+                while (written < sizeof(buf)) {
+                    ssize_t n = write(STDOUT_FILENO, buf + written, sizeof(buf) - written);
+                    if (n <= 0) {
+                        return 2;
+                    }
+                    written += n;
+                }
+                return 0;
+            }
+        "#;
+
+        #[test]
+        fn long_comment_read() {
+            let data_dir = tempfile::tempdir().unwrap();
+            let input_file = data_dir.path().join("input");
+            let answer_file = data_dir.path().join("answer");
+            std::fs::write(&input_file, "1\n").unwrap();
+            std::fs::write(&answer_file, "1\n").unwrap();
+
+            // The checker would be blocked until its real time limit if its comment were not
+            // drained.
+            let test_suite = vec![TestCaseDescriptor::new(input_file, answer_file)];
+            let mut res = judge_with_static_jury(
+                CHECKER_SOURCE, "echo 1", StaticJury::Checker, test_suite, |_, _| ());
+            let res = res.test_suite.pop().unwrap();
+            assert!(res.verdict.is_accepted(), "unexpected verdict: {:?}", res.verdict);
+            assert_eq!(MAX_MESSAGE_LEN, res.comment.unwrap().len());
+        }
+    }
+
+    mod preserved_output {
+        use super::*;
+
+        /// Judge a shell script judgee on the given number of identical test cases with the given
+        /// executor.
+        fn judge_script_with(script: &str, test_cases: usize, executor: &mut JudgeEngineExecutor)
+            -> JudgeResult {
+            let data_dir = tempfile::tempdir().unwrap();
+            let input_file = data_dir.path().join("input");
+            let answer_file = data_dir.path().join("answer");
+            std::fs::write(&input_file, "").unwrap();
+            std::fs::write(&answer_file, "1\n2\n").unwrap();
+
+            let mut task = JudgeTaskDescriptor::new(Program::new("/bin/sh",
+                LanguageIdentifier::new("sh", crate::languages::LanguageBranch::new("sh", "1"))));
+            for _ in 0..test_cases {
+                task.test_suite.push(
+                    TestCaseDescriptor::new(input_file.clone(), answer_file.clone()));
+            }
+
+            let mut judgee_bdr = ProcessBuilder::new("/bin/sh");
+            judgee_bdr.add_arg("-c").unwrap();
+            judgee_bdr.add_arg(script).unwrap();
+            let context = JudgeContext::standard(&task, &task.test_suite,
+                tempfile::tempdir().unwrap(), judgee_bdr.memento(),
+                checkers::get_checker(BuiltinCheckers::Default));
+
+            context.execute(executor).unwrap()
+        }
+
+        /// Judge a shell script judgee on a single test case, preserving the outputs of failed
+        /// judgees into the given directory.
+        fn judge_script(script: &str, preserved_output_dir: &Path) -> TestCaseResult {
+            let mut executor = JudgeEngineExecutor::new(true);
+            executor.preserved_output_dir = Some(preserved_output_dir.to_owned());
+            judge_script_with(script, 1, &mut executor).test_suite.pop().unwrap()
+        }
+
+        #[test]
+        fn partial_output_retained() {
+            let dir = tempfile::tempdir().unwrap();
+            let res = judge_script("echo 1; echo partial; kill -SEGV $$", dir.path());
+            match res.verdict {
+                Verdict::RuntimeError => (),
+                verdict => panic!("unexpected verdict: {:?}", verdict),
+            };
+
+            let path = res.preserved_output.unwrap();
+            assert!(path.starts_with(dir.path()));
+            assert_eq!("1\npartial\n", std::fs::read_to_string(&path).unwrap());
+        }
+
+        #[test]
+        fn not_preserved_when_judgee_exits_normally() {
+            let dir = tempfile::tempdir().unwrap();
+            let res = judge_script("echo 1; echo 2", dir.path());
+            assert!(res.verdict.is_accepted());
+            assert_eq!(None, res.preserved_output);
+
+            let res = judge_script("echo 1; echo 3", dir.path());
+            match res.verdict {
+                Verdict::WrongAnswer => (),
+                verdict => panic!("unexpected verdict: {:?}", verdict),
+            };
+            assert_eq!(None, res.preserved_output);
+            assert_eq!(0, std::fs::read_dir(dir.path()).unwrap().count());
+        }
+
+        #[test]
+        fn output_truncated() {
+            let dir = tempfile::tempdir().unwrap();
+            let mut executor = JudgeEngineExecutor::new(true);
+            executor.preserved_output_dir = Some(dir.path().to_owned());
+            executor.max_preserved_output_len = 4;
+            executor.preserved_output_budget = 10;
+            let res = judge_script_with("echo 12345678; kill -SEGV $$", 4, &mut executor);
+
+            let preserved = res.test_suite.iter()
+                .map(|tc| tc.preserved_output.as_ref()
+                    .map(|path| std::fs::read_to_string(path).unwrap()))
+                .collect::<Vec<Option<String>>>();
+            assert_eq!(vec![Some(String::from("1234")), Some(String::from("1234")),
+                Some(String::from("12")), None], preserved);
+        }
+
+        #[test]
+        fn outputs_of_each_task_in_own_dir() {
+            let dir = tempfile::tempdir().unwrap();
+            let first = judge_script("kill -SEGV $$", dir.path()).preserved_output.unwrap();
+            let second = judge_script("kill -SEGV $$", dir.path()).preserved_output.unwrap();
+            assert_ne!(first.parent(), second.parent());
+            assert_eq!(Some(dir.path()), first.parent().unwrap().parent());
+        }
+
+        #[test]
+        fn old_task_dirs_removed() {
+            let dir = tempfile::tempdir().unwrap();
+            for name in &["task1", "task2", "task3"] {
+                std::fs::create_dir(dir.path().join(name)).unwrap();
+                std::thread::sleep(Duration::from_millis(10));
+            }
+            std::fs::write(dir.path().join("other"), "").unwrap();
+
+            remove_old_preserved_output_dirs(dir.path(), 2).unwrap();
+            assert!(!dir.path().join("task1").exists());
+            assert!(dir.path().join("task2").exists());
+            assert!(dir.path().join("task3").exists());
+            assert!(dir.path().join("other").exists());
+        }
+    }
+
+    mod checker_failure {
+        use super::*;
+
+        /// Judge the output of a judgee with an answer checker compiled from the given source code,
+        /// whose resource limits are set by the given function.
+        fn judge_with_checker<F>(source: &str, set_limits: F) -> TestCaseResult
+            where F: FnOnce(&mut ProcessBuilder) {
+            let data_dir = tempfile::tempdir().unwrap();
+            let input_file = data_dir.path().join("input");
+            let answer_file = data_dir.path().join("answer");
+            std::fs::write(&input_file, "1\n").unwrap();
+            std::fs::write(&answer_file, "1\n").unwrap();
+
+            let test_suite = vec![TestCaseDescriptor::new(input_file, answer_file)];
+            let mut res = judge_with_static_jury(source, "echo 1", StaticJury::Checker, test_suite,
+                |checker_bdr, _| set_limits(checker_bdr));
+            res.test_suite.pop().unwrap()
+        }
+
+        fn assert_checker_failed(res: &TestCaseResult, reason: FailureReason) {
+            match res.verdict {
+                Verdict::CheckerFailed => (),
+                verdict => panic!("unexpected verdict: {:?}", verdict),
+            };
+            assert_eq!(Some(reason), res.failure_reason);
+        }
+
+        #[test]
+        fn cpu_time_limit_exceeded() {
+            let res = judge_with_checker("int main(void) { for (;;) { } }",
+                |bdr| bdr.limits.cpu_time_limit = Some(Duration::from_millis(200)));
+            assert_checker_failed(&res, FailureReason::CheckerTimeLimitExceeded);
+            assert_eq!(Some(ProcessExitStatus::CPUTimeLimitExceeded), res.checker_exit_status);
+        }
+
+        #[test]
+        fn real_time_limit_exceeded() {
+            let res = judge_with_checker("#include <unistd.h>\nint main(void) { sleep(10); }",
+                |bdr| bdr.limits.real_time_limit = Some(Duration::from_millis(200)));
+            assert_checker_failed(&res, FailureReason::CheckerTimeLimitExceeded);
+            assert_eq!(Some(ProcessExitStatus::RealTimeLimitExceeded), res.checker_exit_status);
+        }
+
+        #[test]
+        fn memory_limit_exceeded() {
+            let res = judge_with_checker("#include <stdlib.h>\n#include <string.h>\n\
+                #include <unistd.h>\nint main(void) { size_t size = 256 << 20; \
+                char *p = malloc(size); if (p) memset(p, 1, size); for (;;) pause(); }",
+                |bdr| bdr.limits.memory_limit = Some(MemorySize::MegaBytes(32)));
+            assert_checker_failed(&res, FailureReason::CheckerMemoryLimitExceeded);
+            assert_eq!(Some(ProcessExitStatus::MemoryLimitExceeded), res.checker_exit_status);
+        }
+
+        #[test]
+        fn accepted_without_failure_reason() {
+            let res = judge_with_checker("int main(void) { return 0; }", |_| ());
+            assert!(res.verdict.is_accepted());
+            assert_eq!(None, res.failure_reason);
+            assert_eq!(Some(ProcessExitStatus::Normal(0)), res.checker_exit_status);
+        }
+    }
+
+    mod interactive {
+        use super::*;
+
+        /// Source code of an interactor that sends the number in the input file to the judgee and
+        /// accepts the judgee if it replies with the number in the answer file. Half of the full
+        /// score is declared either way.
+        const INTERACTOR_SOURCE: &str = r#"
+            #include <stdio.h>
+            #include <stdlib.h>
+
+            int main(int argc, char **argv) {
+                if (argc != 4) {
+                    return 3;
+                }
+                FILE *input = fdopen(atoi(argv[1]), "r");
+                FILE *answer = fdopen(atoi(argv[2]), "r");
+                FILE *report = fdopen(atoi(argv[3]), "w");
+
+                int x, expected, reply;
+                if (fscanf(input, "%d", &x) != 1 || fscanf(answer, "%d", &expected) != 1) {
+                    return 3;
+                }
+                printf("%d\n", x);
+                fflush(stdout);
+
+                if (scanf("%d", &reply) != 1) {
+                    fprintf(report, "no reply\n");
+                    return 1;
+                }
+                fprintf(report, "got %d\nscore=0.5\n", reply);
+                return reply == expected ? 0 : 1;
+            }
+        "#;
+
+        /// Source code of an interactor that generates its test case on the fly. It requires both
+        /// the input file and the answer file to be empty, sends 41 to the judgee and accepts the
+        /// judgee if it replies with 42.
+        const GENERATING_INTERACTOR_SOURCE: &str = r#"
+            #include <stdio.h>
+            #include <stdlib.h>
+
+            int main(int argc, char **argv) {
+                if (argc != 4) {
+                    return 3;
+                }
+                FILE *input = fdopen(atoi(argv[1]), "r");
+                FILE *answer = fdopen(atoi(argv[2]), "r");
+                if (fgetc(input) != EOF || fgetc(answer) != EOF) {
+                    return 3;
+                }
+                printf("41\n");
+                fflush(stdout);
+
+                int reply;
+                if (scanf("%d", &reply) != 1) {
+                    return 1;
+                }
+                return reply == 42 ? 0 : 1;
+            }
+        "#;
+
+        /// Source code of an interactor that floods the judgee with more data than a pipe holds
+        /// before reading its reply, so that it writes to a broken pipe if the judgee dies early.
+        const FLOODING_INTERACTOR_SOURCE: &str = r#"
+            #include <signal.h>
+            #include <stdio.h>
+
+            int main(void) {
+                signal(SIGPIPE, SIG_DFL);
+                for (int i = 0; i < 1000000; ++i) {
+                    printf("%d\n", i);
+                }
+                fflush(stdout);
+
+                int reply;
+                return scanf("%d", &reply) == 1 ? 0 : 1;
+            }
+        "#;
+
+        /// Judge a shell script judgee interacting with the interactor above on a single test case
+        /// where the interactor sends 1 and expects 2.
+        fn judge_script(script: &str) -> TestCaseResult {
+            let data_dir = tempfile::tempdir().unwrap();
+            let input_file = data_dir.path().join("input");
+            let answer_file = data_dir.path().join("answer");
+            std::fs::write(&input_file, "1\n").unwrap();
+            std::fs::write(&answer_file, "2\n").unwrap();
+
+            judge_interaction(INTERACTOR_SOURCE, input_file, answer_file, script)
+        }
+
+        /// Judge a shell script judgee interacting with the interactor compiled from the given
+        /// source code on a single test case consisting of the given input file and answer file.
+        fn judge_interaction<P1, P2>(interactor_source: &str, input_file: P1, answer_file: P2,
+            script: &str) -> TestCaseResult
+            where P1: Into<PathBuf>, P2: Into<PathBuf> {
+            let test_suite = vec![TestCaseDescriptor::new(input_file, answer_file)];
+            let mut res = judge_with_static_jury(
+                interactor_source, script, StaticJury::Interactor, test_suite, |_, _| ());
+            res.test_suite.pop().unwrap()
+        }
+
+        #[test]
+        fn accepted_with_score() {
+            let res = judge_script("read x; echo $((x + 1))");
+            assert!(res.verdict.is_accepted(), "unexpected verdict: {:?}", res.verdict);
+            assert_eq!(Some(ProcessExitStatus::Normal(0)), res.interactor_exit_status);
+            assert_eq!(Some(String::from("got 2\n")), res.comment);
+            assert_eq!(Some(0.5), res.score);
+        }
+
+        #[test]
+        fn wrong_answer() {
+            let res = judge_script("read x; echo $x");
+            assert_eq!(Verdict::WrongAnswer, res.verdict);
+            assert_eq!(Some(String::from("got 1\n")), res.comment);
+        }
+
+        #[test]
+        fn judgee_failure_kept() {
+            let res = judge_script("exit 1");
+            assert_eq!(Verdict::RuntimeError, res.verdict);
+            assert_eq!(Some(ProcessExitStatus::Normal(1)), res.interactor_exit_status);
+            assert_eq!(Some(String::from("no reply\n")), res.comment);
+        }
+
+        #[test]
+        fn crashed_judgee() {
+            let data_dir = tempfile::tempdir().unwrap();
+            let input_file = data_dir.path().join("input");
+            std::fs::write(&input_file, "").unwrap();
+
+            let res = judge_interaction(
+                FLOODING_INTERACTOR_SOURCE, &input_file, &input_file, "kill -SEGV $$");
+            assert_eq!(Verdict::RuntimeError, res.verdict);
+            assert_eq!(Some(ProcessExitStatus::KilledBySignal(libc::SIGPIPE)),
+                res.interactor_exit_status);
+        }
+
+        #[test]
+        fn empty_test_case() {
+            // Problems whose interactor generates test cases on the fly are judged on a single
+            // test case with empty input and answer files.
+            let res = judge_interaction(
+                GENERATING_INTERACTOR_SOURCE, "/dev/null", "/dev/null", "read x; echo $((x + 1))");
+            assert!(res.verdict.is_accepted(), "unexpected verdict: {:?}", res.verdict);
+            assert_eq!(Some(ProcessExitStatus::Normal(0)), res.interactor_exit_status);
+        }
     }
 }
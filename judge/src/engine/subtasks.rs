@@ -0,0 +1,213 @@
+//! This module tracks the subtasks of a judge task while its test cases are judged. It decides
+//! which test cases are skipped, and aggregates the results of the test cases into the results of
+//! the subtasks.
+//!
+
+use crate::{Error, ErrorKind, Result};
+use crate::{Subtask, SubtaskResult, TestCaseResult, Verdict};
+
+/// Check that the given subtasks are valid for a test suite consisting of the given number of test
+/// cases: the identifiers of the subtasks are distinct, the test case indices are in range, and
+/// every subtask depends on subtasks listed before it only, which rules out circular dependencies.
+pub(super) fn validate(subtasks: &[Subtask], test_suite_len: usize) -> Result<()> {
+    for (position, subtask) in subtasks.iter().enumerate() {
+        let earlier = &subtasks[..position];
+        if earlier.iter().any(|s| s.id == subtask.id) {
+            return Err(Error::from(ErrorKind::InvalidSubtasks(
+                format!("duplicate subtask {}", subtask.id))));
+        }
+
+        if let Some(index) = subtask.test_cases.iter().find(|index| **index >= test_suite_len) {
+            return Err(Error::from(ErrorKind::InvalidSubtasks(format!(
+                "subtask {} refers to test case {}, but the test suite contains {} test cases",
+                subtask.id, index, test_suite_len))));
+        }
+
+        for dependency in &subtask.dependencies {
+            if !earlier.iter().any(|s| s.id == *dependency) {
+                return Err(Error::from(ErrorKind::InvalidSubtasks(format!(
+                    "subtask {} depends on subtask {}, which is not listed before it",
+                    subtask.id, dependency))));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Track the states of the subtasks of a judge task. The subtasks should have been checked by
+/// `validate`.
+pub(super) struct SubtaskTracker<'a> {
+    /// The subtasks of the judge task.
+    subtasks: &'a [Subtask],
+
+    /// Whether each subtask contains a failed test case.
+    failed: Vec<bool>,
+}
+
+impl<'a> SubtaskTracker<'a> {
+    /// Create a new `SubtaskTracker` instance tracking the given subtasks.
+    pub(super) fn new(subtasks: &'a [Subtask]) -> Self {
+        SubtaskTracker {
+            subtasks,
+            failed: vec![false; subtasks.len()],
+        }
+    }
+
+    /// Determine whether each subtask is dead, i.e. it has failed or depends on a dead subtask.
+    fn dead(&self) -> Vec<bool> {
+        let mut dead = Vec::with_capacity(self.subtasks.len());
+        for (position, subtask) in self.subtasks.iter().enumerate() {
+            // Dependencies are listed before the subtask, so their states are known here.
+            let dependency_dead = subtask.dependencies.iter().any(|id| {
+                self.subtasks.iter().position(|s| s.id == *id)
+                    .map(|dependency| dead[dependency])
+                    .unwrap_or(false)
+            });
+            dead.push(self.failed[position] || dependency_dead);
+        }
+
+        dead
+    }
+
+    /// Determine whether the test case at the given index should be skipped, which is the case if
+    /// it belongs to some subtasks and all of them are dead.
+    pub(super) fn should_skip(&self, index: usize) -> bool {
+        let dead = self.dead();
+        let mut containing = self.subtasks.iter().enumerate()
+            .filter(|(_, subtask)| subtask.test_cases.contains(&index))
+            .peekable();
+        containing.peek().is_some() && containing.all(|(position, _)| dead[position])
+    }
+
+    /// Record the verdict of the test case at the given index.
+    pub(super) fn record(&mut self, index: usize, verdict: Verdict) {
+        if verdict.is_accepted() {
+            return;
+        }
+
+        for (position, subtask) in self.subtasks.iter().enumerate() {
+            if subtask.test_cases.contains(&index) {
+                self.failed[position] = true;
+            }
+        }
+    }
+
+    /// Aggregate the given results of the judged test cases into the results of the subtasks. Test
+    /// cases missing from the results are considered not judged for the judge task running out of
+    /// its time budget.
+    pub(super) fn results(&self, test_suite: &[TestCaseResult]) -> Vec<SubtaskResult> {
+        let dead = self.dead();
+        self.subtasks.iter().enumerate()
+            .map(|(position, subtask)| {
+                if dead[position] && !self.failed[position] {
+                    return SubtaskResult { id: subtask.id, verdict: Verdict::Skipped, score: 0.0 };
+                }
+
+                let mut verdict = Verdict::Accepted;
+                let mut scale: f64 = 1.0;
+                for index in &subtask.test_cases {
+                    let (tc_verdict, tc_score) = match test_suite.get(*index) {
+                        Some(result) => (result.verdict, result.score),
+                        None => (Verdict::JudgeTimeLimitExceeded, None)
+                    };
+                    // Test cases skipped for other failed subtasks do not decide the verdict.
+                    if tc_verdict != Verdict::Skipped {
+                        verdict &= tc_verdict;
+                    }
+                    if let Some(score) = tc_score {
+                        scale = scale.min(score);
+                    }
+                }
+
+                let score = if verdict.is_accepted() { subtask.score * scale } else { 0.0 };
+                SubtaskResult { id: subtask.id, verdict, score }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn subtask(id: u32, test_cases: &[usize], dependencies: &[u32]) -> Subtask {
+        let mut subtask = Subtask::new(id, test_cases.to_vec(), 10.0);
+        subtask.dependencies = dependencies.to_vec();
+        subtask
+    }
+
+    mod validate {
+        use super::*;
+
+        fn assert_invalid(subtasks: &[Subtask]) {
+            match validate(subtasks, 4) {
+                Err(Error(ErrorKind::InvalidSubtasks(..), _)) => (),
+                r => panic!("unexpected result: {:?}", r)
+            };
+        }
+
+        #[test]
+        fn valid() {
+            validate(&[subtask(1, &[0, 1], &[]), subtask(2, &[1, 2, 3], &[1])], 4).unwrap();
+        }
+
+        #[test]
+        fn duplicate_id() {
+            assert_invalid(&[subtask(1, &[0], &[]), subtask(1, &[1], &[])]);
+        }
+
+        #[test]
+        fn test_case_out_of_range() {
+            assert_invalid(&[subtask(1, &[0, 4], &[])]);
+        }
+
+        #[test]
+        fn forward_dependency() {
+            assert_invalid(&[subtask(1, &[0], &[2]), subtask(2, &[1], &[])]);
+            assert_invalid(&[subtask(1, &[0], &[1])]);
+        }
+    }
+
+    mod tracker {
+        use super::*;
+
+        fn result(verdict: Verdict) -> TestCaseResult {
+            let mut result = TestCaseResult::new();
+            result.verdict = verdict;
+            result
+        }
+
+        #[test]
+        fn shared_test_case_judged() {
+            let subtasks = [subtask(1, &[0, 1], &[]), subtask(2, &[1, 2], &[])];
+            let mut tracker = SubtaskTracker::new(&subtasks);
+            tracker.record(0, Verdict::WrongAnswer);
+            // Test case 1 still counts for subtask 2.
+            assert!(!tracker.should_skip(1));
+            assert!(!tracker.should_skip(3));
+        }
+
+        #[test]
+        fn partial_score() {
+            let subtasks = [subtask(1, &[0, 1], &[])];
+            let tracker = SubtaskTracker::new(&subtasks);
+            let mut test_suite = vec![result(Verdict::Accepted), result(Verdict::Accepted)];
+            test_suite[1].score = Some(0.5);
+
+            let results = tracker.results(&test_suite);
+            assert_eq!(Verdict::Accepted, results[0].verdict);
+            assert_eq!(5.0, results[0].score);
+        }
+
+        #[test]
+        fn missing_test_cases() {
+            let subtasks = [subtask(1, &[0, 1], &[])];
+            let tracker = SubtaskTracker::new(&subtasks);
+
+            let results = tracker.results(&[result(Verdict::Accepted)]);
+            assert_eq!(Verdict::JudgeTimeLimitExceeded, results[0].verdict);
+            assert_eq!(0.0, results[0].score);
+        }
+    }
+}
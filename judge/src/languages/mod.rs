@@ -3,6 +3,7 @@
 
 mod loader;
 
+use std::cmp::Ordering;
 use std::collections::HashMap;
 use std::fmt::{Display, Formatter};
 use std::path::{Path, PathBuf};
@@ -13,7 +14,7 @@ use libloading::Library;
 #[cfg(feature = "serde")]
 use serde::{Serialize, Deserialize};
 
-use sandbox::SystemCall;
+use sandbox::{MemorySize, SystemCall, is_valid_c_string};
 
 use super::{Program, ProgramKind};
 
@@ -103,6 +104,12 @@ impl LanguageBranch {
     pub fn version(&self) -> &str {
         &self.1
     }
+
+    /// Parse the version of the branch into a `LanguageVersion` that can be compared with other
+    /// versions. Returns `None` if the version is not in a comparable form.
+    pub fn parse_version(&self) -> Option<LanguageVersion> {
+        LanguageVersion::parse(self.version())
+    }
 }
 
 impl PartialEq for LanguageBranch {
@@ -117,6 +124,68 @@ impl Display for LanguageBranch {
     }
 }
 
+/// A version of a language parsed into comparable parts.
+///
+/// A version consists of an optional non-numeric prefix followed by dot separated numeric
+/// components, e.g. `3.8`, `1.40` and `c++17`. Versions are compared component by component in the
+/// way semantic versions are, where missing components are taken as zeros, so `3.8` equals to
+/// `3.8.0`. Versions with different prefixes are not comparable.
+///
+/// Note that versions named after years in two digits, such as `c++98`, are compared by the plain
+/// numbers and thus precede `c++03`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct LanguageVersion {
+    /// The non-numeric prefix of the version.
+    prefix: String,
+
+    /// The numeric components of the version, with trailing zeros removed.
+    components: Vec<u64>,
+}
+
+impl LanguageVersion {
+    /// Parse the given version string. Returns `None` if the version string does not contain any
+    /// numeric components or its numeric components are malformed.
+    pub fn parse(version: &str) -> Option<Self> {
+        let numeric_start = version.find(|c: char| c.is_ascii_digit())?;
+        let (prefix, numeric) = version.split_at(numeric_start);
+
+        let mut components = Vec::new();
+        for component in numeric.split('.') {
+            if component.is_empty() || !component.bytes().all(|b| b.is_ascii_digit()) {
+                return None;
+            }
+            components.push(component.parse().ok()?);
+        }
+        while components.last() == Some(&0) {
+            components.pop();
+        }
+
+        Some(LanguageVersion { prefix: String::from(prefix), components })
+    }
+
+    /// Get the non-numeric prefix of the version.
+    pub fn prefix(&self) -> &str {
+        &self.prefix
+    }
+
+    /// Get the numeric components of the version, with trailing zeros removed.
+    pub fn components(&self) -> &[u64] {
+        &self.components
+    }
+}
+
+impl PartialOrd for LanguageVersion {
+    fn partial_cmp(&self, other: &LanguageVersion) -> Option<Ordering> {
+        if self.prefix != other.prefix {
+            return None;
+        }
+
+        // Trailing zeros are removed, so comparing the components lexicographically is equivalent
+        // to comparing them with missing components taken as zeros.
+        Some(self.components.cmp(&other.components))
+    }
+}
+
 /// Provide metadata about a language provider.
 #[derive(Debug)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
@@ -160,11 +229,62 @@ pub trait LanguageProvider : Sync {
     /// allocated and has the `'static` lifetime specifier.
     fn metadata(&self) -> &'static LanguageProviderMetadata;
 
+    /// Get the extension (without the leading dot) of source files written in this language, e.g.
+    /// `cpp`. The extension is used to name the file into which source code held in memory is
+    /// saved before compilation, so that the compiler can recognize the language by the file name.
+    ///
+    /// The default implementation returns an empty string, in which case saved source files have
+    /// no extension.
+    fn source_extension(&self) -> &'static str {
+        ""
+    }
+
+    /// Get the memory consumed by the runtime of this language itself, e.g. the Python interpreter
+    /// or the JVM. The overhead is added to the memory limit of judgees written in this language so
+    /// that programs are not rejected for the footprint of the runtime.
+    ///
+    /// The memory usage reported in judge results is always the raw usage measured by the sandbox,
+    /// including the overhead, so it may exceed the memory limit of the problem even if the verdict
+    /// is not `MemoryLimitExceeded`.
+    ///
+    /// The default implementation returns zero, which suits natively compiled languages.
+    fn memory_overhead(&self) -> MemorySize {
+        MemorySize::Bytes(0)
+    }
+
     /// Create a `CompilationInfo` instance containing necessary information used to compile the
     /// source code.
     fn compile(&self, program: &Program, kind: ProgramKind, output_dir: Option<PathBuf>)
         -> std::result::Result<CompilationInfo, Box<dyn std::error::Error>>;
 
+    /// Create a `CompilationInfo` instance like `compile`, with the given extra compiler flags
+    /// requested by the submission. The flags come from untrusted users, so implementations should
+    /// validate them and append only the flags allowed by their policy, after their default flags.
+    /// Implementations should document which flags are honored.
+    ///
+    /// The default implementation ignores all flags and calls `compile`.
+    fn compile_with_flags(&self,
+        program: &Program, kind: ProgramKind, output_dir: Option<PathBuf>, flags: &[String])
+        -> std::result::Result<CompilationInfo, Box<dyn std::error::Error>> {
+        if !flags.is_empty() {
+            log::warn!("Compiler flags for language \"{}\" are not supported and ignored: {:?}",
+                program.language, flags);
+        }
+        self.compile(program, kind, output_dir)
+    }
+
+    /// Create a `CompilationInfo` instance containing necessary information used to check the
+    /// syntax of the source code without producing a runnable program, e.g. the syntax check mode
+    /// of an interpreter. The compiler is expected to exit with a nonzero code if the check fails.
+    ///
+    /// The default implementation returns `None`, in which case the source code is checked by
+    /// compiling it through `compile`. Interpreted languages that do not implement this function
+    /// are not checked at all.
+    fn syntax_check(&self, _program: &Program, _kind: ProgramKind)
+        -> std::result::Result<Option<CompilationInfo>, Box<dyn std::error::Error>> {
+        Ok(None)
+    }
+
     /// Create an `ExecutionInfo` instance containing necessary information used to execute the
     /// program.
     fn execute(&self, program: &Program, kind: ProgramKind)
@@ -219,6 +339,43 @@ impl LanguageManagerImpl {
         None
     }
 
+    /// Find the branch with the highest version among the branches of the given language and
+    /// dialect whose versions are comparable with and not lower than `min_version`, together with
+    /// the `LanguageProvider` instance handling it.
+    fn find_best(&self, language: &str, dialect: &str, min_version: &LanguageVersion)
+        -> Option<(LanguageIdentifier, Arc<Box<dyn LanguageProvider>>)> {
+        let mut best: Option<(LanguageVersion, &LanguageBranch, &Arc<Box<dyn LanguageProvider>>)> =
+            None;
+        for provider in self.providers.get(language)? {
+            for branch in &provider.metadata().branches {
+                if branch.dialect() != dialect {
+                    continue;
+                }
+
+                let version = match branch.parse_version() {
+                    Some(version) => version,
+                    None => continue
+                };
+                match version.partial_cmp(min_version) {
+                    Some(Ordering::Equal) | Some(Ordering::Greater) => (),
+                    _ => continue
+                };
+
+                let is_better = match best {
+                    Some((ref best_version, ..)) =>
+                        version.partial_cmp(best_version) == Some(Ordering::Greater),
+                    None => true
+                };
+                if is_better {
+                    best = Some((version, branch, provider));
+                }
+            }
+        }
+
+        best.map(|(_, branch, provider)|
+            (LanguageIdentifier::new(language, branch.clone()), provider.clone()))
+    }
+
     /// Get all registered languages inside this language manager.
     fn languages(&self) -> Vec<LanguageIdentifier> {
         let mut lang = Vec::new();
@@ -289,6 +446,20 @@ impl LanguageManager {
         lock.find(lang)
     }
 
+    /// Find the branch with the highest version among the registered branches of the given
+    /// language and dialect whose versions are not lower than `min_version`, e.g. the latest C++
+    /// standard at least C++17. Branches whose versions are not comparable with `min_version` are
+    /// ignored.
+    ///
+    /// Returns the identifier of the selected branch together with the `LanguageProvider` instance
+    /// handling it, or `None` if no branch is suitable or `min_version` cannot be parsed.
+    pub fn find_best(&self, language: &str, dialect: &str, min_version: &str)
+        -> Option<(LanguageIdentifier, Arc<Box<dyn LanguageProvider>>)> {
+        let min_version = LanguageVersion::parse(min_version)?;
+        let lock = self.imp.read().unwrap();
+        lock.find_best(language, dialect, &min_version)
+    }
+
     /// Get all registered languages inside this language manager.
     pub fn languages(&self) -> Vec<LanguageIdentifier> {
         let lock = self.imp.read().unwrap();
@@ -315,16 +486,20 @@ impl<'a> LanguageProviderRegister<'a> {
 }
 
 /// Provide necessary information to execute a program.
+///
+/// Language providers should prefer `add_arg` and `add_env` over pushing into the `args` and `envs`
+/// fields directly, since these methods validate the given strings and report invalid ones as soon
+/// as the provider creates them rather than when the program is about to be executed.
 #[derive(Debug)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct ExecutionInfo {
     /// Path to the executable file to be executed.
     pub executable: PathBuf,
 
-    /// Arguments to be passed to the program.
+    /// Arguments to be passed to the program. Prefer `add_arg` to modify this field.
     pub args: Vec<String>,
 
-    /// Environment variables to be passed to the program.
+    /// Environment variables to be passed to the program. Prefer `add_env` to modify this field.
     pub envs: Vec<(String, String)>,
 
     /// System call whitelist specified for this execution.
@@ -342,6 +517,83 @@ impl ExecutionInfo {
             syscall_whitelist: Vec::new(),
         }
     }
+
+    /// Add an argument to be passed to the program. This function returns a `LanguageError` if the
+    /// given argument is not a valid C-style string.
+    pub fn add_arg<T>(&mut self, arg: T) -> crate::Result<()>
+        where T: Into<String> {
+        let arg = arg.into();
+        if !is_valid_c_string(&arg) {
+            return Err(crate::Error::from(crate::ErrorKind::LanguageError(
+                format!("invalid argument: \"{}\": not a valid C string", arg.escape_default()))));
+        }
+
+        self.args.push(arg);
+        Ok(())
+    }
+
+    /// Add an environment variable to be passed to the program. This function returns a
+    /// `LanguageError` if the given name or value is not a valid C-style string.
+    pub fn add_env<T1, T2>(&mut self, name: T1, value: T2) -> crate::Result<()>
+        where T1: Into<String>, T2: Into<String> {
+        let name = name.into();
+        let value = value.into();
+        if !is_valid_c_string(&name) || !is_valid_c_string(&value) {
+            return Err(crate::Error::from(crate::ErrorKind::LanguageError(format!(
+                "invalid environment variable: \"{}={}\": not a valid C string",
+                name.escape_default(), value.escape_default()))));
+        }
+
+        self.envs.push((name, value));
+        Ok(())
+    }
+
+    /// Find the executable file in the same way as the sandbox does when starting the program:
+    /// `executable` is used as is if it exists; otherwise, if it is a relative path, the
+    /// directories listed in the `PATH` environment variable of the current process are searched
+    /// in order.
+    /// Returns `None` if the executable file cannot be found.
+    ///
+    /// The executable file is resolved against the `PATH` and the filesystem of the host only. For
+    /// programs executed inside a chroot jail, the returned path may not exist in the jail, or may
+    /// refer to a different file there.
+    pub fn find_executable(&self) -> Option<PathBuf> {
+        if self.executable.exists() {
+            return Some(self.executable.clone());
+        }
+        if self.executable.is_absolute() {
+            return None;
+        }
+
+        let path_env = std::env::var_os("PATH")?;
+        std::env::split_paths(&path_env)
+            .map(|dir| dir.join(&self.executable))
+            .find(|path| path.exists())
+    }
+
+    /// Check that the interpreter executing the given program exists, e.g. `python3` for Python
+    /// programs or `java` for Java programs. Programs executed directly, e.g. the executables
+    /// produced by compilers, are not checked. This function returns a `LanguageError` naming the
+    /// missing interpreter, which is much clearer than the error reported by the sandbox when the
+    /// program fails to start.
+    pub fn check_interpreter(&self, program: &Program) -> crate::Result<()> {
+        if self.executable == program.file || self.find_executable().is_some() {
+            return Ok(());
+        }
+
+        Err(executable_not_found("interpreter", &self.executable))
+    }
+}
+
+/// Create a `LanguageError` reporting that the given executable of the given kind, e.g. `compiler`
+/// or `interpreter`, cannot be found.
+fn executable_not_found(kind: &str, executable: &Path) -> crate::Error {
+    let message = if executable.is_absolute() {
+        format!("{} '{}' not found", kind, executable.display())
+    } else {
+        format!("{} '{}' not found in PATH", kind, executable.display())
+    };
+    crate::Error::from(crate::ErrorKind::LanguageError(message))
 }
 
 /// Provide necessary information to compile a source program.
@@ -365,4 +617,199 @@ impl CompilationInfo {
             output_file: output_file.into()
         }
     }
+
+    /// Check that the compiler executable exists. This function returns a `LanguageError` naming
+    /// the missing compiler, which is much clearer than the error reported by the sandbox when
+    /// the compiler process fails to start.
+    pub fn check_compiler(&self) -> crate::Result<()> {
+        if self.compiler.find_executable().is_some() {
+            return Ok(());
+        }
+
+        Err(executable_not_found("compiler", &self.compiler.executable))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    mod execution_info {
+        use super::*;
+
+        #[test]
+        fn add_arg() {
+            let mut ei = ExecutionInfo::new("/bin/true");
+            ei.add_arg("-O2").unwrap();
+            assert_eq!(vec![String::from("-O2")], ei.args);
+
+            let err = ei.add_arg("-O2\0").unwrap_err();
+            match err.kind() {
+                crate::ErrorKind::LanguageError(..) => (),
+                kind => panic!("unexpected error kind: {:?}", kind),
+            };
+            assert_eq!(1, ei.args.len());
+        }
+
+        #[test]
+        fn add_env() {
+            let mut ei = ExecutionInfo::new("/bin/true");
+            ei.add_env("EPS", "1e-9").unwrap();
+            assert!(ei.add_env("EPS\0", "1e-9").is_err());
+            assert!(ei.add_env("EPS", "1e-9\0").is_err());
+            assert_eq!(vec![(String::from("EPS"), String::from("1e-9"))], ei.envs);
+        }
+
+        #[test]
+        fn find_executable() {
+            assert_eq!(Some(PathBuf::from("/bin/sh")),
+                ExecutionInfo::new("/bin/sh").find_executable());
+            let found = ExecutionInfo::new("sh").find_executable().unwrap();
+            assert!(found.is_absolute() && found.ends_with("sh"));
+            assert_eq!(None, ExecutionInfo::new("/bin/no-such-program").find_executable());
+            assert_eq!(None, ExecutionInfo::new("no-such-program").find_executable());
+        }
+
+        #[test]
+        fn check_interpreter() {
+            let language = LanguageIdentifier::new("py", LanguageBranch::new("cpython", "3.8"));
+            let program = Program::new("/judge/main.py", language);
+            ExecutionInfo::new("sh").check_interpreter(&program).unwrap();
+
+            let err = ExecutionInfo::new("no-such-python").check_interpreter(&program)
+                .unwrap_err();
+            match err.kind() {
+                crate::ErrorKind::LanguageError(message) =>
+                    assert_eq!("interpreter 'no-such-python' not found in PATH", message),
+                kind => panic!("unexpected error kind: {:?}", kind),
+            };
+
+            // Programs executed directly are not checked.
+            let program = Program::new("/judge/no-such-program", program.language);
+            ExecutionInfo::new("/judge/no-such-program").check_interpreter(&program).unwrap();
+        }
+    }
+
+    mod compilation_info {
+        use super::*;
+
+        #[test]
+        fn check_compiler() {
+            CompilationInfo::new("sh", "a.out").check_compiler().unwrap();
+
+            let err = CompilationInfo::new("no-such-g++", "a.out").check_compiler().unwrap_err();
+            match err.kind() {
+                crate::ErrorKind::LanguageError(message) =>
+                    assert_eq!("compiler 'no-such-g++' not found in PATH", message),
+                kind => panic!("unexpected error kind: {:?}", kind),
+            };
+
+            let err = CompilationInfo::new("/usr/bin/no-such-g++", "a.out")
+                .check_compiler().unwrap_err();
+            assert!(format!("{}", err).contains("compiler '/usr/bin/no-such-g++' not found"));
+        }
+    }
+
+    mod language_version {
+        use super::*;
+
+        fn version(s: &str) -> LanguageVersion {
+            LanguageVersion::parse(s).unwrap()
+        }
+
+        #[test]
+        fn parse() {
+            let v = version("c++17");
+            assert_eq!("c++", v.prefix());
+            assert_eq!(&[17], v.components());
+
+            assert_eq!(&[1, 40], version("1.40").components());
+            assert_eq!(version("3.8"), version("3.8.0"));
+            assert_eq!("", version("11").prefix());
+
+            assert!(LanguageVersion::parse("latest").is_none());
+            assert!(LanguageVersion::parse("3..8").is_none());
+            assert!(LanguageVersion::parse("3.8-beta").is_none());
+            assert!(LanguageVersion::parse("3.").is_none());
+        }
+
+        #[test]
+        fn compare() {
+            assert!(version("1.9") < version("1.10"));
+            assert!(version("1.40") > version("1.39.2"));
+            assert!(version("c++14") < version("c++17"));
+            assert_eq!(Some(Ordering::Equal), version("8").partial_cmp(&version("8.0.0")));
+            assert_eq!(None, version("c++17").partial_cmp(&version("17")));
+        }
+    }
+
+    mod find_best {
+        use super::*;
+
+        struct MockLanguageProvider {
+            metadata: &'static LanguageProviderMetadata,
+        }
+
+        impl LanguageProvider for MockLanguageProvider {
+            fn metadata(&self) -> &'static LanguageProviderMetadata {
+                self.metadata
+            }
+
+            fn compile(&self, _program: &Program, _kind: ProgramKind, _output_dir: Option<PathBuf>)
+                -> std::result::Result<CompilationInfo, Box<dyn std::error::Error>> {
+                unreachable!()
+            }
+
+            fn execute(&self, _program: &Program, _kind: ProgramKind)
+                -> std::result::Result<ExecutionInfo, Box<dyn std::error::Error>> {
+                unreachable!()
+            }
+        }
+
+        fn register(manager: &LanguageManager, language: &str, branches: &[(&str, &str)]) {
+            let mut metadata = LanguageProviderMetadata::new(language, false);
+            for (dialect, version) in branches {
+                metadata.branches.push(LanguageBranch::new(*dialect, *version));
+            }
+            manager.register(Box::new(MockLanguageProvider {
+                metadata: Box::leak(Box::new(metadata)),
+            }));
+        }
+
+        fn manager() -> LanguageManager {
+            let manager = LanguageManager::new();
+            register(&manager, "cpp", &[("gnu", "c++11"), ("gnu", "c++14"), ("clang", "c++20")]);
+            register(&manager, "cpp", &[("gnu", "c++17"), ("gnu", "latest")]);
+            register(&manager, "python", &[("cpy", "3.6"), ("cpy", "3.10"), ("cpy", "3.8")]);
+            manager
+        }
+
+        fn best_version(manager: &LanguageManager, language: &str, dialect: &str,
+            min_version: &str) -> Option<String> {
+            manager.find_best(language, dialect, min_version)
+                .map(|(lang, _)| String::from(lang.version()))
+        }
+
+        #[test]
+        fn highest_version_selected() {
+            let manager = manager();
+            assert_eq!(Some(String::from("c++17")), best_version(&manager, "cpp", "gnu", "c++11"));
+            assert_eq!(Some(String::from("c++17")), best_version(&manager, "cpp", "gnu", "c++17"));
+            assert_eq!(Some(String::from("3.10")), best_version(&manager, "python", "cpy", "3"));
+
+            let (lang, provider) = manager.find_best("cpp", "clang", "c++14").unwrap();
+            assert_eq!(LanguageIdentifier::new("cpp", LanguageBranch::new("clang", "c++20")), lang);
+            assert!(provider.metadata().branches.contains(lang.branch()));
+        }
+
+        #[test]
+        fn no_compatible_version() {
+            let manager = manager();
+            assert_eq!(None, best_version(&manager, "cpp", "gnu", "c++20"));
+            assert_eq!(None, best_version(&manager, "cpp", "gnu", "17"));
+            assert_eq!(None, best_version(&manager, "cpp", "msvc", "c++11"));
+            assert_eq!(None, best_version(&manager, "java", "java", "8"));
+            assert_eq!(None, best_version(&manager, "cpp", "gnu", "latest"));
+        }
+    }
 }
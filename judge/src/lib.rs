@@ -12,17 +12,24 @@ extern crate libloading;
 #[cfg(feature = "serde")]
 extern crate serde;
 
+#[cfg(feature = "tracing")]
+extern crate tracing;
+
 pub mod engine;
 pub mod languages;
 
+use std::fmt::{Display, Formatter};
 use std::ops::{BitAnd, BitAndAssign};
 use std::path::PathBuf;
+use std::str::FromStr;
 use std::time::Duration;
 
 #[cfg(feature = "serde")]
 use serde::{Serialize, Deserialize};
 
-use sandbox::{MemorySize, ProcessResourceUsage, ProcessExitStatus};
+use sandbox::{ProcessResourceUsage, ProcessExitStatus};
+
+pub use sandbox::MemorySize;
 
 use languages::LanguageIdentifier;
 
@@ -51,6 +58,36 @@ error_chain::error_chain! {
             description("language error")
             display("language error: {}", message)
         }
+
+        CheckerNotFound(name: String) {
+            description("checker could not be found")
+            display("checker could not be found: {}", name)
+        }
+
+        TooManyTestCases(count: usize, max: usize) {
+            description("too many test cases")
+            display("too many test cases: {} given, at most {} allowed", count, max)
+        }
+
+        InvalidGraderFile(path: PathBuf) {
+            description("invalid grader file destination")
+            display("invalid grader file destination: {}", path.display())
+        }
+
+        InvalidJudgeSubdir(name: String) {
+            description("invalid judge subdirectory name")
+            display("invalid judge subdirectory name: \"{}\"", name)
+        }
+
+        InvalidEngineConfig(message: String) {
+            description("invalid judge engine configuration")
+            display("invalid judge engine configuration: {}", message)
+        }
+
+        InvalidSubtasks(message: String) {
+            description("invalid subtasks")
+            display("invalid subtasks: {}", message)
+        }
     }
 }
 
@@ -67,6 +104,17 @@ pub struct CompilationTaskDescriptor {
 
     /// The optional output directory.
     pub output_dir: Option<PathBuf>,
+
+    /// Extra compiler flags requested by the submission, e.g. an optimization level. The flags are
+    /// passed to `LanguageProvider::compile_with_flags`, and each language provider decides which
+    /// of them are honored.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub compile_flags: Vec<String>,
+
+    /// Wall-clock time limit of the compiler, if any. A compiler running longer than this limit is
+    /// killed and the compilation fails.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub time_limit: Option<Duration>,
 }
 
 impl CompilationTaskDescriptor {
@@ -75,7 +123,9 @@ impl CompilationTaskDescriptor {
         CompilationTaskDescriptor {
             program,
             kind: ProgramKind::Judgee,
-            output_dir: None
+            output_dir: None,
+            compile_flags: Vec::new(),
+            time_limit: None,
         }
     }
 }
@@ -91,7 +141,14 @@ pub struct CompilationResult {
     pub compiler_out: Option<String>,
 
     /// Path to the output file, if any.
-    pub output_file: Option<PathBuf>
+    pub output_file: Option<PathBuf>,
+
+    /// The command line of the compiler, quoted for a POSIX shell. This is set when the
+    /// compilation fails, or on success if `JudgeEngineConfig::record_compile_command` is set, so
+    /// that operators can tell a misconfigured language provider from an error in the user's
+    /// source code.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub command: Option<String>,
 }
 
 impl CompilationResult {
@@ -101,7 +158,8 @@ impl CompilationResult {
         CompilationResult {
             succeeded: true,
             compiler_out: None,
-            output_file: Some(output_file.into())
+            output_file: Some(output_file.into()),
+            command: None,
         }
     }
 
@@ -111,9 +169,17 @@ impl CompilationResult {
         CompilationResult {
             succeeded: false,
             compiler_out: Some(compiler_out.into()),
-            output_file: None
+            output_file: None,
+            command: None,
         }
     }
+
+    /// Create a `CompilationResult` instance representing a compilation rejected because the size
+    /// of the source code, in bytes, exceeds the given maximum.
+    pub fn source_too_large(size: usize, max_size: usize) -> CompilationResult {
+        CompilationResult::fail(format!(
+            "source too large: {} bytes exceeds the limit of {} bytes", size, max_size))
+    }
 }
 
 /// Describe a judge task.
@@ -132,6 +198,70 @@ pub struct JudgeTaskDescriptor {
     /// The test suite, consisting of multiple test cases described by a 2-tuple (input_file,
     /// output_file).
     pub test_suite: Vec<TestCaseDescriptor>,
+
+    /// Options of the built-in checker used in standard judge mode.
+    pub checker_options: BuiltinCheckerOptions,
+
+    /// How the input, answer and the judgee's output are passed to the answer checker in special
+    /// judge mode.
+    pub checker_io_mode: CheckerIoMode,
+
+    /// Extra environment variables passed to the answer checker or the interactor, but not to the
+    /// judgee. This can be used to pass problem specific parameters (e.g. `EPS=1e-9`) to the jury
+    /// program without recompiling it.
+    pub jury_env: Vec<(String, String)>,
+
+    /// Helper files provided by the problem (e.g. grader libraries and data files) that are copied
+    /// into the judge directory afresh before the judgee runs on each test case, given by pairs of
+    /// the path to the file and the destination path relative to the judge directory.
+    pub grader_files: Vec<(PathBuf, PathBuf)>,
+
+    /// Whether a judgee exiting normally with a non-zero exit code gets the `RuntimeError` verdict.
+    /// If `false`, the exit code of the judgee is ignored and its output is checked as usual.
+    /// Defaults to `true`, which matches the behavior of most judges.
+    pub treat_nonzero_exit_as_runtime_error: bool,
+
+    /// Verdicts of judgees killed by specific signals, given by pairs of signal number and
+    /// verdict. These take precedence over the default mapping implemented by `signal_verdict`.
+    pub signal_verdicts: Vec<(i32, Verdict)>,
+
+    /// Name of a subdirectory of the judge directory under which the temporary directory of this
+    /// judge task is created, instead of directly under the judge directory. Callers judging
+    /// concurrently (e.g. multiple workers) can use distinct subdirectories to keep the files of
+    /// their judgees apart. The name should be a single path component.
+    pub judge_subdir: Option<String>,
+
+    /// A trusted program generating the answer of each test case, if any. When set, the program is
+    /// executed on the input of each test case under the jury's resource limits before the judgee,
+    /// and its output supersedes the answer file of the test case, which need not exist. The
+    /// output of the judgee is then checked against the generated answer as specified by the judge
+    /// mode.
+    ///
+    /// This is useful for problems whose answers are derived from the inputs rather than stored.
+    /// Unlike reference judge mode, the answer generator is run even if the judgee fails, and works
+    /// with any judge mode that uses answer files. It is ignored in reference judge mode.
+    pub answer_generator: Option<Program>,
+
+    /// Identifier of the submission being judged, if any. It is used for diagnostics only, e.g. as
+    /// a field of the spans emitted when the `tracing` feature is enabled.
+    pub submission_id: Option<String>,
+
+    /// Identifier of the problem the judgee is submitted to, if any. Like `submission_id`, it is
+    /// used for diagnostics only.
+    pub problem_id: Option<String>,
+
+    /// Subtasks grouping the test cases of the test suite, if the problem is scored by subtasks.
+    /// Once a test case of a subtask fails, the remaining test cases of the subtask are skipped,
+    /// and subtasks depending on a failed subtask are skipped entirely. Test cases not belonging
+    /// to any subtask are always judged.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub subtasks: Vec<Subtask>,
+
+    /// Wall-clock time budget of this judge task, if any. It caps
+    /// `JudgeEngineConfig::total_judge_time_limit` and works in the same way: test cases not
+    /// started within the budget are not judged and the judge result gets the
+    /// `JudgeTimeLimitExceeded` verdict.
+    pub time_limit: Option<Duration>,
 }
 
 impl JudgeTaskDescriptor {
@@ -141,11 +271,71 @@ impl JudgeTaskDescriptor {
             program,
             mode: JudgeMode::default(),
             limits: ResourceLimits::default(),
-            test_suite: Vec::new()
+            test_suite: Vec::new(),
+            checker_options: BuiltinCheckerOptions::default(),
+            checker_io_mode: CheckerIoMode::default(),
+            jury_env: Vec::new(),
+            grader_files: Vec::new(),
+            treat_nonzero_exit_as_runtime_error: true,
+            signal_verdicts: Vec::new(),
+            judge_subdir: None,
+            answer_generator: None,
+            submission_id: None,
+            problem_id: None,
+            subtasks: Vec::new(),
+            time_limit: None,
+        }
+    }
+}
+
+/// Describe a subtask, which is a group of test cases scored as a whole.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Subtask {
+    /// Identifier of the subtask, which is referred to by the dependencies of other subtasks.
+    pub id: u32,
+
+    /// Indices of the test cases of this subtask in the test suite. A test case may belong to
+    /// multiple subtasks, in which case it is judged only once.
+    pub test_cases: Vec<usize>,
+
+    /// Score of the subtask, granted if the judgee passes all test cases of the subtask.
+    pub score: f64,
+
+    /// Identifiers of the subtasks this subtask depends on, which must be listed before this
+    /// subtask. This subtask is skipped if any of them fails.
+    pub dependencies: Vec<u32>,
+}
+
+impl Subtask {
+    /// Create a new `Subtask` instance without dependencies.
+    pub fn new(id: u32, test_cases: Vec<usize>, score: f64) -> Self {
+        Subtask {
+            id,
+            test_cases,
+            score,
+            dependencies: Vec::new(),
         }
     }
 }
 
+/// Result of a judge task on a specific subtask.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SubtaskResult {
+    /// Identifier of the subtask.
+    pub id: u32,
+
+    /// Verdict of the subtask, which is the verdict of its first failed test case, or `Skipped` if
+    /// the subtask is skipped for a failed dependency.
+    pub verdict: Verdict,
+
+    /// Score granted to the judgee on the subtask. The full score of the subtask is granted if all
+    /// its test cases are accepted, scaled by the smallest score declared on its test cases if any.
+    /// No score is granted otherwise.
+    pub score: f64,
+}
+
 /// Represent a program stored in local disk file, along with the corresponding language
 /// environment. The program file may either be a source file or an executable file.
 #[derive(Clone, Debug)]
@@ -229,7 +419,57 @@ pub enum BuiltinCheckers {
     FloatingPointAware,
 
     /// The case insensitive built-in checker.
-    CaseInsensitive
+    CaseInsensitive,
+
+    /// The built-in checker that compares the lines of the answer and the judgee's output
+    /// regardless of their order. Trailing whitespaces and blank lines are ignored.
+    SortedLines,
+
+    /// The built-in checker that requires the judgee's output to be byte-for-byte identical to the
+    /// answer. The checker options do not apply to this checker.
+    ExactBytes,
+}
+
+impl BuiltinCheckers {
+    /// All the built-in checkers.
+    pub const ALL: [BuiltinCheckers; 5] = [
+        BuiltinCheckers::Default,
+        BuiltinCheckers::FloatingPointAware,
+        BuiltinCheckers::CaseInsensitive,
+        BuiltinCheckers::SortedLines,
+        BuiltinCheckers::ExactBytes,
+    ];
+
+    /// Get the name of the built-in checker. The built-in checker is registered under this name in
+    /// `CheckerRegistry`, and can be parsed from this name.
+    pub fn name(&self) -> &'static str {
+        match self {
+            BuiltinCheckers::Default => "default",
+            BuiltinCheckers::FloatingPointAware => "floating-point-aware",
+            BuiltinCheckers::CaseInsensitive => "case-insensitive",
+            BuiltinCheckers::SortedLines => "sorted-lines",
+            BuiltinCheckers::ExactBytes => "exact-bytes",
+        }
+    }
+
+    /// Get a human readable description of the built-in checker.
+    pub fn description(&self) -> &'static str {
+        match self {
+            BuiltinCheckers::Default =>
+                "Compares the answer and the output token by token. Tokens must match exactly.",
+            BuiltinCheckers::FloatingPointAware =>
+                "Compares the answer and the output token by token. Numeric tokens are accepted if \
+                their absolute or relative error is at most 1e-6.",
+            BuiltinCheckers::CaseInsensitive =>
+                "Compares the answer and the output token by token, ignoring ASCII case.",
+            BuiltinCheckers::SortedLines =>
+                "Compares the lines of the answer and the output regardless of their order. \
+                Trailing whitespaces and blank lines are ignored.",
+            BuiltinCheckers::ExactBytes =>
+                "Compares the answer and the output byte by byte. Whitespaces and line endings \
+                must match exactly.",
+        }
+    }
 }
 
 impl Default for BuiltinCheckers {
@@ -238,6 +478,140 @@ impl Default for BuiltinCheckers {
     }
 }
 
+impl Display for BuiltinCheckers {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for BuiltinCheckers {
+    type Err = Error;
+
+    /// Parse a built-in checker from its name. `floating-point` is accepted as an alias of
+    /// `floating-point-aware`.
+    fn from_str(s: &str) -> Result<Self> {
+        if s == "floating-point" {
+            return Ok(BuiltinCheckers::FloatingPointAware);
+        }
+
+        BuiltinCheckers::ALL.iter()
+            .find(|checker| checker.name() == s)
+            .cloned()
+            .ok_or_else(|| Error::from(ErrorKind::CheckerNotFound(String::from(s))))
+    }
+}
+
+/// Describe a built-in answer checker. Values of this type are returned by `builtin_checkers`, and
+/// are useful for presenting the available built-in checkers to users.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct BuiltinCheckerInfo {
+    /// The built-in checker.
+    pub checker: BuiltinCheckers,
+
+    /// Name of the built-in checker.
+    pub name: String,
+
+    /// Human readable description of the built-in checker.
+    pub description: String,
+
+    /// Options that can be configured for the built-in checker through `BuiltinCheckerOptions`.
+    pub options: Vec<BuiltinCheckerOptionInfo>,
+}
+
+/// Describe a field of `BuiltinCheckerOptions`.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct BuiltinCheckerOptionInfo {
+    /// Name of the field in `BuiltinCheckerOptions`.
+    pub name: String,
+
+    /// Type of the option's value, e.g. `string`.
+    pub value_type: String,
+
+    /// Whether the option can be omitted.
+    pub optional: bool,
+
+    /// Human readable description of the option.
+    pub description: String,
+}
+
+/// Get descriptions of all the built-in checkers.
+pub fn builtin_checkers() -> Vec<BuiltinCheckerInfo> {
+    BuiltinCheckers::ALL.iter()
+        .map(|checker| BuiltinCheckerInfo {
+            checker: *checker,
+            name: String::from(checker.name()),
+            description: String::from(checker.description()),
+            // All the built-in checkers except the exact bytes checker support the same options.
+            options: match checker {
+                BuiltinCheckers::ExactBytes => Vec::new(),
+                _ => vec![
+                    BuiltinCheckerOptionInfo {
+                        name: String::from("ignore_line_prefix"),
+                        value_type: String::from("string"),
+                        optional: true,
+                        description: String::from(
+                            "Lines starting with this prefix in both the answer and the output \
+                            are ignored."),
+                    },
+                    BuiltinCheckerOptionInfo {
+                        name: String::from("normalize_line_endings"),
+                        value_type: String::from("bool"),
+                        optional: true,
+                        description: String::from(
+                            "Carriage returns are stripped from both the answer and the output \
+                            before they are compared. Defaults to true."),
+                    },
+                    BuiltinCheckerOptionInfo {
+                        name: String::from("require_trailing_newline"),
+                        value_type: String::from("bool"),
+                        optional: true,
+                        description: String::from(
+                            "The output must end with a newline if and only if the answer does."),
+                    },
+                ],
+            },
+        })
+        .collect()
+}
+
+/// Options of the built-in answer checkers used in standard judge mode.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct BuiltinCheckerOptions {
+    /// Lines starting with this prefix in both the answer file and the judgee's output are ignored
+    /// entirely, if any. This is useful when some lines are non-deterministic, e.g. debug prints
+    /// prefixed with `#`.
+    pub ignore_line_prefix: Option<String>,
+
+    /// Whether `\r` bytes are stripped from both the answer file and the judgee's output before
+    /// they are compared, so that outputs with CRLF line endings are accepted against answers with
+    /// LF line endings. Defaults to `true`; disable it for problems requiring byte-exact output.
+    pub normalize_line_endings: bool,
+
+    /// Whether the judgee's output must end with a newline if and only if the answer file does.
+    /// Defaults to `false`, in which case trailing newlines are ignored.
+    pub require_trailing_newline: bool,
+}
+
+impl BuiltinCheckerOptions {
+    /// Create a new `BuiltinCheckerOptions` value.
+    pub fn new() -> Self {
+        BuiltinCheckerOptions {
+            ignore_line_prefix: None,
+            normalize_line_endings: true,
+            require_trailing_newline: false,
+        }
+    }
+}
+
+impl Default for BuiltinCheckerOptions {
+    fn default() -> Self {
+        BuiltinCheckerOptions::new()
+    }
+}
+
 /// The judge mode.
 #[derive(Clone, Debug)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
@@ -247,6 +621,10 @@ pub enum JudgeMode {
     /// case by the specified built-in answer checker.
     Standard(BuiltinCheckers),
 
+    /// Standard judge mode using an in-process answer checker registered in the judge engine's
+    /// `CheckerRegistry` under the name given in the variant field.
+    BuiltinNamed(String),
+
     /// Special judge mode. The input of the judgee is redirected to the input file of each test
     /// case, and the output of the judgee, together with the input and answer of the test case, are
     /// sent to a user provided program given in the variant field who is responsible for checking
@@ -257,7 +635,27 @@ pub enum JudgeMode {
     /// program called the interactor. The input and answer of the test case is sent into the
     /// interactor, too. The interator is responsible for checking the correctness of the behavior
     /// of the judgee.
-    Interactive(Program)
+    ///
+    /// The interactor is given the file descriptors of the input file, the answer file and the
+    /// report as its 3 command line arguments. The report becomes the comment of the test case,
+    /// and may end with a score directive; see `TestCaseResult::set_interactor_result`.
+    Interactive(Program),
+
+    /// Reference judge mode. The input of the judgee is redirected to the input file of each test
+    /// case, and the answer is produced on the fly by running a trusted reference solution on the
+    /// same input under the jury's resource limits. The output of the judgee is then compared
+    /// against the output of the reference solution by the specified built-in answer checker. The
+    /// answer files of the test cases are not used.
+    ///
+    /// This mode is useful for stress testing submissions and validating test data with random
+    /// inputs.
+    AgainstReference {
+        /// The reference solution.
+        reference: Program,
+
+        /// The built-in checker comparing the outputs of the judgee and the reference solution.
+        checker: BuiltinCheckers,
+    },
 }
 
 impl Default for JudgeMode {
@@ -266,6 +664,35 @@ impl Default for JudgeMode {
     }
 }
 
+/// Specify how the input file, the answer file and the judgee's output are passed to the answer
+/// checker in special judge mode.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum CheckerIoMode {
+    /// The three files are opened by the judge and their file descriptors are passed to the answer
+    /// checker as command line arguments, in the order of input, answer and output. This is the
+    /// convention used by testlib based checkers.
+    FileDescriptors,
+
+    /// The contents of the three files are concatenated onto the answer checker's standard input,
+    /// in the order of input, answer and output. Each stream is framed by a header line containing
+    /// the length of the stream in bytes as an ASCII decimal number, followed by exactly that many
+    /// bytes of the stream's content:
+    ///
+    /// ```text
+    /// <input length>\n<input bytes><answer length>\n<answer bytes><output length>\n<output bytes>
+    /// ```
+    ///
+    /// No delimiter follows the content of a stream, so the contents may contain arbitrary bytes.
+    StdinDelimited,
+}
+
+impl Default for CheckerIoMode {
+    fn default() -> Self {
+        CheckerIoMode::FileDescriptors
+    }
+}
+
 /// Describe a test case.
 #[derive(Clone, Debug)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
@@ -274,7 +701,12 @@ pub struct TestCaseDescriptor {
     pub input_file: PathBuf,
 
     /// Path to the answer file.
-    pub answer_file: PathBuf
+    pub answer_file: PathBuf,
+
+    /// Whether the test case is public (e.g. a sample test case). The contents of the input file,
+    /// the answer file and the judgee's output are only populated into the judge result for public
+    /// test cases, so that secret test data is not leaked through judge results.
+    pub public: bool,
 }
 
 impl TestCaseDescriptor {
@@ -284,12 +716,13 @@ impl TestCaseDescriptor {
         TestCaseDescriptor {
             input_file: input_file.into(),
             answer_file: answer_file.into(),
+            public: false,
         }
     }
 }
 
 /// Result of a judge task.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct JudgeResult {
     /// Overall verdict of the judge task.
@@ -301,7 +734,16 @@ pub struct JudgeResult {
     /// Judge results of every executed test cases in the test suite. Do not directly modify this
     /// field; use the `add_test_case_result` function instead to maintain `verdict` and `rusage`
     /// accordingly.
-    pub test_suite: Vec<TestCaseResult>
+    pub test_suite: Vec<TestCaseResult>,
+
+    /// The output message generated by the compiler, if the judgee failed to compile.
+    pub compiler_out: Option<String>,
+
+    /// Comment on the overall judge result, if any.
+    pub comment: Option<String>,
+
+    /// Judge results of every subtask of the judge task, in the order the subtasks are given.
+    pub subtasks: Vec<SubtaskResult>,
 }
 
 impl JudgeResult {
@@ -310,7 +752,26 @@ impl JudgeResult {
         JudgeResult {
             verdict: Verdict::Accepted,
             rusage: ProcessResourceUsage::new(),
-            test_suite: Vec::new()
+            test_suite: Vec::new(),
+            compiler_out: None,
+            comment: None,
+            subtasks: Vec::new(),
+        }
+    }
+
+    /// Get the total score granted to the judgee on all subtasks.
+    pub fn subtask_score(&self) -> f64 {
+        self.subtasks.iter().map(|subtask| subtask.score).sum()
+    }
+
+    /// Create a `JudgeResult` instance representing a judge task whose judgee failed to compile.
+    /// The given message is the output generated by the compiler.
+    pub fn compilation_failed<T>(message: T) -> Self
+        where T: Into<String> {
+        JudgeResult {
+            verdict: Verdict::CompilationError,
+            compiler_out: Some(message.into()),
+            ..JudgeResult::new()
         }
     }
 
@@ -329,8 +790,22 @@ impl Default for JudgeResult {
     }
 }
 
+/// Progress of a judge task under execution, reported after each test case is judged or skipped.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct JudgeProgress {
+    /// Number of test cases judged or skipped so far.
+    pub test_cases_done: usize,
+
+    /// Total number of test cases to be judged.
+    pub test_cases_total: usize,
+
+    /// Overall verdict of the test cases judged so far.
+    pub verdict: Verdict,
+}
+
 /// Result of a judge task on a specific test case.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct TestCaseResult {
     /// Verdict of the test case.
@@ -351,6 +826,14 @@ pub struct TestCaseResult {
     /// Comment made by the answer checker or interactor, if any.
     pub comment: Option<String>,
 
+    /// Score of the judgee on the test case as a fraction of the full score, in `[0, 1]`, if the
+    /// interactor declared one through a score directive. See `parse_score_directive`.
+    pub score: Option<f64>,
+
+    /// Reason of the `CheckerFailed` verdict, if any. Unlike the comment, this field is meant to be
+    /// inspected by programs, e.g. to alert operators of problems of the jury.
+    pub failure_reason: Option<FailureReason>,
+
     /// View into the input file of the test case, if any.
     pub input_view: Option<String>,
 
@@ -362,9 +845,26 @@ pub struct TestCaseResult {
 
     /// View into the error contents produced by the judgee, if any.
     pub error_view: Option<String>,
+
+    /// Path to the full output produced by the judgee, if it has been preserved for debugging. See
+    /// `JudgeEngineConfig::preserved_output_dir`.
+    pub preserved_output: Option<PathBuf>,
+
+    /// Structured view of the first difference between the answer and the output found by the
+    /// built-in checker, if any. Like the views of the data, this is only recorded for public test
+    /// cases.
+    pub diff: Option<DiffView>,
 }
 
 impl TestCaseResult {
+    /// Create a new `TestCaseResult` instance representing a skipped test case.
+    pub fn skipped() -> Self {
+        TestCaseResult {
+            verdict: Verdict::Skipped,
+            ..TestCaseResult::new()
+        }
+    }
+
     /// Create a new `TestCaseResult` instance.
     pub fn new() -> Self {
         TestCaseResult {
@@ -374,37 +874,175 @@ impl TestCaseResult {
             interactor_exit_status: None,
             rusage: ProcessResourceUsage::new(),
             comment: None,
+            score: None,
+            failure_reason: None,
             input_view: None,
             answer_view: None,
             output_view: None,
-            error_view: None
+            error_view: None,
+            preserved_output: None,
+            diff: None,
         }
     }
 
     /// Set the judgee's exit status. This function also maintains the `verdict` field accordingly.
+    /// `nonzero_exit_is_error` decides whether a non-zero exit code leads to the `RuntimeError`
+    /// verdict. The verdicts of judgees killed by signals are looked up in `signal_verdicts` first
+    /// and then decided by `signal_verdict`, and the signal is described in the `comment` field.
     ///
     /// This function panics if the given exit status is either `ProcessExitStatus::NotExited`.
-    fn set_judgee_exit_status(&mut self, status: ProcessExitStatus) {
+    fn set_judgee_exit_status(&mut self,
+        status: ProcessExitStatus,
+        nonzero_exit_is_error: bool,
+        signal_verdicts: &[(i32, Verdict)]) {
         self.judgee_exit_status = status;
         self.verdict = match self.judgee_exit_status {
+            ProcessExitStatus::Normal(code) if code != 0 && nonzero_exit_is_error =>
+                Verdict::RuntimeError,
             ProcessExitStatus::Normal(..) => Verdict::Accepted,
-            ProcessExitStatus::KilledBySignal(..) => Verdict::RuntimeError,
+            ProcessExitStatus::KilledBySignal(sig) => {
+                self.comment = Some(describe_signal(sig));
+                signal_verdicts.iter()
+                    .find(|(s, _)| *s == sig)
+                    .map(|(_, verdict)| *verdict)
+                    .unwrap_or_else(|| signal_verdict(sig))
+            },
             ProcessExitStatus::CPUTimeLimitExceeded => Verdict::TimeLimitExceeded,
             ProcessExitStatus::RealTimeLimitExceeded => Verdict::IdlenessLimitExceeded,
             ProcessExitStatus::MemoryLimitExceeded => Verdict::MemoryLimitExceeded,
             ProcessExitStatus::BannedSyscall => Verdict::BannedSystemCall,
+            ProcessExitStatus::SyscallBudgetExceeded => {
+                self.comment = Some(String::from("system call budget exceeded"));
+                Verdict::BannedSystemCall
+            },
+            ProcessExitStatus::DiskQuotaExceeded => Verdict::DiskLimitExceeded,
             ProcessExitStatus::NotExited => panic!("unexpected judgee exit status."),
         };
     }
+
+    /// Set the interactor's exit status and the report written by it. This function also maintains
+    /// the `verdict`, `comment` and `score` fields accordingly, and should be called after the
+    /// judgee's exit status has been set.
+    ///
+    /// An interactor exiting with code 0 accepts the judgee and any other exit code rejects it,
+    /// unless the judgee has been rejected already. The report, with the trailing score directive
+    /// removed, becomes the comment. An interactor killed by a signal after the judgee has been
+    /// rejected keeps the judgee's verdict, since the interactor typically gets `SIGPIPE` when the
+    /// judgee crashes. An interactor killed by `SIGPIPE` after an accepted judgee means the judgee
+    /// stopped reading its input early, which leads to the `WrongAnswer` verdict. An interactor
+    /// that does not exit normally otherwise leads to the `InteractorFailed` verdict.
+    pub fn set_interactor_result(&mut self, status: ProcessExitStatus, report: &str) {
+        self.interactor_exit_status = Some(status.clone());
+        match status {
+            ProcessExitStatus::Normal(code) => {
+                let (comment, score) = split_score_directive(report);
+                if self.verdict == Verdict::Accepted && code != 0 {
+                    self.verdict = Verdict::WrongAnswer;
+                }
+                self.comment = Some(String::from(comment));
+                self.score = score;
+            },
+            ProcessExitStatus::KilledBySignal(sig) => {
+                if self.verdict == Verdict::Accepted {
+                    self.verdict = if sig == libc::SIGPIPE {
+                        Verdict::WrongAnswer
+                    } else {
+                        Verdict::InteractorFailed
+                    };
+                }
+                self.comment = Some(format!("interactor {}", describe_signal(sig)));
+            },
+            status => {
+                self.verdict = Verdict::InteractorFailed;
+                self.comment = Some(format!("interactor failed: {:?}", status));
+            }
+        };
+    }
+}
+
+/// Prefix of the score directive, which is the last non-empty line of an interactor's report
+/// declaring the score of the judgee as a fraction of the full score, e.g. `score=0.5`.
+pub const SCORE_DIRECTIVE_PREFIX: &str = "score=";
+
+/// Split the score directive off the given report of an interactor. Returns the report without the
+/// directive and the declared score. Directives whose scores are not in `[0, 1]` are ignored and
+/// left in the report.
+fn split_score_directive(report: &str) -> (&str, Option<f64>) {
+    let trimmed = report.trim_end();
+    let (rest, last_line) = match trimmed.rfind('\n') {
+        Some(index) => (&trimmed[..index + 1], &trimmed[index + 1..]),
+        None => ("", trimmed)
+    };
+
+    let line = last_line.trim();
+    if !line.starts_with(SCORE_DIRECTIVE_PREFIX) {
+        return (report, None);
+    }
+
+    match line[SCORE_DIRECTIVE_PREFIX.len()..].trim().parse::<f64>() {
+        Ok(score) if score >= 0.0 && score <= 1.0 => (rest, Some(score)),
+        _ => (report, None)
+    }
+}
+
+/// Parse the score directive in the given report of an interactor, if any. The directive must be
+/// the last non-empty line of the report and declare a score in `[0, 1]`.
+pub fn parse_score_directive(report: &str) -> Option<f64> {
+    split_score_directive(report).1
+}
+
+/// Get the name and a short description of the given signal, if it is a common one.
+fn signal_info(sig: i32) -> Option<(&'static str, &'static str)> {
+    let info = match sig {
+        libc::SIGSEGV => ("SIGSEGV", "segmentation fault"),
+        libc::SIGABRT => ("SIGABRT", "aborted"),
+        libc::SIGFPE => ("SIGFPE", "floating point exception"),
+        libc::SIGBUS => ("SIGBUS", "bus error"),
+        libc::SIGILL => ("SIGILL", "illegal instruction"),
+        libc::SIGKILL => ("SIGKILL", "killed"),
+        libc::SIGTERM => ("SIGTERM", "terminated"),
+        libc::SIGPIPE => ("SIGPIPE", "broken pipe"),
+        libc::SIGXCPU => ("SIGXCPU", "CPU time limit exceeded"),
+        libc::SIGXFSZ => ("SIGXFSZ", "output size limit exceeded"),
+        libc::SIGSYS => ("SIGSYS", "bad system call"),
+        _ => return None
+    };
+
+    Some(info)
+}
+
+/// Describe the given signal that killed the judgee in a human readable way, e.g.
+/// `killed by signal 11 (SIGSEGV): segmentation fault`.
+pub fn describe_signal(sig: i32) -> String {
+    match signal_info(sig) {
+        Some((name, description)) =>
+            format!("killed by signal {} ({}): {}", sig, name, description),
+        None => format!("killed by signal {}", sig)
+    }
+}
+
+/// Get the default verdict of a judgee killed by the given signal. Signals raised by the native
+/// resource limits get the verdicts of the corresponding limits, and any other signal leads to the
+/// `RuntimeError` verdict.
+pub fn signal_verdict(sig: i32) -> Verdict {
+    match sig {
+        libc::SIGXCPU => Verdict::TimeLimitExceeded,
+        libc::SIGXFSZ => Verdict::DiskLimitExceeded,
+        libc::SIGSYS => Verdict::BannedSystemCall,
+        _ => Verdict::RuntimeError
+    }
 }
 
 /// Verdict of the judge.
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Verdict {
     /// The judgee accepted all test cases in the test suite.
     Accepted,
 
+    /// The judgee failed to compile.
+    CompilationError,
+
     /// The judgee produced wrong answer on some test case in the test suite.
     WrongAnswer,
 
@@ -423,20 +1061,28 @@ pub enum Verdict {
     /// The judgee called an unexpected system call.
     BannedSystemCall,
 
+    /// The judgee wrote too much data into its working directory.
+    DiskLimitExceeded,
+
     /// The checker failed, so judge cannot continue.
     CheckerFailed,
 
     /// The interactor failed, so judge cannot continue.
-    InteractorFailed
+    InteractorFailed,
+
+    /// The judge task ran out of its total time budget, so the remaining test cases were not
+    /// judged.
+    JudgeTimeLimitExceeded,
+
+    /// The test case was not judged since all subtasks containing it have failed or depend on a
+    /// failed subtask.
+    Skipped,
 }
 
 impl Verdict {
     /// Determine whether this `Verdict` value is `Verdict::Accepted`.
     pub fn is_accepted(&self) -> bool {
-        match self {
-            Verdict::Accepted => true,
-            _ => false
-        }
+        *self == Verdict::Accepted
     }
 
     /// If this `Verdict` is `Verdict::Accepted`, then returns `rhs`; otherwise returns `self`.
@@ -456,8 +1102,446 @@ impl BitAnd for Verdict {
 
 impl BitAndAssign for Verdict {
     fn bitand_assign(&mut self, rhs: Verdict) {
-        if self.is_accepted() {
+        if *self == Verdict::Accepted {
             *self = rhs;
         }
     }
 }
+
+/// Maximum length of the snippets in a `DiffView`, in bytes.
+pub const DIFF_SNIPPET_LEN: usize = 64;
+
+/// Length of the common context kept before the first difference in the snippets of a `DiffView`,
+/// in bytes.
+const DIFF_CONTEXT_LEN: usize = DIFF_SNIPPET_LEN / 4;
+
+/// Provide a structured view of the first difference between the answer and the output of the
+/// judgee, which can be rendered by UIs as a diff. The snippets start at the same position of the
+/// compared contents and are at most `DIFF_SNIPPET_LEN` bytes long.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct DiffView {
+    /// Snippet of the answer around the first difference.
+    pub expected_snippet: String,
+
+    /// Snippet of the output of the judgee around the first difference.
+    pub actual_snippet: String,
+
+    /// Offset of the first difference in both snippets, in bytes. If one snippet is a prefix of
+    /// the other, this is the length of the shorter one.
+    pub first_diff_offset: usize,
+}
+
+impl DiffView {
+    /// Create a new `DiffView` value of the given expected and actual contents. At most
+    /// `DIFF_CONTEXT_LEN` bytes of the common prefix are kept before the first difference.
+    pub fn new(expected: &str, actual: &str) -> Self {
+        let diff = expected.char_indices().zip(actual.chars())
+            .find(|((_, e), a)| e != a)
+            .map(|((offset, _), _)| offset)
+            .unwrap_or_else(|| std::cmp::min(expected.len(), actual.len()));
+
+        // The common prefix is the same in both contents, so `start` is a character boundary of
+        // both of them. `diff` itself is a character boundary, so the loop stops there at last.
+        let mut start = diff.saturating_sub(DIFF_CONTEXT_LEN);
+        while !expected.is_char_boundary(start) {
+            start += 1;
+        }
+
+        DiffView {
+            expected_snippet: String::from(truncate_str(&expected[start..], DIFF_SNIPPET_LEN)),
+            actual_snippet: String::from(truncate_str(&actual[start..], DIFF_SNIPPET_LEN)),
+            first_diff_offset: diff - start,
+        }
+    }
+}
+
+/// Truncate the given string to at most `max_len` bytes, at a character boundary.
+fn truncate_str(s: &str, max_len: usize) -> &str {
+    if s.len() <= max_len {
+        return s;
+    }
+
+    let mut len = max_len;
+    while !s.is_char_boundary(len) {
+        len -= 1;
+    }
+    &s[..len]
+}
+
+/// Reason of a `CheckerFailed` verdict, which tells the problems of the jury apart from each other.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum FailureReason {
+    /// The answer checker ran out of its CPU time limit or real time limit.
+    CheckerTimeLimitExceeded,
+
+    /// The answer checker ran out of its memory limit.
+    CheckerMemoryLimitExceeded,
+
+    /// The answer checker was killed by a signal.
+    CheckerKilledBySignal,
+
+    /// The answer checker called a banned system call.
+    CheckerBannedSystemCall,
+
+    /// The answer checker wrote too much data into its working directory.
+    CheckerDiskLimitExceeded,
+
+    /// The reference solution did not exit normally with a zero exit code.
+    ReferenceFailed,
+
+    /// The answer generator did not exit normally with a zero exit code.
+    AnswerGeneratorFailed,
+}
+
+impl FailureReason {
+    /// Get the reason of the failure of an answer checker exited with the given status, or `None`
+    /// if the answer checker exited normally.
+    pub fn of_checker(status: &ProcessExitStatus) -> Option<Self> {
+        match status {
+            ProcessExitStatus::NotExited | ProcessExitStatus::Normal(..) => None,
+            ProcessExitStatus::KilledBySignal(..) => Some(FailureReason::CheckerKilledBySignal),
+            ProcessExitStatus::CPUTimeLimitExceeded | ProcessExitStatus::RealTimeLimitExceeded =>
+                Some(FailureReason::CheckerTimeLimitExceeded),
+            ProcessExitStatus::MemoryLimitExceeded =>
+                Some(FailureReason::CheckerMemoryLimitExceeded),
+            ProcessExitStatus::BannedSyscall | ProcessExitStatus::SyscallBudgetExceeded =>
+                Some(FailureReason::CheckerBannedSystemCall),
+            ProcessExitStatus::DiskQuotaExceeded => Some(FailureReason::CheckerDiskLimitExceeded),
+        }
+    }
+
+    /// Get the name of this failure reason.
+    pub fn name(&self) -> &'static str {
+        match self {
+            FailureReason::CheckerTimeLimitExceeded => "CheckerTimeLimitExceeded",
+            FailureReason::CheckerMemoryLimitExceeded => "CheckerMemoryLimitExceeded",
+            FailureReason::CheckerKilledBySignal => "CheckerKilledBySignal",
+            FailureReason::CheckerBannedSystemCall => "CheckerBannedSystemCall",
+            FailureReason::CheckerDiskLimitExceeded => "CheckerDiskLimitExceeded",
+            FailureReason::ReferenceFailed => "ReferenceFailed",
+            FailureReason::AnswerGeneratorFailed => "AnswerGeneratorFailed",
+        }
+    }
+}
+
+impl Display for FailureReason {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    mod verdict {
+        use super::*;
+
+        #[test]
+        fn compare() {
+            assert_eq!(Verdict::Accepted, Verdict::Accepted);
+            assert_ne!(Verdict::Accepted, Verdict::WrongAnswer);
+            assert_ne!(Verdict::TimeLimitExceeded, Verdict::IdlenessLimitExceeded);
+        }
+
+        #[test]
+        fn and() {
+            assert_eq!(Verdict::Accepted, Verdict::Accepted & Verdict::Accepted);
+            assert_eq!(Verdict::WrongAnswer, Verdict::Accepted & Verdict::WrongAnswer);
+            assert_eq!(Verdict::RuntimeError, Verdict::RuntimeError & Verdict::WrongAnswer);
+            assert_eq!(Verdict::RuntimeError, Verdict::RuntimeError.and(Verdict::Accepted));
+
+            let mut verdict = Verdict::Accepted;
+            for v in &[Verdict::Accepted, Verdict::TimeLimitExceeded, Verdict::WrongAnswer] {
+                verdict &= *v;
+            }
+            assert_eq!(Verdict::TimeLimitExceeded, verdict);
+        }
+    }
+
+    mod failure_reason {
+        use super::*;
+
+        #[test]
+        fn of_checker() {
+            assert_eq!(None, FailureReason::of_checker(&ProcessExitStatus::Normal(1)));
+            assert_eq!(Some(FailureReason::CheckerTimeLimitExceeded),
+                FailureReason::of_checker(&ProcessExitStatus::CPUTimeLimitExceeded));
+            assert_eq!(Some(FailureReason::CheckerTimeLimitExceeded),
+                FailureReason::of_checker(&ProcessExitStatus::RealTimeLimitExceeded));
+            assert_eq!(Some(FailureReason::CheckerMemoryLimitExceeded),
+                FailureReason::of_checker(&ProcessExitStatus::MemoryLimitExceeded));
+            assert_eq!(Some(FailureReason::CheckerKilledBySignal),
+                FailureReason::of_checker(&ProcessExitStatus::KilledBySignal(libc::SIGSEGV)));
+        }
+    }
+
+    mod judgee_exit_status {
+        use super::*;
+
+        fn verdict_of(status: ProcessExitStatus, nonzero_exit_is_error: bool) -> Verdict {
+            let mut result = TestCaseResult::new();
+            result.set_judgee_exit_status(status, nonzero_exit_is_error, &[]);
+            result.verdict
+        }
+
+        #[test]
+        fn nonzero_exit_is_runtime_error() {
+            match verdict_of(ProcessExitStatus::Normal(1), true) {
+                Verdict::RuntimeError => (),
+                verdict => panic!("unexpected verdict: {:?}", verdict),
+            };
+            assert!(verdict_of(ProcessExitStatus::Normal(0), true).is_accepted());
+        }
+
+        #[test]
+        fn nonzero_exit_ignored() {
+            assert!(verdict_of(ProcessExitStatus::Normal(1), false).is_accepted());
+            match verdict_of(ProcessExitStatus::KilledBySignal(11), false) {
+                Verdict::RuntimeError => (),
+                verdict => panic!("unexpected verdict: {:?}", verdict),
+            };
+        }
+
+        #[test]
+        fn syscall_budget_exceeded() {
+            let mut result = TestCaseResult::new();
+            result.set_judgee_exit_status(ProcessExitStatus::SyscallBudgetExceeded, true, &[]);
+            match result.verdict {
+                Verdict::BannedSystemCall => (),
+                verdict => panic!("unexpected verdict: {:?}", verdict),
+            };
+            assert_eq!(Some(String::from("system call budget exceeded")), result.comment);
+        }
+
+        #[test]
+        fn signal_described() {
+            let mut result = TestCaseResult::new();
+            let status = ProcessExitStatus::KilledBySignal(libc::SIGABRT);
+            result.set_judgee_exit_status(status, true, &[]);
+            assert_eq!(Some(String::from("killed by signal 6 (SIGABRT): aborted")), result.comment);
+
+            assert_eq!("killed by signal 64", describe_signal(64));
+        }
+
+        #[test]
+        fn signal_verdicts() {
+            match verdict_of(ProcessExitStatus::KilledBySignal(libc::SIGXFSZ), true) {
+                Verdict::DiskLimitExceeded => (),
+                verdict => panic!("unexpected verdict: {:?}", verdict),
+            };
+
+            let mut result = TestCaseResult::new();
+            let overrides = [(libc::SIGXFSZ, Verdict::RuntimeError)];
+            result.set_judgee_exit_status(
+                ProcessExitStatus::KilledBySignal(libc::SIGXFSZ), true, &overrides);
+            match result.verdict {
+                Verdict::RuntimeError => (),
+                verdict => panic!("unexpected verdict: {:?}", verdict),
+            };
+        }
+    }
+
+    mod judge_result {
+        use super::*;
+
+        use sandbox::MemorySize;
+
+        fn make_result() -> JudgeResult {
+            let mut result = JudgeResult::new();
+            let mut tc_result = TestCaseResult::new();
+            tc_result.rusage.user_cpu_time = Duration::from_millis(120);
+            tc_result.rusage.resident_set_size = MemorySize::KiloBytes(2048);
+            tc_result.output_view = Some(String::from("42\n"));
+            result.add_test_case_result(tc_result);
+
+            let mut tc_result = TestCaseResult::new();
+            tc_result.set_judgee_exit_status(ProcessExitStatus::Normal(1), true, &[]);
+            result.add_test_case_result(tc_result);
+            result
+        }
+
+        #[test]
+        fn equality() {
+            assert_eq!(make_result(), make_result());
+            assert_eq!(make_result(), make_result().clone());
+            assert_eq!(Verdict::RuntimeError, make_result().verdict);
+
+            let mut other = make_result();
+            other.test_suite[0].output_view = Some(String::from("43\n"));
+            assert_ne!(make_result(), other);
+
+            let mut other = make_result();
+            other.rusage.resident_set_size = MemorySize::MegaBytes(2);
+            assert_eq!(make_result(), other);
+            other.rusage.user_cpu_time = Duration::from_millis(121);
+            assert_ne!(make_result(), other);
+        }
+    }
+
+    mod score_directive {
+        use super::*;
+
+        use std::io::{Read, Seek, SeekFrom};
+
+        use sandbox::ProcessBuilder;
+
+        #[test]
+        fn parse() {
+            assert_eq!(Some(0.5), parse_score_directive("score=0.5"));
+            assert_eq!(Some(1.0), parse_score_directive("ok\nscore= 1\n\n"));
+            assert_eq!(None, parse_score_directive("score=0.5\nok\n"));
+            assert_eq!(None, parse_score_directive("score=1.5\n"));
+            assert_eq!(None, parse_score_directive("score=NaN\n"));
+            assert_eq!(None, parse_score_directive("scores=0.5\n"));
+            assert_eq!(None, parse_score_directive(""));
+        }
+
+        #[test]
+        fn split() {
+            assert_eq!(("partially correct\n", Some(0.25)),
+                split_score_directive("partially correct\nscore=0.25\n"));
+            assert_eq!(("score=2\n", None), split_score_directive("score=2\n"));
+        }
+
+        #[test]
+        fn fractional_score() {
+            let mut report_file = tempfile::tempfile().unwrap();
+            let mut interactor = ProcessBuilder::new("/bin/sh");
+            interactor.add_arg("-c").unwrap();
+            interactor.add_arg("echo '2 of 3 queries answered'; echo 'score=0.667'; exit 1")
+                .unwrap();
+            interactor.redirections.stdout = Some(report_file.try_clone().unwrap());
+
+            let mut handle = interactor.start().unwrap();
+            handle.wait_for_exit().unwrap();
+
+            let mut report = String::new();
+            report_file.seek(SeekFrom::Start(0)).unwrap();
+            report_file.read_to_string(&mut report).unwrap();
+
+            let mut result = TestCaseResult::new();
+            result.set_judgee_exit_status(ProcessExitStatus::Normal(0), true, &[]);
+            result.set_interactor_result(handle.exit_status(), &report);
+            assert_eq!(Verdict::WrongAnswer, result.verdict);
+            assert_eq!(Some(ProcessExitStatus::Normal(1)), result.interactor_exit_status);
+            assert_eq!(Some(String::from("2 of 3 queries answered\n")), result.comment);
+            assert_eq!(Some(0.667), result.score);
+        }
+
+        #[test]
+        fn interactor_failed() {
+            let mut result = TestCaseResult::new();
+            result.set_interactor_result(
+                ProcessExitStatus::KilledBySignal(libc::SIGSEGV), "score=1\n");
+            assert_eq!(Verdict::InteractorFailed, result.verdict);
+            assert_eq!(None, result.score);
+        }
+
+        #[test]
+        fn crashed_judgee_verdict_kept() {
+            let mut result = TestCaseResult::new();
+            result.set_judgee_exit_status(
+                ProcessExitStatus::KilledBySignal(libc::SIGSEGV), true, &[]);
+            result.set_interactor_result(ProcessExitStatus::KilledBySignal(libc::SIGPIPE), "");
+            assert_eq!(Verdict::RuntimeError, result.verdict);
+            assert_eq!(Some(ProcessExitStatus::KilledBySignal(libc::SIGPIPE)),
+                result.interactor_exit_status);
+        }
+
+        #[test]
+        fn broken_pipe_after_accepted_judgee() {
+            let mut result = TestCaseResult::new();
+            result.set_judgee_exit_status(ProcessExitStatus::Normal(0), true, &[]);
+            result.set_interactor_result(ProcessExitStatus::KilledBySignal(libc::SIGPIPE), "");
+            assert_eq!(Verdict::WrongAnswer, result.verdict);
+        }
+    }
+
+    mod diff_view {
+        use super::*;
+
+        #[test]
+        fn first_difference() {
+            let diff = DiffView::new("3.14159", "3.14259");
+            assert_eq!("3.14159", diff.expected_snippet);
+            assert_eq!("3.14259", diff.actual_snippet);
+            assert_eq!(4, diff.first_diff_offset);
+        }
+
+        #[test]
+        fn prefix() {
+            let diff = DiffView::new("abc", "abcdef");
+            assert_eq!(3, diff.first_diff_offset);
+        }
+
+        #[test]
+        fn bounded() {
+            let expected = format!("{}x{}", "a".repeat(100), "b".repeat(100));
+            let actual = format!("{}y{}", "a".repeat(100), "b".repeat(100));
+            let diff = DiffView::new(&expected, &actual);
+            assert_eq!(DIFF_SNIPPET_LEN, diff.expected_snippet.len());
+            assert_eq!(DIFF_SNIPPET_LEN, diff.actual_snippet.len());
+            assert_eq!(DIFF_CONTEXT_LEN, diff.first_diff_offset);
+            assert_eq!(Some('x'), diff.expected_snippet[diff.first_diff_offset..].chars().next());
+        }
+
+        #[test]
+        fn char_boundaries() {
+            let expected = format!("{}1", "\u{4e2d}".repeat(10));
+            let actual = format!("{}2", "\u{4e2d}".repeat(10));
+            let diff = DiffView::new(&expected, &actual);
+            assert!(diff.first_diff_offset <= DIFF_CONTEXT_LEN);
+            assert!(diff.expected_snippet.ends_with('1'));
+            assert!(diff.actual_snippet.ends_with('2'));
+        }
+    }
+
+    mod builtin_checker_info {
+        use super::*;
+
+        #[test]
+        fn name_round_trip() {
+            for checker in BuiltinCheckers::ALL.iter() {
+                let parsed = BuiltinCheckers::from_str(&checker.to_string()).unwrap();
+                assert_eq!(checker.name(), parsed.name());
+            }
+        }
+
+        #[test]
+        fn parse_alias() {
+            let checker: BuiltinCheckers = "floating-point".parse().unwrap();
+            match checker {
+                BuiltinCheckers::FloatingPointAware => (),
+                checker => panic!("unexpected checker: {:?}", checker),
+            };
+        }
+
+        #[test]
+        fn parse_unknown() {
+            match "token-count".parse::<BuiltinCheckers>() {
+                Err(Error(ErrorKind::CheckerNotFound(name), _)) => assert_eq!("token-count", name),
+                res => panic!("unexpected result: {:?}", res),
+            };
+        }
+
+        #[test]
+        fn describe_all() {
+            let infos = builtin_checkers();
+            let names: Vec<&str> = infos.iter().map(|info| info.name.as_str()).collect();
+            assert_eq!(vec!["default", "floating-point-aware", "case-insensitive", "sorted-lines",
+                "exact-bytes"], names);
+            for info in &infos {
+                assert!(!info.description.is_empty());
+                let options = info.options.iter().map(|opt| opt.name.as_str()).collect::<Vec<_>>();
+                match info.checker {
+                    BuiltinCheckers::ExactBytes => assert!(options.is_empty()),
+                    _ => assert_eq!(vec!["ignore_line_prefix", "normalize_line_endings",
+                        "require_trailing_newline"], options),
+                };
+            }
+        }
+    }
+}
@@ -0,0 +1,14 @@
+#include <stdlib.h>
+
+int main() {
+  // Allocate and touch 512 MB of memory.
+  size_t size = 512 * 1024 * 1024;
+  volatile char* p = (volatile char *)malloc(size);
+  if (!p) {
+    return 1;
+  }
+  for (size_t i = 0; i < size; i += 4096) {
+    p[i] = 1;
+  }
+  return 0;
+}
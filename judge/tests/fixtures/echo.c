@@ -0,0 +1,9 @@
+#include <stdio.h>
+
+int main() {
+  int ch;
+  while ((ch = getchar()) != EOF) {
+    putchar(ch);
+  }
+  return 0;
+}
@@ -0,0 +1,8 @@
+volatile int p;
+
+int main() {
+  while (1) {
+    ++p;
+  }
+  return 0;
+}
@@ -0,0 +1,168 @@
+//! End-to-end tests of the judge engine, which compile the C programs under `tests/fixtures` and
+//! judge them inside the sandbox for real.
+//!
+//! The tests require root privileges to enter the chroot jail of the judge directory, and a C
+//! compiler capable of linking static executables, since no shared libraries are available inside
+//! the jail. They are compiled only if the `integration-tests` feature is enabled, and are skipped
+//! with a message if the requirements are not met:
+//!
+//! ```sh
+//! sudo -E cargo test -p judge --features integration-tests --test sandboxed_judge
+//! ```
+//!
+
+#![cfg(feature = "integration-tests")]
+
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use judge::{
+    BuiltinCheckers,
+    CompilationTaskDescriptor,
+    JudgeMode,
+    JudgeTaskDescriptor,
+    MemorySize,
+    Program,
+    ProgramKind,
+    TestCaseDescriptor,
+    Verdict,
+};
+use judge::engine::JudgeEngine;
+use judge::languages::{
+    CompilationInfo,
+    ExecutionInfo,
+    LanguageBranch,
+    LanguageIdentifier,
+    LanguageProvider,
+    LanguageProviderMetadata,
+};
+
+/// A language provider compiling C programs into static executables with the system's C compiler.
+struct StaticCLanguageProvider {
+    /// Metadata of the language provider.
+    metadata: &'static LanguageProviderMetadata,
+}
+
+impl StaticCLanguageProvider {
+    /// Create a new `StaticCLanguageProvider` instance.
+    fn new() -> Self {
+        let mut metadata = LanguageProviderMetadata::new("c", false);
+        metadata.branches.push(LanguageBranch::new("static", "c11"));
+        StaticCLanguageProvider { metadata: Box::leak(Box::new(metadata)) }
+    }
+}
+
+impl LanguageProvider for StaticCLanguageProvider {
+    fn metadata(&self) -> &'static LanguageProviderMetadata {
+        self.metadata
+    }
+
+    fn compile(&self, program: &Program, _kind: ProgramKind, output_dir: Option<PathBuf>)
+        -> Result<CompilationInfo, Box<dyn std::error::Error>> {
+        let output_dir = output_dir.expect("output directory is required");
+        let output_file = output_dir.join(program.file.file_stem().unwrap());
+
+        let mut ci = CompilationInfo::new("cc", output_file.clone());
+        for arg in &["-static", "-O2", "-std=c11", "-o"] {
+            ci.compiler.add_arg(*arg)?;
+        }
+        ci.compiler.add_arg(format!("{}", output_file.display()))?;
+        ci.compiler.add_arg(format!("{}", program.file.display()))?;
+        Ok(ci)
+    }
+
+    fn execute(&self, program: &Program, _kind: ProgramKind)
+        -> Result<ExecutionInfo, Box<dyn std::error::Error>> {
+        Ok(ExecutionInfo::new(&program.file))
+    }
+}
+
+/// Get the path to the given fixture file.
+fn fixture(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("fixtures").join(name)
+}
+
+/// Get the language of the fixture programs.
+fn language() -> LanguageIdentifier {
+    LanguageIdentifier::new("c", LanguageBranch::new("static", "c11"))
+}
+
+/// Determine whether the system's C compiler can link static executables, by linking an empty
+/// program statically.
+fn static_libc_available() -> bool {
+    let dir = tempfile::tempdir().unwrap();
+    let source = dir.path().join("empty.c");
+    std::fs::write(&source, "int main(void) { return 0; }\n").unwrap();
+    std::process::Command::new("cc")
+        .arg("-static")
+        .arg("-o").arg(dir.path().join("empty"))
+        .arg(&source)
+        .status()
+        .map(|status| status.success())
+        .unwrap_or(false)
+}
+
+/// Compile the given fixture program into the given directory. Panics if the program cannot be
+/// compiled.
+fn compile(engine: &JudgeEngine, name: &str, output_dir: &Path) -> PathBuf {
+    let mut task = CompilationTaskDescriptor::new(Program::new(fixture(name), language()));
+    task.output_dir = Some(output_dir.to_owned());
+    let result = engine.compile(task).unwrap();
+    assert!(result.succeeded, "failed to compile fixture \"{}\": {}", name,
+        result.compiler_out.unwrap_or_default());
+
+    result.output_file.unwrap()
+}
+
+/// Judge the given fixture program on the echo test case. Returns `None` if the test should be
+/// skipped.
+fn judge(name: &str) -> Option<Verdict> {
+    if !nix::unistd::geteuid().is_root() {
+        eprintln!("skipped: root privileges are required to judge inside the sandbox");
+        return None;
+    }
+    if !static_libc_available() {
+        eprintln!("skipped: a static C library is required to judge inside the sandbox");
+        return None;
+    }
+
+    let engine = JudgeEngine::new();
+    engine.languages().register(Box::new(StaticCLanguageProvider::new()));
+
+    let output_dir = tempfile::tempdir().unwrap();
+    let executable = compile(&engine, name, output_dir.path());
+
+    let mut task = JudgeTaskDescriptor::new(Program::new(&executable, language()));
+    task.mode = JudgeMode::Standard(BuiltinCheckers::Default);
+    task.limits.cpu_time_limit = Duration::from_secs(1);
+    task.limits.real_time_limit = Duration::from_secs(3);
+    task.limits.memory_limit = MemorySize::MegaBytes(64);
+    task.test_suite.push(TestCaseDescriptor::new(fixture("echo.in"), fixture("echo.ans")));
+    // The judgee runs inside the chroot jail of the judge directory, so the executable is copied
+    // to the same path inside the judge directory.
+    task.grader_files.push((executable.clone(), executable.strip_prefix("/").unwrap().to_owned()));
+
+    let result = engine.judge(task).unwrap();
+    Some(result.verdict)
+}
+
+#[test]
+fn echo_accepted() {
+    if let Some(verdict) = judge("echo.c") {
+        assert_eq!(Verdict::Accepted, verdict);
+    }
+}
+
+#[test]
+fn spin_time_limit_exceeded() {
+    if let Some(verdict) = judge("spin.c") {
+        assert_eq!(Verdict::TimeLimitExceeded, verdict);
+    }
+}
+
+#[test]
+fn alloc_memory_limit_exceeded() {
+    if let Some(verdict) = judge("alloc.c") {
+        assert_eq!(Verdict::MemoryLimitExceeded, verdict);
+    }
+}
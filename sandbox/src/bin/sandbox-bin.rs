@@ -43,15 +43,20 @@ struct ApplicationConfig {
     pub cpu_time_limit: Option<Duration>,
     pub real_time_limit: Option<Duration>,
     pub memory_limit: Option<MemorySize>,
+    pub disk_quota: Option<MemorySize>,
+    pub open_files_limit: Option<u32>,
 
     pub working_dir: Option<PathBuf>,
     pub root_dir: Option<PathBuf>,
     pub uid: Option<UserId>,
     pub syscall_whitelist: Vec<SystemCall>,
+    pub nice: Option<i32>,
 
     pub input_file: Option<PathBuf>,
     pub output_file: Option<PathBuf>,
     pub error_file: Option<PathBuf>,
+
+    pub trace_syscalls: bool,
 }
 
 impl ApplicationConfig {
@@ -64,15 +69,20 @@ impl ApplicationConfig {
             cpu_time_limit: None,
             real_time_limit: None,
             memory_limit: None,
+            disk_quota: None,
+            open_files_limit: None,
 
             working_dir: None,
             root_dir: None,
             uid: None,
             syscall_whitelist: Vec::new(),
+            nice: None,
 
             input_file: None,
             output_file: None,
             error_file: None,
+
+            trace_syscalls: false,
         }
     }
 }
@@ -100,6 +110,16 @@ fn get_app_config() -> Result<ApplicationConfig> {
             .takes_value(true)
             .value_name("MEMORY_LIMIT")
             .help("specify the memory limit, in megabytes."))
+        .arg(clap::Arg::with_name("disk_quota")
+            .long("disk")
+            .takes_value(true)
+            .value_name("DISK_QUOTA")
+            .help("specify the disk quota of the working directory, in megabytes"))
+        .arg(clap::Arg::with_name("open_files_limit")
+            .long("nofile")
+            .takes_value(true)
+            .value_name("OPEN_FILES_LIMIT")
+            .help("specify the maximum number of open files"))
         .arg(clap::Arg::with_name("input_file")
             .short("i")
             .long("input")
@@ -132,6 +152,12 @@ fn get_app_config() -> Result<ApplicationConfig> {
             .multiple(true)
             .value_terminator("--")
             .help("specify the names of allowed system call"))
+        .arg(clap::Arg::with_name("nice")
+            .long("nice")
+            .takes_value(true)
+            .allow_hyphen_values(true)
+            .value_name("NICE")
+            .help("specify the nice value of the sandbox process, from -20 to 19"))
         .arg(clap::Arg::with_name("working_dir")
             .long("workdir")
             .takes_value(true)
@@ -148,6 +174,12 @@ fn get_app_config() -> Result<ApplicationConfig> {
             .value_name("ENVs")
             .multiple(true)
             .help("specify the environment variables passed to the child process"))
+        .arg(clap::Arg::with_name("trace_syscalls")
+            .long("trace-syscalls")
+            .help(concat!(
+                "trace the program and print the names of the distinct system calls it invoked, ",
+                "which can be used as a system call whitelist. Resource limits are not enforced ",
+                "strictly and the system call whitelist is ignored in this mode")))
         .arg(clap::Arg::with_name("program")
             .value_name("PROGRAM")
             .takes_value(true)
@@ -208,6 +240,26 @@ fn get_app_config() -> Result<ApplicationConfig> {
         None => ()
     };
 
+    match matches.value_of("disk_quota") {
+        Some(disk_quota) => {
+            let disk_quota = usize::from_str(disk_quota)
+                .chain_err(|| Error::from(format!("invalid disk quota value: {}", disk_quota)))
+                ?;
+            config.disk_quota = Some(MemorySize::MegaBytes(disk_quota));
+        },
+        None => ()
+    };
+
+    match matches.value_of("open_files_limit") {
+        Some(limit) => {
+            let limit = u32::from_str(limit)
+                .chain_err(|| Error::from(format!("invalid open files limit value: {}", limit)))
+                ?;
+            config.open_files_limit = Some(limit);
+        },
+        None => ()
+    };
+
     config.input_file = matches.value_of("input_file")
         .map(|f| PathBuf::from_str(f).unwrap());
     config.output_file = matches.value_of("output_file")
@@ -234,6 +286,16 @@ fn get_app_config() -> Result<ApplicationConfig> {
         None => ()
     };
 
+    match matches.value_of("nice") {
+        Some(nice) => {
+            let nice = i32::from_str(nice)
+                .chain_err(|| Error::from(format!("invalid nice value: {}", nice)))
+                ?;
+            config.nice = Some(nice);
+        },
+        None => ()
+    };
+
     match matches.value_of("working_dir") {
         Some(work_dir) => config.working_dir = Some(PathBuf::from(work_dir)),
         None => ()
@@ -244,9 +306,33 @@ fn get_app_config() -> Result<ApplicationConfig> {
         None => ()
     }
 
+    config.trace_syscalls = matches.is_present("trace_syscalls");
+
     Ok(config)
 }
 
+fn describe_exit_status(exit_status: &ProcessExitStatus) -> String {
+    match exit_status {
+        ProcessExitStatus::Normal(exit_code) =>
+            format!("normal, exit code = {}", exit_code),
+        ProcessExitStatus::KilledBySignal(signal) =>
+            format!("kill by signal: {}", signal),
+        ProcessExitStatus::CPUTimeLimitExceeded =>
+            String::from("cpu time limit exceeded"),
+        ProcessExitStatus::MemoryLimitExceeded =>
+            String::from("memory limit exceeded"),
+        ProcessExitStatus::RealTimeLimitExceeded =>
+            String::from("real time limit exceeded"),
+        ProcessExitStatus::BannedSyscall =>
+            String::from("banned system call"),
+        ProcessExitStatus::SyscallBudgetExceeded =>
+            String::from("system call budget exceeded"),
+        ProcessExitStatus::DiskQuotaExceeded =>
+            String::from("disk quota exceeded"),
+        _ => unreachable!()
+    }
+}
+
 fn do_main() -> Result<()> {
     let config = get_app_config()?;
 
@@ -261,6 +347,8 @@ fn do_main() -> Result<()> {
     builder.limits.cpu_time_limit = config.cpu_time_limit;
     builder.limits.real_time_limit = config.real_time_limit;
     builder.limits.memory_limit = config.memory_limit;
+    builder.limits.disk_quota = config.disk_quota;
+    builder.limits.open_files_limit = config.open_files_limit;
 
     if config.input_file.is_some() {
         builder.redirections.stdin = Some(File::open(config.input_file.unwrap())
@@ -281,6 +369,7 @@ fn do_main() -> Result<()> {
     }
 
     builder.uid = config.uid;
+    builder.nice = config.nice;
     for syscall in config.syscall_whitelist {
         builder.syscall_whitelist.push(syscall);
     }
@@ -293,26 +382,22 @@ fn do_main() -> Result<()> {
         builder.dir.root_dir = config.root_dir.clone();
     }
 
+    if config.trace_syscalls {
+        let trace = builder.trace_syscalls()?;
+        // The exit status goes to stderr so that stdout contains the system call names only.
+        eprintln!("Process exited: {}", describe_exit_status(&trace.exit_status));
+        for syscall in &trace.syscalls {
+            println!("{}", syscall.name);
+        }
+
+        return Ok(());
+    }
+
     let mut process = builder.start()?;
     process.wait_for_exit()?;
 
     let exit_status = process.exit_status();
-    print!("Process exited: ");
-    match exit_status {
-        ProcessExitStatus::Normal(exit_code) =>
-            println!("normal, exit code = {}", exit_code),
-        ProcessExitStatus::KilledBySignal(signal) =>
-            println!("kill by signal: {}", signal),
-        ProcessExitStatus::CPUTimeLimitExceeded =>
-            println!("cpu time limit exceeded"),
-        ProcessExitStatus::MemoryLimitExceeded =>
-            println!("memory limit exceeded"),
-        ProcessExitStatus::RealTimeLimitExceeded =>
-            println!("real time limit exceeded"),
-        ProcessExitStatus::BannedSyscall =>
-            println!("banned system call"),
-        _ => unreachable!()
-    };
+    println!("Process exited: {}", describe_exit_status(&exit_status));
 
     let rusage = process.rusage();
     println!("Process resource usage:");
@@ -0,0 +1,397 @@
+//! This module implements the setup of child processes between `fork` and `execve`.
+//!
+//! Child processes are forked from the judge, which is a multithreaded process. After `fork`, only
+//! the forking thread exists in the child process, and locks held by the other threads at the time
+//! of `fork` (e.g. the lock of the memory allocator or of the standard streams) are never released.
+//! Thus the child process must only call async-signal-safe functions, which excludes allocating
+//! memory, formatting messages and logging. Everything the child process needs is prepared in a
+//! `ChildImage` before `fork`, and failures are reported without allocating memory.
+//!
+
+use std::ffi::CString;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::path::Path;
+use std::ptr;
+use std::sync::Once;
+
+use nix::errno::Errno;
+use nix::sys::signal::Signal;
+use nix::unistd::{Pid, Uid};
+
+use super::{
+    misc,
+    rlimits::{self, Resource},
+    seccomp,
+    ChildSetupStage,
+    Error,
+    ErrorKind,
+    IoPriority,
+    MemoryLimitPolicy,
+    ProcessBuilder,
+    Result,
+    NATIVE_MEMORY_LIMIT_HEADROOM,
+};
+
+/// Ensure that the warning about the unavailability of seccomp is logged only once.
+static SECCOMP_UNAVAILABLE_WARNING: Once = Once::new();
+
+/// Decide whether the system call whitelist of a child process is enforced through a seccomp
+/// filter, given whether seccomp is available on this system and whether the child process
+/// requires it. Returns `ErrorKind::SeccompUnavailable` if the child process cannot be started.
+fn should_filter_syscalls(available: bool, required: bool) -> Result<bool> {
+    if available {
+        return Ok(true);
+    }
+    if required {
+        return Err(Error::from(ErrorKind::SeccompUnavailable));
+    }
+
+    SECCOMP_UNAVAILABLE_WARNING.call_once(|| {
+        log::warn!(concat!("!!! seccomp is not available on this system. Child processes run ",
+            "WITHOUT system call filtering, and are isolated by their user IDs only. Require ",
+            "seccomp to refuse to run child processes in this case. !!!"));
+    });
+    Ok(false)
+}
+
+/// Failure of setting up a child process, given by the failed stage and the error number.
+pub(super) type ChildSetupFailure = (ChildSetupStage, i32);
+
+/// Everything a child process needs between `fork` and `execve`, prepared before `fork` from a
+/// `ProcessBuilder`.
+pub(super) struct ChildImage {
+    /// Path to the executable file, in which the `PATH` environment variable has been expanded.
+    file: CString,
+
+    /// Storage of the arguments pointed to by `argv`.
+    _args: Vec<CString>,
+
+    /// Storage of the environment variables pointed to by `envp`.
+    _envs: Vec<CString>,
+
+    /// Null-terminated array of pointers to the arguments, as expected by `execve`.
+    argv: Vec<*const libc::c_char>,
+
+    /// Null-terminated array of pointers to the environment variables, as expected by `execve`.
+    envp: Vec<*const libc::c_char>,
+
+    /// File descriptors to be duplicated onto the standard streams, in the order of `stdin`,
+    /// `stdout` and `stderr`.
+    std_fds: [Option<RawFd>; 3],
+
+    /// File descriptors inherited by the child process.
+    inherited_fds: Vec<RawFd>,
+
+    /// File descriptors that are kept open until `execve`, including the inherited ones.
+    keep_fds: Vec<RawFd>,
+
+    /// The nice value of the child process, if any.
+    nice: Option<i32>,
+
+    /// The I/O priority of the child process, if any.
+    io_priority: Option<IoPriority>,
+
+    /// The user ID of the child process, if any.
+    uid: Option<Uid>,
+
+    /// The working directory of the child process, if any.
+    working_dir: Option<CString>,
+
+    /// The root directory of the child process, if any.
+    root_dir: Option<CString>,
+
+    /// Native resource limits applied to the child process.
+    rlimits: Vec<(Resource, u64)>,
+
+    /// The compiled system call filter, if any.
+    seccomp_filter: Option<seccomp::CompiledFilter>,
+}
+
+/// Convert the given path into a `CString`.
+fn path_to_cstring(path: &Path) -> CString {
+    // Paths set in `ProcessBuilder` come from Rust strings and thus never contain nul bytes.
+    CString::new(path.as_os_str().as_bytes()).expect("path contains nul byte")
+}
+
+/// Build a null-terminated array of pointers to the given strings.
+fn pointer_array(strings: &[CString]) -> Vec<*const libc::c_char> {
+    strings.iter()
+        .map(|s| s.as_ptr())
+        .chain(std::iter::once(ptr::null()))
+        .collect()
+}
+
+impl ChildImage {
+    /// Prepare the child image of the given `ProcessBuilder`.
+    ///
+    /// If the executable file cannot be found, this function fails with
+    /// `ErrorKind::FileNotFound`; if the system call whitelist cannot be compiled, this function
+    /// fails with `ErrorKind::ChildSetupFailed` of the `Seccomp` stage.
+    pub(super) fn new(builder: &ProcessBuilder) -> Result<Self> {
+        // Find the executable file by trying to expand the `PATH` environment variable before the
+        // file name.
+        let file = match misc::expand_path(&builder.file) {
+            Some(file) => path_to_cstring(&file),
+            None => return Err(Error::from(ErrorKind::FileNotFound(builder.file.clone())))
+        };
+
+        // Strings used in arguments and environment variables are guaranteed to be valid C-style
+        // string when they were set so we directly unwraps them below.
+        let args = builder.args.iter()
+            .map(|arg| CString::new(arg.clone()).unwrap())
+            .collect::<Vec<CString>>();
+        let envs = builder.envs.iter()
+            .map(|env| CString::new(format!("{}={}", env.0, env.1)).unwrap())
+            .collect::<Vec<CString>>();
+        let argv = pointer_array(&args);
+        let envp = pointer_array(&envs);
+
+        let redirections = &builder.redirections;
+        let std_fds = [
+            redirections.stdin.as_ref().map(|file| file.as_raw_fd()),
+            redirections.stdout.as_ref().map(|file| file.as_raw_fd()),
+            redirections.stderr.as_ref().map(|file| file.as_raw_fd()),
+        ];
+        let inherited_fds = redirections.inherited_files.iter()
+            .map(|file| file.as_raw_fd())
+            .collect::<Vec<RawFd>>();
+        let keep_fds = inherited_fds.clone();
+
+        let use_seccomp = !builder.syscall_whitelist.is_empty() && should_filter_syscalls(
+            seccomp::seccomp_available(), builder.require_seccomp)?;
+        let seccomp_filter = if !use_seccomp {
+            None
+        } else {
+            // If the child process calls syscalls that are not on the list of syscall whitelist,
+            // the kernel will immediately kills the child process, as though it is been killed by
+            // the delivery of a `SIGSYS` signal.
+            let filters = builder.syscall_whitelist.iter()
+                .map(|syscall| seccomp::SyscallFilter::new(syscall.id, seccomp::Action::Allow));
+            let filter = seccomp::compile_syscall_filters(filters).map_err(|e| {
+                Error::from(ErrorKind::ChildSetupFailed(ChildSetupStage::Seccomp, -e.errno()))
+            })?;
+            Some(filter)
+        };
+
+        Ok(ChildImage {
+            file,
+            _args: args,
+            _envs: envs,
+            argv,
+            envp,
+            std_fds,
+            inherited_fds,
+            keep_fds,
+            nice: builder.nice,
+            io_priority: builder.io_priority,
+            uid: builder.uid.map(Uid::from_raw),
+            working_dir: builder.dir.working_dir.as_ref().map(|dir| path_to_cstring(dir)),
+            root_dir: builder.dir.root_dir.as_ref().map(|dir| path_to_cstring(dir)),
+            rlimits: ChildImage::native_rlimits(builder),
+            seccomp_filter,
+        })
+    }
+
+    /// Keep the given file descriptor (e.g. an error pipe) open in the child process until
+    /// `execve`.
+    pub(super) fn keep_open(&mut self, fd: RawFd) {
+        self.keep_fds.push(fd);
+    }
+
+    /// Get the native resource limits that should be applied to the child process of the given
+    /// `ProcessBuilder`.
+    fn native_rlimits(builder: &ProcessBuilder) -> Vec<(Resource, u64)> {
+        let limits = &builder.limits;
+        let mut rlimits = Vec::new();
+        if builder.use_native_rlimit {
+            if let Some(cpu_time_limit) = limits.cpu_time_limit {
+                rlimits.push((Resource::CPUTime, cpu_time_limit.as_secs()));
+            }
+            if let Some(memory_limit) = limits.memory_limit {
+                rlimits.push((Resource::AddressSpace, memory_limit.bytes() as u64));
+            }
+            // The real time limit is ignored here.
+        } else if builder.memory_limit_policy == MemoryLimitPolicy::Native {
+            if let Some(memory_limit) = limits.memory_limit {
+                let limit = memory_limit.bytes().saturating_mul(NATIVE_MEMORY_LIMIT_HEADROOM);
+                rlimits.push((Resource::AddressSpace, limit as u64));
+            }
+        }
+
+        if let Some(open_files_limit) = limits.open_files_limit {
+            rlimits.push((Resource::NoFile, open_files_limit as u64));
+        }
+
+        rlimits
+    }
+
+    /// Set up the calling process, which should be a child process just forked, and then replace
+    /// it with the executable file through `execve`. This function returns only if the setup
+    /// fails, and never allocates memory.
+    pub(super) fn exec(&self) -> ChildSetupFailure {
+        match self.setup() {
+            Ok(()) => {
+                unsafe { libc::execve(self.file.as_ptr(), self.argv.as_ptr(), self.envp.as_ptr()) };
+                (ChildSetupStage::Execve, Errno::last() as i32)
+            },
+            Err(failure) => failure
+        }
+    }
+
+    /// Move the calling process into a new process group, and apply the redirections, file
+    /// descriptors, priorities, user ID, directories, resource limits and the system call filter
+    /// of this image to it, in that order.
+    fn setup(&self) -> std::result::Result<(), ChildSetupFailure> {
+        let this = Pid::from_raw(0);
+        in_stage(ChildSetupStage::ProcessGroup, nix::unistd::setpgid(this, this))?;
+        in_stage(ChildSetupStage::Redirections, self.apply_redirections())?;
+        in_stage(ChildSetupStage::FileDescriptors, misc::close_fds_except(&self.keep_fds))?;
+        // Priorities are applied before `setuid` since raising them requires privileges.
+        in_stage(ChildSetupStage::Priority, self.apply_priorities())?;
+        if let Some(uid) = self.uid {
+            in_stage(ChildSetupStage::SetUid, nix::unistd::setuid(uid))?;
+        }
+        in_stage(ChildSetupStage::Directories, self.apply_directories())?;
+        for (resource, limit) in &self.rlimits {
+            rlimits::setrlimit_hard(*resource, *limit).map_err(|e| {
+                (ChildSetupStage::ResourceLimits, e.raw_os_error().unwrap_or(0))
+            })?;
+        }
+        if let Some(ref filter) = self.seccomp_filter {
+            filter.load().map_err(|e| (ChildSetupStage::Seccomp, -e.errno()))?;
+        }
+
+        Ok(())
+    }
+
+    /// Apply the redirections of this image to the calling process.
+    fn apply_redirections(&self) -> nix::Result<()> {
+        let std_streams = [libc::STDIN_FILENO, libc::STDOUT_FILENO, libc::STDERR_FILENO];
+        for (fd, std_fd) in self.std_fds.iter().zip(&std_streams) {
+            if let Some(fd) = fd {
+                misc::dup_and_cloexec(*fd, *std_fd)?;
+            }
+        }
+        for fd in &self.inherited_fds {
+            misc::clear_cloexec(*fd)?;
+        }
+
+        Ok(())
+    }
+
+    /// Apply the nice value and the I/O priority of this image to the calling process.
+    fn apply_priorities(&self) -> nix::Result<()> {
+        if let Some(nice) = self.nice {
+            let ret = unsafe { libc::setpriority(libc::PRIO_PROCESS, 0, nice) };
+            Errno::result(ret)?;
+        }
+        if let Some(io_priority) = self.io_priority {
+            // There is no wrapper of `ioprio_set` in libc. The first argument is
+            // `IOPRIO_WHO_PROCESS`.
+            let ret = unsafe { libc::syscall(libc::SYS_ioprio_set, 1, 0, io_priority.encode()) };
+            Errno::result(ret)?;
+        }
+
+        Ok(())
+    }
+
+    /// Apply the working directory and the root directory of this image to the calling process.
+    fn apply_directories(&self) -> nix::Result<()> {
+        if let Some(ref working_dir) = self.working_dir {
+            nix::unistd::chdir(working_dir.as_c_str())?;
+        }
+        if let Some(ref root_dir) = self.root_dir {
+            nix::unistd::chroot(root_dir.as_c_str())?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Attach the given setup stage to the error occurred in it.
+fn in_stage<T>(stage: ChildSetupStage, res: nix::Result<T>)
+    -> std::result::Result<T, ChildSetupFailure> {
+    res.map_err(|e| {
+        let errno = match e {
+            nix::Error::Sys(errno) => errno as i32,
+            _ => 0
+        };
+        (stage, errno)
+    })
+}
+
+/// Write the given bytes to `stderr`, ignoring any error.
+fn write_stderr(bytes: &[u8]) {
+    nix::unistd::write(libc::STDERR_FILENO, bytes).ok();
+}
+
+/// Format the given non-negative number in decimal into the given buffer, and returns the
+/// formatted digits.
+fn format_decimal(mut value: u32, buf: &mut [u8; 10]) -> &[u8] {
+    let mut start = buf.len();
+    loop {
+        start -= 1;
+        buf[start] = b'0' + (value % 10) as u8;
+        value /= 10;
+        if value == 0 {
+            break;
+        }
+    }
+
+    &buf[start..]
+}
+
+/// Report the failure of setting up the calling child process and terminate it. The failure is
+/// written to `stderr`, reported to the parent process through the given error pipe if any, and
+/// the child process kills itself with a `SIGUSR1` signal to notify the daemon thread. A failure
+/// of `None` means that the child process failed before its setup started.
+///
+/// This function never allocates memory.
+pub(super) fn abort_child(failure: Option<ChildSetupFailure>, error_pipe: Option<RawFd>) -> ! {
+    write_stderr(b"failed to start child process");
+    if let Some((stage, errno)) = failure {
+        let mut buf = [0u8; 10];
+        write_stderr(b": ");
+        write_stderr(stage.name().as_bytes());
+        write_stderr(b" failed with errno ");
+        write_stderr(format_decimal(errno as u32, &mut buf));
+
+        if let Some(error_pipe) = error_pipe {
+            ProcessBuilder::report_child_setup_error(stage, errno, error_pipe);
+        }
+    }
+    write_stderr(b"\n");
+
+    // Send a `SIGUSR1` signal to self to terminate self and notify the daemon thread.
+    unsafe { libc::kill(libc::getpid(), Signal::SIGUSR1 as libc::c_int) };
+    // Sit in a tight loop, wait to be killed by the delivery of the `SIGUSR1` signal whose default
+    // handling behavior is killing the target process.
+    loop { }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{format_decimal, should_filter_syscalls, ErrorKind};
+
+    #[test]
+    fn test_format_decimal() {
+        let mut buf = [0u8; 10];
+        assert_eq!(b"0", format_decimal(0, &mut buf));
+        assert_eq!(b"13", format_decimal(13, &mut buf));
+        assert_eq!(b"4294967295", format_decimal(std::u32::MAX, &mut buf));
+    }
+
+    #[test]
+    fn test_should_filter_syscalls() {
+        assert!(should_filter_syscalls(true, false).unwrap());
+        assert!(should_filter_syscalls(true, true).unwrap());
+
+        // Without seccomp, the child process runs unfiltered unless seccomp is required.
+        assert!(!should_filter_syscalls(false, false).unwrap());
+        match should_filter_syscalls(false, true).unwrap_err().kind() {
+            ErrorKind::SeccompUnavailable => (),
+            kind => panic!("unexpected error kind: {:?}", kind)
+        };
+    }
+}
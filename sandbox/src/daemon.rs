@@ -1,7 +1,10 @@
+use std::collections::HashSet;
+use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
 use std::thread::JoinHandle;
-use std::time::{Duration, SystemTime};
+use std::time::{Duration, Instant, SystemTime};
 
+use nix::errno::Errno;
 use nix::sys::signal::Signal;
 use nix::sys::wait::{WaitStatus, WaitPidFlag};
 use nix::unistd::Pid;
@@ -10,10 +13,50 @@ use super::{
     Error,
     ErrorKind,
     Result,
+    MemorySize,
     ProcessResourceLimits,
     ProcessResourceUsage,
     ProcessExitStatus,
 };
+use super::trace::SyscallStopTracker;
+
+/// Minimal interval between two consecutive checks of the disk quota. Checking the disk quota walks
+/// the directory tree, which is far more expensive than sampling the resource usage.
+const DISK_QUOTA_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Initial interval between two consecutive polls of the tracees when none of them has changed its
+/// state. The interval doubles after each idle poll until it reaches
+/// `MAX_TRACEE_POLL_INTERVAL` or the sampling interval, whichever is shorter, and is reset once any
+/// tracee changes its state.
+const MIN_TRACEE_POLL_INTERVAL: Duration = Duration::from_micros(50);
+
+/// Maximal interval between two consecutive polls of the tracees.
+const MAX_TRACEE_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Represent the directory on which the disk quota of a child process is implemented.
+pub struct DiskQuotaDir {
+    /// Path to the directory.
+    path: PathBuf,
+
+    /// Size of the directory before the child process starts, in bytes. Files placed in the
+    /// directory before the child process starts are not counted against the disk quota.
+    baseline: u64,
+}
+
+impl DiskQuotaDir {
+    /// Create a new `DiskQuotaDir` value, measuring the current size of the given directory as the
+    /// baseline.
+    pub fn new(path: PathBuf) -> std::io::Result<Self> {
+        let baseline = super::misc::dir_size(&path)?;
+        Ok(DiskQuotaDir { path, baseline })
+    }
+
+    /// Get the size of the files written into the directory since the baseline is measured, in
+    /// bytes.
+    fn usage(&self) -> std::io::Result<u64> {
+        Ok(super::misc::dir_size(&self.path)?.saturating_sub(self.baseline))
+    }
+}
 
 /// Provide a RAII guard type for safely waiting for `pid`s.
 ///
@@ -56,15 +99,22 @@ impl WaitPidGuard {
 
 impl Drop for WaitPidGuard {
     fn drop(&mut self) {
+        // The child process leads its own process group, so the processes started by it are
+        // killed as well. The process group may be gone already, in which case there is nothing
+        // left to kill. Panicking here may abort the process if the daemon thread is unwinding, so
+        // other errors are only logged.
         if self.kill {
-            nix::sys::signal::kill(self.pid, Signal::SIGKILL)
-                .expect("cannot kill the child process in the WaitPidGuard.");
+            match nix::sys::signal::killpg(self.pid, Signal::SIGKILL) {
+                Ok(..) | Err(nix::Error::Sys(Errno::ESRCH)) => (),
+                Err(e) => log::error!("cannot kill the child process in the WaitPidGuard: {}", e)
+            };
         }
     }
 }
 
-/// Type for the join handle of the daemon thread.
-pub type DaemonThreadJoinHandle = JoinHandle<()>;
+/// Type for the join handle of the daemon thread. The daemon thread returns the error occurred
+/// while monitoring the child process, if any.
+pub type DaemonThreadJoinHandle = JoinHandle<Result<()>>;
 
 /// Provide context information used in the daemon thread.
 pub struct ProcessDaemonContext {
@@ -74,24 +124,66 @@ pub struct ProcessDaemonContext {
     /// Process resource limits that should be implemented in the daemon thread.
     limits: Option<ProcessResourceLimits>,
 
+    /// Memory limit of the child process under `MemoryLimitPolicy::Native`, against which the exit
+    /// status of the child process is translated.
+    native_memory_limit: Option<MemorySize>,
+
+    /// Interval between two consecutive samplings of the child process's resource usage.
+    sampling_interval: Duration,
+
+    /// The directory on which the disk quota is implemented, which is usually the working
+    /// directory of the child process.
+    quota_dir: Option<DiskQuotaDir>,
+
     /// Status of the sandboxed child process.
     status: Mutex<ProcessExitStatus>,
 
     /// Resource usage statistics of the child process.
     rusage: Mutex<Option<ProcessResourceUsage>>,
+
+    /// Whether the child process has been reaped by the daemon thread. The daemon thread holds
+    /// this lock while reaping the child process so that `kill` never signals a recycled pid.
+    reaped: Mutex<bool>,
 }
 
 impl ProcessDaemonContext {
     /// Create a new `ProcessDaemonContext` instance.
-    pub fn new(pid: Pid, limits: Option<ProcessResourceLimits>) -> ProcessDaemonContext {
+    pub fn new(pid: Pid,
+        limits: Option<ProcessResourceLimits>,
+        native_memory_limit: Option<MemorySize>,
+        sampling_interval: Duration,
+        quota_dir: Option<DiskQuotaDir>) -> ProcessDaemonContext {
         ProcessDaemonContext {
             pid,
             limits,
+            native_memory_limit,
+            sampling_interval,
+            quota_dir,
             status: Mutex::new(ProcessExitStatus::NotExited),
-            rusage: Mutex::new(None)
+            rusage: Mutex::new(None),
+            reaped: Mutex::new(false)
         }
     }
 
+    /// Get the pid of the child process.
+    pub fn pid(&self) -> Pid {
+        self.pid
+    }
+
+    /// Kill the child process, together with the processes started by it, by sending `SIGKILL` to
+    /// its process group. Does nothing if the child process has been reaped already.
+    pub fn kill(&self) -> Result<()> {
+        let reaped = self.reaped.lock().unwrap();
+        if *reaped {
+            return Ok(());
+        }
+
+        // The child process has not been reaped yet so the pid still refers to it and its process
+        // group, even if it has become a zombie.
+        nix::sys::signal::killpg(self.pid, Signal::SIGKILL)?;
+        Ok(())
+    }
+
     /// Get the exit status stored in the context.
     pub fn exit_status(&self) -> ProcessExitStatus {
         self.status.lock().unwrap().clone()
@@ -101,6 +193,31 @@ impl ProcessDaemonContext {
     pub fn rusage(&self) -> Option<ProcessResourceUsage> {
         *self.rusage.lock().unwrap()
     }
+
+    /// Store the exit status of the child process returned by the daemon thread, translated by
+    /// `native_memory_limit_status` if the memory limit is enforced by the native `rlimit`.
+    fn set_exit_status(&self, status: ProcessExitStatus) {
+        let status = if self.native_memory_limit.is_some() {
+            native_memory_limit_status(status)
+        } else {
+            status
+        };
+        *self.status.lock().unwrap() = status;
+    }
+}
+
+/// Translate the exit status of a child process under `MemoryLimitPolicy::Native`.
+///
+/// An allocation beyond `RLIMIT_AS` fails with `ENOMEM` without the address space of the child
+/// process ever growing, so the daemon thread cannot observe it. Runtimes abort the process when an
+/// allocation fails (e.g. `std::bad_alloc` in C++ or the allocation error handler in Rust), so a
+/// child process killed by `SIGABRT` is reported as `ProcessExitStatus::MemoryLimitExceeded`.
+/// Other exit statuses are returned as is.
+pub(super) fn native_memory_limit_status(status: ProcessExitStatus) -> ProcessExitStatus {
+    match status {
+        ProcessExitStatus::KilledBySignal(libc::SIGABRT) => ProcessExitStatus::MemoryLimitExceeded,
+        status => status
+    }
 }
 
 /// Checks that child process does not exceed daemon implemented limits.
@@ -126,6 +243,29 @@ fn daemon_check_limits(limits: &ProcessResourceLimits, usage: &ProcessResourceUs
     None
 }
 
+/// Checks that the files written by the child process into the quota directory do not exceed the
+/// disk quota. The check is skipped if less than `DISK_QUOTA_CHECK_INTERVAL` has elapsed since the
+/// last check.
+fn daemon_check_disk_quota(limits: &ProcessResourceLimits, quota_dir: Option<&DiskQuotaDir>,
+    last_check: &mut Option<Instant>) -> Result<Option<ProcessExitStatus>> {
+    let (disk_quota, quota_dir) = match (limits.disk_quota, quota_dir) {
+        (Some(disk_quota), Some(quota_dir)) => (disk_quota, quota_dir),
+        _ => return Ok(None)
+    };
+
+    match last_check {
+        Some(t) if t.elapsed() < DISK_QUOTA_CHECK_INTERVAL => return Ok(None),
+        _ => *last_check = Some(Instant::now())
+    };
+
+    let disk_usage = quota_dir.usage()?;
+    if MemorySize::Bytes(disk_usage as usize) > disk_quota {
+        return Ok(Some(ProcessExitStatus::DiskQuotaExceeded));
+    }
+
+    Ok(None)
+}
+
 /// Get resource usage statistics for the given process and update the (maybe) existing one. Returns
 /// the newest resource usage statistics.
 fn daemon_update_rusage(pid: Pid, old: &mut Option<ProcessResourceUsage>)
@@ -139,27 +279,51 @@ fn daemon_update_rusage(pid: Pid, old: &mut Option<ProcessResourceUsage>)
     Ok(*old.as_ref().unwrap())
 }
 
+/// Block until the given child process exits, without reaping it.
+fn wait_exit_no_reap(pid: Pid) -> nix::Result<()> {
+    loop {
+        let mut info: libc::siginfo_t = unsafe { std::mem::zeroed() };
+        let ret = unsafe {
+            libc::waitid(libc::P_PID, pid.as_raw() as libc::id_t, &mut info,
+                libc::WEXITED | libc::WNOWAIT)
+        };
+        match Errno::result(ret) {
+            Err(nix::Error::Sys(Errno::EINTR)) => continue,
+            res => return res.map(drop)
+        };
+    }
+}
+
 /// Main entry point of the daemon thread.
 ///
 /// This function should not return `Ok(ProcessExitStatus::SandboxError)`. Instead, it should return
 /// `Err(e)` with `e` set to the corresponding error.
 fn daemon_main(context: &ProcessDaemonContext) -> Result<ProcessExitStatus> {
-    // Interval between consecutive `wait` calls in the daemon thread.
-    const WAIT_INTERVAL: Duration = Duration::from_millis(10);
-
     let mut wait_guard = WaitPidGuard::new(context.pid);
 
-    // If we have daemon implemented resource constraits, then we should call `wait` with `WNOHANG`
-    // flag; otherwise we should call `wait` without any flags.
-    let wait_flag = context.limits.as_ref().and(Some(WaitPidFlag::WNOHANG));
+    // If we have daemon implemented resource constraits, then we poll the child process
+    // periodically; otherwise we block until the child process exits. The child process is always
+    // reaped by a `wait` call with the `WNOHANG` flag while holding the `reaped` lock.
     let has_daemon_limits = context.limits.is_some();
 
     // `timer` is used to measure elapsed real time.
     let timer = SystemTime::now();
+    let mut last_disk_check = None;
 
     loop {
         log::trace!("Daemon calling wait...");
-        let wait_status = wait_guard.wait(wait_flag)?;
+        if !has_daemon_limits {
+            wait_exit_no_reap(context.pid)?;
+        }
+        let wait_status = {
+            let mut reaped = context.reaped.lock().unwrap();
+            let wait_status = wait_guard.wait(Some(WaitPidFlag::WNOHANG))?;
+            match wait_status {
+                WaitStatus::Exited(..) | WaitStatus::Signaled(..) => *reaped = true,
+                _ => ()
+            };
+            wait_status
+        };
         log::trace!("Daemon loop with wait status: {:?}", wait_status);
 
         match wait_status {
@@ -190,10 +354,188 @@ fn daemon_main(context: &ProcessDaemonContext) -> Result<ProcessExitStatus> {
                 Some(status) => return Ok(status),
                 _ => ()
             };
+            match daemon_check_disk_quota(
+                daemon_limits, context.quota_dir.as_ref(), &mut last_disk_check)? {
+                Some(status) => return Ok(status),
+                _ => ()
+            };
+
+            // Sleep for the sampling interval until the next `wait` call.
+            std::thread::sleep(context.sampling_interval);
+        }
+    }
+}
+
+/// Check whether the sampling interval has elapsed since the last sampling, and if so, collect the
+/// resource usage statistics of the child process and check them against the limits. Returns the
+/// exit status of the child process if any of the limits is exceeded.
+fn traced_daemon_sample(context: &ProcessDaemonContext, last_sampling: &mut Option<Instant>,
+    last_disk_check: &mut Option<Instant>, timer: &SystemTime)
+    -> Result<Option<ProcessExitStatus>> {
+    match last_sampling {
+        Some(t) if t.elapsed() < context.sampling_interval => return Ok(None),
+        _ => *last_sampling = Some(Instant::now())
+    };
+
+    let overall_usage = daemon_update_rusage(context.pid, &mut *context.rusage.lock().unwrap())?;
+    let limits = match context.limits {
+        Some(ref limits) => limits,
+        None => return Ok(None)
+    };
+
+    match daemon_check_limits(limits, &overall_usage, timer.elapsed().unwrap_or_default()) {
+        Some(status) => Ok(Some(status)),
+        None => daemon_check_disk_quota(limits, context.quota_dir.as_ref(), last_disk_check)
+    }
+}
+
+/// Main entry point of the daemon thread of a child process with a system call budget. Besides
+/// checking the resource limits, the daemon thread traces the child process, together with its
+/// threads and child processes, and counts the system calls invoked by them. The child process
+/// should be a tracee of the daemon thread, stopped after its `execve` call.
+///
+/// Since the tracees stop on every system call, the daemon thread polls them with a short interval
+/// that backs off while none of them changes its state, rather than sleeping for the sampling
+/// interval, and samples the resource usage statistics between the polls instead.
+fn traced_daemon_main(context: &ProcessDaemonContext, syscall_budget: u64)
+    -> Result<ProcessExitStatus> {
+    let mut wait_guard = WaitPidGuard::new(context.pid);
+    let timer = SystemTime::now();
+    let mut last_sampling = None;
+    let mut last_disk_check = None;
+    let max_poll_interval = std::cmp::min(MAX_TRACEE_POLL_INTERVAL, context.sampling_interval);
+    let mut poll_interval = MIN_TRACEE_POLL_INTERVAL;
+
+    let mut syscalls: u64 = 0;
+    let mut tracees = vec![context.pid];
+    let mut pending_initial_stop = HashSet::new();
+    // The child process is stopped at `PTRACE_EVENT_EXEC`, inside its `execve` call.
+    let mut syscall_stops = SyscallStopTracker::new();
+    syscall_stops.set_in_syscall(context.pid);
+
+    super::trace::resume(context.pid, None)?;
+    loop {
+        if let Some(status) = traced_daemon_sample(
+            context, &mut last_sampling, &mut last_disk_check, &timer)? {
+            return Ok(status);
+        }
+
+        let mut wait_status = WaitStatus::StillAlive;
+        for pid in &tracees {
+            let flags = Some(WaitPidFlag::__WALL | WaitPidFlag::WNOHANG);
+            wait_status = if *pid == context.pid {
+                let mut reaped = context.reaped.lock().unwrap();
+                let wait_status = wait_guard.wait(flags)?;
+                match wait_status {
+                    WaitStatus::Exited(..) | WaitStatus::Signaled(..) => *reaped = true,
+                    _ => ()
+                };
+                wait_status
+            } else {
+                nix::sys::wait::waitpid(*pid, flags)?
+            };
+            if wait_status != WaitStatus::StillAlive {
+                break;
+            }
+        }
+        log::trace!("Traced daemon loop with wait status: {:?}", wait_status);
 
-            // Sleep for `WAIT_INTERVAL` milliseconds until the next `wait` call.
-            std::thread::sleep(WAIT_INTERVAL);
+        if wait_status == WaitStatus::StillAlive {
+            std::thread::sleep(poll_interval);
+            poll_interval = std::cmp::min(poll_interval * 2, max_poll_interval);
+            continue;
         }
+        poll_interval = MIN_TRACEE_POLL_INTERVAL;
+
+        // A tracee may be killed at any time (e.g. by `Process::kill`), so failures of ptrace
+        // requests with `ESRCH` are ignored. The death of the tracee is reported by a later wait.
+        let res = match wait_status {
+            WaitStatus::Exited(pid, exit_code) if pid == context.pid =>
+                return Ok(ProcessExitStatus::Normal(exit_code)),
+            WaitStatus::Signaled(pid, Signal::SIGSYS, _) if pid == context.pid =>
+                return Ok(ProcessExitStatus::BannedSyscall),
+            WaitStatus::Signaled(pid, sig, _) if pid == context.pid =>
+                return Ok(ProcessExitStatus::KilledBySignal(sig as i32)),
+            WaitStatus::Exited(pid, _) | WaitStatus::Signaled(pid, _, _) => {
+                tracees.retain(|tracee| *tracee != pid);
+                syscall_stops.remove(pid);
+                Ok(())
+            },
+            WaitStatus::PtraceSyscall(pid) => {
+                if syscall_stops.on_syscall_stop(pid) {
+                    syscalls += 1;
+                }
+                if syscalls > syscall_budget {
+                    return Ok(ProcessExitStatus::SyscallBudgetExceeded);
+                }
+                super::trace::resume(pid, None)
+            },
+            WaitStatus::PtraceEvent(pid, _, event) => {
+                if event == libc::PTRACE_EVENT_CLONE ||
+                    event == libc::PTRACE_EVENT_FORK ||
+                    event == libc::PTRACE_EVENT_VFORK {
+                    if let Ok(new_pid) = nix::sys::ptrace::getevent(pid) {
+                        let new_pid = Pid::from_raw(new_pid as libc::pid_t);
+                        tracees.push(new_pid);
+                        pending_initial_stop.insert(new_pid);
+                    }
+                }
+                super::trace::resume(pid, None)
+            },
+            WaitStatus::Stopped(pid, sig) => {
+                // The initial stop of a newly created thread or child process should not be
+                // delivered.
+                let initial_stop = sig == Signal::SIGSTOP && pending_initial_stop.remove(&pid);
+                super::trace::resume(pid, if initial_stop { None } else { Some(sig) })
+            },
+            _ => Ok(())
+        };
+        match res {
+            Ok(..) | Err(nix::Error::Sys(Errno::ESRCH)) => (),
+            Err(e) => return Err(Error::from(e))
+        };
+    }
+}
+
+/// Start the daemon thread of a child process with the given system call budget. Only the thread
+/// that started a process can trace it, so the child process is started by calling `start` in the
+/// daemon thread, which should return the pid of a traced child process stopped after its `execve`
+/// call, see `trace::wait_for_exec`. Returns the context used in the daemon thread and a handle to
+/// the daemon thread, or the error returned by `start`.
+pub fn start_traced<F>(start: F,
+    limits: Option<ProcessResourceLimits>,
+    native_memory_limit: Option<MemorySize>,
+    sampling_interval: Duration,
+    quota_dir: Option<DiskQuotaDir>,
+    syscall_budget: u64) -> Result<(Arc<Box<ProcessDaemonContext>>, DaemonThreadJoinHandle)>
+    where F: 'static + Send + FnOnce() -> Result<Pid> {
+    log::trace!("Starting traced daemon thread...");
+    let (sender, receiver) = std::sync::mpsc::channel();
+    let handle = std::thread::spawn(move || {
+        let pid = match start() {
+            Ok(pid) => pid,
+            Err(e) => {
+                sender.send(Err(e)).ok();
+                return Ok(());
+            }
+        };
+
+        let context = Arc::new(Box::new(ProcessDaemonContext::new(
+            pid, limits, native_memory_limit, sampling_interval, quota_dir)));
+        sender.send(Ok(context.clone())).ok();
+
+        let exit_status = traced_daemon_main(&**context, syscall_budget)?;
+        context.set_exit_status(exit_status);
+        Ok(())
+    });
+
+    match receiver.recv() {
+        Ok(Ok(context)) => Ok((context, handle)),
+        Ok(Err(e)) => {
+            handle.join().ok();
+            Err(e)
+        },
+        Err(..) => Err(Error::from(ErrorKind::DaemonFailed))
     }
 }
 
@@ -203,10 +545,8 @@ fn daemon_main(context: &ProcessDaemonContext) -> Result<ProcessExitStatus> {
 pub fn start(context: Arc<Box<ProcessDaemonContext>>) -> DaemonThreadJoinHandle {
     log::trace!("Starting daemon thread...");
     std::thread::spawn(move || {
-        let exit_status = match daemon_main(&**context) {
-            Ok(exit_status) => exit_status,
-            Err(e) => panic!("daemon error: {}", e)
-        };
-        *(*context).status.lock().unwrap() = exit_status;
+        let exit_status = daemon_main(&**context)?;
+        context.set_exit_status(exit_status);
+        Ok(())
     })
 }
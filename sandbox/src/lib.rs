@@ -10,7 +10,9 @@
 //! * Redirections: redirects stdin, stdout and stderr of child processes to
 //! specific file descriptors;
 //!
-//! * Process syscall filter: filter out unexpected syscalls by seccomp feature.
+//! * Process syscall filter: filter out unexpected syscalls by seccomp feature;
+//!
+//! * Syscall tracer: find out syscalls invoked by a program by ptrace feature.
 //!
 
 extern crate log;
@@ -24,29 +26,37 @@ extern crate procinfo;
 extern crate serde;
 
 
+mod child;
 mod daemon;
 mod seccomp;
 mod misc;
 mod rlimits;
+mod trace;
 
 use std::cmp::Ordering;
-use std::ffi::CString;
+use std::ffi::{CStr, CString};
 use std::fmt::{Display, Formatter};
 use std::fs::File;
 use std::hash::{Hash, Hasher};
+use std::io::{Read, Write};
 use std::path::PathBuf;
 use std::sync::Arc;
 use std::time::Duration;
 
-use std::os::unix::io::AsRawFd;
+use std::os::unix::io::{FromRawFd, RawFd};
 
-use nix::unistd::{Uid, Pid, ForkResult};
+use nix::errno::Errno;
+use nix::fcntl::OFlag;
+use nix::unistd::{Pid, ForkResult};
 
 #[cfg(feature = "serde")]
 use serde::{Serialize, Deserialize};
 
-use daemon::{ProcessDaemonContext, DaemonThreadJoinHandle};
-use rlimits::Resource;
+use child::ChildImage;
+use daemon::{ProcessDaemonContext, DaemonThreadJoinHandle, DiskQuotaDir};
+
+pub use misc::{dir_size, is_valid_c_string};
+pub use trace::SystemCallTrace;
 
 error_chain::error_chain! {
     types {
@@ -77,16 +87,126 @@ error_chain::error_chain! {
             description("invalid system call name")
         }
 
+        InvalidSystemCallId(id: SystemCallId) {
+            description("invalid system call ID")
+            display("invalid system call ID: {}", id)
+        }
+
         ChildStartupFailed {
             description("failed to launch child process")
         }
 
+        ChildSetupFailed(stage: ChildSetupStage, errno: i32) {
+            description("failed to set up child process")
+            display("failed to set up child process: {} failed: {}",
+                stage, Errno::from_i32(*errno).desc())
+        }
+
+        PermissionDenied(stage: ChildSetupStage) {
+            description("insufficient privileges to set up child process")
+            display("insufficient privileges to set up child process: {} failed", stage)
+        }
+
         DaemonFailed {
             description("daemon thread failed")
         }
+
+        SeccompUnavailable {
+            description("seccomp is not available on this system")
+        }
+    }
+}
+
+
+/// Stages of setting up the child process between `fork` and `execve`. Failures of these stages
+/// are reported to the parent process through `ErrorKind::ChildSetupFailed` and
+/// `ErrorKind::PermissionDenied`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChildSetupStage {
+    /// Looking up the executable file. The executable file is looked up before `fork`, and failures
+    /// of this stage are reported through `ErrorKind::FileNotFound` instead.
+    ExecutableLookup,
+
+    /// Moving the child process into a new process group through `setpgid`, so that the child
+    /// process and the processes started by it can be killed together.
+    ProcessGroup,
+
+    /// Redirecting the standard streams.
+    Redirections,
+
+    /// Closing the file descriptors that should not be inherited.
+    FileDescriptors,
+
+    /// Setting the scheduling priority and the I/O priority through `setpriority` and
+    /// `ioprio_set`.
+    Priority,
+
+    /// Changing the user ID through `setuid`.
+    SetUid,
+
+    /// Changing the working directory and the root directory through `chdir` and `chroot`.
+    Directories,
+
+    /// Applying native resource limits through `setrlimit`.
+    ResourceLimits,
+
+    /// Loading the seccomp filter.
+    Seccomp,
+
+    /// Calling `execve`.
+    Execve,
+}
+
+impl ChildSetupStage {
+    /// All stages, in the order they are performed. The index of a stage in this array is used as
+    /// its code when sending it through the error pipe.
+    const ALL: [ChildSetupStage; 10] = [
+        ChildSetupStage::ExecutableLookup,
+        ChildSetupStage::ProcessGroup,
+        ChildSetupStage::Redirections,
+        ChildSetupStage::FileDescriptors,
+        ChildSetupStage::Priority,
+        ChildSetupStage::SetUid,
+        ChildSetupStage::Directories,
+        ChildSetupStage::ResourceLimits,
+        ChildSetupStage::Seccomp,
+        ChildSetupStage::Execve,
+    ];
+
+    /// Get the code of this stage.
+    fn code(self) -> i32 {
+        ChildSetupStage::ALL.iter().position(|s| *s == self).unwrap() as i32
+    }
+
+    /// Get the stage with the given code.
+    fn from_code(code: i32) -> Option<Self> {
+        ChildSetupStage::ALL.get(code as usize).cloned()
+    }
+}
+
+impl ChildSetupStage {
+    /// Get the name of this stage.
+    fn name(self) -> &'static str {
+        match self {
+            ChildSetupStage::ExecutableLookup => "executable lookup",
+            ChildSetupStage::ProcessGroup => "setpgid",
+            ChildSetupStage::Redirections => "redirections",
+            ChildSetupStage::FileDescriptors => "closing file descriptors",
+            ChildSetupStage::Priority => "setpriority / ioprio_set",
+            ChildSetupStage::SetUid => "setuid",
+            ChildSetupStage::Directories => "chdir / chroot",
+            ChildSetupStage::ResourceLimits => "setrlimit",
+            ChildSetupStage::Seccomp => "seccomp",
+            ChildSetupStage::Execve => "execve",
+        }
     }
 }
 
+impl Display for ChildSetupStage {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
 
 /// Measurement of the size of a block of memory.
 #[derive(Clone, Copy, Debug, Eq)]
@@ -204,6 +324,30 @@ impl SystemCall {
             _msrnb: ()
         })
     }
+
+    /// Create a new `SystemCall` instance from a native system call ID of the native architecture.
+    /// Returns `Err(ErrorKind::InvalidSystemCallId(..))` on failure.
+    pub fn from_id(id: SystemCallId) -> Result<Self> {
+        // `SCMP_ARCH_NATIVE` in libseccomp.
+        const NATIVE_ARCH: u32 = 0;
+
+        let name_ptr = unsafe { seccomp_sys::seccomp_syscall_resolve_num_arch(NATIVE_ARCH, id) };
+        if name_ptr.is_null() {
+            log::debug!("Unknown syscall ID: {}", id);
+            return Err(Error::from(ErrorKind::InvalidSystemCallId(id)));
+        }
+
+        // The name returned by libseccomp is dynamically allocated and should be freed by the
+        // caller.
+        let name = unsafe { CStr::from_ptr(name_ptr) }.to_string_lossy().into_owned();
+        unsafe { libc::free(name_ptr as *mut libc::c_void) };
+
+        Ok(SystemCall {
+            name,
+            id,
+            _msrnb: ()
+        })
+    }
 }
 
 impl Display for SystemCall {
@@ -238,7 +382,18 @@ pub struct ProcessResourceLimits {
     pub real_time_limit: Option<Duration>,
 
     /// Limit on memory available for the child process. `None` if no constraits are set.
-    pub memory_limit: Option<MemorySize>
+    pub memory_limit: Option<MemorySize>,
+
+    /// Limit on the total size of the files under the working directory of the child process.
+    /// `None` if no constraits are set. This limit is implemented by the daemon thread, which
+    /// periodically measures the working directory; it is thus ignored if the native `rlimit`
+    /// mechanism is used or no working directory is set for the child process.
+    pub disk_quota: Option<MemorySize>,
+
+    /// Limit on the number of files the child process can open, i.e. the maximum file descriptor
+    /// number plus one. `None` if no constraits are set. This limit is always implemented by the
+    /// native `rlimit` mechanism since the daemon thread cannot enforce it.
+    pub open_files_limit: Option<u32>,
 }
 
 impl ProcessResourceLimits {
@@ -247,7 +402,9 @@ impl ProcessResourceLimits {
         ProcessResourceLimits {
             cpu_time_limit: None,
             real_time_limit: None,
-            memory_limit: None
+            memory_limit: None,
+            disk_quota: None,
+            open_files_limit: None,
         }
     }
 }
@@ -258,6 +415,74 @@ impl Default for ProcessResourceLimits {
     }
 }
 
+/// Specify the I/O scheduling priority of a child process, as set by `ioprio_set`. The I/O
+/// priority takes effect only with I/O schedulers supporting it, e.g. BFQ.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum IoPriority {
+    /// The best-effort scheduling class with the given priority level, ranging from 0 (the
+    /// highest) to 7 (the lowest).
+    BestEffort(u8),
+
+    /// The idle scheduling class. The child process gets I/O time only when no other process
+    /// needs the disk.
+    Idle,
+}
+
+impl IoPriority {
+    /// The `IOPRIO_CLASS_BE` scheduling class.
+    const CLASS_BEST_EFFORT: libc::c_int = 2;
+
+    /// The `IOPRIO_CLASS_IDLE` scheduling class.
+    const CLASS_IDLE: libc::c_int = 3;
+
+    /// Number of bits the scheduling class is shifted by in the encoded I/O priority.
+    const CLASS_SHIFT: libc::c_int = 13;
+
+    /// Get the encoded I/O priority expected by `ioprio_set`. Priority levels out of range are
+    /// clamped to the lowest priority.
+    fn encode(self) -> libc::c_int {
+        match self {
+            IoPriority::BestEffort(level) => {
+                let level = std::cmp::min(level, 7) as libc::c_int;
+                (IoPriority::CLASS_BEST_EFFORT << IoPriority::CLASS_SHIFT) | level
+            },
+            IoPriority::Idle => IoPriority::CLASS_IDLE << IoPriority::CLASS_SHIFT,
+        }
+    }
+}
+
+/// Specify how the memory limit of a child process is enforced when the native `rlimit` mechanism
+/// is not used for all limits.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum MemoryLimitPolicy {
+    /// The daemon thread samples the memory usage of the child process and kills it once the
+    /// memory limit is exceeded. Allocations made between two samplings are not limited.
+    Daemon,
+
+    /// The daemon thread enforces the memory limit as under `MemoryLimitPolicy::Daemon`, while the
+    /// kernel additionally limits the address space of the child process through `RLIMIT_AS`, set
+    /// to `NATIVE_MEMORY_LIMIT_HEADROOM` times the memory limit, to stop the child process from
+    /// allocating memory unboundedly between two samplings.
+    ///
+    /// Allocations beyond `RLIMIT_AS` fail with `ENOMEM`, upon which most runtimes abort the child
+    /// process. A child process killed by `SIGABRT` is therefore reported as
+    /// `ProcessExitStatus::MemoryLimitExceeded` under this policy, including aborts for other
+    /// reasons such as failed assertions. A child process exceeding the memory limit within the
+    /// headroom and exiting before the next sampling is not detected.
+    Native,
+}
+
+impl Default for MemoryLimitPolicy {
+    fn default() -> Self {
+        MemoryLimitPolicy::Daemon
+    }
+}
+
+/// Factor of the memory limit set as `RLIMIT_AS` under `MemoryLimitPolicy::Native`.
+pub const NATIVE_MEMORY_LIMIT_HEADROOM: usize = 2;
+
 /// Specify redirections of standard streams.
 pub struct ProcessRedirection {
     /// Redirected `stdin`, or `None` if `stdin` does not need to be redirected.
@@ -269,7 +494,12 @@ pub struct ProcessRedirection {
 
     /// Redirected `stderr`, or `None` if `stderr` does not need to be
     /// redirected.
-    pub stderr: Option<File>
+    pub stderr: Option<File>,
+
+    /// Extra files to be inherited by the child process, e.g. files whose file descriptors are
+    /// passed to the child process through command line arguments. The file descriptors of these
+    /// files in the child process are the same as in the calling process.
+    pub inherited_files: Vec<File>,
 }
 
 impl ProcessRedirection {
@@ -279,7 +509,8 @@ impl ProcessRedirection {
         ProcessRedirection {
             stdin: None,
             stdout: None,
-            stderr: None
+            stderr: None,
+            inherited_files: Vec::new(),
         }
     }
 
@@ -327,6 +558,10 @@ impl Default for ProcessDirectory {
 /// Provide a type for user IDs.
 pub type UserId = u32;
 
+/// Default interval between two consecutive samplings of the child process's resource usage
+/// statistics in the daemon thread.
+pub const DEFAULT_SAMPLING_INTERVAL: Duration = Duration::from_millis(5);
+
 /// Provide mechanism to build a child process in sandboxed environment.
 pub struct ProcessBuilder {
     /// Path to the executable file.
@@ -350,12 +585,47 @@ pub struct ProcessBuilder {
     /// applied.
     pub use_native_rlimit: bool,
 
+    /// How the memory limit is enforced. This is ignored if `use_native_rlimit` is set, in which
+    /// case the memory limit is always enforced by the native `rlimit` mechanism.
+    pub memory_limit_policy: MemoryLimitPolicy,
+
+    /// Interval between two consecutive samplings of the child process's resource usage statistics
+    /// in the daemon thread. Smaller intervals detect limit violations earlier at the cost of more
+    /// CPU time spent in the daemon thread.
+    pub sampling_interval: Duration,
+
     /// Effective user ID of the new child process.
     pub uid: Option<UserId>,
 
     /// A list of allowed syscalls for the new child process.
     pub syscall_whitelist: Vec<SystemCall>,
 
+    /// The nice value of the new child process, ranging from -20 (the highest priority) to 19 (the
+    /// lowest priority), or `None` to inherit the nice value of the calling process. Running the
+    /// child process at a lower priority than the judge keeps the daemon thread sampling in time
+    /// on busy nodes. Setting a nice value lower than the current one requires the
+    /// `CAP_SYS_NICE` capability.
+    pub nice: Option<i32>,
+
+    /// The I/O priority of the new child process, or `None` to inherit the I/O priority of the
+    /// calling process.
+    pub io_priority: Option<IoPriority>,
+
+    /// Whether the child process fails to start with `ErrorKind::SeccompUnavailable` if
+    /// `syscall_whitelist` is not empty but the kernel does not support seccomp filters (see
+    /// `seccomp::seccomp_available`). If `false` (the default), a warning is logged and the child
+    /// process runs without system call filtering in that case, relying on the other isolation
+    /// mechanisms such as `uid`.
+    pub require_seccomp: bool,
+
+    /// Maximum number of system calls the new child process, together with its threads and child
+    /// processes, may invoke after `execve`, or `None` for no limit. Processes exceeding the budget
+    /// are killed with the `ProcessExitStatus::SyscallBudgetExceeded` exit status.
+    ///
+    /// The budget is enforced by tracing the child process with `ptrace`, which stops the child
+    /// process on every system call, so setting a budget slows down system call heavy programs.
+    pub syscall_budget: Option<u64>,
+
     /// Redirections to be applied to the new child process.
     pub redirections: ProcessRedirection,
 }
@@ -373,10 +643,16 @@ impl ProcessBuilder {
 
             limits: ProcessResourceLimits::empty(),
             use_native_rlimit: false,
+            memory_limit_policy: MemoryLimitPolicy::Daemon,
+            sampling_interval: DEFAULT_SAMPLING_INTERVAL,
             redirections: ProcessRedirection::empty(),
             uid: None,
 
-            syscall_whitelist: Vec::new()
+            syscall_whitelist: Vec::new(),
+            nice: None,
+            io_priority: None,
+            require_seccomp: false,
+            syscall_budget: None,
         };
 
         // Add the path to the executable file as the first argument to the program.
@@ -424,6 +700,11 @@ impl ProcessBuilder {
         Ok(())
     }
 
+    /// Get the environment variables that will be passed to the child process.
+    pub fn envs(&self) -> &[(String, String)] {
+        &self.envs
+    }
+
     /// Add all environment variables in the calling process to the environment variables of the
     /// child process.
     pub fn inherit_envs(&mut self) {
@@ -433,142 +714,59 @@ impl ProcessBuilder {
         }
     }
 
-    /// Apply special directories for the child process.
-    fn apply_directories(&self) -> Result<()> {
-        if self.dir.working_dir.is_some() {
-            nix::unistd::chdir(self.dir.working_dir.as_ref().unwrap())?;
-        }
-
-        if self.dir.root_dir.is_some() {
-            nix::unistd::chroot(self.dir.root_dir.as_ref().unwrap())?;
-        }
+    /// Initializes any necessary components in the parent process to monitor the states of the
+    /// child process. This function should be called after `fork` in the parent process.
+    fn start_parent(self, child_pid: Pid, quota_dir: Option<DiskQuotaDir>) -> Process {
+        log::trace!("Starting parent process daemon...");
 
-        Ok(())
+        let (daemon_limits, native_memory_limit) = self.daemon_limits();
+        Process::attach(child_pid, daemon_limits, native_memory_limit, self.sampling_interval,
+            quota_dir)
     }
 
-    /// Apply resource limits using native `rlimit` mechanism to the calling process.
-    fn apply_native_rlimits(&self) -> Result<()> {
-        if self.use_native_rlimit {
-            if self.limits.cpu_time_limit.is_some() {
-                rlimits::setrlimit_hard(Resource::CPUTime,
-                    self.limits.cpu_time_limit.unwrap().as_secs())?;
-            }
-            if self.limits.memory_limit.is_some() {
-                rlimits::setrlimit_hard(Resource::AddressSpace,
-                    self.limits.memory_limit.unwrap().bytes() as u64)?;
-            }
-            // The real time limit is ignored here.
+    /// Get the directory on which the daemon thread implements the disk quota, if any. This
+    /// function should be called before the child process starts, since the files already in the
+    /// directory are not counted against the disk quota.
+    fn disk_quota_dir(&self) -> Result<Option<DiskQuotaDir>> {
+        if self.use_native_rlimit || self.limits.disk_quota.is_none() {
+            return Ok(None);
         }
 
-        Ok(())
-    }
-
-    /// Apply redirections specified in `self.redirections` to the calling process.
-    fn apply_redirections(&mut self) -> Result<()> {
-        if self.redirections.stdin.is_some() {
-            misc::dup_and_cloexec(
-                self.redirections.stdin.as_ref().unwrap().as_raw_fd(),
-                libc::STDIN_FILENO)?;
-        }
-        if self.redirections.stdout.is_some() {
-            misc::dup_and_cloexec(
-                self.redirections.stdout.as_ref().unwrap().as_raw_fd(),
-                libc::STDOUT_FILENO)?;
+        match self.dir.working_dir {
+            Some(ref working_dir) => Ok(Some(DiskQuotaDir::new(working_dir.clone())?)),
+            None => Ok(None)
         }
-        if self.redirections.stderr.is_some() {
-            misc::dup_and_cloexec(
-                self.redirections.stderr.as_ref().unwrap().as_raw_fd(),
-                libc::STDERR_FILENO)?;
-        }
-
-        Ok(())
     }
 
-    /// Set the effective user ID stored in `self.uid` of the calling process.
-    fn apply_uid(&self) -> Result<()> {
-        if self.uid.is_some() {
-            nix::unistd::setuid(Uid::from_raw(self.uid.unwrap()))?;
+    /// Get the resource limits that should be implemented by the daemon thread, and the memory
+    /// limit enforced by the native `rlimit` under `MemoryLimitPolicy::Native`, if any.
+    fn daemon_limits(&self) -> (Option<ProcessResourceLimits>, Option<MemorySize>) {
+        if self.use_native_rlimit {
+            return (None, None);
         }
 
-        Ok(())
-    }
-
-    /// Apply seccomp to the calling process to filter syscall sequence.
-    fn apply_seccomp(&self) -> Result<()> {
-        if self.syscall_whitelist.is_empty() {
-            return Ok(());
+        match self.memory_limit_policy {
+            MemoryLimitPolicy::Daemon => (Some(self.limits), None),
+            MemoryLimitPolicy::Native => (Some(self.limits), self.limits.memory_limit)
         }
-
-        // If the child process calls syscalls that are not on the list of syscall whitelist, the
-        // kernel will immediately kills the child process, as though it is been killed by the
-        // delivery of a `SIGSYS` signal.
-        seccomp::apply_syscall_filters(self.syscall_whitelist.iter()
-            .map(|syscall| seccomp::SyscallFilter::new(syscall.id, seccomp::Action::Allow)))?;
-
-        Ok(())
     }
 
-    /// Start child process. This function will be called after `fork` in the child process. This
-    /// function initializes necessary components in the child process (e.g. redirections, `setuid`,
-    /// seccomp, etc.) and then calls `execve`.
-    fn start_child(mut self) -> Result<()> {
-        // TODO: Change the return type of this function to Result<!> after the `!` type stablizes.
-
-        // Notes: No log messages are expected in the child process.
-
-        // Find the executable file by trying to expand the `PATH` environment variable before the
-        // file name.
-        let exec_file = match misc::expand_path(&self.file) {
-            Some(file) => file.into_owned(),
-            None => return Err(Error::from(ErrorKind::FileNotFound(self.file.clone())))
-        };
-
-        // Build argv and envs into native format.
-        let native_file = CString::new(exec_file.to_string_lossy().as_bytes()).unwrap();
-
-        // Strings used in arguments and environment variables are guaranteed to be valid C-style
-        // string when they were set so we directly unwraps them below.
-        let native_argv = self.args.iter()
-            .map(|arg| CString::new(arg.clone()).unwrap())
-            .collect::<Vec<CString>>();
-        let native_envs = self.envs.iter()
-            .map(|env| format!("{}={}", env.0, env.1))
-            .map(|env| CString::new(env).unwrap())
-            .collect::<Vec<CString>>();
-
-        // Apply redirections.
-        self.apply_redirections()?;
-
-        // Set current effective user ID if necessary.
-        self.apply_uid()?;
-
-        // Apply special directory changes.
-        self.apply_directories()?;
-
-        // Apply native resource limits.
-        self.apply_native_rlimits()?;
-
-        // Apply seccomp if necessary.
-        self.apply_seccomp()?;
-
-        // Finally, execve!
-        nix::unistd::execve(&native_file, native_argv.as_ref(), native_envs.as_ref())?;
-
-        unreachable!()
-    }
-
-    /// Initializes any necessary components in the parent process to monitor the states of the
-    /// child process. This function should be called after `fork` in the parent process.
-    fn start_parent(self, child_pid: Pid) -> Process {
-        log::trace!("Starting parent process daemon...");
-
-        let daemon_limits = if self.use_native_rlimit {
-            None
-        } else {
-            Some(self.limits)
-        };
-
-        Process::attach(child_pid, daemon_limits)
+    /// Start the process under a daemon thread that also enforces the given system call budget.
+    /// The process is started in the daemon thread since only the thread that started a process
+    /// can trace it.
+    fn start_traced(self, syscall_budget: u64) -> Result<Process> {
+        let (daemon_limits, native_memory_limit) = self.daemon_limits();
+        let sampling_interval = self.sampling_interval;
+        let quota_dir = self.disk_quota_dir()?;
+
+        let (context, daemon_handle) = daemon::start_traced(
+            move || self.fork_child(true),
+            daemon_limits, native_memory_limit, sampling_interval, quota_dir, syscall_budget)?;
+        Ok(Process {
+            pid: context.pid(),
+            context,
+            daemon: Some(daemon_handle)
+        })
     }
 
     /// Create a `ProcessBuilderMemento` object containing the internal status of the current
@@ -583,33 +781,153 @@ impl ProcessBuilder {
             dir: self.dir.clone(),
             limits: self.limits.clone(),
             use_native_rlimit: self.use_native_rlimit,
+            memory_limit_policy: self.memory_limit_policy,
+            sampling_interval: self.sampling_interval,
             uid: self.uid,
             syscall_whitelist: self.syscall_whitelist.clone(),
+            nice: self.nice,
+            io_priority: self.io_priority,
+            require_seccomp: self.require_seccomp,
+            syscall_budget: self.syscall_budget,
         }
     }
 
+    /// Start the process under a system call tracer and wait for it to exit. Returns the exit
+    /// status of the process and the distinct system calls invoked by it, which can be used to build
+    /// a system call whitelist.
+    ///
+    /// This function is intended for diagnosis only. The resource limits are not enforced strictly
+    /// (only native `rlimit`s are effective, if enabled) and the system call whitelist is ignored.
+    pub fn trace_syscalls(self) -> Result<SystemCallTrace> {
+        trace::trace(self)
+    }
+
+    /// Wait for the report of the child process on the given error pipe. Returns `Ok(())` if the
+    /// write end of the pipe is closed by a successful `execve` without any report; otherwise the
+    /// child process is reaped and the reported error is returned.
+    fn check_child_setup(&self, child_pid: Pid, error_pipe: RawFd) -> Result<()> {
+        let mut report = Vec::new();
+        unsafe { File::from_raw_fd(error_pipe) }.read_to_end(&mut report)?;
+        if report.is_empty() {
+            return Ok(());
+        }
+
+        // The child process kills itself after sending the report.
+        nix::sys::wait::waitpid(child_pid, None)?;
+
+        if report.len() != 8 {
+            return Err(Error::from(ErrorKind::ChildStartupFailed));
+        }
+        let stage = i32::from_ne_bytes([report[0], report[1], report[2], report[3]]);
+        let errno = i32::from_ne_bytes([report[4], report[5], report[6], report[7]]);
+        let stage = match ChildSetupStage::from_code(stage) {
+            Some(stage) => stage,
+            None => return Err(Error::from(ErrorKind::ChildStartupFailed))
+        };
+
+        let kind = match (stage, errno) {
+            (ChildSetupStage::ExecutableLookup, _) => ErrorKind::FileNotFound(self.file.clone()),
+            (_, libc::EPERM) => ErrorKind::PermissionDenied(stage),
+            _ => ErrorKind::ChildSetupFailed(stage, errno)
+        };
+        Err(Error::from(kind))
+    }
+
+    /// Report the failure of the given stage with the given error number occurred in the child
+    /// process to the parent process through the given error pipe. This function does not allocate
+    /// memory.
+    fn report_child_setup_error(stage: ChildSetupStage, errno: i32, error_pipe: RawFd) {
+        let mut report = [0u8; 8];
+        report[..4].copy_from_slice(&stage.code().to_ne_bytes());
+        report[4..].copy_from_slice(&errno.to_ne_bytes());
+        // The report is shorter than `PIPE_BUF` so it is written atomically. Nothing can be done
+        // if the write fails since the parent process treats the closed pipe as a success; the
+        // `SIGUSR1` signal sent afterwards still reports the failure to the daemon thread.
+        nix::unistd::write(error_pipe, &report).ok();
+    }
+
     /// Start the process in a sandboxed environment.
+    ///
+    /// If the child process cannot be set up (e.g. the judge process does not have the privilege
+    /// to change the user ID), the failed stage is reported back to the parent process through a
+    /// pipe and this function returns `ErrorKind::PermissionDenied` or
+    /// `ErrorKind::ChildSetupFailed` accordingly.
     pub fn start(self) -> Result<Process> {
+        if let Some(syscall_budget) = self.syscall_budget {
+            return self.start_traced(syscall_budget);
+        }
+
+        let quota_dir = self.disk_quota_dir()?;
+        let child = self.fork_child(false)?;
+        Ok(self.start_parent(child, quota_dir))
+    }
+
+    /// Fork a child process and execute the program in it. Returns the pid of the child process
+    /// once its `execve` call succeeds, or the error reported by the child process if it cannot be
+    /// set up.
+    ///
+    /// If `traced` is `true`, the child process becomes a tracee of the calling thread and is left
+    /// stopped right after its `execve` call succeeds, see `trace::wait_for_exec`.
+    fn fork_child(&self, traced: bool) -> Result<Pid> {
+        // Everything the child process needs is prepared before `fork` since the child process
+        // must not allocate memory, see the `child` module.
+        let mut image = ChildImage::new(self)?;
+
+        // The write end of the error pipe is closed automatically by a successful `execve` in the
+        // child process.
+        let (read_fd, write_fd) = nix::unistd::pipe2(OFlag::O_CLOEXEC)?;
+        image.keep_open(write_fd);
+
         match nix::unistd::fork()? {
-            ForkResult::Parent { child } => Ok(self.start_parent(child)),
+            ForkResult::Parent { child } => {
+                nix::unistd::close(write_fd)?;
+                if traced {
+                    trace::wait_for_exec(child)?;
+                }
+                self.check_child_setup(child, read_fd)?;
+                Ok(child)
+            },
             ForkResult::Child => {
-                match self.start_child() {
-                    Ok(..) => unreachable!(),
-                    Err(e) => {
-                        eprintln!("failed to start child process: {}", e);
-                        // Send a `SIGUSR1` signal to self to terminate self and notify the daemon
-                        // thread.
-                        let sig = nix::sys::signal::Signal::SIGUSR1;
-                        nix::sys::signal::kill(nix::unistd::getpid(), sig)
-                            .expect("cannot kill self.");
-                        // Sit in a tight loop, wait to be killed by the delivery of the `SIGUSR1`
-                        // signal whose default handling behavior is killing the target process.
-                        loop { }
-                    }
+                nix::unistd::close(read_fd).ok();
+                if traced && trace::trace_me().is_err() {
+                    child::abort_child(None, Some(write_fd));
                 }
+                let failure = image.exec();
+                child::abort_child(Some(failure), Some(write_fd))
             }
         }
     }
+
+    /// Start the process with its `stdin` redirected to a pipe fed with the given bytes, which
+    /// saves creating a temporary file to hold small inputs. The redirected `stdin` set in
+    /// `redirections`, if any, is replaced.
+    ///
+    /// The bytes are written into the pipe by a separate thread, so that inputs larger than the
+    /// capacity of the pipe do not block the caller. The write end of the pipe is closed once all
+    /// bytes are written, after which the child process reads EOF. Bytes that the child process
+    /// does not read before it exits are discarded.
+    pub fn spawn_with_stdin_bytes<T>(mut self, input: T) -> Result<Process>
+        where T: Into<Vec<u8>> {
+        // Neither end of the pipe should be inherited by the child process other than through the
+        // `stdin` redirection.
+        let (read_fd, write_fd) = nix::unistd::pipe2(OFlag::O_CLOEXEC)?;
+        let (read_end, mut write_end) = unsafe {
+            (File::from_raw_fd(read_fd), File::from_raw_fd(write_fd))
+        };
+        self.redirections.stdin = Some(read_end);
+        let process = self.start()?;
+
+        let input = input.into();
+        std::thread::spawn(move || {
+            match write_end.write_all(&input) {
+                Ok(..) => (),
+                Err(ref e) if e.kind() == std::io::ErrorKind::BrokenPipe => (),
+                Err(e) => log::warn!("failed to write stdin of the child process: {}", e)
+            };
+        });
+
+        Ok(process)
+    }
 }
 
 impl From<ProcessBuilderMemento> for ProcessBuilder {
@@ -621,8 +939,14 @@ impl From<ProcessBuilderMemento> for ProcessBuilder {
             dir: memento.dir,
             limits: memento.limits,
             use_native_rlimit: memento.use_native_rlimit,
+            memory_limit_policy: memento.memory_limit_policy,
+            sampling_interval: memento.sampling_interval,
             uid: memento.uid,
             syscall_whitelist: memento.syscall_whitelist,
+            nice: memento.nice,
+            io_priority: memento.io_priority,
+            require_seccomp: memento.require_seccomp,
+            syscall_budget: memento.syscall_budget,
             redirections: ProcessRedirection::empty(),
         }
     }
@@ -649,11 +973,29 @@ pub struct ProcessBuilderMemento {
     /// Whether to use native rlimit mechanism to limit the resource usage of the child process.
     use_native_rlimit: bool,
 
+    /// How the memory limit is enforced.
+    memory_limit_policy: MemoryLimitPolicy,
+
+    /// Interval between two consecutive samplings of the child process's resource usage statistics.
+    sampling_interval: Duration,
+
     /// Effective user ID of the new child process.
     uid: Option<UserId>,
 
     /// A list of allowed syscalls for the new child process.
     syscall_whitelist: Vec<SystemCall>,
+
+    /// The nice value of the new child process.
+    nice: Option<i32>,
+
+    /// The I/O priority of the new child process.
+    io_priority: Option<IoPriority>,
+
+    /// Whether seccomp filters are required to start the new child process.
+    require_seccomp: bool,
+
+    /// Maximum number of system calls the new child process may invoke.
+    syscall_budget: Option<u64>,
 }
 
 impl ProcessBuilderMemento {
@@ -666,8 +1008,14 @@ impl ProcessBuilderMemento {
             dir: self.dir.clone(),
             limits: self.limits.clone(),
             use_native_rlimit: self.use_native_rlimit,
+            memory_limit_policy: self.memory_limit_policy,
+            sampling_interval: self.sampling_interval,
             uid: self.uid,
             syscall_whitelist: self.syscall_whitelist.clone(),
+            nice: self.nice,
+            io_priority: self.io_priority,
+            require_seccomp: self.require_seccomp,
+            syscall_budget: self.syscall_budget,
             redirections: ProcessRedirection::empty(),
         }
     }
@@ -682,8 +1030,14 @@ impl From<ProcessBuilder> for ProcessBuilderMemento {
             dir: builder.dir,
             limits: builder.limits,
             use_native_rlimit: builder.use_native_rlimit,
+            memory_limit_policy: builder.memory_limit_policy,
+            sampling_interval: builder.sampling_interval,
             uid: builder.uid,
             syscall_whitelist: builder.syscall_whitelist,
+            nice: builder.nice,
+            io_priority: builder.io_priority,
+            require_seccomp: builder.require_seccomp,
+            syscall_budget: builder.syscall_budget,
         }
     }
 }
@@ -695,7 +1049,7 @@ pub type ExitCode = i32;
 pub type Signal = i32;
 
 /// Exit status of a sandboxed process.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum ProcessExitStatus {
     /// The process has not exited yet.
@@ -718,7 +1072,15 @@ pub enum ProcessExitStatus {
 
     /// The process was killed by the daemon due to its invocation to a banned
     /// system call.
-    BannedSyscall
+    BannedSyscall,
+
+    /// The process was killed by the daemon since the files under its working directory exceed
+    /// the disk quota.
+    DiskQuotaExceeded,
+
+    /// The process was killed by the daemon since it invoked more system calls than its system
+    /// call budget.
+    SyscallBudgetExceeded,
 }
 
 impl ProcessExitStatus {
@@ -739,7 +1101,7 @@ impl Default for ProcessExitStatus {
 }
 
 /// Resource usage statistics of a sandboxed process.
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct ProcessResourceUsage {
     /// CPU time spent in user mode.
@@ -752,7 +1114,25 @@ pub struct ProcessResourceUsage {
     pub virtual_mem_size: MemorySize,
 
     /// Resident set size.
-    pub resident_set_size: MemorySize
+    pub resident_set_size: MemorySize,
+
+    /// Number of minor page faults, i.e. page faults served without loading pages from disk.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub minor_faults: usize,
+
+    /// Number of major page faults, i.e. page faults that required loading pages from disk.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub major_faults: usize,
+
+    /// Number of voluntary context switches, e.g. when the process blocks on IO.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub voluntary_context_switches: usize,
+
+    /// Number of involuntary context switches, i.e. when the process is preempted. A large number
+    /// hints that the process competed for CPUs, which explains a wall time much longer than its
+    /// CPU time.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub involuntary_context_switches: usize,
 }
 
 impl ProcessResourceUsage {
@@ -762,13 +1142,48 @@ impl ProcessResourceUsage {
             user_cpu_time: Duration::new(0, 0),
             kernel_cpu_time: Duration::new(0, 0),
             virtual_mem_size: MemorySize::Bytes(0),
-            resident_set_size: MemorySize::Bytes(0)
+            resident_set_size: MemorySize::Bytes(0),
+            minor_faults: 0,
+            major_faults: 0,
+            voluntary_context_switches: 0,
+            involuntary_context_switches: 0,
         }
     }
 
     /// Get resource usage for the specified process.
+    ///
+    /// Besides the instantaneous memory usage reported in `/proc/<pid>/stat`, this function also
+    /// takes the high-water marks `VmPeak` and `VmHWM` reported in `/proc/<pid>/status` into
+    /// account, so that short memory spikes between two samplings will not be missed. The context
+    /// switch counters are read from `/proc/<pid>/status` as well, and are left zero if it cannot
+    /// be read.
     pub fn usage_of(pid: Pid) -> std::io::Result<Self> {
-        Ok(ProcessResourceUsage::from(procinfo::pid::stat(pid.as_raw())?))
+        let mut usage = ProcessResourceUsage::from(procinfo::pid::stat(pid.as_raw())?);
+        match misc::process_status(pid.as_raw()) {
+            Ok(status) => usage.update_from_status(&status),
+            // The process may have exited between the two reads, in which case the memory
+            // statistics read from `/proc/<pid>/stat` are used.
+            Err(e) => log::debug!("Cannot read status of process {}: {}", pid, e)
+        };
+
+        Ok(usage)
+    }
+
+    /// Update the memory usage statistics stored in this instance to the high-water marks reported
+    /// by the kernel, if they are larger, and set the context switch counters.
+    fn update_from_status(&mut self, status: &misc::ProcessStatus) {
+        let vm_peak = MemorySize::KiloBytes(status.vm_peak);
+        if vm_peak > self.virtual_mem_size {
+            self.virtual_mem_size = vm_peak;
+        }
+
+        let vm_hwm = MemorySize::KiloBytes(status.vm_hwm);
+        if vm_hwm > self.resident_set_size {
+            self.resident_set_size = vm_hwm;
+        }
+
+        self.voluntary_context_switches = status.voluntary_ctxt_switches;
+        self.involuntary_context_switches = status.nonvoluntary_ctxt_switches;
     }
 
     /// Get the total CPU time consumed, a.k.a. the sum of the user CPU time and
@@ -792,6 +1207,15 @@ impl ProcessResourceUsage {
         if other.resident_set_size > self.resident_set_size {
             self.resident_set_size = other.resident_set_size;
         }
+
+        // The counters only grow while the process runs, but the last sampling may fail to read
+        // some of them after the process exits.
+        self.minor_faults = std::cmp::max(self.minor_faults, other.minor_faults);
+        self.major_faults = std::cmp::max(self.major_faults, other.major_faults);
+        self.voluntary_context_switches = std::cmp::max(
+            self.voluntary_context_switches, other.voluntary_context_switches);
+        self.involuntary_context_switches = std::cmp::max(
+            self.involuntary_context_switches, other.involuntary_context_switches);
     }
 }
 
@@ -801,7 +1225,11 @@ impl From<procinfo::pid::Stat> for ProcessResourceUsage {
             user_cpu_time: misc::duration_from_clocks(stat.utime),
             kernel_cpu_time: misc::duration_from_clocks(stat.stime),
             virtual_mem_size: MemorySize::Bytes(stat.vsize),
-            resident_set_size: MemorySize::Bytes(stat.rss)
+            resident_set_size: MemorySize::Bytes(stat.rss),
+            minor_faults: stat.minflt,
+            major_faults: stat.majflt,
+            voluntary_context_switches: 0,
+            involuntary_context_switches: 0,
         }
     }
 }
@@ -830,12 +1258,18 @@ pub struct Process {
 
 impl Process {
     /// Create a new `Process` instance attaching to the specific process.
-    fn attach(pid: Pid, limits: Option<ProcessResourceLimits>) -> Process {
+    fn attach(pid: Pid,
+        limits: Option<ProcessResourceLimits>,
+        native_memory_limit: Option<MemorySize>,
+        sampling_interval: Duration,
+        quota_dir: Option<DiskQuotaDir>) -> Process {
         log::trace!("Process::attach to process ID {}", pid.as_raw());
 
+        let context = ProcessDaemonContext::new(
+            pid, limits, native_memory_limit, sampling_interval, quota_dir);
         let mut handle = Process {
             pid,
-            context: Arc::new(Box::new(ProcessDaemonContext::new(pid, limits))),
+            context: Arc::new(Box::new(context)),
             daemon: None
         };
 
@@ -862,18 +1296,54 @@ impl Process {
             .unwrap_or_else(|| ProcessResourceUsage::new())
     }
 
-    /// Wait for the child process to exit. Panics if this function has been
-    /// called already on the same `Process` instance.
+    /// Check whether the child process has exited, without blocking. Returns `None` if the daemon
+    /// thread has not observed the exit of the child process yet; otherwise returns the exit status
+    /// of the child process. This function does not join the daemon thread, so `wait_for_exit` can
+    /// still be called afterwards.
+    pub fn try_wait(&self) -> Option<ProcessExitStatus> {
+        match self.context.exit_status() {
+            ProcessExitStatus::NotExited => None,
+            status => Some(status)
+        }
+    }
+
+    /// Kill the child process by sending `SIGKILL` to its process group, which also kills the
+    /// processes started by it unless they have left the process group. The exit status reported
+    /// afterwards is `ProcessExitStatus::KilledBySignal(SIGKILL)`, unless the child process has
+    /// exited or been stopped by the daemon thread before the signal is delivered. It is safe to
+    /// call this function more than once, or after the child process has exited.
+    pub fn kill(&self) -> Result<()> {
+        self.context.kill()
+    }
+
+    /// Wait for the child process to exit. Returns the error occurred in the daemon thread while
+    /// monitoring the child process, if any. Panics if this function has been called already on the
+    /// same `Process` instance.
     pub fn wait_for_exit(&mut self) -> Result<()> {
-        self.daemon.take().unwrap().join()
-            .map_err(|_| Error::from(ErrorKind::DaemonFailed))
+        match self.daemon.take().unwrap().join() {
+            Ok(res) => res,
+            Err(..) => Err(Error::from(ErrorKind::DaemonFailed))
+        }
     }
 }
 
 
 #[cfg(test)]
 mod tests {
-    use super::MemorySize;
+    use super::{
+        ChildSetupStage,
+        ErrorKind,
+        IoPriority,
+        MemoryLimitPolicy,
+        MemorySize,
+        ProcessBuilder,
+        ProcessExitStatus,
+        ProcessResourceUsage,
+    };
+
+    use std::path::PathBuf;
+
+    use std::time::{Duration, Instant};
 
     #[test]
     fn test_memory_size_to_bytes() {
@@ -883,4 +1353,550 @@ mod tests {
         assert_eq!(2 * 1024 * 1024 * 1024, MemorySize::GigaBytes(2).bytes());
         assert_eq!(2 * 1024 * 1024 * 1024 * 1024, MemorySize::TeraBytes(2).bytes());
     }
+
+    #[test]
+    fn test_usage_of_reports_peak_memory() {
+        const BUFFER_SIZE: usize = 64 * 1024 * 1024;
+
+        // Briefly allocate a large buffer, touch every page of it and free it immediately.
+        let buffer = vec![1u8; BUFFER_SIZE];
+        assert_eq!(BUFFER_SIZE, buffer.iter().map(|b| *b as usize).sum::<usize>());
+        drop(buffer);
+
+        let usage = ProcessResourceUsage::usage_of(nix::unistd::getpid()).unwrap();
+        assert!(usage.resident_set_size >= MemorySize::Bytes(BUFFER_SIZE));
+        assert!(usage.virtual_mem_size >= MemorySize::Bytes(BUFFER_SIZE));
+    }
+
+    #[test]
+    fn test_usage_from_stat() {
+        let stat = procinfo::pid::Stat {
+            minflt: 1024,
+            majflt: 3,
+            ..Default::default()
+        };
+        let mut usage = ProcessResourceUsage::from(stat);
+        assert_eq!(1024, usage.minor_faults);
+        assert_eq!(3, usage.major_faults);
+        assert_eq!(0, usage.voluntary_context_switches);
+        assert_eq!(0, usage.involuntary_context_switches);
+
+        let mut later = ProcessResourceUsage::new();
+        later.minor_faults = 2048;
+        later.voluntary_context_switches = 7;
+        usage.update(&later);
+        assert_eq!(2048, usage.minor_faults);
+        assert_eq!(3, usage.major_faults);
+        assert_eq!(7, usage.voluntary_context_switches);
+    }
+
+    #[test]
+    fn test_try_wait() {
+        let mut builder = ProcessBuilder::new("/bin/sleep");
+        builder.add_arg("0.2").unwrap();
+        builder.limits.real_time_limit = Some(Duration::from_secs(5));
+        let mut process = builder.start().unwrap();
+        assert!(process.try_wait().is_none());
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let status = loop {
+            if let Some(status) = process.try_wait() {
+                break status;
+            }
+            assert!(Instant::now() < deadline, "child process did not exit in time");
+            std::thread::sleep(Duration::from_millis(10));
+        };
+        match status {
+            ProcessExitStatus::Normal(0) => (),
+            status => panic!("unexpected exit status: {:?}", status),
+        };
+
+        process.wait_for_exit().unwrap();
+        assert_eq!(Some(0), process.exit_status().exit_code());
+    }
+
+    #[test]
+    fn test_unprivileged_setup_reported() {
+        let mut builder = ProcessBuilder::new("/bin/true");
+        let expected_stage = if nix::unistd::geteuid().is_root() {
+            // Root can change its user ID, but cannot change the root directory afterwards.
+            builder.uid = Some(65534);
+            builder.dir.root_dir = Some(PathBuf::from("/"));
+            ChildSetupStage::Directories
+        } else {
+            builder.uid = Some(0);
+            ChildSetupStage::SetUid
+        };
+
+        let err = builder.start().err().expect("child process started unexpectedly");
+        match err.kind() {
+            ErrorKind::PermissionDenied(stage) => assert_eq!(expected_stage, *stage),
+            kind => panic!("unexpected error kind: {:?}", kind),
+        };
+    }
+
+    #[test]
+    fn test_executable_not_found_reported() {
+        let file = PathBuf::from("/nonexistent/program");
+        let err = ProcessBuilder::new(&file).start()
+            .err().expect("child process started unexpectedly");
+        match err.kind() {
+            ErrorKind::FileNotFound(path) => assert_eq!(file, *path),
+            kind => panic!("unexpected error kind: {:?}", kind),
+        };
+    }
+
+    #[test]
+    fn test_kill() {
+        for use_native_rlimit in &[false, true] {
+            let mut builder = ProcessBuilder::new("/bin/sleep");
+            builder.add_arg("60").unwrap();
+            builder.use_native_rlimit = *use_native_rlimit;
+            let mut process = builder.start().unwrap();
+
+            let start = Instant::now();
+            process.kill().unwrap();
+            process.kill().unwrap();
+            process.wait_for_exit().unwrap();
+            assert!(start.elapsed() < Duration::from_secs(5));
+            assert_eq!(ProcessExitStatus::KilledBySignal(libc::SIGKILL), process.exit_status());
+
+            // Killing an exited process does nothing.
+            process.kill().unwrap();
+        }
+    }
+
+    /// Check whether the process with the given pid has exited within 5 seconds. Orphaned processes
+    /// may never be reaped in containers, so zombies count as exited.
+    fn wait_process_exited(pid: i32) -> bool {
+        let start = Instant::now();
+        while start.elapsed() < Duration::from_secs(5) {
+            match std::fs::read_to_string(format!("/proc/{}/stat", pid)) {
+                // The state is the first field after the parenthesized executable name.
+                Ok(stat) => if stat.rsplit(')').next().unwrap().trim_start().starts_with('Z') {
+                    return true;
+                },
+                Err(..) => return true
+            };
+            std::thread::sleep(Duration::from_millis(10));
+        }
+
+        false
+    }
+
+    #[test]
+    fn test_kill_process_group() {
+        let dir = std::env::temp_dir()
+            .join(format!("sandbox-kill-process-group-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        for kill in &[true, false] {
+            let pid_file = dir.join(format!("pid-{}", kill));
+            let mut builder = ProcessBuilder::new("/bin/sh");
+            builder.add_arg("-c").unwrap();
+            builder.add_arg(format!("sleep 60 & echo $! > {}; wait", pid_file.display())).unwrap();
+            // The process is killed by the daemon thread on exceeding the real time limit unless it
+            // is killed explicitly.
+            builder.limits.real_time_limit = Some(Duration::from_millis(500));
+            let mut process = builder.start().unwrap();
+
+            let start = Instant::now();
+            let grandchild = loop {
+                if let Ok(pid) = std::fs::read_to_string(&pid_file) {
+                    if pid.ends_with('\n') {
+                        break pid.trim().parse::<i32>().unwrap();
+                    }
+                }
+                assert!(start.elapsed() < Duration::from_secs(5));
+                std::thread::sleep(Duration::from_millis(10));
+            };
+            if *kill {
+                process.kill().unwrap();
+            }
+            process.wait_for_exit().unwrap();
+            assert!(wait_process_exited(grandchild));
+        }
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_priority() {
+        let mut builder = ProcessBuilder::new("/bin/sleep");
+        builder.add_arg("60").unwrap();
+        builder.nice = Some(7);
+        builder.io_priority = Some(IoPriority::BestEffort(6));
+        let mut process = builder.start().unwrap();
+
+        // The nice value is the 19th field of `/proc/[pid]/stat`, i.e. the 17th field after the
+        // parenthesized executable name.
+        let stat = std::fs::read_to_string(format!("/proc/{}/stat", process.pid())).unwrap();
+        let fields = stat[stat.rfind(')').unwrap() + 2..].split(' ').collect::<Vec<&str>>();
+        assert_eq!("7", fields[16]);
+
+        let io_priority = unsafe {
+            libc::syscall(libc::SYS_ioprio_get, 1 /* IOPRIO_WHO_PROCESS */, process.pid())
+        };
+        assert_eq!(IoPriority::BestEffort(6).encode() as libc::c_long, io_priority);
+
+        process.kill().unwrap();
+        process.wait_for_exit().unwrap();
+    }
+
+    #[test]
+    fn test_disk_quota_exceeded() {
+        let working_dir = std::env::temp_dir()
+            .join(format!("sandbox-disk-quota-{}", std::process::id()));
+        std::fs::create_dir_all(&working_dir).unwrap();
+
+        let mut builder = ProcessBuilder::new("/bin/sh");
+        builder.add_arg("-c").unwrap();
+        builder.add_arg("head -c 4194304 /dev/zero > output; sleep 5").unwrap();
+        builder.dir.working_dir = Some(PathBuf::from(&working_dir));
+        builder.limits.real_time_limit = Some(Duration::from_secs(3));
+        builder.limits.disk_quota = Some(MemorySize::MegaBytes(1));
+        let mut process = builder.start().unwrap();
+        process.wait_for_exit().unwrap();
+        let status = process.exit_status();
+
+        std::fs::remove_dir_all(&working_dir).unwrap();
+        match status {
+            ProcessExitStatus::DiskQuotaExceeded => (),
+            status => panic!("unexpected exit status: {:?}", status),
+        };
+    }
+
+    #[test]
+    fn test_disk_quota_excludes_existing_files() {
+        let working_dir = std::env::temp_dir()
+            .join(format!("sandbox-disk-quota-baseline-{}", std::process::id()));
+        std::fs::create_dir_all(&working_dir).unwrap();
+        std::fs::write(working_dir.join("input"), vec![0u8; 4 * 1024 * 1024]).unwrap();
+
+        let mut builder = ProcessBuilder::new("/bin/sh");
+        builder.add_arg("-c").unwrap();
+        builder.add_arg("head -c 1024 /dev/zero > output; sleep 0.5").unwrap();
+        builder.dir.working_dir = Some(PathBuf::from(&working_dir));
+        builder.limits.real_time_limit = Some(Duration::from_secs(3));
+        builder.limits.disk_quota = Some(MemorySize::MegaBytes(1));
+        let mut process = builder.start().unwrap();
+        process.wait_for_exit().unwrap();
+        let status = process.exit_status();
+
+        std::fs::remove_dir_all(&working_dir).unwrap();
+        assert_eq!(ProcessExitStatus::Normal(0), status);
+    }
+
+    #[test]
+    fn test_syscall_budget() {
+        // The shell invokes at least one system call per loop iteration.
+        let mut builder = ProcessBuilder::new("/bin/sh");
+        builder.add_arg("-c").unwrap();
+        builder.add_arg("while true; do echo x > /dev/null; done").unwrap();
+        builder.limits.real_time_limit = Some(Duration::from_secs(5));
+        builder.syscall_budget = Some(1000);
+        let mut process = builder.start().unwrap();
+        process.wait_for_exit().unwrap();
+        assert_eq!(ProcessExitStatus::SyscallBudgetExceeded, process.exit_status());
+    }
+
+    #[test]
+    fn test_syscall_budget_not_exceeded() {
+        let mut builder = ProcessBuilder::new("/bin/sh");
+        builder.add_arg("-c").unwrap();
+        builder.add_arg("exit 3").unwrap();
+        builder.limits.real_time_limit = Some(Duration::from_secs(5));
+        builder.syscall_budget = Some(1000);
+        let mut process = builder.start().unwrap();
+        process.wait_for_exit().unwrap();
+        assert_eq!(ProcessExitStatus::Normal(3), process.exit_status());
+    }
+
+    #[test]
+    fn test_start_under_concurrent_allocation() {
+        use std::sync::Arc;
+        use std::sync::atomic::{AtomicBool, Ordering};
+
+        // Threads allocating memory in tight loops hold the locks of the memory allocator most of
+        // the time, which would deadlock child processes allocating memory after `fork`.
+        let stop = Arc::new(AtomicBool::new(false));
+        let allocators = (0..4)
+            .map(|_| {
+                let stop = stop.clone();
+                std::thread::spawn(move || {
+                    let mut blocks = Vec::with_capacity(64);
+                    let mut round = 0usize;
+                    while !stop.load(Ordering::SeqCst) {
+                        if blocks.len() == blocks.capacity() {
+                            blocks.clear();
+                        }
+                        blocks.push(format!("block {}", round).into_bytes());
+                        round += 1;
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+
+        for _ in 0..200 {
+            let mut builder = ProcessBuilder::new("/bin/true");
+            builder.add_env("ROUND", "x").unwrap();
+            builder.limits.real_time_limit = Some(Duration::from_secs(5));
+            let mut process = builder.start().unwrap();
+            process.wait_for_exit().unwrap();
+            assert_eq!(ProcessExitStatus::Normal(0), process.exit_status());
+        }
+
+        stop.store(true, Ordering::SeqCst);
+        for allocator in allocators {
+            allocator.join().unwrap();
+        }
+    }
+
+    #[test]
+    fn test_syscall_budget_setup_failure() {
+        let mut builder = ProcessBuilder::new("/nonexistent/program");
+        builder.syscall_budget = Some(1000);
+        match builder.start() {
+            Err(e) => match e.kind() {
+                ErrorKind::FileNotFound(..) => (),
+                kind => panic!("unexpected error kind: {:?}", kind),
+            },
+            Ok(..) => panic!("the process should not start"),
+        };
+    }
+
+    #[test]
+    fn test_open_files_limit() {
+        fn open_many_files(open_files_limit: Option<u32>) -> ProcessExitStatus {
+            // Open files on descriptors 3 to 9 one by one.
+            let mut builder = ProcessBuilder::new("/bin/sh");
+            builder.add_arg("-c").unwrap();
+            builder.add_arg("for i in 3 4 5 6 7 8 9; do eval \"exec $i</dev/null\" || exit 1; done")
+                .unwrap();
+            builder.limits.open_files_limit = open_files_limit;
+            let mut process = builder.start().unwrap();
+            process.wait_for_exit().unwrap();
+            process.exit_status()
+        }
+
+        assert_eq!(ProcessExitStatus::Normal(0), open_many_files(None));
+        assert_ne!(ProcessExitStatus::Normal(0), open_many_files(Some(6)));
+    }
+
+    #[test]
+    fn test_native_memory_limit_status() {
+        use super::daemon::native_memory_limit_status;
+
+        assert_eq!(ProcessExitStatus::MemoryLimitExceeded,
+            native_memory_limit_status(ProcessExitStatus::KilledBySignal(libc::SIGABRT)));
+        assert_eq!(ProcessExitStatus::KilledBySignal(libc::SIGSEGV),
+            native_memory_limit_status(ProcessExitStatus::KilledBySignal(libc::SIGSEGV)));
+        assert_eq!(ProcessExitStatus::Normal(2),
+            native_memory_limit_status(ProcessExitStatus::Normal(2)));
+        assert_eq!(ProcessExitStatus::RealTimeLimitExceeded,
+            native_memory_limit_status(ProcessExitStatus::RealTimeLimitExceeded));
+    }
+
+    #[test]
+    fn test_native_memory_limit_oversized_allocation() {
+        let dir = std::env::temp_dir()
+            .join(format!("sandbox-oversized-allocation-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let source = dir.join("alloc.c");
+        let program = dir.join("alloc");
+        std::fs::write(&source, r#"
+            #include <stdlib.h>
+            #include <string.h>
+
+            int main(void) {
+                size_t size = (size_t)1 << 30;
+                char *p = malloc(size);
+                if (p == NULL) {
+                    abort();
+                }
+                memset(p, 1, size);
+                return 0;
+            }
+        "#).unwrap();
+        let status = std::process::Command::new("gcc")
+            .arg("-o").arg(&program).arg(&source)
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        // The single allocation exceeds `RLIMIT_AS`, so the daemon thread never observes the
+        // address space of the child process growing beyond the memory limit.
+        let mut builder = ProcessBuilder::new(&program);
+        builder.limits.real_time_limit = Some(Duration::from_secs(10));
+        builder.limits.memory_limit = Some(MemorySize::MegaBytes(64));
+        builder.memory_limit_policy = MemoryLimitPolicy::Native;
+        let mut process = builder.start().unwrap();
+        process.wait_for_exit().unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(ProcessExitStatus::MemoryLimitExceeded, process.exit_status());
+    }
+
+    #[test]
+    fn test_native_memory_limit_daemon_enforced() {
+        let mut builder = ProcessBuilder::new("/bin/true");
+        builder.limits.memory_limit = Some(MemorySize::MegaBytes(64));
+        builder.memory_limit_policy = MemoryLimitPolicy::Native;
+        let (daemon_limits, native_memory_limit) = builder.daemon_limits();
+        assert_eq!(Some(MemorySize::MegaBytes(64)), daemon_limits.unwrap().memory_limit);
+        assert_eq!(Some(MemorySize::MegaBytes(64)), native_memory_limit);
+    }
+
+    #[test]
+    fn test_native_memory_limit() {
+        // The shell keeps growing its buffer for the output of the command substitution until an
+        // allocation fails.
+        let mut builder = ProcessBuilder::new("/bin/sh");
+        builder.add_arg("-c").unwrap();
+        builder.add_arg("x=$(head -c 1073741824 /dev/zero | tr '\\0' a)").unwrap();
+        builder.limits.real_time_limit = Some(Duration::from_secs(10));
+        builder.limits.memory_limit = Some(MemorySize::MegaBytes(64));
+        builder.memory_limit_policy = MemoryLimitPolicy::Native;
+        let mut process = builder.start().unwrap();
+        process.wait_for_exit().unwrap();
+        assert_eq!(ProcessExitStatus::MemoryLimitExceeded, process.exit_status());
+
+        let mut builder = ProcessBuilder::new("/bin/sh");
+        builder.add_arg("-c").unwrap();
+        builder.add_arg("exit 3").unwrap();
+        builder.limits.memory_limit = Some(MemorySize::MegaBytes(64));
+        builder.memory_limit_policy = MemoryLimitPolicy::Native;
+        let mut process = builder.start().unwrap();
+        process.wait_for_exit().unwrap();
+        assert_eq!(ProcessExitStatus::Normal(3), process.exit_status());
+    }
+
+    #[test]
+    fn test_spawn_with_stdin_bytes() {
+        let dir = std::env::temp_dir()
+            .join(format!("sandbox-stdin-bytes-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let output = std::fs::File::create(dir.join("output")).unwrap();
+
+        // The input is larger than the capacity of a pipe.
+        let input = (0..100 * 1024).map(|i| b'a' + (i % 26) as u8).collect::<Vec<u8>>();
+        let mut builder = ProcessBuilder::new("/bin/cat");
+        builder.redirections.stdout = Some(output);
+        builder.limits.real_time_limit = Some(Duration::from_secs(5));
+        let mut process = builder.spawn_with_stdin_bytes(input.clone()).unwrap();
+        process.wait_for_exit().unwrap();
+        let status = process.exit_status();
+
+        let content = std::fs::read(dir.join("output")).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(Some(0), status.exit_code());
+        assert_eq!(input, content);
+    }
+
+    #[test]
+    fn test_spawn_with_stdin_bytes_unread() {
+        let mut builder = ProcessBuilder::new("/bin/true");
+        builder.limits.real_time_limit = Some(Duration::from_secs(5));
+        let mut process = builder.spawn_with_stdin_bytes(vec![0u8; 1024 * 1024]).unwrap();
+        process.wait_for_exit().unwrap();
+        assert_eq!(Some(0), process.exit_status().exit_code());
+    }
+
+    #[test]
+    fn test_inherited_files() {
+        use std::os::unix::io::AsRawFd;
+
+        let dir = std::env::temp_dir()
+            .join(format!("sandbox-inherited-files-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("input"), "inherited\n").unwrap();
+        let input = std::fs::File::open(dir.join("input")).unwrap();
+        let output = std::fs::File::create(dir.join("output")).unwrap();
+
+        let mut builder = ProcessBuilder::new("/bin/sh");
+        builder.add_arg("-c").unwrap();
+        builder.add_arg(format!("cat <&{}", input.as_raw_fd())).unwrap();
+        builder.redirections.stdout = Some(output);
+        builder.redirections.inherited_files.push(input);
+        let mut process = builder.start().unwrap();
+        process.wait_for_exit().unwrap();
+        let status = process.exit_status();
+
+        let content = std::fs::read_to_string(dir.join("output")).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(Some(0), status.exit_code());
+        assert_eq!("inherited\n", content);
+    }
+
+    #[test]
+    fn test_leaked_fd_closed() {
+        use std::os::unix::io::IntoRawFd;
+
+        // Files opened by the standard library have the `O_CLOEXEC` flag set, so the flag is
+        // cleared to simulate a file descriptor leaked by other parts of the calling process.
+        let fd = std::fs::File::open("/dev/null").unwrap().into_raw_fd();
+        super::misc::clear_cloexec(fd).unwrap();
+
+        let mut builder = ProcessBuilder::new("/bin/sh");
+        builder.add_arg("-c").unwrap();
+        builder.add_arg(format!("[ -e /proc/$$/fd/{} ]", fd)).unwrap();
+        let mut process = builder.start().unwrap();
+        process.wait_for_exit().unwrap();
+        nix::unistd::close(fd).unwrap();
+
+        assert_eq!(Some(1), process.exit_status().exit_code());
+    }
+
+    #[test]
+    fn test_trace_syscalls() {
+        let mut builder = ProcessBuilder::new("/bin/sh");
+        builder.add_arg("-c").unwrap();
+        builder.add_arg("/bin/true | /bin/cat; exit 3").unwrap();
+        let trace = builder.trace_syscalls().unwrap();
+        assert_eq!(Some(3), trace.exit_status.exit_code());
+
+        let names = trace.syscalls.iter().map(|s| s.name.as_str()).collect::<Vec<&str>>();
+        assert!(names.contains(&"execve"));
+        assert!(names.contains(&"exit_group"));
+        assert!(names.contains(&"pipe") || names.contains(&"pipe2"));
+
+        let mut sorted = names.clone();
+        sorted.sort();
+        sorted.dedup();
+        assert_eq!(sorted, names);
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_exit_status_serde_round_trip() {
+        // Every variant is listed here so that new variants are caught by the exhaustive match.
+        let statuses = vec![
+            ProcessExitStatus::NotExited,
+            ProcessExitStatus::Normal(-3),
+            ProcessExitStatus::KilledBySignal(libc::SIGSEGV),
+            ProcessExitStatus::CPUTimeLimitExceeded,
+            ProcessExitStatus::RealTimeLimitExceeded,
+            ProcessExitStatus::MemoryLimitExceeded,
+            ProcessExitStatus::BannedSyscall,
+            ProcessExitStatus::DiskQuotaExceeded,
+            ProcessExitStatus::SyscallBudgetExceeded,
+        ];
+        for status in &statuses {
+            match status {
+                ProcessExitStatus::NotExited |
+                ProcessExitStatus::Normal(..) |
+                ProcessExitStatus::KilledBySignal(..) |
+                ProcessExitStatus::CPUTimeLimitExceeded |
+                ProcessExitStatus::RealTimeLimitExceeded |
+                ProcessExitStatus::MemoryLimitExceeded |
+                ProcessExitStatus::BannedSyscall |
+                ProcessExitStatus::DiskQuotaExceeded |
+                ProcessExitStatus::SyscallBudgetExceeded => (),
+            };
+
+            let data = rmp_serde::to_vec(status).unwrap();
+            let deserialized: ProcessExitStatus = rmp_serde::from_slice(&data).unwrap();
+            assert_eq!(*status, deserialized);
+        }
+    }
 }
@@ -3,6 +3,7 @@ use std::path::{Path, PathBuf};
 use std::time::Duration;
 use std::os::unix::io::RawFd;
 
+use nix::errno::Errno;
 use nix::fcntl::{FcntlArg, FdFlag};
 
 /// Check if the given string slice is a valid C-style string.
@@ -50,6 +51,163 @@ pub fn dup_and_cloexec(old_fd: RawFd, new_fd: RawFd) -> nix::Result<()> {
     Ok(())
 }
 
+/// Clear the `O_CLOEXEC` flag on the given file descriptor, so that the file descriptor is inherited
+/// by the programs started by `execve`. Files opened by the standard library have this flag set by
+/// default.
+pub fn clear_cloexec(fd: RawFd) -> nix::Result<()> {
+    nix::fcntl::fcntl(fd, FcntlArg::F_SETFD(FdFlag::empty()))?;
+    Ok(())
+}
+
+/// Close all file descriptors of the calling process except the standard streams and the given
+/// ones. File descriptors are enumerated through `/proc/self/fd`.
+///
+/// This function is called after `fork` and thus does not allocate memory: the directory is read
+/// through the `getdents64` system call into a buffer on the stack.
+pub fn close_fds_except(keep: &[RawFd]) -> nix::Result<()> {
+    let dir_fd = unsafe {
+        libc::open(b"/proc/self/fd\0".as_ptr() as *const libc::c_char,
+            libc::O_RDONLY | libc::O_DIRECTORY | libc::O_CLOEXEC)
+    };
+    let dir_fd = Errno::result(dir_fd)?;
+
+    let res = close_listed_fds(dir_fd, keep);
+    nix::unistd::close(dir_fd).ok();
+    res
+}
+
+/// Close the file descriptors listed in the given open `/proc/self/fd` directory, except the
+/// standard streams, the directory itself and the given ones.
+fn close_listed_fds(dir_fd: RawFd, keep: &[RawFd]) -> nix::Result<()> {
+    // Each entry is a `struct linux_dirent64`: an 8-byte inode number, an 8-byte offset, a 2-byte
+    // record length, a 1-byte file type and the null-terminated name.
+    const RECLEN_OFFSET: usize = 16;
+    const NAME_OFFSET: usize = 19;
+
+    let mut buf = [0u8; 4096];
+    loop {
+        let len = unsafe {
+            libc::syscall(libc::SYS_getdents64, dir_fd, buf.as_mut_ptr(), buf.len())
+        };
+        let len = Errno::result(len)? as usize;
+        if len == 0 {
+            return Ok(());
+        }
+
+        let mut offset = 0;
+        while offset < len {
+            let reclen = u16::from_ne_bytes(
+                [buf[offset + RECLEN_OFFSET], buf[offset + RECLEN_OFFSET + 1]]) as usize;
+            if let Some(fd) = parse_fd(&buf[offset + NAME_OFFSET..offset + reclen]) {
+                if fd > libc::STDERR_FILENO && fd != dir_fd && !keep.contains(&fd) {
+                    nix::unistd::close(fd).ok();
+                }
+            }
+            offset += reclen;
+        }
+    }
+}
+
+/// Parse the given null-terminated name of an entry in `/proc/self/fd` into a file descriptor.
+/// Returns `None` for names that are not file descriptors, i.e. `.` and `..`.
+fn parse_fd(name: &[u8]) -> Option<RawFd> {
+    let digits = name.iter().take_while(|b| **b != 0);
+    let mut fd: RawFd = 0;
+    let mut empty = true;
+    for b in digits {
+        if !b.is_ascii_digit() {
+            return None;
+        }
+        fd = fd.checked_mul(10)?.checked_add(RawFd::from(b - b'0'))?;
+        empty = false;
+    }
+
+    if empty { None } else { Some(fd) }
+}
+
+/// Statistics of a process reported in `/proc/<pid>/status` that are not available in
+/// `/proc/<pid>/stat`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ProcessStatus {
+    /// Peak virtual memory size in kilobytes, a.k.a. `VmPeak`.
+    pub vm_peak: usize,
+
+    /// Peak resident set size in kilobytes, a.k.a. `VmHWM`.
+    pub vm_hwm: usize,
+
+    /// Number of voluntary context switches, a.k.a. `voluntary_ctxt_switches`.
+    pub voluntary_ctxt_switches: usize,
+
+    /// Number of involuntary context switches, a.k.a. `nonvoluntary_ctxt_switches`.
+    pub nonvoluntary_ctxt_switches: usize,
+}
+
+/// Parse the contents of a `/proc/<pid>/status` file. Fields that are missing (e.g. memory
+/// statistics of kernel threads) are reported as zero.
+fn parse_process_status(status: &str) -> ProcessStatus {
+    let mut result = ProcessStatus::default();
+    for line in status.lines() {
+        let mut parts = line.splitn(2, ':');
+        let key = parts.next().unwrap_or_default();
+        let field = match key {
+            "VmPeak" => &mut result.vm_peak,
+            "VmHWM" => &mut result.vm_hwm,
+            "voluntary_ctxt_switches" => &mut result.voluntary_ctxt_switches,
+            "nonvoluntary_ctxt_switches" => &mut result.nonvoluntary_ctxt_switches,
+            _ => continue
+        };
+
+        let value = parts.next().unwrap_or_default().trim();
+        let value = value.trim_end_matches("kB").trim();
+        *field = value.parse().unwrap_or_default();
+    }
+
+    result
+}
+
+/// Read the statistics of the given process from `/proc/<pid>/status`.
+///
+/// The `procinfo` crate is not used here since its parser rejects the status files produced by
+/// newer kernels that contain unknown fields.
+pub fn process_status(pid: i32) -> std::io::Result<ProcessStatus> {
+    let status = std::fs::read_to_string(format!("/proc/{}/status", pid))?;
+    Ok(parse_process_status(&status))
+}
+
+/// Get the total size of the regular files under the given directory, in bytes. Symbolic links are
+/// not followed. Files and directories removed by other processes during the walk are skipped.
+pub fn dir_size<P>(path: &P) -> std::io::Result<u64>
+    where P: ?Sized + AsRef<Path> {
+    let mut size = 0;
+    for entry in std::fs::read_dir(path)? {
+        match dir_entry_size(entry) {
+            Ok(entry_size) => size += entry_size,
+            Err(ref e) if is_vanished_error(e) => (),
+            Err(e) => return Err(e)
+        };
+    }
+
+    Ok(size)
+}
+
+/// Get the total size of the regular files under the given directory entry, in bytes.
+fn dir_entry_size(entry: std::io::Result<std::fs::DirEntry>) -> std::io::Result<u64> {
+    let entry = entry?;
+    let file_type = entry.file_type()?;
+    if file_type.is_dir() {
+        dir_size(&entry.path())
+    } else if file_type.is_file() {
+        Ok(entry.metadata()?.len())
+    } else {
+        Ok(0)
+    }
+}
+
+/// Determine whether the given error is caused by a file that has been removed.
+fn is_vanished_error(e: &std::io::Error) -> bool {
+    e.kind() == std::io::ErrorKind::NotFound || e.raw_os_error() == Some(libc::ESTALE)
+}
+
 /// Expand the `PATH` environment variable before the given path and returns the one that exists.
 pub fn expand_path<'a, P>(path: &'a P) -> Option<Cow<'a, Path>>
     where P: ?Sized + AsRef<Path> {
@@ -80,11 +238,63 @@ pub fn expand_path<'a, P>(path: &'a P) -> Option<Cow<'a, Path>>
 
 #[cfg(test)]
 mod tests {
-    use super::is_valid_c_string;
+    use super::{
+        dir_size,
+        is_valid_c_string,
+        is_vanished_error,
+        parse_fd,
+        parse_process_status,
+        ProcessStatus,
+    };
 
     #[test]
     fn test_is_valid_c_string() {
         assert!(is_valid_c_string("abc哈哈哈"));
         assert!(!is_valid_c_string("abc\x00哈哈哈"));
     }
+
+    #[test]
+    fn test_parse_fd() {
+        assert_eq!(Some(0), parse_fd(b"0\0\0\0"));
+        assert_eq!(Some(1024), parse_fd(b"1024\0"));
+        assert_eq!(None, parse_fd(b".\0\0"));
+        assert_eq!(None, parse_fd(b"..\0"));
+        assert_eq!(None, parse_fd(b"99999999999\0"));
+    }
+
+    #[test]
+    fn test_parse_process_status() {
+        let status = "Name:\tcat\nVmPeak:\t    3324 kB\nVmSize:\t    3324 kB\n\
+            VmHWM:\t    2224 kB\nVmRSS:\t    2224 kB\nThreads:\t1\n\
+            voluntary_ctxt_switches:\t12\nnonvoluntary_ctxt_switches:\t3\n";
+        let expected = ProcessStatus {
+            vm_peak: 3324,
+            vm_hwm: 2224,
+            voluntary_ctxt_switches: 12,
+            nonvoluntary_ctxt_switches: 3,
+        };
+        assert_eq!(expected, parse_process_status(status));
+        assert_eq!(ProcessStatus::default(),
+            parse_process_status("Name:\tkthreadd\nThreads:\t1\n"));
+    }
+
+    #[test]
+    fn test_dir_size() {
+        let dir = std::env::temp_dir().join(format!("sandbox-dir-size-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("sub")).unwrap();
+        std::fs::write(dir.join("a"), [0u8; 100]).unwrap();
+        std::fs::write(dir.join("sub").join("b"), [0u8; 28]).unwrap();
+
+        let size = dir_size(&dir).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(128, size);
+    }
+
+    #[test]
+    fn test_is_vanished_error() {
+        let not_found = std::fs::read_dir("/nonexistent-sandbox-dir").unwrap_err();
+        assert!(is_vanished_error(&not_found));
+        assert!(is_vanished_error(&std::io::Error::from_raw_os_error(libc::ESTALE)));
+        assert!(!is_vanished_error(&std::io::Error::from_raw_os_error(libc::EACCES)));
+    }
 }
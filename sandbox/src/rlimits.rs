@@ -13,7 +13,12 @@ pub enum Resource {
 
     /// Limit, in seconds, on the amount of CPU time that the process can
     /// consume. This variant corresponds to the `RLIMIT_CPU` native constant.
-    CPUTime = libc::RLIMIT_CPU
+    CPUTime = libc::RLIMIT_CPU,
+
+    /// Maximum file descriptor number plus one that can be opened by the
+    /// process. This variant corresponds to the `RLIMIT_NOFILE` native
+    /// constant.
+    NoFile = libc::RLIMIT_NOFILE
 }
 
 /// Specify the soft limit and the hard limit for some resource.
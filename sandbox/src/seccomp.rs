@@ -2,12 +2,19 @@
 //! systems. This module is based on the `seccomp_sys` crate which furthur
 //! depends on the `libseccomp` native library.
 //!
-//! Use `apply_syscall_blacklist` function to apply a blacklist of syscalls to
-//! the calling process.
+//! Use `compile_syscall_filters` function to compile a list of syscall filters
+//! into a `CompiledFilter`, which can then be loaded into the calling process.
 //!
 
 use std::error::Error;
+use std::ffi::CStr;
 use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::os::unix::io::{AsRawFd, FromRawFd};
+
+use nix::errno::Errno;
+use nix::sys::memfd::{memfd_create, MemFdCreateFlag};
 
 use seccomp_sys::*;
 
@@ -99,11 +106,73 @@ impl SyscallFilter {
     }
 }
 
-/// Apply a list of syscall filters to the calling process. After calling this function, if the
-/// calling process calls any of the syscalls not on the given list, then the kernel will kill the
-/// calling process immediately; otherwise the corresponding action to the syscall will be
-/// performed.
-pub fn apply_syscall_filters<T>(filters: T) -> Result<()>
+/// Size in bytes of a single BPF instruction, i.e. the native `struct sock_filter`.
+const BPF_INSTRUCTION_SIZE: usize = 8;
+
+/// Native representation of a BPF program, i.e. the native `struct sock_fprog`.
+#[repr(C)]
+struct SockFprog {
+    len: libc::c_ushort,
+    filter: *const u8,
+}
+
+/// A list of syscall filters compiled into a BPF program. Unlike `libseccomp`, loading the compiled
+/// program into the calling process does not allocate memory, which makes it safe to be loaded in
+/// a child process forked from a multithreaded process.
+#[derive(Clone, Debug)]
+pub struct CompiledFilter {
+    /// The BPF program, in the native format expected by the kernel.
+    program: Vec<u8>,
+}
+
+impl CompiledFilter {
+    /// Load the compiled filter into the calling process. After calling this function, if the
+    /// calling process calls any of the syscalls not on the list of syscall filters, then the
+    /// kernel will kill the calling process immediately; otherwise the corresponding action to the
+    /// syscall will be performed.
+    ///
+    /// This function does not allocate memory. As `libseccomp` does, the `no_new_privs` bit of the
+    /// calling process is set before the filter is loaded.
+    pub fn load(&self) -> Result<()> {
+        let prog = SockFprog {
+            len: (self.program.len() / BPF_INSTRUCTION_SIZE) as libc::c_ushort,
+            filter: self.program.as_ptr(),
+        };
+
+        let ret = unsafe { libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) };
+        if ret < 0 {
+            return Err(SeccompError::new(-(Errno::last() as i32)));
+        }
+
+        let ret = unsafe {
+            libc::prctl(libc::PR_SET_SECCOMP, libc::SECCOMP_MODE_FILTER,
+                &prog as *const SockFprog, 0, 0)
+        };
+        if ret < 0 {
+            return Err(SeccompError::new(-(Errno::last() as i32)));
+        }
+
+        Ok(())
+    }
+}
+
+/// Determine whether the running kernel supports seccomp filters. Some container hosts run
+/// kernels built without seccomp, or forbid their containers from loading seccomp filters.
+pub fn seccomp_available() -> bool {
+    // Loading a null filter program fails with `EFAULT` if seccomp filters are supported, and with
+    // `EINVAL` (or another error if the call is forbidden) otherwise. No filter is loaded either
+    // way.
+    let ret = unsafe {
+        libc::prctl(libc::PR_SET_SECCOMP, libc::SECCOMP_MODE_FILTER,
+            std::ptr::null::<SockFprog>(), 0, 0)
+    };
+    ret < 0 && Errno::last() == Errno::EFAULT
+}
+
+/// Compile a list of syscall filters into a BPF program through `libseccomp`. If the compiled
+/// filter is loaded into a process and the process calls any of the syscalls not on the given list,
+/// then the kernel will kill the process immediately.
+pub fn compile_syscall_filters<T>(filters: T) -> Result<CompiledFilter>
     where T: IntoIterator<Item = SyscallFilter>, {
     // TODO: Change the default behavior here to `SCMP_ACT_KILL_PROCESS` after upgrading to
     // TODO: Linux kernel 4.14 or above versions.
@@ -112,6 +181,16 @@ pub fn apply_syscall_filters<T>(filters: T) -> Result<()>
         return Err(SeccompError::new(-1));
     }
 
+    let program = add_rules_and_export(ctx, filters);
+    unsafe { seccomp_release(ctx) };
+
+    Ok(CompiledFilter { program: program? })
+}
+
+/// Add the given syscall filters to the given `libseccomp` context and export the BPF program
+/// generated from the context.
+fn add_rules_and_export<T>(ctx: scmp_filter_ctx, filters: T) -> Result<Vec<u8>>
+    where T: IntoIterator<Item = SyscallFilter>, {
     for filter in filters {
         let ret = unsafe {
             seccomp_rule_add_array(
@@ -122,10 +201,63 @@ pub fn apply_syscall_filters<T>(filters: T) -> Result<()>
         }
     }
 
-    let ret = unsafe { seccomp_load(ctx) };
+    // `libseccomp` can only export the BPF program into a file descriptor, so it is exported into
+    // an anonymous in-memory file and read back.
+    let name = CStr::from_bytes_with_nul(b"seccomp-bpf\0").unwrap();
+    let fd = memfd_create(name, MemFdCreateFlag::MFD_CLOEXEC)
+        .map_err(|_| SeccompError::new(-(Errno::last() as i32)))?;
+    let mut file = unsafe { File::from_raw_fd(fd) };
+
+    let ret = unsafe { seccomp_export_bpf(ctx, file.as_raw_fd()) };
     if ret < 0 {
         return Err(SeccompError::new(ret));
     }
 
-    Ok(())
+    let mut program = Vec::new();
+    file.seek(SeekFrom::Start(0))
+        .and_then(|_| file.read_to_end(&mut program))
+        .map_err(|e| SeccompError::new(-e.raw_os_error().unwrap_or(0)))?;
+
+    Ok(program)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        compile_syscall_filters,
+        seccomp_available,
+        Action,
+        SyscallFilter,
+        BPF_INSTRUCTION_SIZE,
+    };
+
+    #[test]
+    fn test_compile_syscall_filters() {
+        let filters = [libc::SYS_read, libc::SYS_write, libc::SYS_exit_group].iter()
+            .map(|id| SyscallFilter::new(*id as i32, Action::Allow));
+        let filter = compile_syscall_filters(filters).unwrap();
+        assert!(!filter.program.is_empty());
+        assert_eq!(0, filter.program.len() % BPF_INSTRUCTION_SIZE);
+    }
+
+    /// Get the `Seccomp` line in the status of the calling thread, which gives its seccomp mode.
+    fn seccomp_status_line() -> String {
+        let status = std::fs::read_to_string("/proc/thread-self/status").unwrap();
+        status.lines()
+            .find(|line| line.starts_with("Seccomp:"))
+            .expect("no seccomp mode in thread status")
+            .to_owned()
+    }
+
+    #[test]
+    fn test_seccomp_available() {
+        let before = seccomp_status_line();
+
+        // The kernels running the tests support seccomp, which the other tests rely on as well.
+        assert!(seccomp_available());
+
+        // The probe does not load any filter into the calling thread, which may already run under
+        // a filter installed by the environment running the tests.
+        assert_eq!(before, seccomp_status_line());
+    }
 }
@@ -0,0 +1,297 @@
+//! This module implements a `ptrace` based system call tracer. The tracer runs a program and
+//! records the distinct system calls invoked by it, which is useful when building system call
+//! whitelists for new languages.
+//!
+//! Note that resource limits implemented by the daemon thread are not enforced when a program is
+//! traced, and the system call whitelist is not applied either. Only native `rlimit`s are
+//! effective, if enabled.
+//!
+//! This module also starts the child processes with a system call budget, which are traced by
+//! their daemon threads to count the system calls invoked by them.
+//!
+
+use std::collections::{BTreeSet, HashSet};
+use std::ptr;
+use std::time::Duration;
+
+use nix::errno::Errno;
+use nix::sys::ptrace;
+use nix::sys::signal::Signal;
+use nix::sys::wait::{WaitStatus, WaitPidFlag};
+use nix::unistd::{Pid, ForkResult};
+
+use super::child::{self, ChildImage};
+use super::{
+    Error,
+    ErrorKind,
+    Result,
+    SystemCall,
+    SystemCallId,
+    ProcessBuilder,
+    ProcessExitStatus,
+};
+
+/// Result of tracing the system calls invoked by a program.
+#[derive(Clone, Debug)]
+pub struct SystemCallTrace {
+    /// Exit status of the traced program.
+    pub exit_status: ProcessExitStatus,
+
+    /// Distinct system calls invoked by the traced program, sorted by their names. Only the system
+    /// calls invoked since the `execve` call that starts the program are recorded.
+    pub syscalls: Vec<SystemCall>,
+}
+
+/// Options set on the tracees, under which threads and child processes created by the tracees are
+/// traced automatically.
+fn trace_options() -> ptrace::Options {
+    ptrace::Options::PTRACE_O_TRACESYSGOOD |
+    ptrace::Options::PTRACE_O_EXITKILL |
+    ptrace::Options::PTRACE_O_TRACECLONE |
+    ptrace::Options::PTRACE_O_TRACEFORK |
+    ptrace::Options::PTRACE_O_TRACEVFORK |
+    ptrace::Options::PTRACE_O_TRACEEXEC
+}
+
+/// Resume the given stopped tracee until its next system call entry or exit, delivering the given
+/// signal to it.
+pub(super) fn resume(pid: Pid, sig: Option<Signal>) -> nix::Result<()> {
+    let data = sig.map(|sig| sig as libc::c_long).unwrap_or(0);
+    let ret = unsafe {
+        libc::ptrace(libc::PTRACE_SYSCALL, pid.as_raw(), ptr::null_mut::<libc::c_void>(), data)
+    };
+
+    Errno::result(ret).map(drop)
+}
+
+/// Tracks whether each tracee is inside a system call, to tell system call entry stops from exit
+/// stops.
+///
+/// With `PTRACE_O_TRACESYSGOOD`, system call stops are reported distinctly from other stops, and
+/// the entry and exit stops of each tracee alternate. Tracking them per tracee does not depend on
+/// the registers of any particular architecture, and does not mistake the exit of a system call
+/// returning `ENOSYS` for an entry.
+#[derive(Debug, Default)]
+pub(super) struct SyscallStopTracker {
+    /// Tracees that are stopped inside a system call, whose next system call stop is an exit stop.
+    in_syscall: HashSet<Pid>,
+}
+
+impl SyscallStopTracker {
+    /// Create a new `SyscallStopTracker` with no tracee inside a system call.
+    pub(super) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record that the given tracee is stopped inside a system call without a reported entry stop,
+    /// e.g. at `PTRACE_EVENT_EXEC` before it is resumed with `PTRACE_SYSCALL` for the first time.
+    pub(super) fn set_in_syscall(&mut self, pid: Pid) {
+        self.in_syscall.insert(pid);
+    }
+
+    /// Record a system call stop of the given tracee. Returns whether the stop is the entry of a
+    /// system call rather than the exit.
+    pub(super) fn on_syscall_stop(&mut self, pid: Pid) -> bool {
+        if self.in_syscall.remove(&pid) {
+            false
+        } else {
+            self.in_syscall.insert(pid);
+            true
+        }
+    }
+
+    /// Forget the given tracee, which has exited.
+    pub(super) fn remove(&mut self, pid: Pid) {
+        self.in_syscall.remove(&pid);
+    }
+}
+
+/// Get the ID of the system call at which the given tracee is stopped.
+#[cfg(target_arch = "x86_64")]
+fn current_syscall_id(pid: Pid) -> nix::Result<SystemCallId> {
+    Ok(ptrace::getregs(pid)?.orig_rax as SystemCallId)
+}
+
+/// Get the ID of the system call at which the given tracee is stopped. Reading the registers of
+/// tracees is only implemented on x86_64.
+#[cfg(not(target_arch = "x86_64"))]
+fn current_syscall_id(_pid: Pid) -> nix::Result<SystemCallId> {
+    Err(nix::Error::UnsupportedOperation)
+}
+
+/// Wait for a state change of any of the given tracees.
+///
+/// `waitpid(-1, ..)` cannot be used here since it may reap child processes of the calling process
+/// that are monitored by the daemon threads of other `Process` instances.
+fn wait_any(tracees: &[Pid]) -> nix::Result<WaitStatus> {
+    loop {
+        for pid in tracees {
+            match nix::sys::wait::waitpid(*pid, Some(WaitPidFlag::__WALL | WaitPidFlag::WNOHANG))? {
+                WaitStatus::StillAlive => (),
+                status => return Ok(status)
+            };
+        }
+
+        std::thread::sleep(Duration::from_micros(50));
+    }
+}
+
+/// Trace the system calls invoked by the main process, its threads and its child processes until
+/// the main process exits.
+fn trace_parent(child: Pid) -> Result<SystemCallTrace> {
+    // The child process stops itself before calling `execve`, which gives us the chance to set the
+    // tracing options.
+    match nix::sys::wait::waitpid(child, None)? {
+        WaitStatus::Stopped(_, Signal::SIGSTOP) => (),
+        _ => return Err(Error::from(ErrorKind::ChildStartupFailed))
+    };
+
+    ptrace::setoptions(child, trace_options())?;
+    resume(child, None)?;
+
+    // System calls invoked by the sandbox itself before `execve` are not recorded.
+    let mut recording = false;
+    let mut syscall_ids = BTreeSet::new();
+    // The main process is the first tracee. Threads and child processes created by the tracees are
+    // traced automatically and added to the tracee list.
+    let mut tracees = vec![child];
+    let mut pending_initial_stop = HashSet::new();
+
+    let exit_status = loop {
+        let wait_status = wait_any(&tracees)?;
+        log::trace!("Tracer loop with wait status: {:?}", wait_status);
+
+        match wait_status {
+            WaitStatus::Exited(pid, exit_code) if pid == child =>
+                break ProcessExitStatus::Normal(exit_code),
+            WaitStatus::Signaled(pid, Signal::SIGUSR1, _) if pid == child && !recording =>
+                return Err(Error::from(ErrorKind::ChildStartupFailed)),
+            WaitStatus::Signaled(pid, sig, _) if pid == child =>
+                break ProcessExitStatus::KilledBySignal(sig as i32),
+            WaitStatus::Exited(pid, _) | WaitStatus::Signaled(pid, _, _) => {
+                tracees.retain(|tracee| *tracee != pid);
+            },
+            WaitStatus::PtraceSyscall(pid) => {
+                let syscall_id = current_syscall_id(pid)?;
+                if syscall_id == libc::SYS_execve as SystemCallId {
+                    recording = true;
+                }
+                if recording {
+                    syscall_ids.insert(syscall_id);
+                }
+                resume(pid, None)?;
+            },
+            WaitStatus::PtraceEvent(pid, _, event) => {
+                if event == libc::PTRACE_EVENT_CLONE ||
+                    event == libc::PTRACE_EVENT_FORK ||
+                    event == libc::PTRACE_EVENT_VFORK {
+                    let new_pid = Pid::from_raw(ptrace::getevent(pid)? as libc::pid_t);
+                    tracees.push(new_pid);
+                    pending_initial_stop.insert(new_pid);
+                }
+                resume(pid, None)?;
+            },
+            WaitStatus::Stopped(pid, sig) => {
+                // The initial stop of a newly created thread or child process should not be
+                // delivered.
+                let initial_stop = sig == Signal::SIGSTOP && pending_initial_stop.remove(&pid);
+                resume(pid, if initial_stop { None } else { Some(sig) })?;
+            },
+            _ => ()
+        };
+    };
+
+    let mut syscalls = Vec::with_capacity(syscall_ids.len());
+    for id in syscall_ids {
+        match SystemCall::from_id(id) {
+            Ok(syscall) => syscalls.push(syscall),
+            Err(..) => log::warn!("Unknown system call ID: {}", id)
+        };
+    }
+    syscalls.sort_by(|lhs, rhs| lhs.name.cmp(&rhs.name));
+
+    Ok(SystemCallTrace { exit_status, syscalls })
+}
+
+/// Start the process described by the given `ProcessBuilder` under the tracer and wait for it to
+/// exit.
+pub fn trace(mut builder: ProcessBuilder) -> Result<SystemCallTrace> {
+    // The system call whitelist would kill the tracee on the very system calls we want to find.
+    builder.syscall_whitelist.clear();
+    let image = ChildImage::new(&builder)?;
+
+    match nix::unistd::fork()? {
+        ForkResult::Parent { child } => trace_parent(child),
+        ForkResult::Child => {
+            // Failures are reported to the tracer in the same way as `ProcessBuilder::start` does.
+            if trace_me().is_err() {
+                child::abort_child(None, None);
+            }
+            let failure = image.exec();
+            child::abort_child(Some(failure), None)
+        }
+    }
+}
+
+/// Make the calling process a tracee of its parent and stop it, which gives the tracer the chance
+/// to set the tracing options. This function is called in the child process after `fork` and does
+/// not allocate memory.
+pub(super) fn trace_me() -> nix::Result<()> {
+    ptrace::traceme()?;
+    nix::sys::signal::raise(Signal::SIGSTOP)?;
+    Ok(())
+}
+
+/// Set the tracing options on the given child process stopped by `trace_me`, and run it until its
+/// `execve` call succeeds or its setup fails. In the former case the child process is left stopped
+/// right after its `execve` call, so that the system calls invoked by the sandbox to set up the
+/// child process are not traced; in the latter case the child process is left killing itself, to
+/// be reaped by `ProcessBuilder::check_child_setup`.
+pub(super) fn wait_for_exec(child: Pid) -> Result<()> {
+    match nix::sys::wait::waitpid(child, None)? {
+        WaitStatus::Stopped(_, Signal::SIGSTOP) => (),
+        _ => return Err(Error::from(ErrorKind::ChildStartupFailed))
+    };
+    ptrace::setoptions(child, trace_options())?;
+    ptrace::cont(child, None)?;
+
+    loop {
+        match nix::sys::wait::waitpid(child, None)? {
+            WaitStatus::PtraceEvent(_, _, event) if event == libc::PTRACE_EVENT_EXEC =>
+                return Ok(()),
+            WaitStatus::Stopped(_, Signal::SIGUSR1) => {
+                ptrace::cont(child, Some(Signal::SIGUSR1))?;
+                return Ok(());
+            },
+            WaitStatus::Stopped(_, sig) => ptrace::cont(child, Some(sig))?,
+            _ => return Err(Error::from(ErrorKind::ChildStartupFailed))
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Pid, SyscallStopTracker};
+
+    #[test]
+    fn test_syscall_stop_tracker() {
+        let main = Pid::from_raw(100);
+        let thread = Pid::from_raw(101);
+        let mut tracker = SyscallStopTracker::new();
+
+        // The main tracee is stopped inside `execve`, so its first system call stop is an exit.
+        tracker.set_in_syscall(main);
+        assert!(!tracker.on_syscall_stop(main));
+        assert!(tracker.on_syscall_stop(main));
+
+        // Entries and exits are tracked per tracee.
+        assert!(tracker.on_syscall_stop(thread));
+        assert!(!tracker.on_syscall_stop(main));
+        assert!(!tracker.on_syscall_stop(thread));
+
+        // A tracee killed inside a system call starts over if its pid is reused.
+        assert!(tracker.on_syscall_stop(thread));
+        tracker.remove(thread);
+        assert!(tracker.on_syscall_stop(thread));
+    }
+}